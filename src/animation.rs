@@ -0,0 +1,258 @@
+use glam::{Mat4, Quat, Vec3};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub parent: Option<usize>,
+    pub transform: Transform,
+    /// Skin deforming the meshes of the node, their node transform is ignored then
+    pub skin: Option<usize>,
+}
+
+/// Joints deforming a skinned mesh, see `SceneGraph::joint_matrices`.
+#[derive(Debug, Clone)]
+pub struct Skin {
+    /// Node of every joint, vertices name joints by their index in here
+    pub joints: Vec<usize>,
+    /// Mesh space to joint space in the bind pose, one per joint
+    pub inverse_bind_matrices: Vec<Mat4>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+}
+
+#[derive(Debug, Clone)]
+pub enum Keyframes {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub node: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub keyframes: Keyframes,
+}
+
+impl Channel {
+    /// Keyframe pair surrounding `time` and the blend factor between them.
+    fn locate(&self, time: f32) -> (usize, usize, f32) {
+        let last = self.times.len() - 1;
+        let next = self.times.partition_point(|t| *t <= time);
+        if next == 0 {
+            return (0, 0, 0.0);
+        }
+        if next > last {
+            return (last, last, 0.0);
+        }
+        let prev = next - 1;
+        let span = self.times[next] - self.times[prev];
+        let factor = if span > 0.0 {
+            (time - self.times[prev]) / span
+        } else {
+            0.0
+        };
+        match self.interpolation {
+            Interpolation::Step => (prev, prev, 0.0),
+            Interpolation::Linear => (prev, next, factor),
+        }
+    }
+
+    fn apply(&self, time: f32, transform: &mut Transform) {
+        if self.times.is_empty() {
+            return;
+        }
+        let (a, b, t) = self.locate(time);
+        match &self.keyframes {
+            Keyframes::Translation(v) => transform.translation = v[a].lerp(v[b], t),
+            Keyframes::Rotation(v) => transform.rotation = v[a].slerp(v[b], t).normalize(),
+            Keyframes::Scale(v) => transform.scale = v[a].lerp(v[b], t),
+        }
+    }
+}
+
+/// Keyframes of some nodes. Built in code, an animation starts out empty and
+/// gets its channels from `translation`, `rotation` and `scale`:
+///
+/// ```ignore
+/// let spin = [(0.0, Quat::IDENTITY), (2.0, Quat::from_rotation_y(PI))];
+/// let animation = Animation::default()
+///     .translation(node, Interpolation::Linear, &[(0.0, Vec3::ZERO), (2.0, Vec3::X)])
+///     .rotation(node, Interpolation::Linear, &spin);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+    pub channels: Vec<Channel>,
+    pub duration: f32,
+}
+
+impl Animation {
+    /// Move `node` through `keys` of time in seconds and translation, sorted by time.
+    pub fn translation(
+        self,
+        node: usize,
+        interpolation: Interpolation,
+        keys: &[(f32, Vec3)],
+    ) -> Self {
+        let (times, values) = keys.iter().copied().unzip();
+        self.with_channel(node, interpolation, times, Keyframes::Translation(values))
+    }
+
+    /// Turn `node` through `keys` of time in seconds and rotation, sorted by time.
+    pub fn rotation(self, node: usize, interpolation: Interpolation, keys: &[(f32, Quat)]) -> Self {
+        let (times, values) = keys.iter().copied().unzip();
+        self.with_channel(node, interpolation, times, Keyframes::Rotation(values))
+    }
+
+    /// Scale `node` through `keys` of time in seconds and scale, sorted by time.
+    pub fn scale(self, node: usize, interpolation: Interpolation, keys: &[(f32, Vec3)]) -> Self {
+        let (times, values) = keys.iter().copied().unzip();
+        self.with_channel(node, interpolation, times, Keyframes::Scale(values))
+    }
+
+    fn with_channel(
+        mut self,
+        node: usize,
+        interpolation: Interpolation,
+        times: Vec<f32>,
+        keyframes: Keyframes,
+    ) -> Self {
+        self.duration = times.last().copied().unwrap_or(0.0).max(self.duration);
+        self.channels.push(Channel {
+            node,
+            interpolation,
+            times,
+            keyframes,
+        });
+        self
+    }
+}
+
+/// Node hierarchy of an imported scene with the animations driving it.
+#[derive(Debug, Clone, Default)]
+pub struct SceneGraph {
+    pub nodes: Vec<Node>,
+    pub animations: Vec<Animation>,
+    pub skins: Vec<Skin>,
+}
+
+impl SceneGraph {
+    pub fn is_animated(&self) -> bool {
+        !self.animations.is_empty()
+    }
+
+    /// Append a node placed by `transform` relative to `parent`, returns its index.
+    pub fn add_node(&mut self, parent: Option<usize>, transform: Transform) -> usize {
+        self.nodes.push(Node {
+            parent,
+            transform,
+            skin: None,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Length of the scene timeline, the longest animation.
+    pub fn duration(&self) -> f32 {
+        self.animations
+            .iter()
+            .map(|animation| animation.duration)
+            .fold(0.0, f32::max)
+    }
+
+    /// First matrix of the skin of `node` in `joint_matrices`, `None` when the
+    /// meshes of the node are rigid.
+    pub fn joint_offset(&self, node: usize) -> Option<u32> {
+        let skin = self.nodes.get(node)?.skin?;
+        Some(
+            self.skins[..skin]
+                .iter()
+                .map(|skin| skin.joints.len() as u32)
+                .sum(),
+        )
+    }
+
+    /// Mesh to world matrix of every joint of every skin, one skin after the
+    /// other, with the nodes placed at `node_matrices` from `evaluate`.
+    pub fn joint_matrices(&self, node_matrices: &[Mat4]) -> Vec<Mat4> {
+        self.skins
+            .iter()
+            .flat_map(|skin| {
+                skin.joints
+                    .iter()
+                    .zip(&skin.inverse_bind_matrices)
+                    .map(|(&joint, inverse_bind)| node_matrices[joint] * *inverse_bind)
+            })
+            .collect()
+    }
+
+    /// World matrix of every node with all animations sampled at `time` of the
+    /// scene timeline. The timeline loops after the longest animation, shorter
+    /// ones hold their last key until then.
+    pub fn evaluate(&self, time: f32) -> Vec<Mat4> {
+        let mut locals = self
+            .nodes
+            .iter()
+            .map(|node| node.transform)
+            .collect::<Vec<_>>();
+        let duration = self.duration();
+        let time = if duration > 0.0 {
+            time.rem_euclid(duration)
+        } else {
+            0.0
+        };
+        for animation in &self.animations {
+            for channel in &animation.channels {
+                channel.apply(time, &mut locals[channel.node]);
+            }
+        }
+        let mut worlds: Vec<Option<Mat4>> = vec![None; self.nodes.len()];
+        (0..self.nodes.len())
+            .map(|i| self.world_matrix(i, &locals, &mut worlds))
+            .collect()
+    }
+
+    fn world_matrix(
+        &self,
+        index: usize,
+        locals: &[Transform],
+        worlds: &mut [Option<Mat4>],
+    ) -> Mat4 {
+        if let Some(world) = worlds[index] {
+            return world;
+        }
+        let local = locals[index].matrix();
+        let world = match self.nodes[index].parent {
+            Some(parent) => self.world_matrix(parent, locals, worlds) * local,
+            None => local,
+        };
+        worlds[index] = Some(world);
+        world
+    }
+}
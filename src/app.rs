@@ -1,4 +1,47 @@
-use crate::camera;
+use std::path::PathBuf;
+
+use glam::Vec3;
+
+use crate::{
+    benchmark::{Benchmark, BenchmarkSettings},
+    camera,
+    capabilities::Capabilities,
+    debug_draw::{DebugDraw, DebugViews},
+    depth_contours::DepthContours,
+    dock::DockLayout,
+    flythrough::{PathCapture, PathPlayback},
+    frame_stats::FrameStats,
+    gpu_timer::PassTimings,
+    ground_grid::GroundGrid,
+    heatmap::Heatmap,
+    input::InputState,
+    light_orbit::LightOrbit,
+    loader::LoadProgress,
+    logging::{self, LogBuffer, LogView},
+    matcap::Matcap,
+    notifications::{Notifications, Toast},
+    orientation::Orientation,
+    panorama::PanoramaSettings,
+    picking::PickedTriangle,
+    post_process::PostEffectSlot,
+    primitives::{ChannelView, MaterialOverride, NormalMapping},
+    probe::BakeSchedule,
+    probe_convergence::ConvergenceRun,
+    probe_storage::{ProbeStorage, StorageComparison},
+    render_settings::RenderScale,
+    roi::RegionOfInterest,
+    scene_file::SceneFile,
+    shader_preprocessor::ShaderError,
+    sky::Sky,
+    split_view::SplitView,
+    stages::{StageKind, StageSlot},
+    streaming::StreamingSettings,
+    sweep::{Sweep, SweepSettings},
+    texture_preview::TexturePreview,
+    turntable::{Turntable, TurntableSettings},
+    volumetric_fog::VolumetricFog,
+    watchdog::Watchdog,
+};
 
 pub trait RenderStage<T> {
     fn render(&self, state: &mut T, view: &wgpu::TextureView, encoder: &mut wgpu::CommandEncoder);
@@ -6,33 +49,303 @@ pub trait RenderStage<T> {
     fn update(&mut self, state: &T, queue: &wgpu::Queue);
 }
 
+/// Counterpart of `RenderStage` for GPU work recorded into the frame's encoder
+/// before any stage renders, see `compute` for the helpers.
+pub trait ComputeStage<T> {
+    fn record(&self, state: &mut T, encoder: &mut wgpu::CommandEncoder);
+    fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration);
+    fn update(&mut self, state: &T, queue: &wgpu::Queue);
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct AppState {
     pub camera: camera::Camera,
     pub projection: camera::Projection,
+    pub clip_planes: camera::ClipPlanes,
+    /// Name of the next camera bookmark, edited in the UI
+    pub bookmark_name: String,
+    /// World space bounds of the loaded scene, updated every frame
+    pub scene_bounds: Option<(Vec3, Vec3)>,
     pub camera_controller: camera::CameraController,
-    pub mouse_pressed: bool,
+    /// Actions and axes of this frame, fed by the window
+    pub input: InputState,
+    /// Hide and lock the cursor so every mouse motion looks around, toggled with
+    /// the right button or Tab
+    pub mouse_captured: bool,
     pub scale_factor: f32,
+    /// What the adapter supports, see `Capabilities::query`
+    pub capabilities: Capabilities,
+    pub show_help: bool,
+    /// Every panel and window is hidden and the input goes to the camera,
+    /// toggled with F1
+    pub ui_hidden: bool,
+    /// Hide the UI as well while a turntable, camera path, sweep or benchmark
+    /// runs, so recording the window shows only the scene
+    pub clean_capture: bool,
+    /// One simulation step per frame and synchronous scene loads so runs are reproducible
+    pub deterministic: bool,
+    /// Frames updated since startup
+    pub frame_index: u64,
+    /// Nothing moves on its own and the probe bakes wait, see `simulation`
+    pub paused: bool,
+    /// Set by the UI or the step key to run one simulation step while paused
+    pub step_frame: bool,
+    /// Time stands still this frame, paused and not stepping
+    pub time_frozen: bool,
     pub enable_normal_map: bool,
+    /// Set when the normal map toggle or `SceneFile::normal_maps` changed
     pub normal_map_changed: bool,
+    /// Materials with a normal map and the normal mapping they were loaded with
+    pub normal_mapped_materials: Vec<(String, NormalMapping)>,
+    /// Keep generated normals from smoothing across edges sharper than
+    /// `hard_edge_angle`
+    pub hard_edges: bool,
+    /// Degrees
+    pub hard_edge_angle: f32,
+    /// Set by the UI to reload the scene with generated normals for every OBJ mesh
+    pub recompute_normals: bool,
+    /// Submit the geometry from the indirect draw buffer
+    pub indirect_draw: bool,
+    /// Frustum cull the indirect draws with a compute pass
+    pub gpu_culling: bool,
+    pub material_override: MaterialOverride,
+    pub material_override_changed: bool,
+    /// Term of the shading shown instead of the lit color
+    pub channel_view: ChannelView,
+    pub channel_view_changed: bool,
+    /// Lit sphere of the matcap channel
+    pub matcap: Matcap,
+    pub matcap_changed: bool,
     pub given_light_position: bool,
     pub light_position: [f32; 3],
     pub light_input: [String; 3],
+    pub light_orbit: LightOrbit,
+    pub loading: Option<LoadProgress>,
+    /// Shown in an error window until dismissed
+    pub error: Option<String>,
+    /// Queue of toast messages, clone it to push from elsewhere
+    pub notifications: Notifications,
+    /// Toasts on screen, dismissed after a while or when clicked
+    pub toasts: Vec<Toast>,
+    /// Events logged this session, see `logging`
+    pub log: LogBuffer,
+    pub log_view: LogView,
+    /// Panels of the window and where they are docked
+    pub dock_layout: DockLayout,
+    /// Body of the viewport panel in the last frame, `None` while another
+    /// panel covers it
+    pub viewport: Option<egui::Rect>,
+    pub scene_path: Option<PathBuf>,
+    /// Set by the UI to switch to another scene
+    pub requested_scene: Option<PathBuf>,
+    pub scene_file: SceneFile,
+    /// Mesh names of the current scene
+    pub objects: Vec<String>,
+    /// Mesh names left out of the draws, the probe bakes and picking
+    pub hidden_objects: Vec<String>,
+    pub object_classes_changed: bool,
+    /// Orientation of the meshes of the scene judged at load time, see `orientation`
+    pub object_orientations: Vec<(String, Orientation)>,
+    /// Object outlined by the wireframe overlay, kept across scene loads
+    pub wireframe_object: Option<String>,
+    pub wireframe_object_changed: bool,
+    /// Set by a middle click to pick the triangle at this point in normalized
+    /// device coordinates
+    pub pick_triangle_at: Option<glam::Vec2>,
+    /// Shown in the triangle inspector
+    pub picked_triangle: Option<PickedTriangle>,
+    /// Outline the object under the cursor, read from the object id buffer
+    pub hover_highlight: bool,
+    /// Cursor in normalized device coordinates while it hovers the view and
+    /// `hover_highlight` is on, updated every frame
+    pub hover_at: Option<glam::Vec2>,
+    /// Mesh name of the object under the cursor, a few frames late
+    pub hovered_object: Option<String>,
+    pub enable_reflection_probes: bool,
+    pub reflection_probe_intensity: f32,
+    pub reflection_probes_changed: bool,
+    /// Render the first visible mirror of `SceneFile::mirrors` as a planar reflection
+    pub planar_reflections: bool,
+    pub bake_reflection_probes: bool,
+    /// Set by the UI to report when the requested bake finished
+    pub announce_probe_bake: bool,
+    pub probe_bake: BakeSchedule,
+    /// Set by the UI to re-bake the probes until another bounce changes less than
+    /// `probe_convergence_threshold`
+    pub converge_reflection_probes: bool,
+    pub probe_convergence_threshold: f32,
+    /// Latest convergence run, kept after it finished
+    pub probe_convergence: Option<ConvergenceRun>,
+    /// Submit the GI compute separately from the frame instead of inline
+    pub async_compute: bool,
+    /// GPU time of the last timed GI compute against the raster work
+    pub pass_timings: Option<PassTimings>,
+    /// Format of the probe cube array, the render settings format when unset
+    pub probe_storage: Option<ProbeStorage>,
+    pub probe_storage_changed: bool,
+    /// Set by the UI to capture a comparison of the storage formats to the given PNG
+    pub compare_probe_storage: Option<PathBuf>,
+    /// Results of the last comparison, the format as rendered comes first
+    pub probe_storage_comparison: Vec<StorageComparison>,
+    /// Set by the UI to store the baked probes on disk
+    pub save_probe_cache: bool,
+    /// Set by the UI to export the baked probes to the given JSON file
+    pub export_probes: Option<PathBuf>,
+    /// Duration of the previous frame
+    pub frame_time_ms: f32,
+    /// Turns the GI down when frames keep taking too long
+    pub watchdog: Watchdog,
+    /// Reduce every rendered frame to the numbers in `frame_stats`
+    pub frame_stats_enabled: bool,
+    /// Latest statistics read back from the GPU, a few frames old
+    pub frame_stats: Option<FrameStats>,
+    /// Show NaN and Inf pixels in magenta and count them per pass
+    pub nan_guard: bool,
+    pub nan_guard_changed: bool,
+    pub roi: RegionOfInterest,
+    pub split_view: SplitView,
+    /// The settings of the right side changed
+    pub split_view_changed: bool,
+    pub depth_contours: DepthContours,
+    pub depth_contours_changed: bool,
+    pub ground_grid: GroundGrid,
+    pub volumetric_fog: VolumetricFog,
+    pub sky: Sky,
+    pub sky_changed: bool,
+    /// World axes as seen by the camera, in the bottom left corner
+    pub axis_gizmo: bool,
+    /// Lines drawn over the scene in the coming frame, see `debug_draw`
+    pub debug_draw: DebugDraw,
+    pub debug_views: DebugViews,
+    /// Rasterization cost colored over the main view, see `heatmap`
+    pub heatmap: Heatmap,
+    /// Shader and pipeline errors shown in the shader console until dismissed
+    pub shader_errors: Vec<ShaderError>,
+    /// Set by the UI to read the scene shader from disk again
+    pub shader_reload_requested: bool,
+    /// Reload the scene shader whenever its files change
+    pub watch_shaders: bool,
+    /// Set by the UI to capture the next frame with an attached GPU debugger
+    pub gpu_capture_requested: bool,
+    /// Presented region of interest registered with egui and its size in pixels
+    pub roi_texture: Option<(egui::TextureId, [u32; 2])>,
+    /// Renderer textures shown in their own windows
+    pub texture_previews: Vec<TexturePreview>,
+    /// Preview targets registered with egui and their sizes in pixels, in the
+    /// order of `texture_previews`
+    pub preview_textures: Vec<(egui::TextureId, [u32; 2])>,
+    /// Effects applied to the scene color in order, see `post_process`
+    pub post_effects: Vec<PostEffectSlot>,
+    /// Resolution of the scene color and how it is stretched over the window
+    pub render_scale: RenderScale,
+    pub render_scale_changed: bool,
+    /// `.cube` file the color grade looks colors up in, none leaves them as they are
+    pub color_lut: Option<PathBuf>,
+    pub color_lut_changed: bool,
+    /// Stages rendering each frame in order, see `stages`
+    pub render_stages: Vec<StageSlot>,
+    /// Stages were inserted, removed, moved or edited, the window recreates them
+    pub render_stages_changed: bool,
+    pub scene_animated: bool,
+    pub play_animation: bool,
+    pub animation_time: f32,
+    /// The time was set by hand, the scene is posed again even while paused
+    pub animation_time_changed: bool,
+    /// Length of the scene timeline, where `animation_time` loops
+    pub animation_duration: f32,
+    /// Re-bake the reflection probes every frame while the scene animates
+    pub animate_reflection_probes: bool,
+    pub turntable_settings: TurntableSettings,
+    /// Set by the UI to start a turntable capture into the given directory
+    pub start_turntable: Option<PathBuf>,
+    pub stop_turntable: bool,
+    /// Capture in progress, it drives the camera until it ends
+    pub turntable: Option<Turntable>,
+    pub panorama_settings: PanoramaSettings,
+    /// Set by the UI to write a panorama around the camera to the given PNG or EXR file
+    pub capture_panorama: Option<PathBuf>,
+    pub path_playback: PathPlayback,
+    /// Set by the UI to start capturing the camera path into the given directory
+    pub start_path_capture: Option<PathBuf>,
+    pub stop_path_capture: bool,
+    /// Capture in progress, it drives the path playback until it ends
+    pub path_capture: Option<PathCapture>,
+    pub sweep_settings: SweepSettings,
+    /// Set by the UI or `--sweep` to capture the settings matrix into the given PNG
+    pub start_sweep: Option<PathBuf>,
+    pub stop_sweep: bool,
+    /// Sweep in progress, it drives the swept settings until it ends
+    pub sweep: Option<Sweep>,
+    /// Set by `--benchmark` to start once the scene loaded
+    pub start_benchmark: Option<BenchmarkSettings>,
+    /// Benchmark in progress, it drives the camera until it ends
+    pub benchmark: Option<Benchmark>,
+    pub streaming: StreamingSettings,
+    /// Chunks of the scene file in memory and being loaded
+    pub resident_chunks: usize,
+    pub loading_chunks: usize,
 }
 
 impl AppState {
     pub fn new() -> Self {
         let camera = camera::Camera::new((0.0, 5.0, 10.0), -90.0, -20.0);
-        let projection = camera::Projection::new(1, 1, 45.0, 0.1, 100.0);
+        let clip_planes = camera::ClipPlanes::default();
+        let projection = camera::Projection::new(1, 1, 45.0, clip_planes.znear, clip_planes.zfar);
         let camera_controller = camera::CameraController::new(4.0, 0.4);
         Self {
             scale_factor: 1.0,
+            show_help: false,
+            log: logging::buffer(),
             light_input: ["0.0".to_owned(), "0.0".to_owned(), "0.0".to_owned()],
             enable_normal_map: true,
+            hard_edge_angle: 30.0,
+            enable_reflection_probes: true,
+            reflection_probe_intensity: 1.0,
+            planar_reflections: true,
+            hover_highlight: true,
+            probe_convergence_threshold: 0.001,
+            play_animation: true,
+            render_stages: StageKind::RENDERER.map(StageSlot::new).to_vec(),
             camera,
             projection,
             camera_controller,
             ..Default::default()
         }
     }
+    /// Write the scene file next to the current scene and report how it went.
+    pub fn save_scene_file(&self) {
+        let Some(scene_path) = &self.scene_path else {
+            return;
+        };
+        match self.scene_file.save(scene_path) {
+            Ok(()) => self.notifications.info(format!(
+                "Saved {}",
+                SceneFile::path_for(scene_path).display()
+            )),
+            Err(err) => self
+                .notifications
+                .error(format!("Failed to save the scene file: {}", err)),
+        }
+    }
+
+    /// Whether egui is drawn this frame and takes the input it wants.
+    pub fn ui_visible(&self) -> bool {
+        let capturing = self.turntable.is_some()
+            || self.path_capture.is_some()
+            || self.sweep.is_some()
+            || self.benchmark.is_some();
+        let hidden = self.ui_hidden || self.clean_capture && capturing;
+        !hidden
+    }
+
+    /// Move the camera to the pose of the bookmark at `index`, if there is one.
+    pub fn restore_bookmark(&mut self, index: usize) {
+        if let Some(bookmark) = self.scene_file.camera_bookmarks.get(index) {
+            bookmark.restore(
+                &mut self.camera,
+                &mut self.projection,
+                &mut self.clip_planes,
+            );
+        }
+    }
 }
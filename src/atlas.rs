@@ -0,0 +1,179 @@
+use std::{collections::HashMap, sync::Arc};
+
+use glam::{vec4, Vec4};
+use image::{DynamicImage, GenericImageView, RgbaImage};
+
+use crate::{loader::MeshData, logging, texture::TextureImage};
+
+/// Pixels of edge extrusion around every packed texture, keeps linear filtering
+/// from bleeding between neighbours.
+const PADDING: u32 = 1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasOptions {
+    /// Textures with both sides up to this size are candidates for packing.
+    pub max_texture_size: u32,
+    pub atlas_size: u32,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self {
+            max_texture_size: 256,
+            atlas_size: 2048,
+        }
+    }
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    cursor: u32,
+}
+
+struct AtlasBuilder {
+    image: RgbaImage,
+    shelves: Vec<Shelf>,
+}
+
+impl AtlasBuilder {
+    fn new(size: u32) -> Self {
+        Self {
+            image: RgbaImage::new(size, size),
+            shelves: vec![],
+        }
+    }
+
+    /// Find room for a `width` x `height` rectangle using shelf packing.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let size = self.image.width();
+        if let Some(shelf) = self
+            .shelves
+            .iter_mut()
+            .find(|s| s.height >= height && s.cursor + width <= size)
+        {
+            let x = shelf.cursor;
+            shelf.cursor += width;
+            return Some((x, shelf.y));
+        }
+        let y = self.shelves.last().map_or(0, |s| s.y + s.height);
+        if y + height > size || width > size {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height,
+            cursor: width,
+        });
+        Some((0, y))
+    }
+
+    /// Copy `img` into the atlas at (x, y), extruding its border into the padding.
+    fn blit(&mut self, img: &DynamicImage, x: u32, y: u32) {
+        let rgba = img.to_rgba8();
+        let (w, h) = rgba.dimensions();
+        for py in 0..h + 2 * PADDING {
+            for px in 0..w + 2 * PADDING {
+                let sx = px.saturating_sub(PADDING).min(w - 1);
+                let sy = py.saturating_sub(PADDING).min(h - 1);
+                self.image
+                    .put_pixel(x + px, y + py, *rgba.get_pixel(sx, sy));
+            }
+        }
+    }
+}
+
+/// Merge small color textures of `meshes` into shared atlases.
+///
+/// Only meshes whose texture coordinates stay inside `[0, 1]` are packed since
+/// repeating textures cannot be expressed inside an atlas. Packed materials get
+/// their color texture replaced by the atlas and an UV transform pointing to
+/// their sub-rectangle. Packed meshes without other maps then share one
+/// material bind group, see `SceneUpload::material_bind_group`.
+pub fn pack_color_textures(meshes: &mut [MeshData], options: AtlasOptions) {
+    let fits = |img: &DynamicImage| {
+        let (w, h) = img.dimensions();
+        w <= options.max_texture_size && h <= options.max_texture_size
+    };
+    let mut candidates = meshes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, mesh)| {
+            let texture = mesh.material.as_ref()?.color_texture.as_ref()?;
+            // Compressed textures are uploaded as is
+            let TextureImage::Decoded(img) = texture.as_ref() else {
+                return None;
+            };
+            let in_unit_range = mesh
+                .model
+                .texcoords()
+                .iter()
+                .all(|uv| uv.cmpge(glam::Vec2::ZERO).all() && uv.cmple(glam::Vec2::ONE).all());
+            (fits(img) && in_unit_range).then(|| (i, texture.clone()))
+        })
+        .collect::<Vec<_>>();
+    if candidates.len() < 2 {
+        return;
+    }
+    // Taller textures first gives tighter shelves
+    candidates.sort_by_key(|(_, img)| std::cmp::Reverse(img.dimensions().1));
+
+    let mut atlases: Vec<AtlasBuilder> = vec![];
+    // image identity -> (atlas index, uv transform), identical images are packed once
+    let mut placed: HashMap<*const TextureImage, (usize, Vec4)> = HashMap::new();
+    let mut assignments = vec![];
+    for (mesh_index, texture) in candidates {
+        let key = Arc::as_ptr(&texture);
+        let TextureImage::Decoded(img) = texture.as_ref() else {
+            continue;
+        };
+        if let Some(&placement) = placed.get(&key) {
+            assignments.push((mesh_index, placement));
+            continue;
+        }
+        let (w, h) = img.dimensions();
+        let (padded_w, padded_h) = (w + 2 * PADDING, h + 2 * PADDING);
+        let slot = atlases
+            .iter_mut()
+            .enumerate()
+            .find_map(|(i, atlas)| atlas.allocate(padded_w, padded_h).map(|pos| (i, pos)));
+        let (atlas_index, (x, y)) = match slot {
+            Some(slot) => slot,
+            None => {
+                let mut atlas = AtlasBuilder::new(options.atlas_size);
+                let Some(pos) = atlas.allocate(padded_w, padded_h) else {
+                    continue;
+                };
+                atlases.push(atlas);
+                (atlases.len() - 1, pos)
+            }
+        };
+        atlases[atlas_index].blit(img, x, y);
+        let size = options.atlas_size as f32;
+        let transform = vec4(
+            w as f32 / size,
+            h as f32 / size,
+            (x + PADDING) as f32 / size,
+            (y + PADDING) as f32 / size,
+        );
+        placed.insert(key, (atlas_index, transform));
+        assignments.push((mesh_index, (atlas_index, transform)));
+    }
+
+    let atlases = atlases
+        .into_iter()
+        .map(|atlas| Arc::new(TextureImage::Decoded(DynamicImage::ImageRgba8(atlas.image))))
+        .collect::<Vec<_>>();
+    tracing::info!(
+        target: logging::SCENE,
+        "packed {} textures into {} atlases",
+        placed.len(),
+        atlases.len()
+    );
+    for (mesh_index, (atlas_index, transform)) in assignments {
+        if let Some(material) = meshes[mesh_index].material.as_mut() {
+            material.color_texture = Some(atlases[atlas_index].clone());
+            material.color_uv_transform = Some(transform);
+        }
+    }
+}
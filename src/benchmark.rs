@@ -0,0 +1,322 @@
+//! Plays a fixed camera path over the scene for a number of frames and writes
+//! the frame times and the GPU time of every render graph pass into a report,
+//! so runs on different commits can be compared.
+//!
+//! The path is the camera path of the scene file stretched over the frames, or
+//! an orbit around the scene when it has none. Frames are only recorded once
+//! the reflection probes finished baking.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    camera::Camera,
+    flythrough::CameraPath,
+    gpu_timer::PassProfile,
+    turntable::{Turntable, TurntableSettings},
+    AppState,
+};
+
+/// Frames rendered after the probes finished baking that still pay for the
+/// bake or for pipelines created on first use, left out of the report.
+const WARMUP_FRAMES: u32 = 8;
+
+/// Benchmark options given with `--benchmark=FRAMES`.
+#[derive(Debug, Clone)]
+pub struct BenchmarkSettings {
+    /// Frames recorded along the path
+    pub frames: u32,
+    /// CSV report, or JSON when the extension is `.json`
+    pub output: PathBuf,
+}
+
+impl BenchmarkSettings {
+    /// Read `FRAMES` as given to `--benchmark`.
+    pub fn parse(frames: &str, output: &Path) -> Result<Self, String> {
+        let frames = frames
+            .parse::<u32>()
+            .ok()
+            .filter(|&frames| frames > 0)
+            .ok_or_else(|| format!("expected --benchmark=FRAMES, got {:?}", frames))?;
+        Ok(Self {
+            frames,
+            output: output.to_owned(),
+        })
+    }
+}
+
+/// Where the camera goes during a benchmark.
+#[derive(Debug, Clone)]
+pub enum BenchmarkPath {
+    /// Played from start to end over the recorded frames
+    Camera(CameraPath),
+    /// One revolution over the recorded frames
+    Orbit(Turntable),
+}
+
+impl BenchmarkPath {
+    /// The camera path of the scene file, or an orbit around `pivot` from
+    /// `camera` without one.
+    pub fn new(camera_path: &CameraPath, pivot: glam::Vec3, camera: &Camera, frames: u32) -> Self {
+        if camera_path.keyframes.is_empty() {
+            let settings = TurntableSettings {
+                frames,
+                pivot_object: None,
+            };
+            Self::Orbit(Turntable::new(&settings, pivot, camera, Path::new("")))
+        } else {
+            Self::Camera(camera_path.clone())
+        }
+    }
+}
+
+/// Times of one recorded frame.
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameRecord {
+    pub frame: u32,
+    /// Wall clock time since the frame before
+    pub frame_ms: f32,
+    /// Spent on the CPU updating, recording and submitting the frame
+    pub cpu_ms: f32,
+    /// From the start of the first pass to the end of the last, `None` when
+    /// the frame was not measured
+    pub gpu_ms: Option<f32>,
+    /// GPU time of every pass in the order of `BenchmarkReport::passes`, empty
+    /// when the frame was not measured
+    pub pass_ms: Vec<f32>,
+    /// `AppState::frame_index`, matches the GPU times read back later
+    #[serde(skip)]
+    frame_index: u64,
+}
+
+/// Mean and spread of one column of the report.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Statistics {
+    pub mean: f32,
+    pub median: f32,
+    pub p95: f32,
+    pub max: f32,
+}
+
+impl Statistics {
+    /// `None` without any values.
+    fn of(values: impl IntoIterator<Item = f32>) -> Option<Self> {
+        let mut values = values.into_iter().collect::<Vec<_>>();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f32::total_cmp);
+        let at = |fraction: f32| values[((values.len() - 1) as f32 * fraction).round() as usize];
+        Some(Self {
+            mean: values.iter().sum::<f32>() / values.len() as f32,
+            median: at(0.5),
+            p95: at(0.95),
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct PassSummary {
+    name: &'static str,
+    #[serde(flatten)]
+    statistics: Statistics,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Summary {
+    frame_ms: Option<Statistics>,
+    cpu_ms: Option<Statistics>,
+    gpu_ms: Option<Statistics>,
+    passes: Vec<PassSummary>,
+}
+
+/// Layout of the JSON report.
+#[derive(Debug, Clone, Serialize)]
+struct BenchmarkReport<'a> {
+    scene: Option<&'a Path>,
+    adapter: &'a str,
+    resolution: (u32, u32),
+    frames: u32,
+    /// Frames whose GPU times were read back
+    measured_frames: usize,
+    passes: &'a [&'static str],
+    summary: Summary,
+    records: &'a [FrameRecord],
+}
+
+/// A running benchmark, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    settings: BenchmarkSettings,
+    path: BenchmarkPath,
+    /// Frames rendered since the probes finished baking, counting the warmup
+    settled_frames: u32,
+    pass_names: Vec<&'static str>,
+    resolution: (u32, u32),
+    records: Vec<FrameRecord>,
+}
+
+impl Benchmark {
+    pub fn new(
+        settings: BenchmarkSettings,
+        path: BenchmarkPath,
+        pass_names: Vec<&'static str>,
+        resolution: (u32, u32),
+    ) -> Self {
+        Self {
+            records: Vec::with_capacity(settings.frames as usize),
+            settings,
+            path,
+            settled_frames: 0,
+            pass_names,
+            resolution,
+        }
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.settings.frames
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.settings.output
+    }
+
+    /// The warmup is over, the frame being recorded goes into the report.
+    pub fn recording(&self) -> bool {
+        self.settled_frames >= WARMUP_FRAMES
+    }
+
+    /// Put `camera` where the frame being rendered sees the scene from, the
+    /// warmup stays at the start of the path.
+    pub fn place_camera(&mut self, camera: &mut Camera) {
+        let frame = self.records.len() as u32;
+        match &mut self.path {
+            BenchmarkPath::Camera(path) => {
+                let progress = frame as f32 / (self.settings.frames - 1).max(1) as f32;
+                path.place_camera(progress * path.duration(), camera);
+            }
+            BenchmarkPath::Orbit(turntable) => {
+                turntable.frame = frame;
+                turntable.place_camera(camera);
+            }
+        }
+    }
+
+    /// Call after each submitted frame with the CPU time it took, true once
+    /// every frame is recorded.
+    pub fn frame_rendered(&mut self, state: &AppState, cpu_ms: f32) -> bool {
+        if !self.recording() {
+            if state.bake_reflection_probes || state.probe_bake.is_pending() {
+                self.settled_frames = 0;
+            } else {
+                self.settled_frames += 1;
+            }
+            return false;
+        }
+        self.records.push(FrameRecord {
+            frame: self.records.len() as u32,
+            frame_ms: state.frame_time_ms,
+            cpu_ms,
+            gpu_ms: None,
+            pass_ms: Vec::new(),
+            frame_index: state.frame_index,
+        });
+        self.records.len() as u32 >= self.settings.frames
+    }
+
+    /// Fill in the GPU times of a recorded frame once they were read back.
+    pub fn record_gpu(&mut self, profile: PassProfile) {
+        if let Some(record) = self
+            .records
+            .iter_mut()
+            .find(|record| record.frame_index == profile.frame)
+        {
+            record.gpu_ms = Some(profile.total_ms);
+            record.pass_ms = profile.pass_ms;
+        }
+    }
+
+    fn summary(&self) -> Summary {
+        let measured = || self.records.iter().filter(|record| record.gpu_ms.is_some());
+        Summary {
+            frame_ms: Statistics::of(self.records.iter().map(|record| record.frame_ms)),
+            cpu_ms: Statistics::of(self.records.iter().map(|record| record.cpu_ms)),
+            gpu_ms: Statistics::of(measured().filter_map(|record| record.gpu_ms)),
+            passes: self
+                .pass_names
+                .iter()
+                .enumerate()
+                .filter_map(|(index, &name)| {
+                    let statistics =
+                        Statistics::of(measured().map(|record| record.pass_ms[index]))?;
+                    Some(PassSummary { name, statistics })
+                })
+                .collect(),
+        }
+    }
+
+    /// One row per frame, cells of times that were not measured stay empty.
+    fn csv(&self) -> String {
+        let mut csv = String::from("frame,frame_ms,cpu_ms,gpu_ms");
+        for name in &self.pass_names {
+            write!(csv, ",{}_ms", name.replace(' ', "_")).unwrap();
+        }
+        csv.push('\n');
+        for record in &self.records {
+            write!(
+                csv,
+                "{},{:.4},{:.4},",
+                record.frame, record.frame_ms, record.cpu_ms
+            )
+            .unwrap();
+            if let Some(gpu_ms) = record.gpu_ms {
+                write!(csv, "{:.4}", gpu_ms).unwrap();
+            }
+            for index in 0..self.pass_names.len() {
+                csv.push(',');
+                if let Some(pass_ms) = record.pass_ms.get(index) {
+                    write!(csv, "{:.4}", pass_ms).unwrap();
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Write the report, returns the mean frame time. Frames whose GPU times
+    /// were still being read back are written without them.
+    pub fn finish(self, state: &AppState) -> anyhow::Result<Option<Statistics>> {
+        let summary = self.summary();
+        let frame_ms = summary.frame_ms;
+        let json = self
+            .settings
+            .output
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let report = if json {
+            serde_json::to_string_pretty(&BenchmarkReport {
+                scene: state.scene_path.as_deref(),
+                adapter: &state.capabilities.adapter_name,
+                resolution: self.resolution,
+                frames: self.settings.frames,
+                measured_frames: self
+                    .records
+                    .iter()
+                    .filter(|record| record.gpu_ms.is_some())
+                    .count(),
+                passes: &self.pass_names,
+                summary,
+                records: &self.records,
+            })?
+        } else {
+            self.csv()
+        };
+        std::fs::write(&self.settings.output, report)?;
+        Ok(frame_ms)
+    }
+}
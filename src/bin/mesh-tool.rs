@@ -0,0 +1,9 @@
+//! Prepares meshes with the loader of the viewer, see `mesh_tool::run`.
+
+use radiance_cascade::{logging, mesh_tool};
+
+fn main() {
+    logging::init();
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    std::process::exit(if mesh_tool::run(&args) { 0 } else { 1 });
+}
@@ -1,30 +1,40 @@
 use bytemuck::{Pod, Zeroable};
-use glam::{Mat4, Vec4};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
 use std::f32::consts::FRAC_PI_2;
 use std::time::Duration;
-use winit::dpi::PhysicalPosition;
-use winit::event::*;
-use winit::keyboard::{Key, KeyCode, NamedKey, PhysicalKey};
+
+use crate::input::{Axis, InputState};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable, Default)]
 pub struct UniformCamera {
     matrix: Mat4,
     eye: Vec4,
+    /// Back from clip space, for shaders tracing view rays
+    inverse_matrix: Mat4,
 }
 
 impl UniformCamera {
     pub fn from_camera_project(camera: &Camera, projection: &Projection) -> Self {
+        Self::from_matrix(
+            projection.calc_matrix() * camera.calc_matrix(),
+            camera.position,
+        )
+    }
+
+    pub fn from_matrix(matrix: Mat4, eye: glam::Vec3) -> Self {
         Self {
-            eye: camera.position.extend(1.0),
-            matrix: projection.calc_matrix() * camera.calc_matrix(),
+            matrix,
+            eye: eye.extend(1.0),
+            inverse_matrix: matrix.inverse(),
         }
     }
 }
 
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Camera {
     pub position: glam::Vec3,
     yaw: f32,
@@ -40,6 +50,35 @@ impl Camera {
         }
     }
 
+    /// Yaw and pitch in radians.
+    pub fn orientation(&self) -> (f32, f32) {
+        (self.yaw, self.pitch)
+    }
+
+    pub fn set_orientation(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2);
+    }
+
+    /// The pose `t` of the way from `self` to `other`.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, t),
+            yaw: self.yaw + (other.yaw - self.yaw) * t,
+            pitch: self.pitch + (other.pitch - self.pitch) * t,
+        }
+    }
+
+    /// Turn towards `target` without moving.
+    pub fn look_at(&mut self, target: glam::Vec3) {
+        let direction = (target - self.position).normalize_or_zero();
+        if direction == glam::Vec3::ZERO {
+            return;
+        }
+        self.yaw = direction.z.atan2(direction.x);
+        self.pitch = direction.y.asin().clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2);
+    }
+
     pub fn calc_matrix(&self) -> glam::Mat4 {
         let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
@@ -52,12 +91,24 @@ impl Camera {
     }
 }
 
+/// How the view is projected onto the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProjectionMode {
+    #[default]
+    Perspective,
+    /// Parallel rays, sizes do not shrink with distance
+    Orthographic,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Projection {
     aspect: f32,
     fovy: f32,
     znear: f32,
     zfar: f32,
+    mode: ProjectionMode,
+    /// World space height of the orthographic view
+    ortho_height: f32,
 }
 
 impl Projection {
@@ -67,6 +118,8 @@ impl Projection {
             fovy: fovy.to_radians(),
             znear,
             zfar,
+            mode: ProjectionMode::Perspective,
+            ortho_height: 10.0,
         }
     }
 
@@ -75,121 +128,247 @@ impl Projection {
     }
 
     pub fn calc_matrix(&self) -> glam::Mat4 {
-        glam::Mat4::perspective_rh(self.fovy, self.aspect, self.znear, self.zfar)
+        match self.mode {
+            ProjectionMode::Perspective => {
+                glam::Mat4::perspective_rh(self.fovy, self.aspect, self.znear, self.zfar)
+            }
+            ProjectionMode::Orthographic => {
+                let half_height = self.ortho_height * 0.5;
+                let half_width = half_height * self.aspect;
+                glam::Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.znear,
+                    self.zfar,
+                )
+            }
+        }
+    }
+
+    pub fn mode(&self) -> ProjectionMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ProjectionMode) {
+        self.mode = mode;
+    }
+
+    /// Vertical field of view in radians.
+    pub fn fovy(&self) -> f32 {
+        self.fovy
+    }
+
+    pub fn set_fovy(&mut self, fovy: f32) {
+        self.fovy = fovy;
+    }
+
+    pub fn ortho_height(&self) -> f32 {
+        self.ortho_height
+    }
+
+    pub fn set_ortho_height(&mut self, height: f32) {
+        self.ortho_height = height;
+    }
+
+    /// Orthographic height framing what the perspective view shows at `distance`.
+    pub fn matching_ortho_height(&self, distance: f32) -> f32 {
+        2.0 * distance * (self.fovy * 0.5).tan()
+    }
+
+    pub fn planes(&self) -> (f32, f32) {
+        (self.znear, self.zfar)
+    }
+
+    pub fn set_planes(&mut self, znear: f32, zfar: f32) {
+        self.znear = znear;
+        self.zfar = zfar;
+    }
+
+    /// Smallest depth difference the depth buffer resolves at `distance` from the eye.
+    pub fn depth_resolution(&self, distance: f32) -> f32 {
+        let (n, f) = (self.znear, self.zfar);
+        match self.mode {
+            // Depth is f / (f - n) * (1 - n / z), stored as a float close to one
+            // whose steps are half an epsilon
+            ProjectionMode::Perspective => {
+                f32::EPSILON * 0.5 * (f - n) * distance * distance / (f * n)
+            }
+            // Depth is linear, the steps close to one are the coarsest
+            ProjectionMode::Orthographic => f32::EPSILON * 0.5 * (f - n),
+        }
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct CameraController {
-    amount_left: f32,
-    amount_right: f32,
-    amount_forward: f32,
-    amount_backward: f32,
-    amount_up: f32,
-    amount_down: f32,
-    rotate_horizontal: f32,
-    rotate_vertical: f32,
-    scroll: f32,
-    speed: f32,
-    sensitivity: f32,
+/// Near plane distance relative to the scene radius.
+const NEAR_PLANE_FRACTION: f32 = 1e-3;
+
+/// Depth steps coarser than this fraction of the scene radius show as z-fighting.
+pub const POOR_DEPTH_RESOLUTION: f32 = 1e-3;
+
+/// How the near and far planes are chosen.
+#[derive(Debug, Clone, Copy)]
+pub struct ClipPlanes {
+    /// Fit the planes to the scene bounds every frame
+    pub auto: bool,
+    /// The fitted planes while `auto` is on
+    pub znear: f32,
+    pub zfar: f32,
 }
 
-impl CameraController {
-    pub fn new(speed: f32, sensitivity: f32) -> Self {
+impl Default for ClipPlanes {
+    fn default() -> Self {
         Self {
-            amount_left: 0.0,
-            amount_right: 0.0,
-            amount_forward: 0.0,
-            amount_backward: 0.0,
-            amount_up: 0.0,
-            amount_down: 0.0,
-            rotate_horizontal: 0.0,
-            rotate_vertical: 0.0,
-            scroll: 0.0,
-            speed,
-            sensitivity,
+            auto: true,
+            znear: 0.1,
+            zfar: 100.0,
         }
     }
+}
 
-    pub fn process_keyboard(
-        &mut self,
-        physical_key: &PhysicalKey,
-        logical_key: &Key,
-        state: ElementState,
-    ) -> bool {
-        let amount = if state == ElementState::Pressed {
-            1.0
-        } else {
-            0.0
-        };
-        match logical_key {
-            Key::Named(NamedKey::Space) => {
-                self.amount_up = amount;
-                return true;
-            }
-            _ => {}
+impl ClipPlanes {
+    /// Planes keeping the bounds `min`..`max` in view from `eye`. The near plane
+    /// scales with the scene, so tiny and huge models get the same precision.
+    pub fn fit(eye: Vec3, min: Vec3, max: Vec3) -> (f32, f32) {
+        let center = (min + max) * 0.5;
+        let radius = (max - min).length().max(1e-3) * 0.5;
+        let distance = eye.distance(center);
+        let zfar = (distance + radius) * 1.01;
+        // Nothing of the scene is closer than its bounding sphere
+        let znear = (radius * NEAR_PLANE_FRACTION).max((distance - radius) * 0.9);
+        (znear, zfar)
+    }
+}
+
+/// Named camera pose stored in the scene file, the first nine are restored with
+/// the number keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub position: [f32; 3],
+    /// Radians
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Vertical field of view in radians
+    pub fovy: f32,
+    /// Near and far plane, `None` while they are fitted to the scene
+    pub clip_planes: Option<[f32; 2]>,
+}
+
+impl CameraBookmark {
+    pub fn capture(
+        name: String,
+        camera: &Camera,
+        projection: &Projection,
+        clip_planes: &ClipPlanes,
+    ) -> Self {
+        Self {
+            name,
+            position: camera.position.to_array(),
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            fovy: projection.fovy,
+            clip_planes: (!clip_planes.auto).then_some([clip_planes.znear, clip_planes.zfar]),
         }
-        match physical_key {
-            PhysicalKey::Code(KeyCode::ShiftLeft) => {
-                self.amount_down = amount;
-                true
-            }
-            PhysicalKey::Code(KeyCode::KeyW) => {
-                self.amount_forward = amount;
-                true
-            }
-            PhysicalKey::Code(KeyCode::KeyA) => {
-                self.amount_left = amount;
-                true
-            }
-            PhysicalKey::Code(KeyCode::KeyS) => {
-                self.amount_backward = amount;
-                true
-            }
-            PhysicalKey::Code(KeyCode::KeyD) => {
-                self.amount_right = amount;
-                true
-            }
-            _ => false,
+    }
+
+    pub fn restore(
+        &self,
+        camera: &mut Camera,
+        projection: &mut Projection,
+        clip_planes: &mut ClipPlanes,
+    ) {
+        camera.position = Vec3::from_array(self.position);
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+        projection.fovy = self.fovy;
+        clip_planes.auto = self.clip_planes.is_none();
+        if let Some([znear, zfar]) = self.clip_planes {
+            (clip_planes.znear, clip_planes.zfar) = (znear, zfar);
+            projection.set_planes(znear, zfar);
         }
     }
+}
 
-    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
+#[derive(Debug, Clone, Default)]
+pub struct CameraController {
+    speed: f32,
+    sensitivity: f32,
+    /// Carried over between frames so motion eases in and out
+    velocity: Vec3,
+    /// Smoothed mouse delta applied to the view direction
+    look: Vec2,
+    /// Seconds to get most of the way to full speed while keys are held, 0 starts instantly
+    pub acceleration: f32,
+    /// Seconds to coast most of the way to a halt once keys are released, 0 stops instantly
+    pub damping: f32,
+    /// Seconds mouse look trails behind the mouse, 0 follows it exactly
+    pub look_smoothing: f32,
+}
+
+/// Fraction of the way to a target covered during `dt` when approaching it
+/// exponentially with `time_constant`.
+fn smoothing(time_constant: f32, dt: f32) -> f32 {
+    if time_constant > 0.0 {
+        1.0 - (-dt / time_constant).exp()
+    } else {
+        1.0
     }
+}
 
-    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = -match delta {
-            // 假定一行为 100 个像素，你可以随意修改这个值
-            MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
-            MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => *scroll as f32,
-        };
+impl CameraController {
+    pub fn new(speed: f32, sensitivity: f32) -> Self {
+        Self {
+            speed,
+            sensitivity,
+            velocity: Vec3::ZERO,
+            look: Vec2::ZERO,
+            acceleration: 0.15,
+            damping: 0.3,
+            look_smoothing: 0.05,
+        }
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+    /// Move and turn `camera` by the axes, look and scroll of `input`.
+    pub fn update_camera(&mut self, camera: &mut Camera, input: &InputState, dt: Duration) {
         let dt = dt.as_secs_f32();
 
         let (yaw_sin, yaw_cos) = camera.yaw.sin_cos();
         let forward = glam::Vec3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = glam::Vec3::new(-yaw_sin, 0.0, yaw_cos).normalize();
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
+        let target_velocity = (forward * input.axis(Axis::Forward)
+            + right * input.axis(Axis::Strafe)
+            + Vec3::Y * input.axis(Axis::Lift))
+            * self.speed;
+        let time_constant = if target_velocity == Vec3::ZERO {
+            self.damping
+        } else {
+            self.acceleration
+        };
+        self.velocity = self
+            .velocity
+            .lerp(target_velocity, smoothing(time_constant, dt));
+        // Settle exactly, a lingering drift would keep the view changing
+        if self.velocity.length_squared() < 1e-6 * self.speed * self.speed {
+            self.velocity = Vec3::ZERO;
+        }
+        camera.position += self.velocity * dt;
 
         let (pitch_sin, pitch_cos) = camera.pitch.sin_cos();
         let scrollward =
             glam::Vec3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
-        camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
-        self.scroll = 0.0;
-
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
+        camera.position += scrollward * input.scroll() * self.speed * self.sensitivity * dt;
 
         // 旋转
-        camera.yaw += self.rotate_horizontal * self.sensitivity * dt;
-        camera.pitch += -self.rotate_vertical * self.sensitivity * dt;
-
-        self.rotate_horizontal = 0.0;
-        self.rotate_vertical = 0.0;
+        self.look = self
+            .look
+            .lerp(input.look(), smoothing(self.look_smoothing, dt));
+        if self.look.length_squared() < 1e-6 {
+            self.look = Vec2::ZERO;
+        }
+        camera.yaw += self.look.x * self.sensitivity * dt;
+        camera.pitch += -self.look.y * self.sensitivity * dt;
 
         if camera.pitch < -SAFE_FRAC_PI_2 {
             camera.pitch = -SAFE_FRAC_PI_2;
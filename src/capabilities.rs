@@ -0,0 +1,141 @@
+use wgpu::{naga, DownlevelFlags, Features};
+
+use crate::logging;
+
+/// What the adapter supports, queried once at startup. Subsystems check these
+/// flags to pick a fallback instead of failing device creation.
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+    pub adapter_name: String,
+    /// Non-uniformly indexed texture arrays
+    pub bindless: bool,
+    pub ray_query: bool,
+    pub multiview: bool,
+    pub timestamp_queries: bool,
+    /// Timestamps between passes, needed by the benchmark pass breakdown
+    pub encoder_timestamps: bool,
+    pub texture_compression_bc: bool,
+    pub texture_compression_astc: bool,
+    /// Format support beyond the guaranteed WebGPU set, used by the render settings
+    pub adapter_format_features: bool,
+    /// Needed by the frame statistics
+    pub compute_shaders: bool,
+    /// Needed by the reflection probes
+    pub cube_arrays: bool,
+    /// Storage buffers written by fragment shaders, needed by the quad usage
+    /// heatmap
+    pub fragment_storage: bool,
+    /// Indirect draws starting at any instance, needed by the indirect draw path
+    pub indirect_first_instance: bool,
+    /// Several indirect draws per call, otherwise they are issued one at a time
+    pub multi_draw_indirect: bool,
+    /// Limits requested for the device, the defaults if the adapter meets them
+    pub limits: wgpu::Limits,
+}
+
+impl Capabilities {
+    pub fn query(adapter: &wgpu::Adapter) -> Self {
+        let features = adapter.features();
+        let downlevel = adapter.get_downlevel_capabilities().flags;
+        let limits = if wgpu::Limits::default().check_limits(&adapter.limits()) {
+            wgpu::Limits::default()
+        } else {
+            wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits())
+        };
+        let capabilities = Self {
+            adapter_name: adapter.get_info().name,
+            bindless: features.contains(
+                Features::TEXTURE_BINDING_ARRAY
+                    | Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+            ),
+            ray_query: features
+                .contains(Features::RAY_QUERY | Features::RAY_TRACING_ACCELERATION_STRUCTURE),
+            multiview: features.contains(Features::MULTIVIEW),
+            timestamp_queries: features.contains(Features::TIMESTAMP_QUERY),
+            encoder_timestamps: features
+                .contains(Features::TIMESTAMP_QUERY | Features::TIMESTAMP_QUERY_INSIDE_ENCODERS),
+            texture_compression_bc: features.contains(Features::TEXTURE_COMPRESSION_BC),
+            texture_compression_astc: features.contains(Features::TEXTURE_COMPRESSION_ASTC),
+            adapter_format_features: features
+                .contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES),
+            compute_shaders: downlevel.contains(DownlevelFlags::COMPUTE_SHADERS),
+            cube_arrays: downlevel.contains(DownlevelFlags::CUBE_ARRAY_TEXTURES),
+            fragment_storage: downlevel.contains(DownlevelFlags::FRAGMENT_WRITABLE_STORAGE),
+            indirect_first_instance: features.contains(Features::INDIRECT_FIRST_INSTANCE)
+                && downlevel.contains(DownlevelFlags::INDIRECT_EXECUTION),
+            multi_draw_indirect: features.contains(Features::MULTI_DRAW_INDIRECT),
+            limits,
+        };
+        tracing::info!(
+            target: logging::GPU,
+            "adapter {}: compute {}, cube arrays {}, fragment storage {}, BC {}, ASTC {}, \
+             timestamps {}, indirect {}, multi draw {}, bindless {}, ray query {}, multiview {}",
+            capabilities.adapter_name,
+            capabilities.compute_shaders,
+            capabilities.cube_arrays,
+            capabilities.fragment_storage,
+            capabilities.texture_compression_bc,
+            capabilities.texture_compression_astc,
+            capabilities.timestamp_queries,
+            capabilities.indirect_first_instance,
+            capabilities.multi_draw_indirect,
+            capabilities.bindless,
+            capabilities.ray_query,
+            capabilities.multiview,
+        );
+        if !capabilities.cube_arrays {
+            tracing::warn!(
+                target: logging::GPU,
+                "{} cannot sample cube arrays, reflection probes will fail",
+                capabilities.adapter_name
+            );
+        }
+        capabilities
+    }
+
+    /// What naga validates shaders against, derived the way wgpu derives it from
+    /// the device features and downlevel flags. None of the optional `features`
+    /// enables a shader capability so far.
+    pub fn shader_capabilities(&self) -> naga::valid::Capabilities {
+        let mut capabilities = naga::valid::Capabilities::empty();
+        capabilities.set(
+            naga::valid::Capabilities::CUBE_ARRAY_TEXTURES,
+            self.cube_arrays,
+        );
+        capabilities
+    }
+
+    /// Optional features to enable on the device. Bindless, ray query and
+    /// multiview are only reported until something uses them.
+    pub fn features(&self) -> Features {
+        [
+            (
+                self.texture_compression_bc,
+                Features::TEXTURE_COMPRESSION_BC,
+            ),
+            (
+                self.texture_compression_astc,
+                Features::TEXTURE_COMPRESSION_ASTC,
+            ),
+            (
+                self.adapter_format_features,
+                Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+            ),
+            (self.timestamp_queries, Features::TIMESTAMP_QUERY),
+            (
+                self.encoder_timestamps,
+                Features::TIMESTAMP_QUERY_INSIDE_ENCODERS,
+            ),
+            (
+                self.indirect_first_instance,
+                Features::INDIRECT_FIRST_INSTANCE,
+            ),
+            (self.multi_draw_indirect, Features::MULTI_DRAW_INDIRECT),
+        ]
+        .into_iter()
+        .filter(|(supported, _)| *supported)
+        .fold(Features::empty(), |features, (_, feature)| {
+            features | feature
+        })
+    }
+}
@@ -0,0 +1,141 @@
+//! 3D color lookup tables in the `.cube` format of Adobe and Resolve, read by
+//! the color grading post effect.
+//!
+//! A table maps each color of its domain to a graded color. The entries sample
+//! a cube of `size` steps per channel, red varying fastest. The grading shader
+//! interpolates between them in a 3D texture.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context};
+use glam::Vec3;
+
+/// Steps per channel of the largest table the format allows
+const MAX_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeLut {
+    /// Entries per channel
+    pub size: u32,
+    /// Input colors mapped to the first and last entry of each channel
+    pub domain_min: Vec3,
+    pub domain_max: Vec3,
+    /// `size` cubed graded colors, red fastest, then green, then blue
+    pub table: Vec<Vec3>,
+}
+
+impl Default for CubeLut {
+    /// Leaves every color as it is.
+    fn default() -> Self {
+        Self {
+            size: 2,
+            domain_min: Vec3::ZERO,
+            domain_max: Vec3::ONE,
+            table: (0..8)
+                .map(|i| Vec3::new((i & 1) as f32, ((i >> 1) & 1) as f32, (i >> 2) as f32))
+                .collect(),
+        }
+    }
+}
+
+impl CubeLut {
+    pub fn open<P: AsRef<Path>>(path: P) -> crate::error::Result<Self> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Self::parse(&text))
+            .map_err(|reason| crate::error::Error::Texture {
+                path: path.to_path_buf(),
+                reason,
+            })
+    }
+
+    /// Read a 3D table, 1D tables are not supported.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut size = None;
+        let mut domain_min = Vec3::ZERO;
+        let mut domain_max = Vec3::ONE;
+        let mut table = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let context = || format!("line {}", number + 1);
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            match keyword {
+                "LUT_1D_SIZE" => bail!("1D tables are not supported"),
+                "LUT_3D_SIZE" => {
+                    let value: u32 = words
+                        .next()
+                        .ok_or_else(|| anyhow!("missing size"))?
+                        .parse()
+                        .with_context(context)?;
+                    if !(2..=MAX_SIZE).contains(&value) {
+                        bail!("size {} out of range", value);
+                    }
+                    size = Some(value);
+                }
+                "DOMAIN_MIN" => domain_min = parse_color(words).with_context(context)?,
+                "DOMAIN_MAX" => domain_max = parse_color(words).with_context(context)?,
+                // Resolve writes one range for all channels
+                "LUT_3D_INPUT_RANGE" => {
+                    let mut bound = || -> anyhow::Result<f32> {
+                        Ok(words
+                            .next()
+                            .ok_or_else(|| anyhow!("expected two values"))?
+                            .parse()?)
+                    };
+                    domain_min = Vec3::splat(bound().with_context(context)?);
+                    domain_max = Vec3::splat(bound().with_context(context)?);
+                }
+                // Other keywords only describe the table
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => table.push(parse_color(line.split_whitespace()).with_context(context)?),
+            }
+        }
+        let size = size.ok_or_else(|| anyhow!("missing LUT_3D_SIZE"))?;
+        if table.len() != size.pow(3) as usize {
+            bail!(
+                "expected {} entries for size {}, found {}",
+                size.pow(3),
+                size,
+                table.len()
+            );
+        }
+        if domain_min.cmpge(domain_max).any() {
+            bail!("empty domain");
+        }
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// The table as RGBA8 texels, clamped to the displayable range.
+    pub fn rgba8(&self) -> Vec<u8> {
+        self.table
+            .iter()
+            .flat_map(|color| {
+                let [r, g, b] = color
+                    .clamp(Vec3::ZERO, Vec3::ONE)
+                    .to_array()
+                    .map(|c| (c * 255.0).round() as u8);
+                [r, g, b, 255]
+            })
+            .collect()
+    }
+}
+
+fn parse_color<'a>(mut words: impl Iterator<Item = &'a str>) -> anyhow::Result<Vec3> {
+    let mut channel = || -> anyhow::Result<f32> {
+        Ok(words
+            .next()
+            .ok_or_else(|| anyhow!("expected three values"))?
+            .parse()?)
+    };
+    Ok(Vec3::new(channel()?, channel()?, channel()?))
+}
@@ -0,0 +1,132 @@
+//! Pieces every compute pass needs: layout entries visible to compute shaders,
+//! pipeline creation, labelled passes and dispatch sizing. Work that runs once
+//! a frame on its own implements `ComputeStage`.
+
+use wgpu::Device;
+
+/// Invocations per workgroup, as declared by `@workgroup_size` in the shader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkgroupSize {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl WorkgroupSize {
+    pub const fn new(x: u32, y: u32, z: u32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Workgroups covering one invocation per element of `extent`, the last
+    /// workgroup along each axis may reach past it.
+    pub fn workgroups(self, [x, y, z]: [u32; 3]) -> [u32; 3] {
+        [x.div_ceil(self.x), y.div_ceil(self.y), z.div_ceil(self.z)]
+    }
+
+    /// Dispatch the workgroups covering `extent` with the pipeline and bind
+    /// groups set on `compute_pass`.
+    pub fn dispatch(self, compute_pass: &mut wgpu::ComputePass, extent: [u32; 3]) {
+        let [x, y, z] = self.workgroups(extent);
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+}
+
+pub fn uniform_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub fn storage_entry(binding: u32, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// A float texture read with `textureLoad`.
+pub fn texture_entry(
+    binding: u32,
+    view_dimension: wgpu::TextureViewDimension,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension,
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+        },
+        count: None,
+    }
+}
+
+/// Bind group binding each of `buffers` whole, at the binding of its index.
+pub fn buffer_bind_group(
+    device: &Device,
+    label: &str,
+    layout: &wgpu::BindGroupLayout,
+    buffers: &[&wgpu::Buffer],
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>(),
+        label: Some(label),
+    })
+}
+
+/// Pipeline running `entry_point` of `module` with `bind_group_layouts` as
+/// groups 0 and up, labelled `<name> Pipeline`.
+pub fn create_pipeline(
+    device: &Device,
+    name: &str,
+    module: &wgpu::ShaderModule,
+    entry_point: &str,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+) -> wgpu::ComputePipeline {
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(&format!("{} Pipeline Layout", name)),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(&format!("{} Pipeline", name)),
+        layout: Some(&pipeline_layout),
+        module,
+        entry_point: Some(entry_point),
+        compilation_options: wgpu::PipelineCompilationOptions::default(),
+        cache: None,
+    })
+}
+
+/// Compute pass labelled `Compute Pass: <name>` like the render passes.
+pub fn begin_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    name: &str,
+    timestamp_writes: Option<wgpu::ComputePassTimestampWrites<'a>>,
+) -> wgpu::ComputePass<'a> {
+    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some(&format!("Compute Pass: {}", name)),
+        timestamp_writes,
+    })
+}
@@ -0,0 +1,124 @@
+use image::{imageops, Rgba, RgbaImage};
+
+/// Cells are scaled down to this width, full window captures would make huge sheets.
+const CELL_WIDTH: u32 = 480;
+const MARGIN: u32 = 8;
+/// Image pixels per font pixel
+const TEXT_SCALE: u32 = 2;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const BACKGROUND: Rgba<u8> = Rgba([24, 24, 24, 255]);
+const TEXT: Rgba<u8> = Rgba([230, 230, 230, 255]);
+
+/// Capture placed on a contact sheet with a caption below it.
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub image: RgbaImage,
+    pub caption: String,
+}
+
+/// Lay `cells` out row by row, `columns` per row, scaled to the size of the first.
+pub fn compose(cells: &[Cell], columns: usize) -> RgbaImage {
+    let Some(first) = cells.first() else {
+        return RgbaImage::from_pixel(1, 1, BACKGROUND);
+    };
+    let columns = columns.max(1);
+    let rows = cells.len().div_ceil(columns);
+    let width = first.image.width().clamp(1, CELL_WIDTH);
+    let height = (first.image.height() * width / first.image.width().max(1)).max(1);
+    let caption_height = GLYPH_HEIGHT * TEXT_SCALE + MARGIN;
+    let (pitch_x, pitch_y) = (width + MARGIN, height + caption_height + MARGIN);
+    let mut sheet = RgbaImage::from_pixel(
+        columns as u32 * pitch_x + MARGIN,
+        rows as u32 * pitch_y + MARGIN,
+        BACKGROUND,
+    );
+    for (i, cell) in cells.iter().enumerate() {
+        let x = MARGIN + (i % columns) as u32 * pitch_x;
+        let y = MARGIN + (i / columns) as u32 * pitch_y;
+        let scaled = imageops::resize(&cell.image, width, height, imageops::FilterType::Triangle);
+        imageops::replace(&mut sheet, &scaled, x as i64, y as i64);
+        draw_text(&mut sheet, &cell.caption, x, y + height + MARGIN / 2, width);
+    }
+    sheet
+}
+
+/// Draw `text` in capitals with the built in bitmap font, cut off at `max_width`.
+fn draw_text(image: &mut RgbaImage, text: &str, x: u32, y: u32, max_width: u32) {
+    let advance = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+    let fitting = (max_width / advance) as usize;
+    for (i, c) in text.chars().take(fitting).enumerate() {
+        let glyph_x = x + i as u32 * advance;
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits >> (GLYPH_WIDTH - 1 - column) & 1 == 0 {
+                    continue;
+                }
+                for dy in 0..TEXT_SCALE {
+                    for dx in 0..TEXT_SCALE {
+                        image.put_pixel(
+                            glyph_x + column * TEXT_SCALE + dx,
+                            y + row as u32 * TEXT_SCALE + dy,
+                            TEXT,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of a 5x7 glyph from the top, the highest of the five bits is the left column.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ' ' => [0; 7],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '/' => [0x01, 0x01, 0x02, 0x04, 0x08, 0x10, 0x10],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
@@ -0,0 +1,100 @@
+use glam::{Vec3, Vec4};
+use wgpu::Device;
+
+use crate::{
+    capabilities::Capabilities,
+    compute::{self, WorkgroupSize},
+    shader_preprocessor::{self, ShaderDefs},
+};
+
+/// Matches `culling.wgsl`, one invocation per draw
+const WORKGROUP_SIZE: WorkgroupSize = WorkgroupSize::new(64, 1, 1);
+
+/// Bounding box of a geom in object space, matches `Bounds` in `culling.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UniformBounds {
+    center: Vec4,
+    extent: Vec4,
+}
+
+impl UniformBounds {
+    pub fn new(center: Vec3, extent: Vec3) -> Self {
+        Self {
+            center: center.extend(1.0),
+            extent: extent.extend(0.0),
+        }
+    }
+}
+
+/// Frustum culling of the indirect draws on the GPU. Every draw is copied with
+/// its instance count zeroed when its bounds lie outside of the camera frustum.
+pub struct GpuCulling {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl GpuCulling {
+    pub fn new(device: &Device, capabilities: &Capabilities) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                compute::uniform_entry(0),
+                // objects, bounds, draws and culled draws
+                compute::storage_entry(1, true),
+                compute::storage_entry(2, true),
+                compute::storage_entry(3, true),
+                compute::storage_entry(4, false),
+            ],
+            label: Some("Culling Bind Group Layout"),
+        });
+        let shader = shader_preprocessor::create_shader_module(
+            device,
+            capabilities,
+            "culling.wgsl",
+            &ShaderDefs::default(),
+        );
+        let pipeline =
+            compute::create_pipeline(device, "Culling", &shader, "cs_main", &[&bind_group_layout]);
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    /// Bind the buffers of one scene, `draws` are read and `culled_draws` written.
+    pub fn create_bind_group(
+        &self,
+        device: &Device,
+        camera_buffer: &wgpu::Buffer,
+        object_buffer: &wgpu::Buffer,
+        bounds_buffer: &wgpu::Buffer,
+        draws: &wgpu::Buffer,
+        culled_draws: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        compute::buffer_bind_group(
+            device,
+            "Culling Bind Group",
+            &self.bind_group_layout,
+            &[
+                camera_buffer,
+                object_buffer,
+                bounds_buffer,
+                draws,
+                culled_draws,
+            ],
+        )
+    }
+
+    /// Cull `draw_count` draws against the camera as it was last written.
+    pub fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group: &wgpu::BindGroup,
+        draw_count: u32,
+    ) {
+        let mut compute_pass = compute::begin_pass(encoder, "culling", None);
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        WORKGROUP_SIZE.dispatch(&mut compute_pass, [draw_count, 1, 1]);
+    }
+}
@@ -0,0 +1,62 @@
+// Frustum cull the draw of every geom, culled draws keep zero instances
+
+#include "scene_types.wgsl"
+
+// Axis aligned box in object space
+struct Bounds {
+    center: vec4<f32>,
+    extent: vec4<f32>,
+}
+
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(0) @binding(1)
+var<storage, read> objects: array<Object>;
+@group(0) @binding(2)
+var<storage, read> bounds: array<Bounds>;
+@group(0) @binding(3)
+var<storage, read> draws: array<DrawArgs>;
+@group(0) @binding(4)
+var<storage, read_write> culled_draws: array<DrawArgs>;
+
+@compute @workgroup_size(64, 1, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= arrayLength(&draws) {
+        return;
+    }
+    let model = objects[i].model;
+    let center = (model * vec4<f32>(bounds[i].center.xyz, 1.0)).xyz;
+    let axes = mat3x3<f32>(abs(model[0].xyz), abs(model[1].xyz), abs(model[2].xyz));
+    let extent = axes * bounds[i].extent.xyz;
+
+    // Frustum planes of the clip space matrix, depth ranges from 0 to 1
+    let m = transpose(camera.view_matrix);
+    let planes = array<vec4<f32>, 6>(
+        m[3] + m[0],
+        m[3] - m[0],
+        m[3] + m[1],
+        m[3] - m[1],
+        m[2],
+        m[3] - m[2],
+    );
+    var visible = true;
+    for (var p = 0; p < 6; p++) {
+        let plane = planes[p];
+        let distance = dot(plane.xyz, center) + plane.w;
+        let radius = dot(abs(plane.xyz), extent);
+        visible = visible && distance + radius >= 0.0;
+    }
+
+    var draw = draws[i];
+    draw.instance_count = select(0u, draw.instance_count, visible);
+    culled_draws[i] = draw;
+}
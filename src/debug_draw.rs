@@ -0,0 +1,312 @@
+//! Immediate mode lines for gizmos and visualizations.
+//!
+//! Anything holding the `AppState` records lines into `AppState::debug_draw`
+//! during a frame, `DefaultRenderer::prepare_debug_draw` uploads them into one
+//! vertex buffer and empties the recorder again, and the debug draw pass draws
+//! them over the scene. Lines have to be recorded again every frame they should
+//! stay visible.
+//!
+//! The `DebugViews` chosen in the Debug menu are recorded the same way, from the
+//! geometry kept in the `SceneWorld`.
+
+use std::f32::consts::TAU;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat3, Mat4, Vec3};
+use wgpu::Device;
+
+use crate::{scene_world::SceneWorld, texture};
+
+/// Segments of each circle of `DebugDraw::sphere`
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Vertices whose vectors are drawn at most, larger scenes are sampled evenly
+const MAX_VECTOR_VERTICES: usize = 65536;
+
+const STATIC_BOUNDS_COLOR: [f32; 3] = [1.0, 0.85, 0.2];
+const DYNAMIC_BOUNDS_COLOR: [f32; 3] = [0.9, 0.3, 1.0];
+const NORMAL_COLOR: [f32; 3] = [0.2, 0.4, 1.0];
+const TANGENT_COLOR: [f32; 3] = [1.0, 0.2, 0.2];
+const BITANGENT_COLOR: [f32; 3] = [0.2, 1.0, 0.2];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl DebugVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Lines recorded for the coming frame, in world space with a linear color.
+#[derive(Debug, Clone, Default)]
+pub struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+}
+
+impl DebugDraw {
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: [f32; 3]) {
+        self.vertices.extend([a, b].map(|position| DebugVertex {
+            position: position.to_array(),
+            color,
+        }));
+    }
+
+    /// Edges of the axis aligned box between `min` and `max`.
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: [f32; 3]) {
+        self.oriented_box(Mat4::IDENTITY, (min + max) * 0.5, (max - min) * 0.5, color);
+    }
+
+    /// Edges of the box `center` ± `extent` in the space `transform` maps to
+    /// world space, like the bounds of a mesh in object space.
+    pub fn oriented_box(&mut self, transform: Mat4, center: Vec3, extent: Vec3, color: [f32; 3]) {
+        let corner = |i: usize| {
+            let sign = Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            );
+            transform.transform_point3(center + sign * extent)
+        };
+        // Every pair of corners differing in one axis
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corner(i), corner(i | axis), color);
+                }
+            }
+        }
+    }
+
+    /// Circles around `center` in the three axis planes.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: [f32; 3]) {
+        let point = |i: usize| {
+            let (sin, cos) = (i as f32 / CIRCLE_SEGMENTS as f32 * TAU).sin_cos();
+            (sin * radius, cos * radius)
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            let [(s0, c0), (s1, c1)] = [point(i), point(i + 1)];
+            self.line(
+                center + Vec3::new(s0, c0, 0.0),
+                center + Vec3::new(s1, c1, 0.0),
+                color,
+            );
+            self.line(
+                center + Vec3::new(s0, 0.0, c0),
+                center + Vec3::new(s1, 0.0, c1),
+                color,
+            );
+            self.line(
+                center + Vec3::new(0.0, s0, c0),
+                center + Vec3::new(0.0, s1, c1),
+                color,
+            );
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
+
+/// Geometry diagnostics drawn as lines, chosen in the Debug menu.
+#[derive(Debug, Clone, Copy)]
+pub struct DebugViews {
+    /// World space bounds of every visible geom, as tested by the culling
+    pub bounding_boxes: bool,
+    pub normals: bool,
+    pub tangents: bool,
+    /// Rebuilt from the normal, tangent and its sign like `shader.wgsl` does
+    pub bitangents: bool,
+    /// World length of the vertex vectors
+    pub vector_length: f32,
+}
+
+impl Default for DebugViews {
+    fn default() -> Self {
+        Self {
+            bounding_boxes: false,
+            normals: false,
+            tangents: false,
+            bitangents: false,
+            vector_length: 0.1,
+        }
+    }
+}
+
+impl DebugViews {
+    /// Any of the vertex vectors is drawn
+    pub fn vectors(&self) -> bool {
+        self.normals || self.tangents || self.bitangents
+    }
+
+    /// Record the chosen views of the visible geoms of `scene` into `draw`.
+    pub fn record(&self, scene: &SceneWorld, draw: &mut DebugDraw) {
+        if self.bounding_boxes {
+            for geom in scene.culled(true) {
+                let (min, max) = geom.world_bounds();
+                let color = if geom.dynamic {
+                    DYNAMIC_BOUNDS_COLOR
+                } else {
+                    STATIC_BOUNDS_COLOR
+                };
+                draw.aabb(min, max, color);
+            }
+        }
+        if !self.vectors() {
+            return;
+        }
+        let total = scene
+            .culled(true)
+            .map(|geom| geom.mesh.vertices.len())
+            .sum::<usize>();
+        let stride = total.div_ceil(MAX_VECTOR_VERTICES).max(1);
+        for geom in scene.culled(true) {
+            let model = geom.transform.matrix;
+            let normal_matrix = Mat3::from_mat4(model).inverse().transpose();
+            for vertex in geom.mesh.vertices.iter().step_by(stride) {
+                let position = model.transform_point3(vertex.position);
+                let normal = (normal_matrix * vertex.normal).normalize_or_zero();
+                let tangent = model.transform_vector3(vertex.tangent).normalize_or_zero();
+                if self.normals {
+                    draw.line(
+                        position,
+                        position + normal * self.vector_length,
+                        NORMAL_COLOR,
+                    );
+                }
+                if self.tangents {
+                    draw.line(
+                        position,
+                        position + tangent * self.vector_length,
+                        TANGENT_COLOR,
+                    );
+                }
+                if self.bitangents {
+                    let bitangent = vertex.tangent_sign * normal.cross(tangent);
+                    draw.line(
+                        position,
+                        position + bitangent * self.vector_length,
+                        BITANGENT_COLOR,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Draws the lines of a `DebugDraw` over the scene, hidden by the geometry in
+/// front of them.
+pub struct DebugDrawOverlay {
+    pipeline: wgpu::RenderPipeline,
+    /// Grown to the largest frame so far, missing until anything was drawn
+    vertex_buffer: Option<wgpu::Buffer>,
+    vertex_count: u32,
+}
+
+impl DebugDrawOverlay {
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("debug_draw.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Draw Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Draw Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[DebugVertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            pipeline,
+            vertex_buffer: None,
+            vertex_count: 0,
+        }
+    }
+
+    /// Upload the lines of `draw`, growing the vertex buffer when they do not fit.
+    pub fn prepare(&mut self, device: &Device, queue: &wgpu::Queue, draw: &DebugDraw) {
+        self.vertex_count = draw.vertices.len() as u32;
+        if draw.is_empty() {
+            return;
+        }
+        let size = std::mem::size_of_val(draw.vertices.as_slice()) as wgpu::BufferAddress;
+        if self
+            .vertex_buffer
+            .as_ref()
+            .is_none_or(|buffer| buffer.size() < size)
+        {
+            self.vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Debug Draw Vertex Buffer"),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        if let Some(buffer) = &self.vertex_buffer {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&draw.vertices));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertex_count == 0
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        let Some(buffer) = self.vertex_buffer.as_ref().filter(|_| !self.is_empty()) else {
+            return;
+        };
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,23 @@
+/// Debug overlay drawn while presenting: lines of equal view depth and a band
+/// around the focus plane, to read the depth structure of the scene at a glance.
+#[derive(Debug, Clone, Copy)]
+pub struct DepthContours {
+    pub enabled: bool,
+    /// View depth between two contour lines
+    pub spacing: f32,
+    /// View depth of the highlighted focus plane
+    pub focus_distance: f32,
+    /// Depth range around the focus plane shown as in focus
+    pub focus_range: f32,
+}
+
+impl Default for DepthContours {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            spacing: 1.0,
+            focus_distance: 10.0,
+            focus_range: 1.0,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Panels of the viewer window, docked around the view of the scene.
+//!
+//! The scene is still drawn behind the whole window, the viewport panel leaves
+//! its part uncovered and takes the camera and picking input. Panels can be
+//! dragged into other places, stacked as tabs and closed, the arrangement is
+//! kept in `resources/render_settings.json`, see `RenderSettings::layout`.
+
+use egui_dock::{DockState, NodeIndex};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Panel {
+    /// The scene, transparent and never closed
+    Viewport,
+    /// Camera, clip planes, bookmarks and how the frame is drawn
+    Inspector,
+    Materials,
+    Lights,
+    Passes,
+    Log,
+    Stats,
+}
+
+impl Panel {
+    pub const ALL: [Self; 7] = [
+        Self::Viewport,
+        Self::Inspector,
+        Self::Materials,
+        Self::Lights,
+        Self::Passes,
+        Self::Log,
+        Self::Stats,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Viewport => "Viewport",
+            Self::Inspector => "Inspector",
+            Self::Materials => "Materials",
+            Self::Lights => "Lights",
+            Self::Passes => "Passes",
+            Self::Log => "Log",
+            Self::Stats => "Stats",
+        }
+    }
+}
+
+/// Arrangement of the panels, the default puts the settings left of the
+/// viewport, the passes and statistics right of it and the log below.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DockLayout(pub DockState<Panel>);
+
+impl Default for DockLayout {
+    fn default() -> Self {
+        let mut dock = DockState::new(vec![Panel::Viewport]);
+        let surface = dock.main_surface_mut();
+        // Fractions are what the viewport keeps of the node it is split from
+        let [viewport, _] = surface.split_left(
+            NodeIndex::root(),
+            0.75,
+            vec![Panel::Inspector, Panel::Materials, Panel::Lights],
+        );
+        let [viewport, _] = surface.split_right(viewport, 0.72, vec![Panel::Passes, Panel::Stats]);
+        surface.split_below(viewport, 0.75, vec![Panel::Log]);
+        Self(dock)
+    }
+}
+
+impl DockLayout {
+    /// Bring `panel` to the front, a closed panel is opened again next to the
+    /// focused one.
+    pub fn open(&mut self, panel: Panel) {
+        match self.0.find_tab(&panel) {
+            Some(tab) => {
+                let (surface, node, _) = tab;
+                self.0.set_active_tab(tab);
+                self.0.set_focused_node_and_surface((surface, node));
+            }
+            None => self.0.push_to_focused_leaf(panel),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+/// Failures shown to the user instead of aborting, at startup in a message box
+/// and while running in an error window.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to create the window: {0}")]
+    Window(#[from] winit::error::OsError),
+    #[error("failed to create the surface: {0}")]
+    Surface(#[from] wgpu::CreateSurfaceError),
+    #[error("no graphics adapter can present to the window")]
+    NoAdapter,
+    #[error("no fallback adapter, install a software driver such as lavapipe")]
+    NoFallbackAdapter,
+    #[error("failed to create the graphics device: {0}")]
+    Device(#[from] wgpu::RequestDeviceError),
+    #[error("the window cannot be presented in {0:?}")]
+    SurfaceFormat(wgpu::TextureFormat),
+    #[error("invalid argument: {0}")]
+    Argument(String),
+    #[error("model not found: {}", .0.display())]
+    ModelNotFound(PathBuf),
+    #[error("failed to read the OBJ model: {0}")]
+    Obj(#[from] tobj::LoadError),
+    #[error("failed to read the glTF model: {0}")]
+    Gltf(#[from] gltf::Error),
+    #[error("failed to decode {}: {reason:#}", path.display())]
+    Texture {
+        path: PathBuf,
+        reason: anyhow::Error,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,181 @@
+use std::{
+    f32::consts::{PI, TAU},
+    ops::{Add, Mul, Sub},
+    path::{Path, PathBuf},
+};
+
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+
+/// Camera pose of a flythrough at `time` seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathKeyframe {
+    pub time: f32,
+    pub position: [f32; 3],
+    /// Radians, unwrapped against the previous keyframe so turns take the short way
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+/// Keyframes of a flythrough sorted by time, interpolated with Catmull-Rom splines.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CameraPath {
+    pub keyframes: Vec<PathKeyframe>,
+}
+
+impl CameraPath {
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |key| key.time)
+    }
+
+    /// Append the pose of `camera` `interval` seconds after the last keyframe.
+    pub fn record(&mut self, camera: &Camera, interval: f32) {
+        let (mut yaw, pitch) = camera.orientation();
+        let time = match self.keyframes.last() {
+            Some(last) => {
+                yaw = last.yaw + (yaw - last.yaw + PI).rem_euclid(TAU) - PI;
+                last.time + interval
+            }
+            None => 0.0,
+        };
+        self.keyframes.push(PathKeyframe {
+            time,
+            position: camera.position.to_array(),
+            yaw,
+            pitch,
+        });
+    }
+
+    /// Restore the order after keyframe times were edited.
+    pub fn sort(&mut self) {
+        self.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    /// Put `camera` at the pose of the path at `time`, clamped to its ends.
+    pub fn place_camera(&self, time: f32, camera: &mut Camera) {
+        let keys = &self.keyframes;
+        if keys.is_empty() {
+            return;
+        }
+        // Segment between keys[i - 1] and keys[i]
+        let i = keys
+            .partition_point(|key| key.time <= time)
+            .clamp(1, keys.len().max(2) - 1);
+        let key = |i: usize| &keys[i.min(keys.len() - 1)];
+        let indices = [i.saturating_sub(2), i - 1, i, i + 1];
+        let times = indices.map(|i| key(i).time);
+        let position = catmull_rom(
+            indices.map(|i| Vec3::from_array(key(i).position)),
+            times,
+            time,
+        );
+        let angles = catmull_rom(
+            indices.map(|i| Vec2::new(key(i).yaw, key(i).pitch)),
+            times,
+            time,
+        );
+        camera.position = position;
+        camera.set_orientation(angles.x, angles.y);
+    }
+}
+
+/// Hermite spline through `points[1]` and `points[2]` with Catmull-Rom tangents
+/// scaled by the key spacing, so uneven keyframe times keep a steady speed.
+fn catmull_rom<T>(points: [T; 4], times: [f32; 4], time: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let [p0, p1, p2, p3] = points;
+    let [t0, t1, t2, t3] = times;
+    let span = t2 - t1;
+    if span <= f32::EPSILON {
+        return if time < t2 { p1 } else { p2 };
+    }
+    let m1 = (p2 - p0) * (span / (t2 - t0).max(f32::EPSILON));
+    let m2 = (p3 - p1) * (span / (t3 - t1).max(f32::EPSILON));
+    let s = ((time - t1) / span).clamp(0.0, 1.0);
+    let (s2, s3) = (s * s, s * s * s);
+    p1 * (2.0 * s3 - 3.0 * s2 + 1.0)
+        + m1 * (s3 - 2.0 * s2 + s)
+        + p2 * (3.0 * s2 - 2.0 * s3)
+        + m2 * (s3 - s2)
+}
+
+/// Playback options and position on the path, edited in the UI.
+#[derive(Debug, Clone)]
+pub struct PathPlayback {
+    /// Position on the path in seconds
+    pub time: f32,
+    pub playing: bool,
+    /// The camera follows the path, set by playing or scrubbing
+    pub following: bool,
+    /// Seconds between keyframes appended by recording
+    pub record_interval: f32,
+    /// Frames written per second of the path by a capture
+    pub capture_fps: u32,
+}
+
+impl Default for PathPlayback {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            playing: false,
+            following: false,
+            record_interval: 2.0,
+            capture_fps: 30,
+        }
+    }
+}
+
+/// A running capture playing the path at a fixed frame rate, every frame is
+/// written as a numbered PNG so runs render identical sequences.
+#[derive(Debug, Clone)]
+pub struct PathCapture {
+    /// Frame being rendered
+    pub frame: u32,
+    pub frames: u32,
+    fps: u32,
+    output: PathBuf,
+    /// Camera before the capture, restored afterwards
+    camera: Camera,
+}
+
+impl PathCapture {
+    pub fn new(path: &CameraPath, fps: u32, camera: &Camera, output: &Path) -> Self {
+        let fps = fps.max(1);
+        Self {
+            frame: 0,
+            frames: (path.duration() * fps as f32).floor() as u32 + 1,
+            fps,
+            output: output.to_owned(),
+            camera: camera.clone(),
+        }
+    }
+
+    /// Time on the path of the frame being rendered.
+    pub fn time(&self) -> f32 {
+        self.frame as f32 / self.fps as f32
+    }
+
+    pub fn frame_path(&self) -> PathBuf {
+        self.output.join(format!("frame_{:04}.png", self.frame))
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Move on to the next frame, false once the path is complete.
+    pub fn advance(&mut self) -> bool {
+        self.frame += 1;
+        self.frame < self.frames
+    }
+
+    /// Camera to return to once the capture ended.
+    pub fn into_camera(self) -> Camera {
+        self.camera
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use wgpu::Device;
+
+use crate::{
+    compute::{self, WorkgroupSize},
+    logging, texture,
+};
+
+const HISTOGRAM_BINS: usize = 64;
+/// Must match `frame_stats.wgsl`
+const MIN_LOG2_LUMINANCE: f32 = -12.0;
+const BINS_PER_STOP: f32 = 4.0;
+/// Counters of each stage live at a dynamic offset, aligned for storage bindings
+const COUNTERS_STRIDE: wgpu::BufferAddress = 512;
+/// Matches `frame_stats.wgsl`, one invocation per pixel
+const WORKGROUP_SIZE: WorkgroupSize = WorkgroupSize::new(16, 16, 1);
+
+/// Points of the frame the statistics can be taken at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsStage {
+    /// After the opaque geometry, only measured in NaN guard mode
+    Opaque,
+    /// After everything was drawn, right before presenting
+    Final,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Counters {
+    nan_count: u32,
+    inf_count: u32,
+    histogram: [u32; HISTOGRAM_BINS],
+}
+
+/// Numbers describing one rendered frame, luminance values are estimated from a
+/// histogram with four bins per stop.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// Finite pixels the luminance values are computed from
+    pub pixels: u32,
+    pub nan_count: u32,
+    pub inf_count: u32,
+    pub mean_luminance: f32,
+    pub median_luminance: f32,
+    pub p95_luminance: f32,
+    pub p99_luminance: f32,
+    /// NaN and Inf pixels right after the opaque pass, if it was measured
+    pub opaque_invalid: Option<(u32, u32)>,
+}
+
+impl FrameStats {
+    fn bin_luminance(bin: usize) -> f32 {
+        (MIN_LOG2_LUMINANCE + (bin as f32 + 0.5) / BINS_PER_STOP).exp2()
+    }
+
+    fn from_counters(counters: &Counters) -> Self {
+        let pixels = counters.histogram.iter().sum::<u32>();
+        let percentile = |p: f32| {
+            let target = (pixels as f32 * p).ceil() as u32;
+            let mut seen = 0;
+            for (bin, count) in counters.histogram.iter().enumerate() {
+                seen += count;
+                if seen >= target.max(1) {
+                    return Self::bin_luminance(bin);
+                }
+            }
+            0.0
+        };
+        let total = counters
+            .histogram
+            .iter()
+            .enumerate()
+            .map(|(bin, &count)| count as f32 * Self::bin_luminance(bin))
+            .sum::<f32>();
+        Self {
+            pixels,
+            nan_count: counters.nan_count,
+            inf_count: counters.inf_count,
+            mean_luminance: if pixels > 0 {
+                total / pixels as f32
+            } else {
+                0.0
+            },
+            median_luminance: percentile(0.5),
+            p95_luminance: percentile(0.95),
+            p99_luminance: percentile(0.99),
+            opaque_invalid: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Readback {
+    Idle,
+    /// Counters were copied in the frame being recorded
+    Copied,
+    Mapping,
+}
+
+/// GPU reduction of the HDR scene color, read back without stalling. Results
+/// arrive a few frames late and frames are skipped while a readback is in flight.
+pub struct FrameStatistics {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    counter_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    readback: Cell<Readback>,
+    /// The opaque stage was reduced in the frame being read back
+    opaque_recorded: Cell<bool>,
+    mapped: Arc<AtomicBool>,
+}
+
+impl FrameStatistics {
+    pub fn new(device: &Device, scene_color: &texture::Texture) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                compute::texture_entry(0, wgpu::TextureViewDimension::D2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<Counters>() as u64
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Frame Statistics Bind Group Layout"),
+        });
+        let size = COUNTERS_STRIDE * 2;
+        let counter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Statistics Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Statistics Readback Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("frame_stats.wgsl"));
+        let pipeline = compute::create_pipeline(
+            device,
+            "Frame Statistics",
+            &shader,
+            "cs_main",
+            &[&bind_group_layout],
+        );
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, scene_color, &counter_buffer);
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            counter_buffer,
+            readback_buffer,
+            readback: Cell::new(Readback::Idle),
+            opaque_recorded: Cell::new(false),
+            mapped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &wgpu::BindGroupLayout,
+        scene_color: &texture::Texture,
+        counter_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&scene_color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: counter_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<Counters>() as u64),
+                    }),
+                },
+            ],
+            label: Some("Frame Statistics Bind Group"),
+        })
+    }
+
+    /// Follow the scene color texture after it was recreated.
+    pub fn resize(&mut self, device: &Device, scene_color: &texture::Texture) {
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            scene_color,
+            &self.counter_buffer,
+        );
+    }
+
+    /// Reduce `scene_color` as it is at `stage` unless the previous result is still
+    /// being read back. The final stage has to come last in a frame.
+    pub fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene_color: &texture::Texture,
+        stage: StatsStage,
+    ) {
+        if self.readback.get() != Readback::Idle {
+            return;
+        }
+        let size = scene_color.texture.size();
+        let offset = stage as wgpu::BufferAddress * COUNTERS_STRIDE;
+        encoder.clear_buffer(&self.counter_buffer, offset, Some(COUNTERS_STRIDE));
+        {
+            let mut compute_pass = compute::begin_pass(encoder, "frame statistics", None);
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &self.bind_group, &[offset as u32]);
+            WORKGROUP_SIZE.dispatch(&mut compute_pass, [size.width, size.height, 1]);
+        }
+        if stage == StatsStage::Opaque {
+            self.opaque_recorded.set(true);
+            return;
+        }
+        encoder.copy_buffer_to_buffer(
+            &self.counter_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            self.counter_buffer.size(),
+        );
+        self.readback.set(Readback::Copied);
+    }
+
+    /// Start mapping the counters, call once the recorded frame was submitted.
+    pub fn submitted(&self) {
+        if self.readback.get() != Readback::Copied {
+            return;
+        }
+        self.readback.set(Readback::Mapping);
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(err) => tracing::error!(
+                    target: logging::RENDER,
+                    "failed to map frame statistics: {}",
+                    err
+                ),
+            });
+    }
+
+    /// Statistics of an earlier frame once its readback finished, the device has
+    /// to be polled for this to make progress.
+    pub fn read(&self) -> Option<FrameStats> {
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        let counters = |stage: StatsStage| {
+            let offset = stage as usize * COUNTERS_STRIDE as usize;
+            let view = self.readback_buffer.slice(..).get_mapped_range();
+            *bytemuck::from_bytes::<Counters>(
+                &view[offset..offset + std::mem::size_of::<Counters>()],
+            )
+        };
+        let mut stats = FrameStats::from_counters(&counters(StatsStage::Final));
+        if self.opaque_recorded.take() {
+            let opaque = counters(StatsStage::Opaque);
+            stats.opaque_invalid = Some((opaque.nan_count, opaque.inf_count));
+        }
+        self.readback_buffer.unmap();
+        self.readback.set(Readback::Idle);
+        Some(stats)
+    }
+}
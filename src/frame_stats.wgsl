@@ -0,0 +1,50 @@
+// Reduce the HDR scene color into a luminance histogram and NaN/Inf counts
+
+const HISTOGRAM_BINS: u32 = 64u;
+const MIN_LOG2_LUMINANCE: f32 = -12.0;
+const BINS_PER_STOP: f32 = 4.0;
+
+struct Counters {
+    nan_count: atomic<u32>,
+    inf_count: atomic<u32>,
+    histogram: array<atomic<u32>, HISTOGRAM_BINS>,
+}
+
+@group(0) @binding(0)
+var scene_color: texture_2d<f32>;
+@group(0) @binding(1)
+var<storage, read_write> counters: Counters;
+
+// Compare bit patterns, `x != x` may be folded away by the driver
+fn is_not_finite(x: f32) -> bool {
+    return (bitcast<u32>(x) & 0x7f800000u) == 0x7f800000u;
+}
+
+fn is_nan(x: f32) -> bool {
+    return is_not_finite(x) && (bitcast<u32>(x) & 0x007fffffu) != 0u;
+}
+
+@compute @workgroup_size(16, 16, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(scene_color);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+    let color = textureLoad(scene_color, id.xy, 0).rgb;
+    if is_nan(color.r) || is_nan(color.g) || is_nan(color.b) {
+        atomicAdd(&counters.nan_count, 1u);
+        return;
+    }
+    if is_not_finite(color.r) || is_not_finite(color.g) || is_not_finite(color.b) {
+        atomicAdd(&counters.inf_count, 1u);
+        return;
+    }
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    // Black and negative pixels land in the first bin, everything too bright in the last
+    var bin = 0u;
+    if luminance > 0.0 {
+        let position = (log2(luminance) - MIN_LOG2_LUMINANCE) * BINS_PER_STOP;
+        bin = u32(clamp(position, 0.0, f32(HISTOGRAM_BINS - 1u)));
+    }
+    atomicAdd(&counters.histogram[bin], 1u);
+}
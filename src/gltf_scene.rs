@@ -0,0 +1,330 @@
+use std::{path::Path, sync::Arc};
+
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use gltf::animation::{util::ReadOutputs, Interpolation as GltfInterpolation};
+use image::{DynamicImage, RgbImage, RgbaImage};
+
+use crate::{
+    animation::{Animation, Channel, Interpolation, Keyframes, Node, SceneGraph, Skin, Transform},
+    error::{Error, Result},
+    logging,
+    primitives::{resource_path, AlphaMode, CullMode, Material, NormalMapping, Scene},
+    texture::{ImageCache, TextureImage},
+};
+
+/// One primitive of a glTF mesh, attached to a node of the scene graph.
+#[derive(Debug, Clone)]
+pub struct GltfScene {
+    name: String,
+    pub node: usize,
+    positions: Box<[Vec3]>,
+    normals: Box<[Vec3]>,
+    colors: Box<[Vec3]>,
+    texcoords: Box<[Vec2]>,
+    /// Empty unless the node has a skin
+    joints: Box<[[u32; 4]]>,
+    weights: Box<[Vec4]>,
+    indices: Box<[u32]>,
+    material: Option<Material>,
+}
+
+fn to_dynamic_image(data: &gltf::image::Data) -> Option<DynamicImage> {
+    use gltf::image::Format;
+    match data.format {
+        Format::R8G8B8A8 => RgbaImage::from_raw(data.width, data.height, data.pixels.clone())
+            .map(DynamicImage::ImageRgba8),
+        Format::R8G8B8 => RgbImage::from_raw(data.width, data.height, data.pixels.clone())
+            .map(DynamicImage::ImageRgb8),
+        format => {
+            tracing::warn!(
+                target: logging::SCENE,
+                "unsupported glTF image format {:?}",
+                format
+            );
+            None
+        }
+    }
+}
+
+fn load_material(material: gltf::Material, images: &[Option<Arc<TextureImage>>]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let image_of = |texture: gltf::Texture| images[texture.source().index()].clone();
+    let base_color = Vec4::from(pbr.base_color_factor());
+    let normal_texture = material.normal_texture();
+    Material {
+        name: material.name().unwrap_or_default().to_owned(),
+        diffuse: Some(base_color.truncate()),
+        alpha: Some(base_color.w),
+        cull_mode: if material.double_sided() {
+            CullMode::None
+        } else {
+            CullMode::Back
+        },
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask {
+                cutoff: material.alpha_cutoff().unwrap_or(0.5),
+            },
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        color_texture: pbr
+            .base_color_texture()
+            .and_then(|info| image_of(info.texture())),
+        // glTF normal maps follow the OpenGL convention
+        normal_mapping: NormalMapping {
+            strength: normal_texture.as_ref().map_or(1.0, |info| info.scale()),
+            flip_y: false,
+        },
+        normal_texture: normal_texture.and_then(|info| image_of(info.texture())),
+        // Metallic roughness keeps roughness in green like our roughness maps
+        roughness_texture: pbr
+            .metallic_roughness_texture()
+            .and_then(|info| image_of(info.texture())),
+        ambient_occlusion_texture: material
+            .occlusion_texture()
+            .and_then(|info| image_of(info.texture())),
+        ..Default::default()
+    }
+}
+
+impl GltfScene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<(Vec<Self>, SceneGraph)> {
+        if !resource_path(&path).exists() {
+            return Err(Error::ModelNotFound(path.as_ref().to_path_buf()));
+        }
+        let (document, buffers, images) = gltf::import(resource_path(path))?;
+        let images = images
+            .iter()
+            .map(|data| to_dynamic_image(data).map(|img| Arc::new(TextureImage::Decoded(img))))
+            .collect::<Vec<_>>();
+
+        let mut nodes = document
+            .nodes()
+            .map(|node| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                Node {
+                    parent: None,
+                    transform: Transform {
+                        translation: Vec3::from(translation),
+                        rotation: Quat::from_array(rotation),
+                        scale: Vec3::from(scale),
+                    },
+                    skin: node.skin().map(|skin| skin.index()),
+                }
+            })
+            .collect::<Vec<_>>();
+        for node in document.nodes() {
+            for child in node.children() {
+                nodes[child.index()].parent = Some(node.index());
+            }
+        }
+
+        let mut meshes = vec![];
+        for node in document.nodes() {
+            let Some(mesh) = node.mesh() else {
+                continue;
+            };
+            for (i, primitive) in mesh.primitives().enumerate() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    tracing::warn!(
+                        target: logging::SCENE,
+                        "skipping non triangle primitive of mesh {:?}",
+                        mesh.name()
+                    );
+                    continue;
+                }
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let Some(positions) = reader.read_positions() else {
+                    continue;
+                };
+                let positions = positions.map(Vec3::from).collect::<Box<[_]>>();
+                let indices = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+                // Joints and weights only mean something together with the skin
+                let (joints, weights) = match reader.read_joints(0).zip(reader.read_weights(0)) {
+                    Some((joints, weights)) if node.skin().is_some() => (
+                        joints.into_u16().map(|j| j.map(u32::from)).collect(),
+                        weights.into_f32().map(Vec4::from).collect(),
+                    ),
+                    _ => Default::default(),
+                };
+                meshes.push(Self {
+                    name: format!("{}#{}", mesh.name().unwrap_or("mesh"), i),
+                    node: node.index(),
+                    normals: reader
+                        .read_normals()
+                        .map(|n| n.map(Vec3::from).collect())
+                        .unwrap_or_default(),
+                    colors: reader
+                        .read_colors(0)
+                        .map(|c| {
+                            c.into_rgba_f32()
+                                .map(|c| Vec4::from(c).truncate())
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    // glTF has its texture origin at the top left, flip to match OBJ
+                    texcoords: reader
+                        .read_tex_coords(0)
+                        .map(|t| t.into_f32().map(|[u, v]| Vec2::new(u, 1.0 - v)).collect())
+                        .unwrap_or_default(),
+                    joints,
+                    weights,
+                    // Reverse the winding like `ObjScene::indices` does
+                    indices: indices
+                        .chunks(3)
+                        .flat_map(|e| e.iter().cloned().rev())
+                        .collect(),
+                    positions,
+                    material: Some(load_material(primitive.material(), &images)),
+                });
+            }
+        }
+
+        let animations = document
+            .animations()
+            .map(|animation| {
+                let channels = animation
+                    .channels()
+                    .filter_map(|channel| {
+                        let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+                        let times = reader.read_inputs()?.collect::<Vec<_>>();
+                        let sampler = channel.sampler();
+                        let cubic = sampler.interpolation() == GltfInterpolation::CubicSpline;
+                        // Cubic splines store (in tangent, value, out tangent) per key,
+                        // only the values are kept and interpolated linearly
+                        let values = |len: usize| {
+                            (0..len)
+                                .filter(move |i| !cubic || i % 3 == 1)
+                                .collect::<Vec<_>>()
+                        };
+                        // `Channel::apply` indexes the outputs by key, channels
+                        // with too few or too many are skipped
+                        let expected = times.len() * if cubic { 3 } else { 1 };
+                        let check = |len: usize| {
+                            if len == expected {
+                                return Some(());
+                            }
+                            tracing::warn!(
+                                target: logging::SCENE,
+                                "skipping channel of animation {:?} with {} outputs for {} keys",
+                                animation.name(),
+                                len,
+                                times.len()
+                            );
+                            None
+                        };
+                        let keyframes = match reader.read_outputs()? {
+                            ReadOutputs::Translations(t) => {
+                                let t = t.map(Vec3::from).collect::<Vec<_>>();
+                                check(t.len())?;
+                                Keyframes::Translation(
+                                    values(t.len()).iter().map(|&i| t[i]).collect(),
+                                )
+                            }
+                            ReadOutputs::Rotations(r) => {
+                                let r = r.into_f32().map(Quat::from_array).collect::<Vec<_>>();
+                                check(r.len())?;
+                                Keyframes::Rotation(values(r.len()).iter().map(|&i| r[i]).collect())
+                            }
+                            ReadOutputs::Scales(s) => {
+                                let s = s.map(Vec3::from).collect::<Vec<_>>();
+                                check(s.len())?;
+                                Keyframes::Scale(values(s.len()).iter().map(|&i| s[i]).collect())
+                            }
+                            ReadOutputs::MorphTargetWeights(_) => return None,
+                        };
+                        Some(Channel {
+                            node: channel.target().node().index(),
+                            interpolation: match sampler.interpolation() {
+                                GltfInterpolation::Step => Interpolation::Step,
+                                _ => Interpolation::Linear,
+                            },
+                            times,
+                            keyframes,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let duration = channels
+                    .iter()
+                    .filter_map(|c| c.times.last().copied())
+                    .fold(0.0, f32::max);
+                Animation { channels, duration }
+            })
+            .collect();
+
+        let skins = document
+            .skins()
+            .map(|skin| {
+                let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+                let joints = skin.joints().map(|joint| joint.index()).collect::<Vec<_>>();
+                // Missing inverse bind matrices are identities
+                let inverse_bind_matrices = reader
+                    .read_inverse_bind_matrices()
+                    .map(|matrices| {
+                        matrices
+                            .map(|matrix| Mat4::from_cols_array_2d(&matrix))
+                            .collect()
+                    })
+                    .unwrap_or_else(|| vec![Mat4::IDENTITY; joints.len()]);
+                Skin {
+                    joints,
+                    inverse_bind_matrices,
+                }
+            })
+            .collect();
+
+        Ok((
+            meshes,
+            SceneGraph {
+                nodes,
+                animations,
+                skins,
+            },
+        ))
+    }
+}
+
+impl Scene<Vec3, Vec3, Vec3, Vec2> for GltfScene {
+    fn vertices(&self) -> Box<[Vec3]> {
+        self.positions.clone()
+    }
+
+    fn vertex_colors(&self) -> Box<[Vec3]> {
+        self.colors.clone()
+    }
+
+    fn normals(&self) -> Box<[Vec3]> {
+        self.normals.clone()
+    }
+
+    fn texcoords(&self) -> Box<[Vec2]> {
+        self.texcoords.clone()
+    }
+
+    fn joints(&self) -> Box<[[u32; 4]]> {
+        self.joints.clone()
+    }
+
+    fn weights(&self) -> Box<[Vec4]> {
+        self.weights.clone()
+    }
+
+    fn indices(&self) -> Box<[u32]> {
+        self.indices.clone()
+    }
+
+    fn vertex_count(&self) -> u32 {
+        self.indices.len() as u32
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn material(&self, _images: &mut ImageCache) -> Option<Material> {
+        self.material.clone()
+    }
+}
@@ -0,0 +1,328 @@
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use wgpu::{Device, Queue};
+
+use crate::logging;
+
+/// Timestamps written per measurement, the begin and end of each timed pass.
+const TIMESTAMP_COUNT: u32 = 4;
+
+/// Timestamps of one measurement on their way back: resolved into a buffer,
+/// mapped once the resolving encoder was submitted and read without stalling.
+struct TimestampReadback {
+    label: &'static str,
+    buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Resolved in the frame being recorded, mapped once submitted
+    resolved: Cell<bool>,
+    /// A measurement is being read back
+    in_flight: Cell<bool>,
+    mapped: Arc<AtomicBool>,
+}
+
+impl TimestampReadback {
+    fn new(device: &Device, queue: &Queue, label: &'static str, count: u32) -> Self {
+        Self {
+            label,
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: count as wgpu::BufferAddress * wgpu::QUERY_SIZE as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            resolved: Cell::new(false),
+            in_flight: Cell::new(false),
+            mapped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Resolve the queries of `query_set` through `resolve_buffer`, which has
+    /// to be as large as this readback.
+    fn resolve(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        query_set: &wgpu::QuerySet,
+        resolve_buffer: &wgpu::Buffer,
+    ) {
+        let count = (self.buffer.size() / wgpu::QUERY_SIZE as wgpu::BufferAddress) as u32;
+        encoder.resolve_query_set(query_set, 0..count, resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(resolve_buffer, 0, &self.buffer, 0, self.buffer.size());
+        self.resolved.set(true);
+        self.in_flight.set(true);
+    }
+
+    /// Start mapping the timestamps, call once the resolving encoder was submitted.
+    fn submitted(&self) {
+        if !self.resolved.take() {
+            return;
+        }
+        let mapped = self.mapped.clone();
+        let label = self.label;
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(err) => tracing::error!(
+                    target: logging::RENDER,
+                    "failed to map {}: {}",
+                    label,
+                    err
+                ),
+            });
+    }
+
+    /// The timestamps once mapped, which frees the readback for the next
+    /// measurement.
+    fn read(&self) -> Option<Vec<u64>> {
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        let timestamps = {
+            let view = self.buffer.slice(..).get_mapped_range();
+            view.chunks_exact(wgpu::QUERY_SIZE as usize)
+                .map(bytemuck::pod_read_unaligned)
+                .collect()
+        };
+        self.buffer.unmap();
+        self.in_flight.set(false);
+        Some(timestamps)
+    }
+
+    /// Milliseconds between two timestamps.
+    fn ms(&self, begin: u64, end: u64) -> f32 {
+        end.saturating_sub(begin) as f32 * self.period / 1_000_000.0
+    }
+}
+
+/// GPU time of the GI compute and the main raster pass of one frame, read back
+/// a few frames late.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PassTimings {
+    pub compute_ms: f32,
+    pub raster_ms: f32,
+    /// Time both passes were running at once
+    pub overlap_ms: f32,
+}
+
+/// Times the GI compute against the raster work with timestamp queries, so it
+/// shows whether a separate compute submission actually overlaps. Only frames
+/// that run GI compute are measured, read back without stalling like the frame
+/// statistics.
+pub struct PassTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback: TimestampReadback,
+    /// Timestamps of the frame being recorded are wanted
+    armed: Cell<bool>,
+}
+
+impl PassTimer {
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pass Timer Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: TIMESTAMP_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pass Timer Resolve Buffer"),
+            size: TIMESTAMP_COUNT as wgpu::BufferAddress * wgpu::QUERY_SIZE as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        Self {
+            query_set,
+            resolve_buffer,
+            readback: TimestampReadback::new(
+                device,
+                queue,
+                "Pass Timer Readback Buffer",
+                TIMESTAMP_COUNT,
+            ),
+            armed: Cell::new(false),
+        }
+    }
+
+    /// Measure the frame being recorded unless an earlier one is still read back.
+    pub fn arm(&self) {
+        if !self.readback.in_flight.get() {
+            self.armed.set(true);
+        }
+    }
+
+    /// Skip the frame being recorded, its GI compute did not run.
+    pub fn disarm(&self) {
+        self.armed.set(false);
+    }
+
+    pub fn compute_writes(&self) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.armed
+            .get()
+            .then_some(wgpu::ComputePassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: Some(0),
+                end_of_pass_write_index: Some(1),
+            })
+    }
+
+    pub fn raster_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.armed.get().then_some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(2),
+            end_of_pass_write_index: Some(3),
+        })
+    }
+
+    /// Copy the timestamps out, call after both timed passes were recorded.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.armed.take() {
+            self.readback
+                .resolve(encoder, &self.query_set, &self.resolve_buffer);
+        }
+    }
+
+    /// Call once the resolving encoder was submitted.
+    pub fn submitted(&self) {
+        self.readback.submitted();
+    }
+
+    /// Timings of the last measured frame once its readback finished, the
+    /// device has to be polled for this to make progress.
+    pub fn read(&self) -> Option<PassTimings> {
+        let timestamps = self.readback.read()?;
+        let &[compute_begin, compute_end, raster_begin, raster_end] = timestamps.as_slice() else {
+            return None;
+        };
+        Some(PassTimings {
+            compute_ms: self.readback.ms(compute_begin, compute_end),
+            raster_ms: self.readback.ms(raster_begin, raster_end),
+            overlap_ms: self
+                .readback
+                .ms(compute_begin.max(raster_begin), compute_end.min(raster_end)),
+        })
+    }
+}
+
+/// Frames a `PassProfiler` reads back at once, further frames are not measured
+/// until one of them was read.
+const PROFILER_SLOTS: usize = 4;
+
+/// GPU time of every render graph pass of one frame.
+#[derive(Debug, Clone)]
+pub struct PassProfile {
+    /// `AppState::frame_index` of the measured frame
+    pub frame: u64,
+    /// In the order of the render graph
+    pub pass_ms: Vec<f32>,
+    /// From the start of the first pass to the end of the last
+    pub total_ms: f32,
+}
+
+/// Times each pass of the render graph with timestamps written between the
+/// passes, for the benchmark. Unlike `PassTimer` several frames are read back
+/// at once, so consecutive frames are measured without stalling.
+pub struct PassProfiler {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    /// The measured frames and their timestamps
+    slots: Vec<(Cell<u64>, TimestampReadback)>,
+    /// Slot measuring the frame being recorded
+    recording: Cell<Option<usize>>,
+}
+
+impl PassProfiler {
+    pub fn new(device: &Device, queue: &Queue, pass_count: usize) -> Self {
+        let count = pass_count as u32 * 2;
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pass Profiler Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pass Profiler Resolve Buffer"),
+            size: count as wgpu::BufferAddress * wgpu::QUERY_SIZE as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let slots = (0..PROFILER_SLOTS)
+            .map(|_| {
+                let readback =
+                    TimestampReadback::new(device, queue, "Pass Profiler Readback Buffer", count);
+                (Cell::new(0), readback)
+            })
+            .collect();
+        Self {
+            query_set,
+            resolve_buffer,
+            slots,
+            recording: Cell::new(None),
+        }
+    }
+
+    /// Measure the frame being recorded if a slot is free.
+    pub fn begin(&self, frame: u64) {
+        let slot = self
+            .slots
+            .iter()
+            .position(|(_, readback)| !readback.in_flight.get());
+        if let Some(index) = slot {
+            self.slots[index].0.set(frame);
+        }
+        self.recording.set(slot);
+    }
+
+    /// Write the timestamp before the pass at `index` of the graph when
+    /// `end` is false, after it otherwise.
+    pub fn write(&self, encoder: &mut wgpu::CommandEncoder, index: usize, end: bool) {
+        if self.recording.get().is_some() {
+            encoder.write_timestamp(&self.query_set, index as u32 * 2 + end as u32);
+        }
+    }
+
+    /// Copy the timestamps out, call after the last pass was recorded.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(index) = self.recording.take() {
+            self.slots[index]
+                .1
+                .resolve(encoder, &self.query_set, &self.resolve_buffer);
+        }
+    }
+
+    /// Call once the resolving encoder was submitted.
+    pub fn submitted(&self) {
+        for (_, readback) in &self.slots {
+            readback.submitted();
+        }
+    }
+
+    /// Profiles of the frames whose readback finished since the last call, the
+    /// device has to be polled for this to make progress.
+    pub fn read(&self) -> Vec<PassProfile> {
+        self.slots
+            .iter()
+            .filter_map(|(frame, readback)| {
+                let timestamps = readback.read()?;
+                let pass_ms = timestamps
+                    .chunks_exact(2)
+                    .map(|pass| readback.ms(pass[0], pass[1]))
+                    .collect();
+                let total_ms = match (timestamps.first(), timestamps.last()) {
+                    (Some(&begin), Some(&end)) => readback.ms(begin, end),
+                    _ => 0.0,
+                };
+                Some(PassProfile {
+                    frame: frame.get(),
+                    pass_ms,
+                    total_ms,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,146 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{util::DeviceExt, Device};
+
+use crate::texture;
+
+/// Lines on the ground plane around the camera for judging the scale and
+/// placement of a model, the X and Z axes are drawn in color.
+#[derive(Debug, Clone, Copy)]
+pub struct GroundGrid {
+    pub enabled: bool,
+    /// World distance between two lines, every tenth line is a major one
+    pub spacing: f32,
+    /// Distance from the camera at which the grid has faded out
+    pub fade_distance: f32,
+}
+
+impl Default for GroundGrid {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            spacing: 1.0,
+            fade_distance: 100.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct UniformGrid {
+    spacing: f32,
+    fade_distance: f32,
+    _padding: [f32; 2],
+}
+
+impl From<&GroundGrid> for UniformGrid {
+    fn from(grid: &GroundGrid) -> Self {
+        Self {
+            spacing: grid.spacing,
+            fade_distance: grid.fade_distance,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Draws the `GroundGrid` blended over the shaded image, hidden by the
+/// geometry in front of it.
+pub struct GroundGridOverlay {
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl GroundGridOverlay {
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        grid: &GroundGrid,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("ground_grid.wgsl"));
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Ground Grid Bind Group Layout"),
+        });
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Ground Grid Buffer"),
+            contents: bytemuck::cast_slice(&[UniformGrid::from(grid)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("Ground Grid Bind Group"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Ground Grid Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Ground Grid Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            // Seen from above and below
+            primitive: wgpu::PrimitiveState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            // Tested against the shaded scene but never occluding it
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            pipeline,
+            buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, grid: &GroundGrid) {
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[UniformGrid::from(grid)]),
+        );
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
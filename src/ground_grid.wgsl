@@ -0,0 +1,74 @@
+// Grid on the ground plane around the camera, fading out with the distance
+
+struct Camera {
+    view_matrix: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Grid {
+    // World distance between two lines, every tenth line is a major one
+    spacing: f32,
+    // Distance from the camera at which the grid has faded out
+    fade_distance: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> grid: Grid;
+
+const LINE_COLOR: vec3<f32> = vec3<f32>(0.5, 0.5, 0.5);
+const X_AXIS_COLOR: vec3<f32> = vec3<f32>(0.9, 0.2, 0.2);
+const Z_AXIS_COLOR: vec3<f32> = vec3<f32>(0.2, 0.4, 0.9);
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+}
+
+// Two triangles of a square under the camera reaching the fade distance
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let ground = camera.view_position.xz + corners[index] * grid.fade_distance;
+    var out: VertexOutput;
+    out.world_position = vec3<f32>(ground.x, 0.0, ground.y);
+    out.clip_position = camera.view_matrix * vec4<f32>(out.world_position, 1.0);
+    return out;
+}
+
+// Coverage of the lines every `spacing` through `ground`, one pixel wide
+fn lines(ground: vec2<f32>, spacing: f32) -> f32 {
+    let coord = ground / spacing;
+    let distance = abs(fract(coord - 0.5) - 0.5) / fwidth(coord);
+    return 1.0 - min(min(distance.x, distance.y), 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let ground = in.world_position.xz;
+    let width = fwidth(ground);
+    // The X axis runs where z is zero and the other way around
+    let x_axis = 1.0 - min(abs(ground.y) / width.y, 1.0);
+    let z_axis = 1.0 - min(abs(ground.x) / width.x, 1.0);
+    var color = LINE_COLOR;
+    var alpha = max(lines(ground, grid.spacing) * 0.3, lines(ground, grid.spacing * 10.0) * 0.6);
+    color = mix(color, X_AXIS_COLOR, x_axis);
+    alpha = max(alpha, x_axis);
+    color = mix(color, Z_AXIS_COLOR, z_axis);
+    alpha = max(alpha, z_axis);
+    let distance = length(ground - camera.view_position.xz);
+    alpha *= 1.0 - smoothstep(grid.fade_distance * 0.5, grid.fade_distance, distance);
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(color, alpha);
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Overlay lines for composing captures, drawn with the UI so they never end up
+/// in captured frames. Stored in the scene file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompositionGuides {
+    /// Lines dividing the frame into thirds
+    pub thirds: bool,
+    /// Cross at the center of the frame
+    pub center: bool,
+    /// Outline of the title safe area
+    pub safe_area: bool,
+    /// Fraction of the frame width and height inside the title safe area
+    pub safe_fraction: f32,
+    /// Width over height of the framed image, the area outside is darkened.
+    /// The whole window is the frame when `None`
+    pub aspect: Option<f32>,
+}
+
+impl Default for CompositionGuides {
+    fn default() -> Self {
+        Self {
+            thirds: false,
+            center: false,
+            safe_area: false,
+            safe_fraction: 0.9,
+            aspect: None,
+        }
+    }
+}
+
+impl CompositionGuides {
+    /// Common frame aspects offered in the UI
+    pub const ASPECTS: [(&'static str, f32); 5] = [
+        ("16:9", 16.0 / 9.0),
+        ("2.39:1", 2.39),
+        ("1:1", 1.0),
+        ("4:5", 0.8),
+        ("9:16", 9.0 / 16.0),
+    ];
+
+    pub fn is_visible(&self) -> bool {
+        self.thirds || self.center || self.safe_area || self.aspect.is_some()
+    }
+
+    /// Offset and size of the largest rectangle of the frame aspect centered in
+    /// a `width` x `height` window.
+    pub fn frame(&self, width: f32, height: f32) -> ([f32; 2], [f32; 2]) {
+        let Some(aspect) = self.aspect.filter(|aspect| *aspect > 0.0) else {
+            return ([0.0, 0.0], [width, height]);
+        };
+        let (frame_width, frame_height) = if width / height > aspect {
+            (height * aspect, height)
+        } else {
+            (width, width / aspect)
+        };
+        (
+            [(width - frame_width) * 0.5, (height - frame_height) * 0.5],
+            [frame_width, frame_height],
+        )
+    }
+}
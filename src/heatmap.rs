@@ -0,0 +1,456 @@
+//! Heatmaps of what the geometry of the main view costs to rasterize, to tell a
+//! scene that is slow to draw from lighting that is slow to compute.
+//!
+//! - overdraw: every fragment of the visible geoms counted by additive blending,
+//!   without the depth test, so the hidden layers show as well
+//! - quad usage: GPUs shade 2x2 pixel quads, the pixels a triangle does not
+//!   cover are shaded as helpers and thrown away. Each quad counts its covered
+//!   fragments and how often it was launched, small and thin triangles use a
+//!   quarter of the work they cause.
+//!
+//! The geometry is drawn again after the scene, directly and culled on the CPU
+//! only. The result is colored over the scene color, the post effects still
+//! apply on top.
+
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{util::DeviceExt, Device};
+
+use crate::{
+    capabilities::Capabilities,
+    compute,
+    primitives::{CullMode, Vertex},
+    shader_preprocessor::{self, ShaderDefs},
+    texture,
+};
+
+/// Fragment counts of the overdraw heatmap, blendable and exact up to 2048
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+/// Bytes of one `Quad` in `heatmap.wgsl`
+const QUAD_SIZE: u64 = 12;
+
+/// Cost shown instead of the shaded colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Heatmap {
+    #[default]
+    Off,
+    Overdraw,
+    /// Needs storage buffers written by fragment shaders
+    QuadUsage,
+}
+
+impl Heatmap {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Overdraw, Self::QuadUsage];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Overdraw => "Overdraw",
+            Self::QuadUsage => "Quad usage",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct UniformHeatmap {
+    quad_columns: u32,
+    _padding: [u32; 3],
+}
+
+impl UniformHeatmap {
+    fn new(overdraw: &texture::Texture) -> Self {
+        Self {
+            quad_columns: quad_extent(overdraw).0,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// 2x2 pixel quads across and down `overdraw`, partial ones included.
+fn quad_extent(overdraw: &texture::Texture) -> (u32, u32) {
+    let size = overdraw.texture.size();
+    (size.width.div_ceil(2), size.height.div_ceil(2))
+}
+
+/// Draw of one geom, see `DefaultRenderer::render_heatmap`.
+pub struct HeatDraw {
+    pub indices: Range<u32>,
+    pub base_vertex: i32,
+    pub object_index: u32,
+    pub cull_mode: CullMode,
+}
+
+/// Scene buffers and bind groups the geometry is drawn with.
+pub struct HeatGeometry<'a> {
+    pub vertex_buffer: &'a wgpu::Buffer,
+    pub index_buffer: &'a wgpu::Buffer,
+    pub camera_bind_group: &'a wgpu::BindGroup,
+    pub object_bind_group: &'a wgpu::BindGroup,
+}
+
+/// Buffers and bind groups following the size of the scene textures, see
+/// `resize`.
+struct HeatTargets {
+    quad_bind_group: wgpu::BindGroup,
+    resolve_bind_group: wgpu::BindGroup,
+    quad_buffer: wgpu::Buffer,
+}
+
+pub struct HeatmapPass {
+    /// Indexed by `CullMode::index` like the scene pipelines
+    overdraw_pipelines: [wgpu::RenderPipeline; 3],
+    /// Missing without writable storage in fragment shaders
+    quad_pipelines: Option<[wgpu::RenderPipeline; 3]>,
+    resolve_overdraw_pipeline: wgpu::RenderPipeline,
+    resolve_quad_pipeline: wgpu::RenderPipeline,
+    quad_bind_group_layout: wgpu::BindGroupLayout,
+    resolve_bind_group_layout: wgpu::BindGroupLayout,
+    targets: HeatTargets,
+}
+
+impl HeatmapPass {
+    pub fn new(
+        device: &Device,
+        capabilities: &Capabilities,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        object_bind_group_layout: &wgpu::BindGroupLayout,
+        overdraw: &texture::Texture,
+        scene_depth: &texture::Texture,
+    ) -> Self {
+        let shader = shader_preprocessor::create_shader_module(
+            device,
+            capabilities,
+            "heatmap.wgsl",
+            &ShaderDefs::default(),
+        );
+        let fragment = |entry: wgpu::BindGroupLayoutEntry| wgpu::BindGroupLayoutEntry {
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ..entry
+        };
+        let quad_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    fragment(compute::storage_entry(0, false)),
+                    fragment(compute::uniform_entry(1)),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("Heatmap Quad Bind Group Layout"),
+            });
+        let resolve_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    fragment(compute::uniform_entry(0)),
+                    fragment(compute::texture_entry(1, wgpu::TextureViewDimension::D2)),
+                    fragment(compute::storage_entry(2, true)),
+                ],
+                label: Some("Heatmap Resolve Bind Group Layout"),
+            });
+
+        let geometry_pipeline = |label: &str,
+                                 layouts: &[&wgpu::BindGroupLayout],
+                                 entry_point: &str,
+                                 blend: Option<wgpu::BlendState>,
+                                 write_mask: wgpu::ColorWrites,
+                                 cull_mode: CullMode| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[Vertex::layout()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                // Faces are skipped where the scene pipelines skip them
+                primitive: wgpu::PrimitiveState {
+                    front_face: wgpu::FrontFace::Cw,
+                    cull_mode: cull_mode.face(),
+                    ..Default::default()
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: FORMAT,
+                        blend,
+                        write_mask,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let overdraw_pipelines = CullMode::ALL.map(|cull_mode| {
+            geometry_pipeline(
+                "Heatmap Overdraw Pipeline",
+                &[camera_bind_group_layout, object_bind_group_layout],
+                "fs_overdraw",
+                Some(wgpu::BlendState {
+                    color: additive,
+                    alpha: additive,
+                }),
+                wgpu::ColorWrites::ALL,
+                cull_mode,
+            )
+        });
+        // The counts go to storage, the target only gives the pass its size
+        let quad_pipelines = capabilities.fragment_storage.then(|| {
+            CullMode::ALL.map(|cull_mode| {
+                geometry_pipeline(
+                    "Heatmap Quad Usage Pipeline",
+                    &[
+                        camera_bind_group_layout,
+                        object_bind_group_layout,
+                        &quad_bind_group_layout,
+                    ],
+                    "fs_quad_usage",
+                    None,
+                    wgpu::ColorWrites::empty(),
+                    cull_mode,
+                )
+            })
+        });
+
+        let resolve_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Heatmap Resolve Pipeline Layout"),
+            bind_group_layouts: &[&resolve_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let resolve_pipeline = |entry_point: &str| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Heatmap Resolve Pipeline"),
+                layout: Some(&resolve_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_fullscreen"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    // Over the scene color, which keeps its alpha
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::SrcAlpha,
+                                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::Zero,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let resolve_overdraw_pipeline = resolve_pipeline("fs_resolve_overdraw");
+        let resolve_quad_pipeline = resolve_pipeline("fs_resolve_quad_usage");
+
+        let targets = Self::create_targets(
+            device,
+            &quad_bind_group_layout,
+            &resolve_bind_group_layout,
+            overdraw,
+            scene_depth,
+        );
+        Self {
+            overdraw_pipelines,
+            quad_pipelines,
+            resolve_overdraw_pipeline,
+            resolve_quad_pipeline,
+            quad_bind_group_layout,
+            resolve_bind_group_layout,
+            targets,
+        }
+    }
+
+    fn create_targets(
+        device: &Device,
+        quad_bind_group_layout: &wgpu::BindGroupLayout,
+        resolve_bind_group_layout: &wgpu::BindGroupLayout,
+        overdraw: &texture::Texture,
+        scene_depth: &texture::Texture,
+    ) -> HeatTargets {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Heatmap Buffer"),
+            contents: bytemuck::cast_slice(&[UniformHeatmap::new(overdraw)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let (columns, rows) = quad_extent(overdraw);
+        let quad_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Heatmap Quad Buffer"),
+            size: columns as u64 * rows as u64 * QUAD_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let quad_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: quad_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: quad_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&scene_depth.view),
+                },
+            ],
+            label: Some("Heatmap Quad Bind Group"),
+        });
+        let resolve_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: resolve_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&overdraw.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: quad_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Heatmap Resolve Bind Group"),
+        });
+        HeatTargets {
+            quad_bind_group,
+            resolve_bind_group,
+            quad_buffer,
+        }
+    }
+
+    /// Follow the overdraw and scene depth textures after they were recreated.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        overdraw: &texture::Texture,
+        scene_depth: &texture::Texture,
+    ) {
+        self.targets = Self::create_targets(
+            device,
+            &self.quad_bind_group_layout,
+            &self.resolve_bind_group_layout,
+            overdraw,
+            scene_depth,
+        );
+    }
+
+    pub fn supports(&self, heatmap: Heatmap) -> bool {
+        heatmap != Heatmap::QuadUsage || self.quad_pipelines.is_some()
+    }
+
+    /// Count `draws` into `overdraw` or the quads and color `color` with the
+    /// result. `overdraw` and the scene depth are the textures given to `new`
+    /// or `resize`, the depth holds the finished opaque pass.
+    pub fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        heatmap: Heatmap,
+        geometry: &HeatGeometry,
+        draws: &[HeatDraw],
+        overdraw: &wgpu::TextureView,
+        color: &wgpu::TextureView,
+    ) {
+        let (pipelines, resolve_pipeline) = match heatmap {
+            Heatmap::Off => return,
+            Heatmap::Overdraw => (&self.overdraw_pipelines, &self.resolve_overdraw_pipeline),
+            Heatmap::QuadUsage => match &self.quad_pipelines {
+                Some(pipelines) => (pipelines, &self.resolve_quad_pipeline),
+                None => return,
+            },
+        };
+        if heatmap == Heatmap::QuadUsage {
+            encoder.clear_buffer(&self.targets.quad_buffer, 0, None);
+        }
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass: heatmap count"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: overdraw,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_vertex_buffer(0, geometry.vertex_buffer.slice(..));
+            render_pass
+                .set_index_buffer(geometry.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.set_bind_group(0, geometry.camera_bind_group, &[]);
+            render_pass.set_bind_group(1, geometry.object_bind_group, &[]);
+            if heatmap == Heatmap::QuadUsage {
+                render_pass.set_bind_group(2, &self.targets.quad_bind_group, &[]);
+            }
+            for draw in draws {
+                render_pass.set_pipeline(&pipelines[draw.cull_mode.index()]);
+                let instance = draw.object_index..draw.object_index + 1;
+                render_pass.draw_indexed(draw.indices.clone(), draw.base_vertex, instance);
+            }
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass: heatmap resolve"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(resolve_pipeline);
+        render_pass.set_bind_group(0, &self.targets.resolve_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,146 @@
+// Overdraw and quad usage of the main view, counted by drawing the geometry
+// again and colored over the scene
+
+#define OBJECT_GROUP 1
+#include "skinning.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Heatmap {
+    // 2x2 pixel quads per row of the view
+    quad_columns: u32,
+    _padding: vec3<u32>,
+}
+
+// Fragments of the visible triangles in one 2x2 pixel quad and how many
+// times the quad was launched for them
+struct Quad {
+    fragments: atomic<u32>,
+    launches: atomic<u32>,
+    // triangle of the lane that counted last, tells a new launch apart
+    token: atomic<u32>,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> quads: array<Quad>;
+
+@group(2) @binding(1)
+var<uniform> heatmap: Heatmap;
+
+@group(2) @binding(2)
+var scene_depth: texture_depth_2d;
+
+// Allowed difference to the depth of the opaque pass, which transformed the
+// vertices the same way
+const DEPTH_TOLERANCE: f32 = 1e-5;
+
+struct HeatVertex {
+    @builtin(position) clip_position: vec4<f32>,
+    // taken from the first vertex of each triangle
+    @location(0) @interpolate(flat) token: u32,
+}
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(6) joint_indices: vec4<u32>,
+    @location(7) weights: vec4<f32>,
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) object_index: u32,
+) -> HeatVertex {
+    // Skinned like in `shader.wgsl`, so the counts match the posed mesh
+    let transform = object_transform(objects[object_index], joint_indices, weights);
+    var out: HeatVertex;
+    out.clip_position = camera.view_matrix * transform * vec4<f32>(position, 1.0);
+    // Triangles only share a token when they start at the same vertex of the
+    // same object, zero is left to cleared quads
+    out.token = (vertex_index * 0x9e3779b1u ^ object_index * 0x85ebca6bu) | 1u;
+    return out;
+}
+
+// Summed by additive blending
+@fragment
+fn fs_overdraw() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+
+// Helper lanes shading the uncovered pixels of a quad do not write storage,
+// so the covered fragments and the launches are counted apart. Lanes of one
+// launch run together, the first of them finds another token in the quad.
+@fragment
+fn fs_quad_usage(in: HeatVertex) -> @location(0) vec4<f32> {
+    let pixel = vec2<u32>(in.clip_position.xy);
+    // Occluded fragments are rejected before shading by the depth test
+    if in.clip_position.z > textureLoad(scene_depth, pixel, 0) + DEPTH_TOLERANCE {
+        discard;
+    }
+    let quad = pixel.y / 2u * heatmap.quad_columns + pixel.x / 2u;
+    atomicAdd(&quads[quad].fragments, 1u);
+    if atomicExchange(&quads[quad].token, in.token) != in.token {
+        atomicAdd(&quads[quad].launches, 1u);
+    }
+    return vec4<f32>(0.0);
+}
+
+// Resolve
+
+// Same layout as `Quad`, read once counting finished
+struct QuadCount {
+    fragments: u32,
+    launches: u32,
+    token: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> resolve_heatmap: Heatmap;
+
+@group(0) @binding(1)
+var overdraw: texture_2d<f32>;
+
+@group(0) @binding(2)
+var<storage, read> quad_counts: array<QuadCount>;
+
+// Fragments per pixel shown in red
+const MAX_OVERDRAW: f32 = 16.0;
+// How much of the scene shows through the colors
+const OPACITY: f32 = 0.85;
+
+// One triangle covering the whole screen
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Blue at zero through cyan, green and yellow to red at one
+fn heat(t: f32) -> vec3<f32> {
+    let x = clamp(t, 0.0, 1.0) * 4.0;
+    return vec3<f32>(
+        clamp(x - 2.0, 0.0, 1.0),
+        clamp(x, 0.0, 1.0) * clamp(4.0 - x, 0.0, 1.0),
+        clamp(2.0 - x, 0.0, 1.0),
+    );
+}
+
+// A single layer is blue, every doubling moves a step towards red
+@fragment
+fn fs_resolve_overdraw(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let count = textureLoad(overdraw, vec2<u32>(position.xy), 0).x;
+    if count < 0.5 {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>(heat(log2(count) / log2(MAX_OVERDRAW)), OPACITY);
+}
+
+// Blue where the launches cover four fragments each, red where they cover one
+@fragment
+fn fs_resolve_quad_usage(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<u32>(position.xy);
+    let quad = quad_counts[pixel.y / 2u * resolve_heatmap.quad_columns + pixel.x / 2u];
+    if quad.launches == 0u {
+        return vec4<f32>(0.0);
+    }
+    let usage = f32(quad.fragments) / f32(4u * quad.launches);
+    return vec4<f32>(heat((1.0 - usage) / 0.75), OPACITY);
+}
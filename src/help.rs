@@ -0,0 +1,966 @@
+use std::{collections::BTreeSet, sync::Mutex};
+
+use crate::{logging, AppState};
+
+/// What a UI setting does and what it costs, shown as tooltip and in the help overlay.
+pub struct SettingHelp {
+    /// `AppState` field the setting edits
+    pub field: &'static str,
+    pub label: &'static str,
+    pub effect: &'static str,
+    pub cost: &'static str,
+}
+
+pub const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("W / A / S / D", "Move the camera"),
+    ("Space / Left Shift", "Move the camera up / down"),
+    ("Left mouse drag", "Look around"),
+    (
+        "Right click / Tab",
+        "Toggle mouse look, Esc releases the cursor",
+    ),
+    ("Mouse wheel", "Move along the view direction"),
+    ("Middle click", "Inspect the triangle under the cursor"),
+    ("1 - 9", "Jump to a camera bookmark"),
+    ("P", "Pause or resume"),
+    (".", "Pause and advance by one step"),
+    ("F1", "Hide or show the UI"),
+    ("F2", "Toggle this help"),
+    (
+        "F11",
+        "Capture the next frame in an attached RenderDoc or Xcode",
+    ),
+];
+
+impl AppState {
+    pub const SETTINGS_HELP: &'static [SettingHelp] = &[
+        SettingHelp {
+            field: "light_position",
+            label: "Light position",
+            effect: "Position of the point light, locked when the scene provides its own light.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "light_orbit.enabled",
+            label: "Orbit light",
+            effect: "Move the light around where it is on a circle or an ellipse in the XZ \
+                     plane, to show how the lighting follows a moving light. The light \
+                     returns to the center when the orbit stops. The reflection probes \
+                     follow while re-baking them while playing is checked in the Animation \
+                     window.",
+            cost: "None, or continuous probe refreshes when they follow the light.",
+        },
+        SettingHelp {
+            field: "light_orbit.radius",
+            label: "Radius",
+            effect: "Distance of the light from the orbit center, along X for an ellipse.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "light_orbit.minor_radius",
+            label: "Minor radius",
+            effect: "Distance of the light from the orbit center along Z for an ellipse.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "light_orbit.speed",
+            label: "Speed",
+            effect: "Degrees per second the light moves along the orbit, negative values \
+                     orbit the other way.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "projection.mode",
+            label: "Projection",
+            effect: "Perspective shrinks distant geometry, orthographic keeps sizes constant \
+                     for measuring and technical views. Switching keeps the scene center \
+                     framed.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "projection.fovy",
+            label: "Field of view",
+            effect: "Vertical opening angle of the perspective projection.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "projection.ortho_height",
+            label: "Orthographic height",
+            effect: "World space height the orthographic view shows, moving the camera \
+                     forward does not zoom it.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "deterministic",
+            label: "Deterministic",
+            effect: "Run exactly one 1/60 s simulation step per frame and load scenes \
+                     synchronously, so the same input produces the same frames on every \
+                     run. Otherwise frames take as many steps as their time covers and \
+                     blend between the last two.",
+            cost: "Animation speed follows the frame rate instead of the wall clock.",
+        },
+        SettingHelp {
+            field: "paused",
+            label: "Pause",
+            effect: "Stop everything that moves on its own: animations, the light orbit, \
+                     camera path playback, the camera with its inertia and the time sliced \
+                     probe refreshes, which light each refresh with the one before. The \
+                     scene is still drawn every frame and settings apply right away.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "step_frame",
+            label: "Step",
+            effect: "Pause and advance by exactly one 1/60 s step, moving the camera by \
+                     the keys held and baking the next probe faces. For following a \
+                     temporal artifact from one step to the next.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "camera_controller.acceleration",
+            label: "Acceleration",
+            effect: "Time the camera takes to get most of the way to full speed while a \
+                     movement key is held. 0 starts moving instantly.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "camera_controller.damping",
+            label: "Damping",
+            effect: "Time the camera keeps coasting once the movement keys are released. \
+                     0 stops instantly.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "camera_controller.look_smoothing",
+            label: "Look smoothing",
+            effect: "Time mouse look trails behind the mouse, evening out jerky motion in \
+                     recordings. 0 follows the mouse exactly.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "clip_planes.auto",
+            label: "Auto clip planes",
+            effect: "Fit the near and far planes to the scene bounds every frame. Turn off to \
+                     set them by hand, a near plane too close for the scene size causes \
+                     z-fighting.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "scene_file.camera_bookmarks",
+            label: "Bookmarks",
+            effect: "Named camera poses including the field of view and clip planes, for \
+                     comparing changes from identical viewpoints. The keys 1 to 9 jump to \
+                     the first nine, Save keeps them in the scene file.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "enable_normal_map",
+            label: "Enable normal map",
+            effect: "Perturb shading normals with the material normal maps.",
+            cost: "One texture sample per pixel.",
+        },
+        SettingHelp {
+            field: "hard_edge_angle",
+            label: "Hard edges",
+            effect: "Normals generated for meshes without any are not smoothed across edges \
+                     sharper than the angle. Applies on the next load or Recompute normals.",
+            cost: "Vertices on hard edges are duplicated.",
+        },
+        SettingHelp {
+            field: "material_override",
+            label: "Material override",
+            effect: "Replace every material with matte white, mid gray or chrome to judge the \
+                     lighting on its own. Normal maps and cutouts are kept.",
+            cost: "Re-bakes the reflection probes.",
+        },
+        SettingHelp {
+            field: "channel_view",
+            label: "Channel",
+            effect: "Show one term of the shading instead of the lit color: the surface \
+                     color, the world space normal after normal mapping, the distance \
+                     between the clip planes on a log scale, the roughness, the ambient \
+                     occlusion, the ambient and reflection probe lighting without the direct \
+                     light, or how much the light reaches the surface. No shadows are cast \
+                     yet, so the latter only shows surfaces turning away from the light. The \
+                     matcap channel shades with a lit sphere instead, by the view space \
+                     normal, to judge normals and tangents apart from the scene lighting. \
+                     The probes are still baked lit.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "matcap",
+            label: "Matcap",
+            effect: "Lit sphere the matcap channel looks the normals up in, one of the \
+                     built in ones or an image of a sphere filling a square.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "indirect_draw",
+            label: "Indirect draws",
+            effect: "Read the draw parameters from a GPU buffer. Meshes sharing a material \
+                     are drawn with a single call where multi draw is supported.",
+            cost: "None, fewer draw calls on scenes with shared materials.",
+        },
+        SettingHelp {
+            field: "gpu_culling",
+            label: "GPU culling",
+            effect: "Skip the indirect draws of meshes outside of the view, tested against \
+                     their bounding boxes on the GPU. Probe bakes are never culled.",
+            cost: "One small compute pass per frame, saves the vertex work of hidden meshes.",
+        },
+        SettingHelp {
+            field: "enable_reflection_probes",
+            label: "Enable reflection probes",
+            effect: "Add box projected reflections from the baked probes to specular surfaces, \
+                     rougher surfaces read blurrier prefiltered levels.",
+            cost: "One cube sample per probe per pixel, each bake prefilters the probes.",
+        },
+        SettingHelp {
+            field: "planar_reflections",
+            label: "Planar reflections",
+            effect: "Render the scene mirrored about the first visible mirror of the Objects \
+                     window and show it in the mirror.",
+            cost: "The opaque scene is drawn a second time each frame while a mirror is \
+                   visible.",
+        },
+        SettingHelp {
+            field: "reflection_probe_intensity",
+            label: "Intensity",
+            effect: "Scale of the probe reflections.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "probe_bake.budget_ms",
+            label: "Update budget",
+            effect: "Milliseconds per frame spent baking probes, 0 bakes everything in one frame. \
+                     Smaller budgets spread a refresh over more frames.",
+            cost: "Up to the budget per frame while baking.",
+        },
+        SettingHelp {
+            field: "async_compute",
+            label: "Async GI compute",
+            effect: "Submit the probe convergence compute on its own after the frame instead \
+                     of inside it, so the driver may run it alongside the raster passes. wgpu \
+                     exposes a single queue, submission order replaces semaphores. With \
+                     timestamp queries the overlap of both is shown below.",
+            cost: "One more submission per measured refresh.",
+        },
+        SettingHelp {
+            field: "probe_storage",
+            label: "Probe storage",
+            effect: "Format the baked probes are kept in. rg11b10ufloat and rgb9e5ufloat halve \
+                     the memory of rgba16float but drop alpha and quantize colors, Compare \
+                     formats bakes each one and writes a capture with the error.",
+            cost: "Re-bakes the reflection probes, other formats than the rendered one add \
+                   a compute pass per face.",
+        },
+        SettingHelp {
+            field: "probe_convergence_threshold",
+            label: "Convergence threshold",
+            effect: "Bake until converged repeats full refreshes, each adding a light bounce, \
+                     until the mean luminance change relative to the mean luminance drops \
+                     below this value.",
+            cost: "A full refresh per bounce and a compute pass comparing the probes.",
+        },
+        SettingHelp {
+            field: "scene_file.dynamic_objects",
+            label: "Dynamic objects",
+            effect: "Dynamic objects are left out of the probe bakes and never trigger a re-bake.",
+            cost: "Changing the set re-bakes every probe.",
+        },
+        SettingHelp {
+            field: "scene_file.mirrors",
+            label: "Mirror",
+            effect: "The first visible mirror reflects the scene as a flat mirror instead of \
+                     the probes, its specular color sets how strongly. The normal of its first \
+                     vertex gives the plane.",
+            cost: "Changing the set re-bakes every probe.",
+        },
+        SettingHelp {
+            field: "scene_file.normal_maps.strength",
+            label: "Normal map strength",
+            effect: "Scale of the bend the normal map gives the surface, zero shows it flat and \
+                     above one exaggerates it. Applies to every mesh with the material.",
+            cost: "Re-bakes the reflection probes.",
+        },
+        SettingHelp {
+            field: "scene_file.normal_maps.flip_y",
+            label: "Flip Y (DirectX)",
+            effect: "Negate the green channel of the normal map, for maps baked for DirectX \
+                     whose bumps otherwise look lit from below.",
+            cost: "Re-bakes the reflection probes.",
+        },
+        SettingHelp {
+            field: "hidden_objects",
+            label: "Visible",
+            effect: "Hidden objects are neither drawn, baked into the reflection probes nor \
+                     picked. Not saved with the scene.",
+            cost: "Hiding or showing an object re-bakes every probe.",
+        },
+        SettingHelp {
+            field: "scene_file.flipped_winding",
+            label: "Flip winding",
+            effect: "Wind the triangles of the mesh the other way when it is loaded, so its \
+                     other side is the front face. Fixes meshes whose back faces show and \
+                     get culled.",
+            cost: "Saves the scene file and loads the scene again.",
+        },
+        SettingHelp {
+            field: "scene_file.flipped_normals",
+            label: "Flip normals",
+            effect: "Negate the normals of the mesh when it is loaded. Fixes meshes lit from \
+                     the wrong side.",
+            cost: "Saves the scene file and loads the scene again.",
+        },
+        SettingHelp {
+            field: "wireframe_object",
+            label: "Wireframe overlay",
+            effect: "Draws the triangle edges of the chosen object over the shaded image, \
+                     including where it is transparent.",
+            cost: "One line pass per view, negligible for most meshes.",
+        },
+        SettingHelp {
+            field: "hover_highlight",
+            label: "Highlight under cursor",
+            effect: "Outlines the object covering the pixel under the cursor, as the main \
+                     view drew it into its object id buffer. Blended objects have no id, the \
+                     opaque object behind them is outlined. Middle click picks use the same \
+                     buffer either way.",
+            cost: "One texel read back per frame, arriving a few frames late. Rebuilding the \
+                   outline of a dense mesh takes a moment whenever another object is hovered.",
+        },
+        SettingHelp {
+            field: "frame_stats_enabled",
+            label: "Frame statistics",
+            effect: "Measure luminance percentiles and count NaN and Inf pixels of the \
+                     rendered frames, read back a few frames late.",
+            cost: "One compute pass over the screen whenever no readback is in flight.",
+        },
+        SettingHelp {
+            field: "nan_guard",
+            label: "NaN guard",
+            effect: "Paint NaN and Inf pixels magenta and count them after the opaque pass \
+                     and at the end of the frame, reported with the frame statistics.",
+            cost: "Up to two compute passes over the screen per frame.",
+        },
+        SettingHelp {
+            field: "turntable_settings.frames",
+            label: "Frames",
+            effect: "Images written for one full orbit around the pivot, starting from the \
+                     current camera position.",
+            cost: "Every frame is read back and encoded as PNG while capturing.",
+        },
+        SettingHelp {
+            field: "turntable_settings.pivot_object",
+            label: "Pivot",
+            effect: "Center of the orbit, the bounding box center of the chosen mesh or of \
+                     the whole scene.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "panorama_settings.face_resolution",
+            label: "Face resolution",
+            effect: "Side of the six cube faces rendered around the camera. The panorama is \
+                     four faces wide and two high, centered on the camera heading. PNG keeps \
+                     the colors as shown, EXR the linear radiance for environment maps.",
+            cost: "One blocking render and readback of six faces per capture, 2048 needs \
+                   close to 1 GiB of memory while stitching.",
+        },
+        SettingHelp {
+            field: "split_view.enabled",
+            label: "Split view",
+            effect: "Render the same camera a second time with the right side settings and \
+                     show it right of a draggable splitter, the current settings stay on the \
+                     left.",
+            cost: "Renders the scene twice per frame.",
+        },
+        SettingHelp {
+            field: "split_view.split",
+            label: "Splitter",
+            effect: "Position of the splitter as a fraction of the window width, also set \
+                     by dragging it in the viewport.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "split_view.right.material_override",
+            label: "Right side material override",
+            effect: "Material override used right of the splitter.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "split_view.right.enable_reflection_probes",
+            label: "Right side reflection probes",
+            effect: "Light the right side with the reflection probes, off shows direct \
+                     lighting only.",
+            cost: "None, the probes are shared with the left side.",
+        },
+        SettingHelp {
+            field: "composition_guides.thirds",
+            label: "Rule of thirds",
+            effect: "Lines dividing the frame into thirds, points of interest usually sit \
+                     on them. Guides are never captured and are stored in the scene file.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "composition_guides.center",
+            label: "Center cross",
+            effect: "Marks the center of the frame.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "composition_guides.safe_area",
+            label: "Title safe",
+            effect: "Outline of the area text and key detail should stay inside, as a \
+                     fraction of the frame.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "composition_guides.aspect",
+            label: "Frame",
+            effect: "Darkens the window outside the given aspect ratio, the other guides \
+                     follow the framed area. Captures still cover the whole window.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "sweep_settings.rows",
+            label: "Sweep rows",
+            effect: "Setting varied down the contact sheet of a settings sweep. Every value \
+                     is rendered from the current view against every column value, the \
+                     probes are baked again for each cell.",
+            cost: "A capture and a probe bake per cell while sweeping.",
+        },
+        SettingHelp {
+            field: "sweep_settings.columns",
+            label: "Sweep columns",
+            effect: "Setting varied across the contact sheet of a settings sweep, each cell \
+                     is captioned with both values and its average frame time.",
+            cost: "A capture and a probe bake per cell while sweeping.",
+        },
+        SettingHelp {
+            field: "scene_file.camera_path",
+            label: "Camera path",
+            effect: "Flythrough keyframes joined by a Catmull-Rom spline. Play or scrub to \
+                     follow the path, Capture writes it at a fixed frame rate for \
+                     repeatable benchmark sequences.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "path_playback.record_interval",
+            label: "Record interval",
+            effect: "Seconds between the last keyframe and a newly recorded one.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "path_playback.capture_fps",
+            label: "Capture frame rate",
+            effect: "Frames written per second of the path, independent of the rendering speed.",
+            cost: "Every frame is read back from the GPU and encoded as PNG.",
+        },
+        SettingHelp {
+            field: "roi.enabled",
+            label: "Region of interest",
+            effect: "Render the outlined rectangle again at a higher resolution and show it \
+                     at 1:1 pixels, for inspecting artifacts up close.",
+            cost: "The scene is drawn a second time into the region target.",
+        },
+        SettingHelp {
+            field: "roi.zoom",
+            label: "Zoom",
+            effect: "Pixels rendered per window pixel along each axis of the region.",
+            cost: "The region target grows with the square of the zoom.",
+        },
+        SettingHelp {
+            field: "texture_previews",
+            label: "Texture previews",
+            effect: "Open a window showing a texture of the renderer as it is drawn: the \
+                     scene color before it is presented, the scene depth from white at the \
+                     near plane to black at the far plane, or one face of a reflection probe.",
+            cost: "One small extra pass per open preview.",
+        },
+        SettingHelp {
+            field: "texture_previews.exposure",
+            label: "Exposure",
+            effect: "Scale the previewed color, to see dark or overbright texels.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "watchdog.enabled",
+            label: "GPU watchdog",
+            effect: "When several frames in a row take longer than the threshold, turn the \
+                     GI down one notch: stop re-baking probes while animating, then limit \
+                     the probe update budget, then turn the reflection probes off. Each step \
+                     is announced in a toast and can be undone by hand.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "watchdog.threshold_ms",
+            label: "Watchdog threshold",
+            effect: "Frame time above which a frame counts as slow.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "post_effects",
+            label: "Post processing",
+            effect: "Add a full screen effect to the end of the stack. Effects run in the \
+                     listed order on the scene color before it is presented, the split view \
+                     and the region of interest are shown without them.",
+            cost: "One full screen pass per enabled effect.",
+        },
+        SettingHelp {
+            field: "render_scale.scale",
+            label: "Render scale",
+            effect: "Resolution the scene renders at relative to the window, stretched over \
+                     the window when presented. Starts at the scene scale of \
+                     render_settings.json.",
+            cost: "Per pixel costs follow the square of the scale, the targets are \
+                   allocated again on every change.",
+        },
+        SettingHelp {
+            field: "render_scale.upsampling",
+            label: "Upsampling",
+            effect: "How the scene color is stretched over the window: the closest texel, \
+                     interpolated between the four closest, or interpolated and then \
+                     sharpened where the contrast allows, after the sharpening of AMD \
+                     FidelityFX Super Resolution 1.",
+            cost: "Four texel loads for bilinear, twenty for sharpen, per window pixel.",
+        },
+        SettingHelp {
+            field: "render_scale.sharpness",
+            label: "Sharpness",
+            effect: "Strength of the sharpening after upsampling.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "post_effects.enabled",
+            label: "Enabled",
+            effect: "Skip the effect, the next one reads what the effect would have read.",
+            cost: "Saves the pass of the effect.",
+        },
+        SettingHelp {
+            field: "post_effects.tonemap",
+            label: "Tonemap exposure",
+            effect: "Scale the scene color before the fitted ACES curve maps it into the \
+                     displayable range, highlights roll off instead of clipping.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "post_effects.vignette",
+            label: "Vignette",
+            effect: "Darken the frame towards the corners. Strength is the darkening at the \
+                     corners, start the distance from the center where it begins, relative to \
+                     the corners.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "post_effects.motion_blur",
+            label: "Motion blur",
+            effect: "Smear the frame along how far each pixel moved on screen since the last \
+                     frame, rebuilt from the depth and the camera of both frames, so only \
+                     camera motion blurs. The shutter angle is how much of the frame the \
+                     shutter stays open, 180 degrees like film. Place it before the tonemap \
+                     so highlights streak at full brightness. Captures at a fixed frame rate \
+                     blur evenly.",
+            cost: "One full screen pass of 16 texel loads.",
+        },
+        SettingHelp {
+            field: "post_effects.color_grade",
+            label: "Color grade",
+            effect: "Lift raises the blacks, gamma brightens or darkens the mid tones and \
+                     gain scales the whites, then the saturation is changed and the color \
+                     looked up in the color table. Place it after the tonemap, it works on \
+                     the displayable range.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "color_lut",
+            label: "Color table",
+            effect: "3D lookup table in the .cube format the color grade maps colors \
+                     through, as exported by Resolve and other grading tools. Without one \
+                     the colors are kept.",
+            cost: "Loaded once.",
+        },
+        SettingHelp {
+            field: "render_stages",
+            label: "Passes",
+            effect: "Add a stage to the end of the frame. Stages render in the listed order, \
+                     Present and Clear write the window and a Clear stage overwrites whatever \
+                     stages before it drew there. The scene, debug view and post process \
+                     stages draw into the targets Present reads.",
+            cost: "One extra pass per enabled stage.",
+        },
+        SettingHelp {
+            field: "render_stages.enabled",
+            label: "Enabled",
+            effect: "Skip the stage while rendering. Disabling Present leaves the window to \
+                     the other stages, put a Clear stage first so nothing stale shows. Moving \
+                     Present before a stage of the renderer shows that stage a frame late.",
+            cost: "Disabling the scene saves its whole frame.",
+        },
+        SettingHelp {
+            field: "depth_contours.enabled",
+            label: "Depth contours",
+            effect: "Draw lines of equal view depth over the scene and highlight the focus \
+                     plane in green, to read the depth structure at a glance.",
+            cost: "A few instructions per pixel of the present pass.",
+        },
+        SettingHelp {
+            field: "depth_contours.spacing",
+            label: "Spacing",
+            effect: "View depth between two contour lines.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "depth_contours.focus_distance",
+            label: "Focus distance",
+            effect: "View depth of the highlighted focus plane.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "depth_contours.focus_range",
+            label: "Focus range",
+            effect: "Depth range around the focus plane tinted as in focus.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "ground_grid.enabled",
+            label: "Ground grid",
+            effect: "Draw lines on the ground plane around the camera, every tenth one \
+                     stronger, with the X axis in red and the Z axis in blue. Blended \
+                     geometry is drawn over it.",
+            cost: "A blended pass over the ground visible within the fade distance.",
+        },
+        SettingHelp {
+            field: "ground_grid.spacing",
+            label: "Spacing",
+            effect: "World distance between two grid lines.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "ground_grid.fade_distance",
+            label: "Fade distance",
+            effect: "Distance from the camera at which the grid has faded out.",
+            cost: "Covers more pixels when far.",
+        },
+        SettingHelp {
+            field: "volumetric_fog.enabled",
+            label: "Volumetric fog",
+            effect: "Fill the view with fog lit by the scene light, integrated through \
+                     froxels aligned with the main view. Overlays are drawn over it.",
+            cost: "Two compute passes over 160x90x64 froxels and a full screen pass.",
+        },
+        SettingHelp {
+            field: "volumetric_fog.density",
+            label: "Density",
+            effect: "Extinction per world unit below the fog height.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "volumetric_fog.height",
+            label: "Height",
+            effect: "World height the fog thins out above, it stays dense below.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "volumetric_fog.height_falloff",
+            label: "Height falloff",
+            effect: "How quickly the fog thins out above its height, zero fills the \
+                     whole scene evenly.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "volumetric_fog.albedo",
+            label: "Albedo",
+            effect: "Part of the extinguished light scattered again per color, the \
+                     rest is absorbed.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "volumetric_fog.anisotropy",
+            label: "Anisotropy",
+            effect: "Positive values scatter light onwards and make a halo around the \
+                     light when looking towards it, negative values scatter it back.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "volumetric_fog.light_intensity",
+            label: "Light intensity",
+            effect: "Strength of the scene light scattered by the fog. The fog does not \
+                     cast shadows, like the surfaces.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "volumetric_fog.ambient_intensity",
+            label: "Probe ambient",
+            effect: "Light the fog with the mean radiance of the nearby reflection \
+                     probes, so it picks up the color of the surrounding scene.",
+            cost: "Six probe samples per probe and froxel.",
+        },
+        SettingHelp {
+            field: "volumetric_fog.max_distance",
+            label: "Max distance",
+            effect: "View distance the froxels reach, everything further is fogged as \
+                     if it were there. Shorter ranges resolve the fog finer.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "sky.enabled",
+            label: "Sky",
+            effect: "Draw an analytic daylight sky (Preetham) behind the scene, also seen \
+                     by the reflection probes. Changing the sky bakes the probes again.",
+            cost: "A full screen pass over the uncovered pixels.",
+        },
+        SettingHelp {
+            field: "sky.time_of_day",
+            label: "Time of day",
+            effect: "Moves the sun along its arc, it rises at 6:00 and sets at 18:00. \
+                     The sky fades to black at night.",
+            cost: "None, besides the probe bakes.",
+        },
+        SettingHelp {
+            field: "sky.noon_elevation",
+            label: "Noon elevation",
+            effect: "Degrees above the horizon the sun reaches at noon.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "sky.azimuth",
+            label: "Azimuth",
+            effect: "Turns the path of the sun around the up axis, at zero it rises \
+                     towards +X.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "sky.turbidity",
+            label: "Turbidity",
+            effect: "Haze of the atmosphere, low values give a deep blue sky and high \
+                     values a pale one with a wide glow around the sun.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "sky.intensity",
+            label: "Intensity",
+            effect: "Brightness of the sky.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "sky.environment_intensity",
+            label: "Environment",
+            effect: "Light the surfaces with the sky as ambient light, and reflect it \
+                     where the scene has no reflection probes.",
+            cost: "Two sky evaluations per shaded pixel.",
+        },
+        SettingHelp {
+            field: "sky.sun_light",
+            label: "Sun drives the light",
+            effect: "The scene light becomes a directional light shining from the sun, \
+                     its position is ignored meanwhile.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "debug_views.bounding_boxes",
+            label: "Bounding boxes",
+            effect: "Outline the world space bounds of every visible object the way the \
+                     culling tests them, static objects in yellow and dynamic ones in \
+                     purple.",
+            cost: "Twelve lines per object.",
+        },
+        SettingHelp {
+            field: "debug_views.normals",
+            label: "Normals",
+            effect: "Draw the normal of every vertex in blue.",
+            cost: "A line per vertex, large scenes only show an even sample of 65536 \
+                   vertices.",
+        },
+        SettingHelp {
+            field: "debug_views.tangents",
+            label: "Tangents",
+            effect: "Draw the tangent of every vertex in red, the direction in which the \
+                     texture coordinate U grows for normal mapping.",
+            cost: "A line per vertex, large scenes only show an even sample of 65536 \
+                   vertices.",
+        },
+        SettingHelp {
+            field: "debug_views.bitangents",
+            label: "Bitangents",
+            effect: "Draw the bitangent of every vertex in green, rebuilt from the normal, \
+                     the tangent and its sign like the shader does. It points where V \
+                     grows unless the texture is mirrored.",
+            cost: "A line per vertex, large scenes only show an even sample of 65536 \
+                   vertices.",
+        },
+        SettingHelp {
+            field: "debug_views.vector_length",
+            label: "Length",
+            effect: "World length of the normal, tangent and bitangent lines.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "heatmap",
+            label: "Heatmap",
+            effect: "Color the view by what its geometry costs to rasterize. Overdraw \
+                     counts the layers drawn at each pixel, hidden ones included, from \
+                     blue for one through green and yellow to red for 16 or more. Quad \
+                     usage shows how much of the 2x2 pixel blocks the GPU shades is \
+                     kept, blue where triangles cover whole blocks and red where small \
+                     or thin triangles waste three pixels out of four. Tonemapping and \
+                     the other post effects still shift the colors.",
+            cost: "The visible geometry is drawn once more, without culling on the GPU.",
+        },
+        SettingHelp {
+            field: "dock_layout",
+            label: "Panels",
+            effect: "The settings, passes, statistics and log are panels docked around \
+                     the viewport. Drag a tab to dock it elsewhere or to stack it with \
+                     others, closed panels are opened again from the Panels menu. The \
+                     layout is saved to resources/render_settings.json when the window \
+                     closes, resetting it goes back to the default arrangement.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "ui_hidden",
+            label: "Hide UI",
+            effect: "Hide the menu bar, the panels and every window so only the scene \
+                     is on screen, for screenshots and screen recordings. The mouse, \
+                     wheel and keys all go to the camera while hidden. Press F1 to show \
+                     the UI again.",
+            cost: "Saves drawing the UI.",
+        },
+        SettingHelp {
+            field: "clean_capture",
+            label: "Clean capture",
+            effect: "Hide the UI on its own while a turntable, camera path, settings \
+                     sweep or benchmark runs and show it again once it ends. The \
+                     captured files never contain the UI, this keeps it out of a \
+                     recording of the window as well.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "log_view",
+            label: "Log",
+            effect: "Show the log of this session, filtered by level, subsystem and \
+                     text. Each line names the subsystem that logged it and the pass, \
+                     stage or geom it happened in, GPU validation errors included. \
+                     Without RUST_LOG only errors are printed to the terminal, the \
+                     window keeps debug messages of the app and warnings of the \
+                     libraries.",
+            cost: "The last 2000 messages are kept in memory.",
+        },
+        SettingHelp {
+            field: "gpu_capture_requested",
+            label: "Capture GPU frame",
+            effect: "Record the next frame in RenderDoc on Vulkan, DX12 and OpenGL or in \
+                     Xcode on Metal, when the app was started from them. Passes, stages \
+                     and probe faces are debug groups, each draw is grouped by material \
+                     with a marker naming its geom. PIX takes its captures from its own \
+                     window, the groups and markers show there as well.",
+            cost: "One frame, nothing without a debugger attached.",
+        },
+        SettingHelp {
+            field: "shader_reload_requested",
+            label: "Reload shaders",
+            effect: "Read shader.wgsl and the files it includes from the src directory of \
+                     the source tree again and rebuild the scene pipelines. Errors are \
+                     listed in the Shader Errors window with their file and line, the \
+                     pipelines built before keep drawing until the shader is fixed.",
+            cost: "Every pipeline permutation in use is rebuilt, the frame hitches.",
+        },
+        SettingHelp {
+            field: "watch_shaders",
+            label: "Reload on change",
+            effect: "Reload the scene shader whenever one of its files is saved.",
+            cost: "The modification times of the shader files are read every frame.",
+        },
+        SettingHelp {
+            field: "axis_gizmo",
+            label: "Axis gizmo",
+            effect: "Show the world X, Y and Z axes as seen by the camera in the bottom left \
+                     corner. Drawn with the UI, so captures leave it out.",
+            cost: "None.",
+        },
+        SettingHelp {
+            field: "streaming.enabled",
+            label: "Streaming",
+            effect: "Load the chunks listed in the scene file while the camera is near \
+                     them and drop them again once it moved away. Off unloads every chunk.",
+            cost: "Chunks load on background threads, the scene buffers are rebuilt on the \
+                   GPU and the reflection probes re-baked whenever a chunk comes or goes.",
+        },
+        SettingHelp {
+            field: "streaming.load_distance",
+            label: "Load distance",
+            effect: "Chunks whose bounds are closer to the camera are loaded, they are \
+                     unloaded a quarter further away.",
+            cost: "Memory and draw time grow with the chunks in range.",
+        },
+        SettingHelp {
+            field: "play_animation",
+            label: "Play",
+            effect: "Advance the scene animations.",
+            cost: "Node transforms are evaluated and uploaded every frame.",
+        },
+        SettingHelp {
+            field: "animation_time",
+            label: "Time",
+            effect: "Pose the scene at a point of its animations, also while paused.",
+            cost: "Node transforms and joint matrices are evaluated once per change.",
+        },
+        SettingHelp {
+            field: "animate_reflection_probes",
+            label: "Re-bake while playing",
+            effect: "Continuously refresh the probes while the animation plays.",
+            cost: "Six scene renders per probe per refresh, bounded by the update budget.",
+        },
+    ];
+
+    /// Tooltip text of the setting editing `field`. Tooltips are built every
+    /// frame, a missing entry is only logged the first time.
+    pub fn setting_help(field: &str) -> String {
+        static MISSING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+        match Self::SETTINGS_HELP.iter().find(|help| help.field == field) {
+            Some(help) => format!("{}\n\nCost: {}", help.effect, help.cost),
+            None => {
+                if MISSING.lock().unwrap().insert(field.to_owned()) {
+                    tracing::warn!(target: logging::APP, "no help for setting {}", field);
+                }
+                String::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widgets_have_help() {
+        let widgets = include_str!("widget.rs");
+        let fields = widgets
+            .split("setting_help(")
+            .skip(1)
+            .filter_map(|call| call.trim_start().strip_prefix('"')?.split('"').next())
+            .collect::<Vec<_>>();
+        assert!(!fields.is_empty());
+        for field in fields {
+            assert!(
+                AppState::SETTINGS_HELP
+                    .iter()
+                    .any(|help| help.field == field),
+                "no help for setting {}",
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn fields_are_unique() {
+        let mut fields = BTreeSet::new();
+        for help in AppState::SETTINGS_HELP {
+            assert!(
+                fields.insert(help.field),
+                "duplicate help for {}",
+                help.field
+            );
+        }
+    }
+}
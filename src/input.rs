@@ -0,0 +1,180 @@
+//! Input of the viewer, independent of the window library.
+//!
+//! Window events are translated into the actions and axes of an `InputState`
+//! before anything reacts to them, the camera controller and picking read the
+//! state rather than winit events. A gamepad or touch source only has to feed
+//! the same actions and axes. Events egui wants are not fed at all, so dragging
+//! a slider does not turn the camera as well.
+
+use std::collections::HashSet;
+
+use glam::Vec2;
+
+/// What an input does, whichever key or button it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    /// Held to look around with the pointer
+    Look,
+    /// Lock the cursor so every motion looks around, or release it
+    ToggleMouseLook,
+    ReleaseMouseLook,
+    /// Inspect the triangle under the pointer
+    Pick,
+    /// Restore the camera bookmark at this index
+    Bookmark(usize),
+    TogglePause,
+    /// Pause and advance by exactly one simulation step
+    StepFrame,
+    ToggleUi,
+    ToggleHelp,
+    CaptureGpuFrame,
+}
+
+impl Action {
+    /// Taken even while a text field of the UI has the keyboard.
+    pub fn global(self) -> bool {
+        matches!(
+            self,
+            Self::ReleaseMouseLook | Self::ToggleUi | Self::ToggleHelp | Self::CaptureGpuFrame
+        )
+    }
+}
+
+/// Movement of the camera from -1 to 1, in its own frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    /// Right is positive
+    Strafe,
+    /// Forward is positive
+    Forward,
+    /// Up is positive
+    Lift,
+}
+
+impl Axis {
+    /// Actions moving along the axis, negative first.
+    fn actions(self) -> (Action, Action) {
+        match self {
+            Self::Strafe => (Action::MoveLeft, Action::MoveRight),
+            Self::Forward => (Action::MoveBackward, Action::MoveForward),
+            Self::Lift => (Action::MoveDown, Action::MoveUp),
+        }
+    }
+}
+
+/// Actions and axes of the current frame, cleared of the presses by
+/// `end_frame`. Look and scroll add up until the simulation takes them.
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    held: HashSet<Action>,
+    /// Pressed since the last frame, repeats left out
+    pressed: HashSet<Action>,
+    /// Analog values of a stick or touch control, added to the held actions
+    analog: [f32; 3],
+    /// Look delta since the last simulation step, in pixels of pointer motion
+    look: Vec2,
+    /// Movement along the view direction since the last simulation step, in
+    /// pixels, positive moves forward
+    scroll: f32,
+    /// Pointer in normalized device coordinates of the view, `None` while it
+    /// is over the UI or outside of the window
+    pointer: Option<Vec2>,
+}
+
+impl InputState {
+    pub fn press(&mut self, action: Action) {
+        if self.held.insert(action) {
+            self.pressed.insert(action);
+        }
+    }
+
+    pub fn release(&mut self, action: Action) {
+        self.held.remove(&action);
+    }
+
+    /// Let go of everything, when the window loses the focus and the releases
+    /// would go elsewhere.
+    pub fn release_all(&mut self) {
+        self.held.clear();
+        self.analog = [0.0; 3];
+    }
+
+    pub fn held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    /// Whether `action` was pressed since the last frame.
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    /// Bookmark whose key was pressed since the last frame.
+    pub fn pressed_bookmark(&self) -> Option<usize> {
+        self.pressed.iter().find_map(|action| match action {
+            Action::Bookmark(index) => Some(*index),
+            _ => None,
+        })
+    }
+
+    pub fn set_analog(&mut self, axis: Axis, value: f32) {
+        self.analog[axis as usize] = value;
+    }
+
+    /// Position on `axis` of the held actions and the analog value together.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        let (negative, positive) = axis.actions();
+        let digital = self.held(positive) as i32 - self.held(negative) as i32;
+        (digital as f32 + self.analog[axis as usize]).clamp(-1.0, 1.0)
+    }
+
+    pub fn add_look(&mut self, delta: Vec2) {
+        self.look += delta;
+    }
+
+    pub fn look(&self) -> Vec2 {
+        self.look
+    }
+
+    pub fn add_scroll(&mut self, pixels: f32) {
+        self.scroll += pixels;
+    }
+
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    pub fn set_pointer(&mut self, pointer: Option<Vec2>) {
+        self.pointer = pointer;
+    }
+
+    pub fn pointer(&self) -> Option<Vec2> {
+        self.pointer
+    }
+
+    /// Take the look and scroll gathered so far, spread evenly over `steps`
+    /// simulation steps. Each step reads the returned state.
+    pub fn take_steps(&mut self, steps: u32) -> Self {
+        let share = 1.0 / steps as f32;
+        let mut input = self.clone();
+        input.look = std::mem::take(&mut self.look) * share;
+        input.scroll = std::mem::take(&mut self.scroll) * share;
+        input
+    }
+
+    /// Drop the look and scroll gathered so far, while nothing moves.
+    pub fn clear_motion(&mut self) {
+        self.look = Vec2::ZERO;
+        self.scroll = 0.0;
+    }
+
+    /// Forget the presses of the frame that was just updated.
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+    }
+}
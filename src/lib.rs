@@ -0,0 +1,82 @@
+//! Renderer of the radiance cascade viewer without its window, for embedding
+//! the renderer in other programs and for running it headless.
+//!
+//! The viewer binary adds the winit window and the egui widgets on top. A host
+//! loads a `Scene` with a `SceneLoader`, hands it to `Renderer::set_scene` and
+//! renders it through the `RenderStage`s of `stages` with an `AppState`
+//! describing the frame.
+
+pub mod animation;
+pub mod app;
+pub mod atlas;
+pub mod benchmark;
+pub mod camera;
+pub mod capabilities;
+pub mod color_lut;
+pub mod compute;
+pub mod contact_sheet;
+pub mod culling;
+pub mod debug_draw;
+pub mod depth_contours;
+pub mod dock;
+pub mod error;
+pub mod flythrough;
+pub mod frame_stats;
+pub mod gltf_scene;
+pub mod gpu_timer;
+pub mod ground_grid;
+pub mod guides;
+pub mod heatmap;
+pub mod help;
+pub mod input;
+pub mod light_orbit;
+pub mod loader;
+pub mod logging;
+pub mod matcap;
+pub mod material_file;
+pub mod mesh_cache;
+pub mod mesh_tool;
+pub mod notifications;
+pub mod object_ids;
+pub mod orientation;
+pub mod panorama;
+pub mod picking;
+pub mod planar_reflection;
+pub mod post_process;
+pub mod primitives;
+pub mod probe;
+pub mod probe_convergence;
+pub mod probe_export;
+pub mod probe_prefilter;
+pub mod probe_storage;
+pub mod render_graph;
+pub mod render_settings;
+pub mod renderer;
+pub mod roi;
+pub mod scene_file;
+pub mod scene_pipelines;
+pub mod scene_world;
+pub mod schema;
+pub mod shader_preprocessor;
+pub mod simulation;
+pub mod sky;
+pub mod split_view;
+pub mod staging;
+pub mod stages;
+pub mod streaming;
+pub mod sweep;
+pub mod tangents;
+pub mod texture;
+pub mod texture_pool;
+pub mod texture_preview;
+pub mod turntable;
+pub mod validate;
+pub mod volumetric_fog;
+pub mod watchdog;
+pub mod wireframe;
+
+pub use app::*;
+pub use camera::Camera;
+pub use loader::{LoadOptions, LoadedScene as Scene, SceneLoader};
+pub use renderer::DefaultRenderer as Renderer;
+pub use texture::Texture;
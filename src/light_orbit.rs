@@ -0,0 +1,85 @@
+use std::f32::consts::TAU;
+
+use glam::Vec3;
+
+/// Path of the light around the orbit center, in the XZ plane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrbitShape {
+    #[default]
+    Circle,
+    /// `radius` along X, `minor_radius` along Z
+    Ellipse,
+}
+
+impl OrbitShape {
+    pub const ALL: [Self; 2] = [Self::Circle, Self::Ellipse];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Circle => "Circle",
+            Self::Ellipse => "Ellipse",
+        }
+    }
+}
+
+/// Moves the light around where it was when the orbit started, so the response
+/// of the lighting to a moving light can be shown without dragging sliders.
+#[derive(Debug, Clone, Copy)]
+pub struct LightOrbit {
+    pub enabled: bool,
+    pub shape: OrbitShape,
+    pub radius: f32,
+    pub minor_radius: f32,
+    /// Degrees per second, negative orbits the other way
+    pub speed: f32,
+    /// Light position when the orbit started, restored when it stops
+    center: Option<Vec3>,
+    angle: f32,
+}
+
+impl Default for LightOrbit {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            shape: OrbitShape::Circle,
+            radius: 2.0,
+            minor_radius: 1.0,
+            speed: 45.0,
+            center: None,
+            angle: 0.0,
+        }
+    }
+}
+
+impl LightOrbit {
+    /// Orbit around the light position given next, such as the light of a newly
+    /// loaded scene.
+    pub fn recenter(&mut self) {
+        self.center = None;
+    }
+
+    /// Move the light along the orbit by `dt` seconds. `light` is taken as the
+    /// center when the orbit starts and put back when it stops. Returns whether
+    /// the light moved.
+    pub fn advance(&mut self, light: &mut [f32; 3], dt: f32) -> bool {
+        if !self.enabled {
+            let Some(center) = self.center.take() else {
+                return false;
+            };
+            *light = center.to_array();
+            return true;
+        }
+        let center = *self.center.get_or_insert_with(|| {
+            self.angle = 0.0;
+            Vec3::from(*light)
+        });
+        self.angle = (self.angle + self.speed.to_radians() * dt).rem_euclid(TAU);
+        let minor_radius = match self.shape {
+            OrbitShape::Circle => self.radius,
+            OrbitShape::Ellipse => self.minor_radius,
+        };
+        let (sin, cos) = self.angle.sin_cos();
+        *light = (center + Vec3::new(cos * self.radius, 0.0, sin * minor_radius)).to_array();
+        true
+    }
+}
@@ -0,0 +1,436 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use glam::{Vec2, Vec3};
+use itertools::Itertools;
+use rayon::prelude::*;
+
+use crate::{
+    animation::SceneGraph,
+    atlas::{self, AtlasOptions},
+    gltf_scene::GltfScene,
+    logging,
+    mesh_cache::{CachedMesh, MeshCache},
+    orientation::Orientation,
+    primitives::{DynScene, Material, ObjScene, Vertex},
+    probe::ProbeCache,
+    scene_file::SceneFile,
+    tangents,
+    texture::ImageCache,
+};
+
+/// CPU side data of a single mesh, ready to be uploaded to the GPU.
+pub struct MeshData {
+    pub model: Box<DynScene>,
+    /// Scene graph node driving the mesh transform
+    pub node: Option<usize>,
+    pub vertex_data: Vec<Vertex>,
+    pub indices: Box<[u32]>,
+    pub material: Option<Material>,
+    /// Judged after the flips of the scene file were applied
+    pub orientation: Orientation,
+}
+
+pub struct LoadedScene {
+    pub path: PathBuf,
+    pub meshes: Vec<MeshData>,
+    pub light: Option<Vec3>,
+    pub scene_graph: Option<SceneGraph>,
+    pub scene_file: SceneFile,
+    pub probe_cache: Option<ProbeCache>,
+}
+
+impl LoadedScene {
+    /// Scene of meshes built in code, such as `primitives::shapes`, without any
+    /// files behind it. `path` only names the scene.
+    pub fn from_models(path: PathBuf, models: Vec<Box<DynScene>>) -> Self {
+        let mut images = ImageCache::default();
+        let meshes = models
+            .into_iter()
+            .map(|model| {
+                let material = model.material(&mut images);
+                let (vertex_data, indices, orientation) =
+                    assemble_vertices(model.as_ref(), false, false);
+                MeshData {
+                    model,
+                    node: None,
+                    vertex_data,
+                    indices,
+                    material,
+                    orientation,
+                }
+            })
+            .collect();
+        Self {
+            path,
+            meshes,
+            light: None,
+            scene_graph: None,
+            scene_file: SceneFile::default(),
+            probe_cache: None,
+        }
+    }
+
+    /// Move the meshes with the animations of `graph`, `nodes` names the node of
+    /// every mesh in order. Built in code with `SceneGraph::add_node` and
+    /// `animation::Animation`, it is played like the animations of a glTF file.
+    pub fn with_scene_graph(mut self, graph: SceneGraph, nodes: &[Option<usize>]) -> Self {
+        for (mesh, &node) in self.meshes.iter_mut().zip(nodes) {
+            mesh.node = node;
+        }
+        self.scene_graph = Some(graph);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadOptions {
+    /// Pack small color textures into shared atlases
+    pub texture_atlas: Option<AtlasOptions>,
+    /// Generate normals for OBJ meshes that come with their own as well
+    pub recompute_normals: bool,
+    /// Generated normals are not smoothed across edges sharper than this, in degrees
+    pub hard_edge_angle: Option<f32>,
+    /// Assemble every mesh even if the scene has a mesh cache, see `mesh_cache`
+    pub ignore_mesh_cache: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadProgress {
+    pub stage: &'static str,
+    pub done: usize,
+    pub total: usize,
+}
+
+impl LoadProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.done as f32 / self.total as f32
+        }
+    }
+}
+
+enum LoadMessage {
+    Progress(LoadProgress),
+    /// Boxed to keep progress messages small
+    Finished(anyhow::Result<Box<LoadedScene>>),
+}
+
+/// Loads a scene on a background thread, the result is picked up by polling.
+pub struct SceneLoader {
+    receiver: Receiver<LoadMessage>,
+    progress: LoadProgress,
+}
+
+impl SceneLoader {
+    pub fn spawn<P: Into<PathBuf>>(path: P, options: LoadOptions) -> Self {
+        let path = path.into();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let report = |stage, done, total| {
+                let _ = sender.send(LoadMessage::Progress(LoadProgress { stage, done, total }));
+            };
+            let result = load_scene(path, options, report).map(Box::new);
+            let _ = sender.send(LoadMessage::Finished(result));
+        });
+        Self {
+            receiver,
+            progress: LoadProgress {
+                stage: "Parsing scene",
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn progress(&self) -> &LoadProgress {
+        &self.progress
+    }
+
+    /// Block until the loader thread is done.
+    pub fn wait(&mut self) -> anyhow::Result<LoadedScene> {
+        loop {
+            match self.receiver.recv() {
+                Ok(LoadMessage::Progress(progress)) => self.progress = progress,
+                Ok(LoadMessage::Finished(result)) => return result.map(|scene| *scene),
+                Err(_) => return Err(anyhow::anyhow!("scene loader thread died")),
+            }
+        }
+    }
+
+    /// Drain pending messages, returns the scene once the loader thread is done.
+    pub fn poll(&mut self) -> Option<anyhow::Result<LoadedScene>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(LoadMessage::Progress(progress)) => self.progress = progress,
+                Ok(LoadMessage::Finished(result)) => return Some(result.map(|scene| *scene)),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err(anyhow::anyhow!("scene loader thread died")))
+                }
+            }
+        }
+    }
+}
+
+/// Wall clock time of each loading stage, logged once the scene is loaded.
+struct LoadTimer {
+    started: Instant,
+    last: Instant,
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl LoadTimer {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            last: now,
+            stages: Vec::new(),
+        }
+    }
+
+    /// End `stage`, the next one starts now.
+    fn lap(&mut self, stage: &'static str) {
+        let now = Instant::now();
+        self.stages.push((stage, now - self.last));
+        self.last = now;
+    }
+
+    fn log(&self, path: &Path) {
+        let ms = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        tracing::info!(
+            target: logging::SCENE,
+            "loaded {} in {:.0} ms: {}",
+            path.display(),
+            ms(self.started.elapsed()),
+            self.stages
+                .iter()
+                .map(|(stage, duration)| format!("{} {:.0} ms", stage, ms(*duration)))
+                .join(", ")
+        );
+    }
+}
+
+fn load_scene<F>(path: PathBuf, options: LoadOptions, report: F) -> anyhow::Result<LoadedScene>
+where
+    F: Fn(&'static str, usize, usize) + Sync,
+{
+    let mut timer = LoadTimer::new();
+    // Read first, it flips the winding or normals of some meshes
+    let scene_file = SceneFile::load(&path)
+        .inspect_err(|err| {
+            tracing::warn!(target: logging::SCENE, "failed to read scene file: {}", err)
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    // Regenerated normals have to be assembled again
+    let mesh_cache = (!options.ignore_mesh_cache && !options.recompute_normals)
+        .then(|| MeshCache::load(&path))
+        .transpose()
+        .inspect_err(|err| {
+            tracing::warn!(target: logging::SCENE, "failed to read mesh cache: {}", err)
+        })
+        .ok()
+        .flatten()
+        .flatten()
+        .filter(|cache| cache.hard_edge_angle == options.hard_edge_angle);
+    let flips = |name: &str| {
+        let flipped = |names: &[String]| names.iter().any(|n| n == name);
+        (
+            flipped(&scene_file.flipped_winding),
+            flipped(&scene_file.flipped_normals),
+        )
+    };
+    let cached = |index: usize, name: &str| {
+        let (flip_winding, flip_normals) = flips(name);
+        mesh_cache
+            .as_ref()
+            .and_then(|cache| cache.mesh(index, name, flip_winding, flip_normals))
+    };
+    let is_gltf = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gltf") || ext.eq_ignore_ascii_case("glb"));
+    let (models, light, scene_graph): (Vec<(Box<DynScene>, Option<usize>)>, _, _) = if is_gltf {
+        report("Parsing glTF", 0, 1);
+        let (models, scene_graph) = GltfScene::load(&path)?;
+        let models = models
+            .into_iter()
+            .map(|m| {
+                let node = Some(m.node);
+                (Box::new(m) as Box<DynScene>, node)
+            })
+            .collect();
+        (models, None, Some(scene_graph))
+    } else {
+        report("Parsing OBJ", 0, 1);
+        let (mut models, light) = ObjScene::load(&path, |mt| mt.name == "Light")?;
+        let hard_edge_angle = options.hard_edge_angle.map(f32::to_radians);
+        models
+            .par_iter_mut()
+            .enumerate()
+            .filter(|(i, m)| {
+                cached(*i, &m.model.name).is_none()
+                    && (options.recompute_normals
+                        || m.model.mesh.normals.len() != m.model.mesh.positions.len())
+            })
+            .for_each(|(_, m)| m.generate_normals(hard_edge_angle));
+        let models = models
+            .into_iter()
+            .map(|m| (Box::new(m) as Box<DynScene>, None))
+            .collect();
+        (models, light, None)
+    };
+    timer.lap("parse");
+    let total = models.len();
+    // Textures are shared between meshes through the cache, so they load in order
+    let mut images = ImageCache::default();
+    let materials: Vec<_> = models
+        .iter()
+        .enumerate()
+        .map(|(i, (model, _))| {
+            report("Loading textures", i, total);
+            model.material(&mut images)
+        })
+        .collect();
+    timer.lap("textures");
+    let done = AtomicUsize::new(0);
+    let mut meshes: Vec<_> = models
+        .into_par_iter()
+        .zip(materials)
+        .enumerate()
+        .map(|(i, ((model, node), material))| {
+            let (vertex_data, indices, orientation) = match cached(i, model.name()) {
+                Some(mesh) => cached_vertices(mesh),
+                None => {
+                    let (flip_winding, flip_normals) = flips(model.name());
+                    assemble_vertices(model.as_ref(), flip_winding, flip_normals)
+                }
+            };
+            report(
+                "Loading meshes",
+                done.fetch_add(1, Ordering::Relaxed) + 1,
+                total,
+            );
+            MeshData {
+                model,
+                node,
+                vertex_data,
+                indices,
+                material,
+                orientation,
+            }
+        })
+        .collect();
+    timer.lap(if mesh_cache.is_some() {
+        "cached meshes"
+    } else {
+        "meshes"
+    });
+    if let Some(atlas_options) = options.texture_atlas {
+        report("Packing textures", total, total);
+        atlas::pack_color_textures(&mut meshes, atlas_options);
+        timer.lap("atlas");
+    }
+    let probe_cache = ProbeCache::load(&path)
+        .inspect_err(|err| {
+            tracing::warn!(
+                target: logging::SCENE,
+                "failed to read probe cache: {}",
+                err
+            )
+        })
+        .ok()
+        .flatten();
+    timer.lap("scene files");
+    timer.log(&path);
+    report("Uploading", total, total);
+    Ok(LoadedScene {
+        path,
+        meshes,
+        light,
+        scene_graph,
+        scene_file,
+        probe_cache,
+    })
+}
+
+/// Vertices and indices of a mesh cache entry, judged like assembled ones.
+fn cached_vertices(mesh: &CachedMesh) -> (Vec<Vertex>, Box<[u32]>, Orientation) {
+    let positions = mesh.vertices.iter().map(|v| v.position).collect::<Vec<_>>();
+    let normals = mesh.vertices.iter().map(|v| v.normal).collect::<Vec<_>>();
+    let orientation = Orientation::analyze(&positions, &normals, &mesh.indices);
+    (mesh.vertices.clone(), mesh.indices.clone(), orientation)
+}
+
+/// Build the vertex buffer content and the indices into it, missing attributes
+/// get neutral defaults. Vertices on mirrored UV seams are split, see `tangents`.
+/// The winding and normals are flipped as asked before the orientation is judged.
+fn assemble_vertices(
+    model: &DynScene,
+    flip_winding: bool,
+    flip_normals: bool,
+) -> (Vec<Vertex>, Box<[u32]>, Orientation) {
+    let positions = model.vertices();
+    let mut indices = model.indices();
+    if flip_winding {
+        indices.chunks_exact_mut(3).for_each(|c| c.swap(1, 2));
+    }
+    let colors = model.vertex_colors();
+    let mut normals = Some(model.normals())
+        .filter(|normals| normals.len() == positions.len())
+        .unwrap_or_else(|| tangents::vertex_normals(&positions, &indices));
+    if flip_normals {
+        normals.iter_mut().for_each(|n| *n = -*n);
+    }
+    let orientation = Orientation::analyze(&positions, &normals, &indices);
+    // Rigid unless every vertex has its joints and weights
+    let (joints, weights) = (model.joints(), model.weights());
+    let skinned = joints.len() == positions.len() && weights.len() == positions.len();
+    let texcoords = Some(model.texcoords())
+        .filter(|texcoords| texcoords.len() == positions.len())
+        .unwrap_or_else(|| vec![Vec2::ZERO; positions.len()].into());
+    // Given frames no longer match flipped triangles or normals
+    let given = Some(model.tangents())
+        .filter(|tangents| tangents.len() == positions.len() && !flip_winding && !flip_normals);
+    let frames = match given {
+        Some(given) => tangents::TangentFrames {
+            sources: (0..positions.len() as u32).collect(),
+            tangents: given.into_vec(),
+            indices,
+        },
+        None => tangents::generate(&positions, &normals, &texcoords, &indices),
+    };
+    let vertices = frames
+        .sources
+        .iter()
+        .zip(&frames.tangents)
+        .map(|(&source, tangent)| {
+            let i = source as usize;
+            Vertex {
+                position: positions[i],
+                color: colors.get(i).copied().unwrap_or(Vec3::ONE),
+                normal: normals[i],
+                tangent: tangent.truncate(),
+                tangent_sign: tangent.w,
+                texcoord: texcoords[i],
+                joints: if skinned { joints[i] } else { [0; 4] },
+                weights: if skinned {
+                    weights[i].to_array()
+                } else {
+                    [0.0; 4]
+                },
+            }
+        })
+        .collect();
+    (vertices, frames.indices, orientation)
+}
@@ -0,0 +1,261 @@
+//! Logging through `tracing`, with a target per subsystem so the output can be
+//! narrowed down, e.g. `RUST_LOG=warn,gpu=debug`.
+//!
+//! Messages of crates logging with `log`, wgpu among them, are forwarded. The
+//! events are printed to stderr as filtered by `RUST_LOG` and kept in a
+//! `LogBuffer` for the log window, which always records the targets below.
+//!
+//! wgpu validation errors are logged under `GPU` inside the spans entered
+//! around the call that caused them, such as the pass or the geom.
+
+use std::{
+    collections::VecDeque,
+    fmt::{self, Write},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Level, Subscriber,
+};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::{
+    filter::Targets,
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    Layer,
+};
+use wgpu::Device;
+
+/// Window, input, settings and captures
+pub const APP: &str = "app";
+/// Loading and streaming scenes, meshes, materials and textures
+pub const SCENE: &str = "scene";
+/// Passes of the renderer and their statistics
+pub const RENDER: &str = "render";
+pub const PROBES: &str = "probes";
+pub const SHADERS: &str = "shaders";
+/// Adapter, device and the validation errors wgpu raises
+pub const GPU: &str = "gpu";
+pub const TARGETS: [&str; 6] = [APP, SCENE, RENDER, PROBES, SHADERS, GPU];
+
+/// Records kept for the log window, older ones are dropped
+pub const MAX_RECORDS: usize = 2000;
+
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    /// Since logging started
+    pub time: Duration,
+    pub level: Level,
+    pub target: String,
+    /// Spans the event happened in, outermost first, like `pass{name="opaque"}`
+    pub spans: String,
+    pub message: String,
+}
+
+/// Events of the session shown by the log window. Clones share the records.
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer {
+    records: Arc<Mutex<VecDeque<LogRecord>>>,
+}
+
+impl LogBuffer {
+    fn push(&self, record: LogRecord) {
+        let mut records = self.records.lock().unwrap_or_else(|err| err.into_inner());
+        if records.len() == MAX_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Look at the records, oldest first, without copying them.
+    pub fn read<T>(&self, read: impl FnOnce(&VecDeque<LogRecord>) -> T) -> T {
+        read(&self.records.lock().unwrap_or_else(|err| err.into_inner()))
+    }
+
+    pub fn clear(&self) {
+        self.records
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clear();
+    }
+}
+
+static BUFFER: OnceLock<LogBuffer> = OnceLock::new();
+
+/// The records of `init`, empty when logging was not initialized.
+pub fn buffer() -> LogBuffer {
+    BUFFER.get().cloned().unwrap_or_default()
+}
+
+/// Install the stderr output and the `LogBuffer`, call once at startup.
+/// Without `RUST_LOG` only errors are printed.
+pub fn init() {
+    let printed = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|filter| {
+            filter
+                .parse::<Targets>()
+                .inspect_err(|err| eprintln!("ignoring RUST_LOG {:?}: {}", filter, err))
+                .ok()
+        })
+        .unwrap_or_else(|| Targets::new().with_default(Level::ERROR));
+    let recorded = Targets::new()
+        .with_default(Level::WARN)
+        .with_targets(TARGETS.map(|target| (target, Level::DEBUG)));
+    let buffer = BUFFER.get_or_init(LogBuffer::default).clone();
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(printed),
+        )
+        .with(
+            BufferLayer {
+                buffer,
+                start: Instant::now(),
+            }
+            .with_filter(recorded),
+        )
+        .init();
+}
+
+/// Run `create` in a wgpu validation error scope and log what it raised under
+/// `GPU`, in the spans entered around the call.
+pub fn validated<T>(device: &Device, create: impl FnOnce() -> T) -> T {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let created = create();
+    if let Some(err) = pollster::block_on(device.pop_error_scope()) {
+        tracing::error!(target: GPU, "{}", err);
+    }
+    created
+}
+
+/// Log the wgpu errors no scope caught instead of panicking, they are raised
+/// while the call that caused them is still in its spans.
+pub fn log_uncaptured_errors(device: &Device) {
+    device.on_uncaptured_error(Box::new(|err| {
+        tracing::error!(target: GPU, "{}", err);
+    }));
+}
+
+/// Fields of a span, formatted once when it is created.
+struct SpanFields(String);
+
+/// The message and the other fields of an event, or the fields of a span.
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            // Metadata of forwarded `log` records
+            name if name.starts_with("log.") => {}
+            name => {
+                if !self.fields.is_empty() {
+                    self.fields.push(' ');
+                }
+                let _ = write!(self.fields, "{}={:?}", name, value);
+            }
+        }
+    }
+}
+
+struct BufferLayer {
+    buffer: LogBuffer,
+    start: Instant,
+}
+
+impl<S> Layer<S> for BufferLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(visitor.fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let message = if visitor.fields.is_empty() {
+            visitor.message
+        } else {
+            format!("{} {}", visitor.message, visitor.fields)
+        };
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| match span.extensions().get::<SpanFields>() {
+                        Some(SpanFields(fields)) if !fields.is_empty() => {
+                            format!("{}{{{}}}", span.name(), fields)
+                        }
+                        _ => span.name().to_owned(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(":")
+            })
+            .unwrap_or_default();
+        // Forwarded `log` records name their target in fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        self.buffer.push(LogRecord {
+            time: self.start.elapsed(),
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            spans,
+            message,
+        });
+    }
+}
+
+/// What the log window shows of the `LogBuffer`.
+#[derive(Debug, Clone)]
+pub struct LogView {
+    /// Most verbose level shown
+    pub level: Level,
+    /// One of `TARGETS`, `None` shows every target including other crates
+    pub target: Option<&'static str>,
+    /// Only records whose message or spans contain this, ignoring case
+    pub search: String,
+}
+
+impl Default for LogView {
+    fn default() -> Self {
+        Self {
+            level: Level::INFO,
+            target: None,
+            search: String::new(),
+        }
+    }
+}
+
+impl LogView {
+    pub const LEVELS: [Level; 5] = [
+        Level::ERROR,
+        Level::WARN,
+        Level::INFO,
+        Level::DEBUG,
+        Level::TRACE,
+    ];
+
+    pub fn shows(&self, record: &LogRecord) -> bool {
+        let search = self.search.to_lowercase();
+        record.level <= self.level
+            && self.target.is_none_or(|target| record.target == target)
+            && (search.is_empty()
+                || record.message.to_lowercase().contains(&search)
+                || record.spans.to_lowercase().contains(&search))
+    }
+}
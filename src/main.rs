@@ -1,17 +1,22 @@
 use winit::event_loop::{ControlFlow, EventLoop};
 
-mod app;
-mod camera;
-mod primitives;
-mod renderer;
-mod texture;
 mod widget;
 mod window;
-use app::*;
+use radiance_cascade::*;
 
 #[pollster::main]
 async fn main() {
-    env_logger::init();
+    logging::init();
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--validate-scene") {
+        let scenes: Vec<_> = args
+            .iter()
+            .filter(|arg| !arg.starts_with("--"))
+            .map(String::as_str)
+            .collect();
+        let valid = validate::validate_scenes(&scenes);
+        std::process::exit(if valid { 0 } else { 1 });
+    }
     let event_loop = EventLoop::new().unwrap();
 
     event_loop.set_control_flow(ControlFlow::Poll);
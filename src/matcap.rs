@@ -0,0 +1,198 @@
+//! Images of a lit sphere for the matcap channel.
+//!
+//! The matcap channel colors each surface by its normal turned into view space.
+//! The x and y of that normal index a picture of a lit sphere as seen from the
+//! camera. The lighting stays fixed to the view, so bumps, seams and twisted
+//! tangents show up the same from every side. The presets are drawn here. An
+//! image file can be picked instead and is resized to the same square.
+
+use std::path::{Path, PathBuf};
+
+use glam::Vec3;
+use image::{imageops, Rgba, RgbaImage};
+use wgpu::Device;
+
+use crate::texture;
+
+/// Side of the matcap texture, images read from disk are resized to it
+pub const RESOLUTION: u32 = 256;
+
+/// Lit sphere shown by the matcap channel.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Matcap {
+    /// Matte warm white
+    #[default]
+    Clay,
+    /// Red, soft and with a sharp highlight
+    RedWax,
+    /// Green with a glow around the rim
+    Jade,
+    /// Reflection of a sky over a dark ground
+    Chrome,
+    /// Square image of a lit sphere
+    File(PathBuf),
+}
+
+impl Matcap {
+    pub const PRESETS: [Self; 4] = [Self::Clay, Self::RedWax, Self::Jade, Self::Chrome];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Clay => "Clay",
+            Self::RedWax => "Red wax",
+            Self::Jade => "Jade",
+            Self::Chrome => "Chrome",
+            Self::File(path) => path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("Image"),
+        }
+    }
+
+    /// The sphere as `RESOLUTION` squared sRGB pixels.
+    pub fn image(&self) -> crate::error::Result<RgbaImage> {
+        match self {
+            Self::File(path) => open(path),
+            preset => Ok(RgbaImage::from_fn(RESOLUTION, RESOLUTION, |x, y| {
+                let color = preset.shade(sphere_normal(x, y));
+                let [r, g, b] = color
+                    .clamp(Vec3::ZERO, Vec3::ONE)
+                    .powf(1.0 / 2.2)
+                    .to_array()
+                    .map(|c| (c * 255.0).round() as u8);
+                Rgba([r, g, b, 255])
+            })),
+        }
+    }
+
+    /// Linear color of the sphere where it faces `normal`, the camera looks down -Z.
+    fn shade(&self, normal: Vec3) -> Vec3 {
+        let light = Vec3::new(-0.5, 0.6, 0.6).normalize();
+        let half = (light + Vec3::Z).normalize();
+        let diffuse = normal.dot(light).max(0.0);
+        let rim = (1.0 - normal.z).powi(3);
+        match self {
+            Self::Clay => {
+                Vec3::new(0.8, 0.72, 0.62) * (0.25 + 0.75 * diffuse)
+                    + Vec3::splat(0.1 * normal.dot(half).max(0.0).powf(8.0))
+            }
+            Self::RedWax => {
+                // Light scattered under the surface wraps past the terminator
+                let wrap = ((normal.dot(light) + 0.4) / 1.4).max(0.0);
+                Vec3::new(0.6, 0.05, 0.04) * (0.1 + 0.9 * wrap)
+                    + Vec3::splat(0.8 * normal.dot(half).max(0.0).powf(96.0))
+            }
+            Self::Jade => {
+                Vec3::new(0.1, 0.4, 0.25) * (0.2 + 0.8 * diffuse)
+                    + Vec3::new(0.4, 0.9, 0.6) * rim * 0.6
+                    + Vec3::splat(0.4 * normal.dot(half).max(0.0).powf(32.0))
+            }
+            Self::Chrome => {
+                let reflected = 2.0 * normal.z * normal - Vec3::Z;
+                let sky = Vec3::new(0.3, 0.45, 0.7)
+                    .lerp(Vec3::new(0.9, 0.95, 1.0), 1.0 - reflected.y.max(0.0));
+                let ground = Vec3::new(0.15, 0.12, 0.1)
+                    .lerp(Vec3::splat(0.02), (-reflected.y).max(0.0).sqrt());
+                let environment = if reflected.y >= 0.0 { sky } else { ground };
+                environment + Vec3::splat(reflected.dot(light).max(0.0).powf(256.0) * 2.0)
+            }
+            Self::File(_) => Vec3::ZERO,
+        }
+    }
+}
+
+/// Normal of the sphere filling the square at texel `x`, `y`, the corners
+/// outside it repeat the silhouette.
+fn sphere_normal(x: u32, y: u32) -> Vec3 {
+    let u = (x as f32 + 0.5) / RESOLUTION as f32 * 2.0 - 1.0;
+    let v = 1.0 - (y as f32 + 0.5) / RESOLUTION as f32 * 2.0;
+    let squared = u * u + v * v;
+    if squared >= 1.0 {
+        let edge = glam::Vec2::new(u, v) / squared.sqrt();
+        edge.extend(0.0)
+    } else {
+        Vec3::new(u, v, (1.0 - squared).sqrt())
+    }
+}
+
+fn open(path: &Path) -> crate::error::Result<RgbaImage> {
+    let decode = || -> anyhow::Result<_> { Ok(image::ImageReader::open(path)?.decode()?) };
+    let image = decode().map_err(|reason| crate::error::Error::Texture {
+        path: path.to_path_buf(),
+        reason,
+    })?;
+    Ok(imageops::resize(
+        &image.to_rgba8(),
+        RESOLUTION,
+        RESOLUTION,
+        imageops::FilterType::Triangle,
+    ))
+}
+
+/// Texture the matcap channel samples, rewritten in place when another
+/// `Matcap` is picked.
+pub struct MatcapTexture {
+    pub texture: texture::Texture,
+}
+
+impl MatcapTexture {
+    pub fn new(device: &Device, queue: &wgpu::Queue) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Matcap Texture"),
+            size: wgpu::Extent3d {
+                width: RESOLUTION,
+                height: RESOLUTION,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Matcap Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let matcap = Self {
+            texture: texture::Texture {
+                texture,
+                view,
+                sampler,
+            },
+        };
+        matcap
+            .set(queue, &Matcap::default())
+            .expect("the presets are drawn without failing");
+        matcap
+    }
+
+    /// Upload `matcap`, the texture keeps its previous image on failure.
+    pub fn set(&self, queue: &wgpu::Queue, matcap: &Matcap) -> crate::error::Result<()> {
+        let image = matcap.image()?;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &image,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * RESOLUTION),
+                rows_per_image: Some(RESOLUTION),
+            },
+            wgpu::Extent3d {
+                width: RESOLUTION,
+                height: RESOLUTION,
+                depth_or_array_layers: 1,
+            },
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+//! Per material overrides stored next to an OBJ as `<name>.materials.ron`, for
+//! what MTL cannot express or an exporter got wrong. Entries are keyed by the
+//! MTL material name and every field may be left out, the value of the MTL
+//! file is kept then:
+//!
+//! ```ron
+//! {
+//!     "floor": (roughness: 0.35, normal_texture: "textures/floor_normal.png"),
+//!     "lamp": (emissive: (1.0, 0.8, 0.5), emissive_strength: 4.0),
+//!     "leaves": (alpha_cutoff: 0.4, cull_mode: None),
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use glam::Vec3;
+use serde::Deserialize;
+
+use crate::{
+    primitives::{resource_path, AlphaMode, CullMode, Material},
+    texture::TextureImage,
+};
+
+/// Overrides of one material, applied over the values read from MTL.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaterialPatch {
+    pub ambient: Option<[f32; 3]>,
+    pub diffuse: Option<[f32; 3]>,
+    pub specular: Option<[f32; 3]>,
+    pub shininess: Option<f32>,
+    /// Constant roughness, turned into the Blinn-Phong exponent. A roughness
+    /// texture takes precedence
+    pub roughness: Option<f32>,
+    /// Texture paths relative to the OBJ file
+    pub color_texture: Option<PathBuf>,
+    pub normal_texture: Option<PathBuf>,
+    pub specular_texture: Option<PathBuf>,
+    pub roughness_texture: Option<PathBuf>,
+    pub ambient_occlusion_texture: Option<PathBuf>,
+    pub alpha_texture: Option<PathBuf>,
+    /// Below one the material is blended
+    pub alpha: Option<f32>,
+    /// Cut out fragments whose alpha is below the value instead of blending them
+    pub alpha_cutoff: Option<f32>,
+    /// Faces that are not rendered, takes precedence over `double_sided`
+    pub cull_mode: Option<CullMode>,
+    /// `true` is `cull_mode: None` and `false` is `cull_mode: Back`
+    pub double_sided: Option<bool>,
+    /// Light given off by the surface, `Ke` of the MTL file when left out
+    pub emissive: Option<[f32; 3]>,
+    /// Scale of the emissive color
+    pub emissive_strength: Option<f32>,
+    /// Scale of the bend of the normal map, see `NormalMapping`
+    pub normal_strength: Option<f32>,
+    /// The normal map was baked for DirectX, with green pointing down
+    pub normal_flip_y: Option<bool>,
+    /// Texture repeats per world unit of a projection along the axes, used in
+    /// place of the texture coordinates. Zero turns it off
+    pub triplanar_scale: Option<f32>,
+}
+
+impl MaterialPatch {
+    /// Apply the overrides to `material`, `open` loads the texture at a path
+    /// relative to the OBJ file.
+    pub fn apply<F>(&self, material: &mut Material, mut open: F)
+    where
+        F: FnMut(&Path) -> Option<Arc<TextureImage>>,
+    {
+        let color = |value: Option<[f32; 3]>, current: &mut Option<Vec3>| {
+            if let Some(value) = value {
+                *current = Some(Vec3::from_array(value));
+            }
+        };
+        color(self.ambient, &mut material.ambient);
+        color(self.diffuse, &mut material.diffuse);
+        color(self.specular, &mut material.specular);
+        color(self.emissive, &mut material.emissive);
+        if let Some(strength) = self.emissive_strength {
+            material.emissive = material.emissive.map(|emissive| emissive * strength);
+        }
+        if let Some(shininess) = self.shininess {
+            material.shininess = Some(shininess);
+        }
+        if let Some(roughness) = self.roughness {
+            // Inverse of the exponent `shader.wgsl` matches to a roughness map
+            let alpha = (roughness * roughness).max(1e-3);
+            material.shininess = Some(2.0 / (alpha * alpha) - 2.0);
+        }
+        let mut texture = |path: &Option<PathBuf>, current: &mut Option<_>| {
+            if let Some(path) = path {
+                *current = open(path);
+            }
+        };
+        texture(&self.color_texture, &mut material.color_texture);
+        texture(&self.normal_texture, &mut material.normal_texture);
+        texture(&self.specular_texture, &mut material.specular_texture);
+        texture(&self.roughness_texture, &mut material.roughness_texture);
+        texture(
+            &self.ambient_occlusion_texture,
+            &mut material.ambient_occlusion_texture,
+        );
+        texture(&self.alpha_texture, &mut material.alpha_texture);
+        if let Some(alpha) = self.alpha {
+            material.alpha = Some(alpha);
+            material.alpha_mode = if alpha < 1.0 {
+                AlphaMode::Blend
+            } else {
+                AlphaMode::Opaque
+            };
+        }
+        if let Some(cutoff) = self.alpha_cutoff {
+            material.alpha_mode = AlphaMode::Mask { cutoff };
+        }
+        if let Some(double_sided) = self.double_sided {
+            material.cull_mode = if double_sided {
+                CullMode::None
+            } else {
+                CullMode::Back
+            };
+        }
+        if let Some(cull_mode) = self.cull_mode {
+            material.cull_mode = cull_mode;
+        }
+        if let Some(strength) = self.normal_strength {
+            material.normal_mapping.strength = strength;
+        }
+        if let Some(flip_y) = self.normal_flip_y {
+            material.normal_mapping.flip_y = flip_y;
+        }
+        if let Some(scale) = self.triplanar_scale {
+            material.triplanar_scale = Some(scale).filter(|scale| *scale > 0.0);
+        }
+    }
+}
+
+/// Contents of a `<name>.materials.ron`, see the module docs.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct MaterialFile {
+    pub materials: HashMap<String, MaterialPatch>,
+}
+
+impl MaterialFile {
+    pub fn path_for<P: AsRef<Path>>(scene_path: P) -> PathBuf {
+        resource_path(scene_path).with_extension("materials.ron")
+    }
+
+    /// Read the material file belonging to `scene_path`, a missing file is not
+    /// an error.
+    pub fn load<P: AsRef<Path>>(scene_path: P) -> anyhow::Result<Option<Self>> {
+        let path = Self::path_for(scene_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        // Optional fields are written without `Some(..)`
+        let file = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(&content)
+            .with_context(|| format!("invalid material file {}", path.display()))?;
+        Ok(Some(file))
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    logging,
+    primitives::{resource_path, Vertex},
+};
+
+const CACHE_MAGIC: [u8; 4] = *b"MSH2";
+const FLIP_WINDING: u32 = 1;
+const FLIP_NORMALS: u32 = 2;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct CacheHeader {
+    magic: [u8; 4],
+    mesh_count: u32,
+    /// NaN when normals are smoothed across every edge
+    hard_edge_angle: f32,
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct MeshHeader {
+    name_len: u32,
+    /// `FLIP_WINDING` and `FLIP_NORMALS`, the flips of the scene file applied
+    flips: u32,
+    vertex_count: u32,
+    index_count: u32,
+}
+
+/// Vertices and indices of one mesh as the loader assembles them.
+#[derive(Debug, Clone)]
+pub struct CachedMesh {
+    pub name: String,
+    pub flip_winding: bool,
+    pub flip_normals: bool,
+    pub vertices: Vec<Vertex>,
+    pub indices: Box<[u32]>,
+}
+
+/// Assembled meshes of a scene stored next to it as `<name>.meshes.bin`, written
+/// by `mesh-tool convert`. The loader takes vertices and indices from it instead
+/// of generating normals and tangents.
+#[derive(Debug, Clone)]
+pub struct MeshCache {
+    /// Normals were not smoothed across edges sharper than this, in degrees
+    pub hard_edge_angle: Option<f32>,
+    /// In the order the scene lists its meshes
+    pub meshes: Vec<CachedMesh>,
+}
+
+impl MeshCache {
+    pub fn path_for<P: AsRef<Path>>(scene_path: P) -> PathBuf {
+        resource_path(scene_path).with_extension("meshes.bin")
+    }
+
+    /// Read the cache belonging to `scene_path`. A missing cache or one older
+    /// than the scene is not an error.
+    pub fn load<P: AsRef<Path>>(scene_path: P) -> anyhow::Result<Option<Self>> {
+        let path = Self::path_for(&scene_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+        if modified(&path)? < modified(&resource_path(scene_path))? {
+            tracing::info!(
+                target: logging::SCENE,
+                "ignoring {}, the scene changed since",
+                path.display()
+            );
+            return Ok(None);
+        }
+        let data = fs::read(&path)?;
+        let mut reader = Reader { data: &data };
+        let header: CacheHeader = reader.read()?;
+        anyhow::ensure!(header.magic == CACHE_MAGIC, "not a mesh cache");
+        let meshes = (0..header.mesh_count)
+            .map(|_| {
+                let mesh: MeshHeader = reader.read()?;
+                let name = String::from_utf8(reader.bytes(mesh.name_len as usize)?.to_vec())?;
+                let vertex_size = std::mem::size_of::<Vertex>();
+                let vertices = reader
+                    .bytes(mesh.vertex_count as usize * vertex_size)?
+                    .chunks_exact(vertex_size)
+                    .map(bytemuck::pod_read_unaligned)
+                    .collect();
+                let indices = reader
+                    .bytes(mesh.index_count as usize * 4)?
+                    .chunks_exact(4)
+                    .map(bytemuck::pod_read_unaligned)
+                    .collect();
+                Ok(CachedMesh {
+                    name,
+                    flip_winding: mesh.flips & FLIP_WINDING != 0,
+                    flip_normals: mesh.flips & FLIP_NORMALS != 0,
+                    vertices,
+                    indices,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Some(Self {
+            hard_edge_angle: Some(header.hard_edge_angle).filter(|angle| !angle.is_nan()),
+            meshes,
+        }))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, scene_path: P) -> anyhow::Result<PathBuf> {
+        let path = Self::path_for(scene_path);
+        let header = CacheHeader {
+            magic: CACHE_MAGIC,
+            mesh_count: self.meshes.len() as u32,
+            hard_edge_angle: self.hard_edge_angle.unwrap_or(f32::NAN),
+            _padding: 0,
+        };
+        let mut data = bytemuck::bytes_of(&header).to_vec();
+        for mesh in &self.meshes {
+            let flips = if mesh.flip_winding { FLIP_WINDING } else { 0 }
+                | if mesh.flip_normals { FLIP_NORMALS } else { 0 };
+            let mesh_header = MeshHeader {
+                name_len: mesh.name.len() as u32,
+                flips,
+                vertex_count: mesh.vertices.len() as u32,
+                index_count: mesh.indices.len() as u32,
+            };
+            data.extend_from_slice(bytemuck::bytes_of(&mesh_header));
+            data.extend_from_slice(mesh.name.as_bytes());
+            data.extend_from_slice(bytemuck::cast_slice(&mesh.vertices));
+            data.extend_from_slice(bytemuck::cast_slice(&mesh.indices));
+        }
+        fs::write(&path, data)?;
+        Ok(path)
+    }
+
+    /// Mesh `index` of the cache if it was made from a mesh called `name` with
+    /// the same flips.
+    pub fn mesh(
+        &self,
+        index: usize,
+        name: &str,
+        flip_winding: bool,
+        flip_normals: bool,
+    ) -> Option<&CachedMesh> {
+        self.meshes.get(index).filter(|mesh| {
+            mesh.name == name
+                && mesh.flip_winding == flip_winding
+                && mesh.flip_normals == flip_normals
+        })
+    }
+}
+
+/// Reads consecutive parts of a cache file.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        anyhow::ensure!(self.data.len() >= len, "truncated mesh cache");
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read<T: Pod>(&mut self) -> anyhow::Result<T> {
+        Ok(bytemuck::pod_read_unaligned(
+            self.bytes(std::mem::size_of::<T>())?,
+        ))
+    }
+}
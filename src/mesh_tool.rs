@@ -0,0 +1,258 @@
+//! `mesh-tool`: prepare the meshes of scenes without opening a window, through
+//! the loader the viewer uses.
+//!
+//! - `validate <scene>...`: check the assembled meshes, exits non-zero when a
+//!   mesh has non-finite attributes or indices past its vertices
+//! - `convert [--normals] [--hard-edge-angle=DEGREES] [--optimize] <scene>...`:
+//!   generate normals and tangents and store the result as the mesh cache of
+//!   each scene, see `mesh_cache`
+
+use std::collections::HashMap;
+
+use crate::{
+    loader::{LoadOptions, LoadedScene, MeshData, SceneLoader},
+    mesh_cache::{CachedMesh, MeshCache},
+    picking,
+    primitives::Vertex,
+};
+
+/// Largest deviation of a tangent frame determinant from one or minus one
+/// before the frame counts as broken.
+const TBN_TOLERANCE: f32 = 1e-3;
+
+/// Run the command in `args`, returns whether it succeeded.
+pub fn run(args: &[String]) -> bool {
+    let scenes = args
+        .iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let flag = |name: &str| args.iter().any(|arg| arg == name);
+    match args.first().map(String::as_str) {
+        Some("validate") => {
+            let mut valid = true;
+            for scene in scenes {
+                valid &= validate(scene);
+            }
+            valid
+        }
+        Some("convert") => {
+            let hard_edge_angle = match args
+                .iter()
+                .find_map(|arg| arg.strip_prefix("--hard-edge-angle="))
+                .map(str::parse)
+                .transpose()
+            {
+                Ok(angle) => angle,
+                Err(err) => {
+                    eprintln!("invalid --hard-edge-angle: {}", err);
+                    return false;
+                }
+            };
+            let options = LoadOptions {
+                recompute_normals: flag("--normals"),
+                hard_edge_angle,
+                ignore_mesh_cache: true,
+                ..Default::default()
+            };
+            let mut converted = true;
+            for scene in scenes {
+                converted &= convert(scene, options, flag("--optimize"));
+            }
+            converted
+        }
+        _ => {
+            eprintln!("usage: mesh-tool validate <scene>...");
+            eprintln!(
+                "       mesh-tool convert [--normals] [--hard-edge-angle=DEGREES] [--optimize] <scene>..."
+            );
+            false
+        }
+    }
+}
+
+fn load(scene: &str, options: LoadOptions) -> Option<LoadedScene> {
+    match SceneLoader::spawn(scene, options).wait() {
+        Ok(loaded) => Some(loaded),
+        Err(err) => {
+            println!("invalid {}: {:#}", scene, err);
+            None
+        }
+    }
+}
+
+/// Print one line per mesh of `scene` as the viewer loads it, returns whether
+/// every mesh is valid.
+fn validate(scene: &str) -> bool {
+    let Some(loaded) = load(scene, LoadOptions::default()) else {
+        return false;
+    };
+    let mut valid = true;
+    for mesh in &loaded.meshes {
+        let (errors, warnings) = check(mesh);
+        let name = mesh.model.name();
+        let summary = format!(
+            "{} vertices, {} triangles",
+            mesh.vertex_data.len(),
+            mesh.indices.len() / 3
+        );
+        if errors.is_empty() {
+            println!("ok      {}: mesh {}: {}", scene, name, summary);
+        } else {
+            println!("invalid {}: mesh {}: {}", scene, name, errors.join(", "));
+            valid = false;
+        }
+        if !warnings.is_empty() {
+            println!("suspect {}: mesh {}: {}", scene, name, warnings.join(", "));
+        }
+    }
+    valid
+}
+
+/// Problems that make `mesh` unusable and problems that only look wrong.
+fn check(mesh: &MeshData) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let vertices = &mesh.vertex_data;
+    let non_finite = vertices
+        .iter()
+        .filter(|v| {
+            !(v.position.is_finite()
+                && v.normal.is_finite()
+                && v.tangent.is_finite()
+                && v.texcoord.is_finite())
+        })
+        .count();
+    if non_finite > 0 {
+        errors.push(format!(
+            "{} vertices with non-finite attributes",
+            non_finite
+        ));
+    }
+    let out_of_range = mesh
+        .indices
+        .iter()
+        .filter(|&&i| i as usize >= vertices.len())
+        .count();
+    if out_of_range > 0 {
+        errors.push(format!("{} indices past the last vertex", out_of_range));
+        return (errors, warnings);
+    }
+    let degenerate = mesh
+        .indices
+        .chunks_exact(3)
+        .filter(|c| {
+            let [a, b, c] = [c[0], c[1], c[2]].map(|i| vertices[i as usize].position);
+            (b - a).cross(c - a) == glam::Vec3::ZERO
+        })
+        .count();
+    if degenerate > 0 {
+        warnings.push(format!("{} triangles without area", degenerate));
+    }
+    let broken_frames = vertices
+        .iter()
+        .filter(|v| (picking::tbn_determinant(v).abs() - 1.0).abs() > TBN_TOLERANCE)
+        .count();
+    if broken_frames > 0 {
+        warnings.push(format!(
+            "{} vertices without an orthonormal tangent frame",
+            broken_frames
+        ));
+    }
+    warnings.extend(mesh.orientation.problems());
+    (errors, warnings)
+}
+
+/// Write the mesh cache of `scene`, returns whether it was written.
+fn convert(scene: &str, options: LoadOptions, optimize: bool) -> bool {
+    let Some(loaded) = load(scene, options) else {
+        return false;
+    };
+    let flipped = |names: &[String], name: &str| names.iter().any(|n| n == name);
+    let mut removed = (0, 0);
+    let meshes = loaded
+        .meshes
+        .into_iter()
+        .map(|mesh| {
+            let name = mesh.model.name().to_owned();
+            let (mut vertices, mut indices) = (mesh.vertex_data, mesh.indices);
+            if optimize {
+                let (vertex_count, triangle_count) = (vertices.len(), indices.len() / 3);
+                (vertices, indices) = optimized(&vertices, &indices);
+                removed.0 += vertex_count - vertices.len();
+                removed.1 += triangle_count - indices.len() / 3;
+            }
+            CachedMesh {
+                flip_winding: flipped(&loaded.scene_file.flipped_winding, &name),
+                flip_normals: flipped(&loaded.scene_file.flipped_normals, &name),
+                name,
+                vertices,
+                indices,
+            }
+        })
+        .collect::<Vec<_>>();
+    let cache = MeshCache {
+        hard_edge_angle: options.hard_edge_angle,
+        meshes,
+    };
+    let vertex_count = cache.meshes.iter().map(|m| m.vertices.len()).sum::<usize>();
+    let triangle_count = cache
+        .meshes
+        .iter()
+        .map(|m| m.indices.len() / 3)
+        .sum::<usize>();
+    match cache.save(scene) {
+        Ok(path) => {
+            print!(
+                "wrote   {}: {} meshes, {} vertices, {} triangles",
+                path.display(),
+                cache.meshes.len(),
+                vertex_count,
+                triangle_count
+            );
+            if optimize {
+                print!(
+                    ", removed {} vertices and {} triangles",
+                    removed.0, removed.1
+                );
+            }
+            println!();
+            true
+        }
+        Err(err) => {
+            println!(
+                "invalid {}: {:#}",
+                MeshCache::path_for(scene).display(),
+                err
+            );
+            false
+        }
+    }
+}
+
+/// The mesh without triangles that have no area and without vertices no
+/// triangle uses or that repeat another one exactly. Vertices are numbered in
+/// the order the triangles first use them, which keeps vertex fetches close.
+fn optimized(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>, Box<[u32]>) {
+    let mut welded = HashMap::new();
+    let mut kept = Vec::new();
+    let mut kept_indices = Vec::with_capacity(indices.len());
+    for c in indices.chunks_exact(3) {
+        let [a, b, c] = [c[0], c[1], c[2]].map(|i| vertices[i as usize]);
+        if (b.position - a.position).cross(c.position - a.position) == glam::Vec3::ZERO {
+            continue;
+        }
+        for vertex in [a, b, c] {
+            let next = kept.len() as u32;
+            let index = *welded
+                .entry(bytemuck::cast::<Vertex, [u32; 15]>(vertex))
+                .or_insert_with(|| {
+                    kept.push(vertex);
+                    next
+                });
+            kept_indices.push(index);
+        }
+    }
+    (kept, kept_indices.into_boxed_slice())
+}
@@ -0,0 +1,90 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::logging;
+
+/// Toasts on screen at once, older ones are dismissed early.
+pub const MAX_TOASTS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    /// How long a toast stays on screen, errors linger so they are not missed.
+    pub fn duration(self) -> Duration {
+        Duration::from_secs(match self {
+            Level::Info => 4,
+            Level::Warning => 6,
+            Level::Error => 10,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub level: Level,
+    pub message: String,
+    /// When the toast was first shown, set by the UI
+    pub shown: Option<Instant>,
+}
+
+impl Toast {
+    pub fn expired(&self, now: Instant) -> bool {
+        self.shown
+            .is_some_and(|shown| now.duration_since(shown) >= self.level.duration())
+    }
+}
+
+/// Queue of messages shown as toasts without blocking the UI. Clones share the
+/// queue, so subsystems and background threads can keep a handle and push to it.
+/// Every message is logged as well.
+#[derive(Debug, Clone, Default)]
+pub struct Notifications {
+    queue: Arc<Mutex<VecDeque<Toast>>>,
+}
+
+impl Notifications {
+    pub fn info(&self, message: impl Into<String>) {
+        self.push(Level::Info, message.into());
+    }
+
+    pub fn warn(&self, message: impl Into<String>) {
+        self.push(Level::Warning, message.into());
+    }
+
+    pub fn error(&self, message: impl Into<String>) {
+        self.push(Level::Error, message.into());
+    }
+
+    fn push(&self, level: Level, message: String) {
+        match level {
+            Level::Info => tracing::info!(target: logging::APP, "{}", message),
+            Level::Warning => tracing::warn!(target: logging::APP, "{}", message),
+            Level::Error => tracing::error!(target: logging::APP, "{}", message),
+        }
+        self.queue
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push_back(Toast {
+                level,
+                message,
+                shown: None,
+            });
+    }
+
+    /// Take the messages pushed since the last call, oldest first.
+    pub fn take(&self) -> Vec<Toast> {
+        self.queue
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .drain(..)
+            .collect()
+    }
+}
@@ -0,0 +1,175 @@
+//! Object ids of the main view, for picking exactly what a pixel shows.
+//!
+//! The opaque pass of the main view writes the object index plus one of every
+//! pixel it covers into a second target, zero stays where only the sky or
+//! nothing was drawn. Blended geometry does not write ids, the pixel keeps the
+//! opaque object behind it. Hovering reads the texel under the cursor back
+//! without stalling, a few frames late, while a pick reads it right away.
+
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+};
+
+use glam::Vec2;
+use wgpu::{Device, Queue};
+
+use crate::{logging, texture};
+
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+/// Bytes of one id texel
+const TEXEL_SIZE: u64 = 4;
+
+/// Texel of `ids` under `ndc`, in normalized device coordinates of the view.
+fn texel(ids: &texture::Texture, ndc: Vec2) -> wgpu::Origin3d {
+    let size = ids.texture.size();
+    let fraction = (Vec2::new(ndc.x, -ndc.y) * 0.5 + 0.5).clamp(Vec2::ZERO, Vec2::ONE);
+    wgpu::Origin3d {
+        x: ((fraction.x * size.width as f32) as u32).min(size.width - 1),
+        y: ((fraction.y * size.height as f32) as u32).min(size.height - 1),
+        z: 0,
+    }
+}
+
+fn copy_texel(
+    encoder: &mut wgpu::CommandEncoder,
+    ids: &texture::Texture,
+    ndc: Vec2,
+    buffer: &wgpu::Buffer,
+) {
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture: &ids.texture,
+            mip_level: 0,
+            origin: texel(ids, ndc),
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: None,
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+/// Object index of an id, `None` for the background.
+fn object_index(id: u32) -> Option<usize> {
+    id.checked_sub(1).map(|index| index as usize)
+}
+
+/// Object index under `ndc` in the last frame drawn into `ids`. Blocks until
+/// the GPU finished that frame.
+pub fn read(
+    device: &Device,
+    queue: &Queue,
+    ids: &texture::Texture,
+    ndc: Vec2,
+) -> anyhow::Result<Option<usize>> {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Object Id Pick Buffer"),
+        size: TEXEL_SIZE,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Object Id Pick"),
+    });
+    copy_texel(&mut encoder, ids, ndc, &buffer);
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv()??;
+    let id = *bytemuck::from_bytes::<u32>(&slice.get_mapped_range());
+    buffer.unmap();
+    Ok(object_index(id))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Readback {
+    Idle,
+    /// The texel was copied in the frame being recorded
+    Copied,
+    Mapping,
+}
+
+/// Reads the id under the cursor back without stalling, frames are skipped
+/// while a readback is in flight.
+pub struct HoverReadback {
+    readback_buffer: wgpu::Buffer,
+    readback: Cell<Readback>,
+    mapped: Arc<AtomicBool>,
+}
+
+impl HoverReadback {
+    pub fn new(device: &Device) -> Self {
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Object Id Hover Buffer"),
+            size: TEXEL_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            readback_buffer,
+            readback: Cell::new(Readback::Idle),
+            mapped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Copy the id under `ndc` once the opaque pass wrote `ids`, unless the
+    /// previous one is still being read back.
+    pub fn record(&self, encoder: &mut wgpu::CommandEncoder, ids: &texture::Texture, ndc: Vec2) {
+        if self.readback.get() != Readback::Idle {
+            return;
+        }
+        copy_texel(encoder, ids, ndc, &self.readback_buffer);
+        self.readback.set(Readback::Copied);
+    }
+
+    /// Start mapping the id, call once the recorded frame was submitted.
+    pub fn submitted(&self) {
+        if self.readback.get() != Readback::Copied {
+            return;
+        }
+        self.readback.set(Readback::Mapping);
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(err) => tracing::error!(
+                    target: logging::RENDER,
+                    "failed to map the hovered object id: {}",
+                    err
+                ),
+            });
+    }
+
+    /// Object index hovered in an earlier frame once its readback finished,
+    /// `Some(None)` over the background. The device has to be polled for this
+    /// to make progress.
+    pub fn read(&self) -> Option<Option<usize>> {
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        let id = *bytemuck::from_bytes::<u32>(&self.readback_buffer.slice(..).get_mapped_range());
+        self.readback_buffer.unmap();
+        self.readback.set(Readback::Idle);
+        Some(object_index(id))
+    }
+}
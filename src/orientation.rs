@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+/// Share of the triangles whose normals have to point away from their front face
+/// before the normals are considered flipped.
+const FLIPPED_SHARE: f32 = 0.5;
+
+/// Flip that makes the front faces and normals of a mesh point outwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrientationFix {
+    Winding,
+    Normals,
+    Both,
+}
+
+impl OrientationFix {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Winding => "flip the winding",
+            Self::Normals => "flip the normals",
+            Self::Both => "flip the winding and normals",
+        }
+    }
+
+    pub fn flips_winding(self) -> bool {
+        self != Self::Normals
+    }
+
+    pub fn flips_normals(self) -> bool {
+        self != Self::Winding
+    }
+}
+
+/// How the faces of a mesh are oriented, judged when it is loaded. The front
+/// face follows the uploaded winding, see `tangents::vertex_normals`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Orientation {
+    pub triangles: usize,
+    /// Triangles whose vertex normals point away from their front face
+    pub normals_against_winding: usize,
+    /// Edges two triangles run along in the same direction, the mesh mixes
+    /// windings and no flip of the whole mesh fixes it
+    pub inconsistent_edges: usize,
+    /// Enclosed volume, only known for closed meshes. Negative when the front
+    /// faces point inwards.
+    pub volume: Option<f32>,
+}
+
+impl Orientation {
+    pub fn analyze(positions: &[Vec3], normals: &[Vec3], indices: &[u32]) -> Self {
+        // Meshes are split where UVs or normals differ, edges are matched by position
+        let mut welded = HashMap::new();
+        let groups = positions
+            .iter()
+            .map(|p| {
+                let next = welded.len() as u32;
+                *welded.entry(p.to_array().map(f32::to_bits)).or_insert(next)
+            })
+            .collect::<Vec<_>>();
+        let mut edges = HashMap::<(u32, u32), (u32, u32)>::new();
+        let mut orientation = Self::default();
+        let mut volume = 0.0;
+        for c in indices.chunks_exact(3) {
+            let [p0, p1, p2] = [c[0], c[1], c[2]].map(|i| positions[i as usize]);
+            let face = (p2 - p0).cross(p1 - p0);
+            if face == Vec3::ZERO {
+                continue;
+            }
+            orientation.triangles += 1;
+            let normal = c.iter().map(|&i| normals[i as usize]).sum::<Vec3>();
+            if face.dot(normal) < 0.0 {
+                orientation.normals_against_winding += 1;
+            }
+            volume += p0.dot(face) / 6.0;
+            for (a, b) in [(c[0], c[1]), (c[1], c[2]), (c[2], c[0])] {
+                let (a, b) = (groups[a as usize], groups[b as usize]);
+                let (forward, backward) = edges.entry((a.min(b), a.max(b))).or_default();
+                if a < b {
+                    *forward += 1;
+                } else {
+                    *backward += 1;
+                }
+            }
+        }
+        orientation.inconsistent_edges = edges
+            .values()
+            .filter(|&&(forward, backward)| forward > 1 || backward > 1)
+            .count();
+        // Every edge of a closed mesh is shared by two triangles
+        let closed = edges
+            .values()
+            .all(|&(forward, backward)| forward + backward == 2);
+        orientation.volume = (closed && orientation.triangles > 0).then_some(volume);
+        orientation
+    }
+
+    /// Flip that would make the mesh face outwards, none when it already does or
+    /// nothing can be told.
+    pub fn suggested_fix(&self) -> Option<OrientationFix> {
+        let normals_flipped =
+            self.normals_against_winding as f32 > self.triangles as f32 * FLIPPED_SHARE;
+        match (self.volume.map(|volume| volume < 0.0), normals_flipped) {
+            (Some(true), false) => Some(OrientationFix::Both),
+            (Some(true), true) => Some(OrientationFix::Winding),
+            (Some(false), true) => Some(OrientationFix::Normals),
+            // Open meshes have no inside, the normals are more likely authored
+            // with care than the winding of the exporter
+            (None, true) => Some(OrientationFix::Winding),
+            (_, false) => None,
+        }
+    }
+
+    /// What looks wrong about the mesh, empty when nothing does.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.volume.is_some_and(|volume| volume < 0.0) {
+            problems.push("front faces point inwards".to_owned());
+        }
+        if self.normals_against_winding > 0 {
+            problems.push(format!(
+                "normals point away from the front face of {} of {} triangles",
+                self.normals_against_winding, self.triangles
+            ));
+        }
+        if self.inconsistent_edges > 0 {
+            problems.push(format!(
+                "winding is mixed along {} edges",
+                self.inconsistent_edges
+            ));
+        }
+        problems
+    }
+}
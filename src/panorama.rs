@@ -0,0 +1,357 @@
+//! Equirectangular panoramas of the scene around the camera.
+//!
+//! The six faces of a cube are rendered from the camera position with the
+//! reflection probe pipelines, read back and resampled to an image twice as
+//! wide as high. Its center looks along the heading of the camera with the
+//! horizon level, longitude grows to the right. PNG stores the colors as the
+//! window shows them, clamped and sRGB encoded. EXR keeps the linear radiance,
+//! so the panorama can light other scenes as an environment map.
+
+use std::{
+    f32::consts::{PI, TAU},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::mpsc,
+};
+
+use glam::Vec3;
+use image::{Rgb, RgbImage};
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use crate::{camera::UniformCamera, probe_storage, texture};
+
+// Each face camera lives in its own slice of one buffer
+const FACE_UNIFORM_STRIDE: u64 = 256;
+
+/// Panorama options edited in the UI.
+#[derive(Debug, Clone)]
+pub struct PanoramaSettings {
+    /// Side of each cube face, the panorama is four faces wide and two high
+    pub face_resolution: u32,
+}
+
+impl PanoramaSettings {
+    pub const RESOLUTIONS: [u32; 4] = [256, 512, 1024, 2048];
+}
+
+impl Default for PanoramaSettings {
+    fn default() -> Self {
+        Self {
+            face_resolution: 1024,
+        }
+    }
+}
+
+/// Render target of the six faces, created for a single capture.
+pub struct CubeCapture {
+    face_texture: wgpu::Texture,
+    face_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    camera_bind_groups: Vec<wgpu::BindGroup>,
+    resolution: u32,
+}
+
+impl CubeCapture {
+    /// `cameras` are the face cameras, see `probe::cube_face_cameras`.
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        resolution: u32,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        cameras: &[UniformCamera; 6],
+    ) -> Self {
+        let face_size = wgpu::Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: 1,
+        };
+        let face_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Panorama Face"),
+            size: face_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let face_view = face_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Panorama Depth"),
+            size: face_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: texture::Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut contents = vec![0; cameras.len() * FACE_UNIFORM_STRIDE as usize];
+        for (chunk, camera) in contents
+            .chunks_exact_mut(FACE_UNIFORM_STRIDE as usize)
+            .zip(cameras)
+        {
+            chunk[..std::mem::size_of::<UniformCamera>()]
+                .copy_from_slice(bytemuck::bytes_of(camera));
+        }
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Panorama Camera Buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera_bind_groups = (0..cameras.len() as u64)
+            .map(|i| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: camera_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &camera_buffer,
+                            offset: i * FACE_UNIFORM_STRIDE,
+                            size: wgpu::BufferSize::new(
+                                std::mem::size_of::<UniformCamera>() as u64
+                            ),
+                        }),
+                    }],
+                    label: Some("Panorama Camera Bind Group"),
+                })
+            })
+            .collect();
+        Self {
+            face_texture,
+            face_view,
+            depth_view,
+            camera_bind_groups,
+            resolution,
+        }
+    }
+
+    /// Render the faces, `draw` renders the scene with the given camera, and
+    /// read them back as linear RGB, face after face. Blocks until the GPU is done.
+    pub fn render<F>(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        draw: F,
+    ) -> anyhow::Result<Vec<[f32; 3]>>
+    where
+        F: Fn(&mut wgpu::RenderPass, &wgpu::BindGroup),
+    {
+        let format = self.face_texture.format();
+        let bytes_per_row = self.resolution * format.block_copy_size(None).unwrap_or(4);
+        // Rows of a buffer copy have to be aligned
+        let padded_row = bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let face_bytes = (padded_row * self.resolution) as u64;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Panorama Readback Buffer"),
+            size: face_bytes * self.camera_bind_groups.len() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Panorama Encoder"),
+        });
+        for (face, camera_bind_group) in self.camera_bind_groups.iter().enumerate() {
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Render Pass: panorama face"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &self.face_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &self.depth_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                draw(&mut render_pass, camera_bind_group);
+            }
+            encoder.copy_texture_to_buffer(
+                self.face_texture.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &readback,
+                    layout: wgpu::ImageDataLayout {
+                        offset: face as u64 * face_bytes,
+                        bytes_per_row: Some(padded_row),
+                        rows_per_image: Some(self.resolution),
+                    },
+                },
+                self.face_texture.size(),
+            );
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+        let pixels = slice
+            .get_mapped_range()
+            .chunks(padded_row as usize)
+            .flat_map(|row| &row[..bytes_per_row as usize])
+            .copied()
+            .collect::<Vec<_>>();
+        readback.unmap();
+        probe_storage::decode(format, &pixels)
+    }
+}
+
+/// Resample the six `faces` of side `resolution` to a panorama centered on
+/// `yaw`, returns its width, height and pixels row by row.
+pub fn equirectangular(faces: &[[f32; 3]], resolution: u32, yaw: f32) -> (u32, u32, Vec<[f32; 3]>) {
+    let (width, height) = (resolution * 4, resolution * 2);
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let longitude = yaw + ((x as f32 + 0.5) / width as f32 - 0.5) * TAU;
+            let latitude = (0.5 - (y as f32 + 0.5) / height as f32) * PI;
+            let direction = Vec3::new(
+                latitude.cos() * longitude.cos(),
+                latitude.sin(),
+                latitude.cos() * longitude.sin(),
+            );
+            sample_cube(faces, resolution, direction)
+        })
+        .collect();
+    (width, height, pixels)
+}
+
+/// Bilinear lookup of `direction` in the faces, which are not blended across
+/// their edges. Follows the cubemap sampling convention like `probe_prefilter.wgsl`.
+fn sample_cube(faces: &[[f32; 3]], resolution: u32, direction: Vec3) -> [f32; 3] {
+    let Vec3 { x, y, z } = direction;
+    let (face, s, t) = if x.abs() >= y.abs() && x.abs() >= z.abs() {
+        if x > 0.0 {
+            (0, -z / x, -y / x)
+        } else {
+            (1, -z / x, y / x)
+        }
+    } else if y.abs() >= z.abs() {
+        if y > 0.0 {
+            (2, x / y, z / y)
+        } else {
+            (3, -x / y, z / y)
+        }
+    } else if z > 0.0 {
+        (4, x / z, -y / z)
+    } else {
+        (5, x / z, y / z)
+    };
+    let size = resolution as usize;
+    let face = &faces[face * size * size..(face + 1) * size * size];
+    let max = (resolution - 1) as f32;
+    let u = ((s + 1.0) * 0.5 * resolution as f32 - 0.5).clamp(0.0, max);
+    let v = ((t + 1.0) * 0.5 * resolution as f32 - 0.5).clamp(0.0, max);
+    let (x0, y0) = (u as usize, v as usize);
+    let (x1, y1) = ((x0 + 1).min(size - 1), (y0 + 1).min(size - 1));
+    let (fx, fy) = (u.fract(), v.fract());
+    let texel = |x: usize, y: usize| Vec3::from(face[y * size + x]);
+    let top = texel(x0, y0).lerp(texel(x1, y0), fx);
+    let bottom = texel(x0, y1).lerp(texel(x1, y1), fx);
+    top.lerp(bottom, fy).to_array()
+}
+
+/// Write the panorama as EXR when `path` ends in `.exr`, as PNG otherwise.
+pub fn save(path: &Path, width: u32, height: u32, pixels: &[[f32; 3]]) -> anyhow::Result<()> {
+    let is_exr = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"));
+    if is_exr {
+        return write_exr(path, width, height, pixels);
+    }
+    let mut image = RgbImage::new(width, height);
+    for (pixel, color) in image.pixels_mut().zip(pixels) {
+        *pixel = Rgb(color.map(|c| (linear_to_srgb(c) * 255.0).round() as u8));
+    }
+    image.save(path)?;
+    Ok(())
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    // NaN turns black instead of poisoning the conversion
+    let value = if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(0.0, 1.0)
+    };
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Single part scanline OpenEXR with uncompressed 32 bit float R, G and B.
+fn write_exr(path: &Path, width: u32, height: u32, pixels: &[[f32; 3]]) -> anyhow::Result<()> {
+    // Magic number, then version 2 without any flags
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        for text in [name, kind] {
+            header.extend_from_slice(text.as_bytes());
+            header.push(0);
+        }
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+    // Channels are listed and stored in alphabetical order
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        // FLOAT pixels, not perceptually linear, then the x and y sampling
+        for value in [2, 0, 1, 1] {
+            channels.extend_from_slice(&i32::to_le_bytes(value));
+        }
+    }
+    channels.push(0);
+    let window = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect::<Vec<_>>();
+    attribute("channels", "chlist", &channels);
+    attribute("compression", "compression", &[0]);
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    let row_bytes = width as u64 * 3 * 4;
+    // Each scanline chunk starts with its y and its size
+    let chunk_bytes = 8 + row_bytes;
+    let first_chunk = header.len() as u64 + height as u64 * 8;
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&header)?;
+    for y in 0..height as u64 {
+        file.write_all(&(first_chunk + y * chunk_bytes).to_le_bytes())?;
+    }
+    for (y, row) in pixels.chunks_exact(width as usize).enumerate() {
+        file.write_all(&(y as i32).to_le_bytes())?;
+        file.write_all(&(row_bytes as i32).to_le_bytes())?;
+        for channel in [2, 1, 0] {
+            for color in row {
+                file.write_all(&color[channel].to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()?;
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::primitives::Vertex;
+
+/// Ray through a point of the window, `ndc` in normalized device coordinates,
+/// as origin and direction in world space.
+pub fn ray_from_ndc(view_projection: Mat4, ndc: Vec2) -> (Vec3, Vec3) {
+    let inverse = view_projection.inverse();
+    let near = inverse.project_point3(ndc.extend(0.0));
+    let far = inverse.project_point3(ndc.extend(1.0));
+    (near, (far - near).normalize())
+}
+
+/// Distance along the ray to where it hits the triangle, from either side.
+pub fn intersect_triangle(origin: Vec3, direction: Vec3, [a, b, c]: [Vec3; 3]) -> Option<f32> {
+    // Möller–Trumbore
+    let (ab, ac) = (b - a, c - a);
+    let p = direction.cross(ac);
+    let det = ab.dot(p);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) / det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(ab);
+    let v = direction.dot(q) / det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(q) / det;
+    (t > 0.0).then_some(t)
+}
+
+/// Whether the ray passes through the box given by its `center` and half `extent`.
+pub fn intersects_box(origin: Vec3, direction: Vec3, center: Vec3, extent: Vec3) -> bool {
+    let inverse = direction.recip();
+    let first = (center - extent - origin) * inverse;
+    let second = (center + extent - origin) * inverse;
+    let near = first.min(second).max_element();
+    let far = first.max(second).min_element();
+    near <= far && far >= 0.0
+}
+
+/// Triangle picked in the viewport with the attributes of its vertices as uploaded.
+#[derive(Debug, Clone)]
+pub struct PickedTriangle {
+    pub object: String,
+    /// Object index of the geom, see `SceneWorld::geom`
+    pub geom: usize,
+    /// Index of the triangle within its mesh
+    pub triangle: usize,
+    /// Vertex indices within the mesh
+    pub indices: [u32; 3],
+    pub vertices: [Vertex; 3],
+    /// The object transform flips the handedness of its tangent frames
+    pub mirrored_transform: bool,
+}
+
+impl PickedTriangle {
+    /// Normal of the triangle plane in object space, following the winding.
+    pub fn face_normal(&self) -> Vec3 {
+        let [a, b, c] = self.vertices.map(|v| v.position);
+        (b - a).cross(c - a).normalize_or_zero()
+    }
+
+    /// Signed area of the triangle in texture space, negative where the UVs are
+    /// mirrored against the winding.
+    pub fn uv_area(&self) -> f32 {
+        let [a, b, c] = self.vertices.map(|v| v.texcoord);
+        (b - a).perp_dot(c - a) * 0.5
+    }
+}
+
+/// Determinant of the tangent frame the shader builds for `vertex`, 1 for a
+/// right handed orthonormal frame and -1 for a mirrored one. Anything else means
+/// the normal and tangent are not unit length or not perpendicular.
+pub fn tbn_determinant(vertex: &Vertex) -> f32 {
+    let bitangent = vertex.tangent_sign * vertex.normal.cross(vertex.tangent);
+    vertex.tangent.dot(bitangent.cross(vertex.normal))
+}
@@ -0,0 +1,131 @@
+//! Planar reflections for flat mirror geoms.
+//!
+//! Meshes listed in `SceneFile::mirrors` are tagged `Mirror`, and the first
+//! visible one reflects. Each frame the opaque scene is rendered once more with
+//! the camera mirrored about its plane, into a texture the size of the scene
+//! color. An oblique near plane on the mirror clips away everything behind it.
+//! The mirror samples the texture where the reflection probes would be sampled,
+//! so its specular color sets how strongly it reflects. Other mirrors keep the
+//! probe reflections.
+
+use glam::{Mat3, Mat4, Vec3, Vec4};
+use wgpu::{util::DeviceExt, Device};
+
+use crate::{camera::UniformCamera, scene_world::Geom, texture};
+
+/// Enable bit of the `UniformObject` of the reflecting mirror, after the
+/// material maps in `shader.wgsl`
+pub const MIRROR_BIT: u32 = 1 << 6;
+
+/// World space plane of the mirror as `dot(xyz, p) + w = 0`, facing `eye`.
+/// The normal of the first vertex stands for the whole mesh, which is assumed
+/// to be flat.
+pub fn mirror_plane(geom: &Geom, eye: Vec3) -> Option<Vec4> {
+    let vertex = geom.mesh.vertices.first()?;
+    let model = geom.transform.matrix;
+    let normal = (Mat3::from_mat4(model).inverse().transpose() * vertex.normal).try_normalize()?;
+    let point = model.transform_point3(vertex.position);
+    let normal = if normal.dot(eye - point) < 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    Some(normal.extend(-normal.dot(point)))
+}
+
+/// Reflection about `plane`, see `mirror_plane`.
+fn reflection_matrix(plane: Vec4) -> Mat4 {
+    let normal = plane.truncate();
+    let outer = Mat3::from_cols(normal * normal.x, normal * normal.y, normal * normal.z);
+    let linear = Mat3::IDENTITY - 2.0 * outer;
+    Mat4::from_cols(
+        linear.x_axis.extend(0.0),
+        linear.y_axis.extend(0.0),
+        linear.z_axis.extend(0.0),
+        (-2.0 * plane.w * normal).extend(1.0),
+    )
+}
+
+/// Replace the near plane of `projection` with `clip_plane`, given in view space
+/// with the kept side positive, after Lengyel's oblique view frustum. The far
+/// plane is tilted to still enclose the view frustum.
+fn oblique_projection(projection: Mat4, clip_plane: Vec4) -> Mat4 {
+    // Corner of the view frustum opposite the clip plane, clip space depth
+    // runs from zero to one
+    let corner =
+        projection.inverse() * Vec4::new(clip_plane.x.signum(), clip_plane.y.signum(), 1.0, 1.0);
+    let row = clip_plane / clip_plane.dot(corner);
+    let mut projection = projection;
+    projection.x_axis.z = row.x;
+    projection.y_axis.z = row.y;
+    projection.z_axis.z = row.z;
+    projection.w_axis.z = row.w;
+    projection
+}
+
+/// Camera and fallback texture of the mirrored pass, the texture it renders
+/// into is allocated by the frame graph.
+pub struct PlanarReflection {
+    /// `UniformCamera` of the mirrored view, also read by the mirror to find the
+    /// texel reflected at a point
+    pub camera_buffer: wgpu::Buffer,
+    pub camera_bind_group: wgpu::BindGroup,
+    /// Bound in place of the reflection while it is rendered
+    pub placeholder: texture::Texture,
+}
+
+impl PlanarReflection {
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Planar Reflection Camera Buffer"),
+            contents: bytemuck::cast_slice(&[UniformCamera::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("Planar Reflection Camera Bind Group"),
+        });
+        let placeholder = texture::Texture::create_color_target(
+            device,
+            (1, 1),
+            format,
+            "Planar Reflection Placeholder",
+        );
+        Self {
+            camera_buffer,
+            camera_bind_group,
+            placeholder,
+        }
+    }
+
+    /// Mirror the camera `view` and `projection` about `plane`, see `mirror_plane`.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        view: Mat4,
+        projection: Mat4,
+        eye: Vec3,
+        plane: Vec4,
+    ) {
+        let reflection = reflection_matrix(plane);
+        let mirrored_view = view * reflection;
+        // Planes transform with the inverse transpose
+        let clip_plane = mirrored_view.inverse().transpose() * plane;
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[UniformCamera::from_matrix(
+                oblique_projection(projection, clip_plane) * mirrored_view,
+                reflection.transform_point3(eye),
+            )]),
+        );
+    }
+}
@@ -0,0 +1,74 @@
+
+// Built-in post-processing effects, appended to `post_process.wgsl`
+
+// params.x: exposure. Fitted ACES curve by Krzysztof Narkowicz
+@fragment
+fn fs_tonemap(in: VertexOutput) -> @location(0) vec4<f32> {
+    let x = input_color(in.clip_position).rgb * post.params.x;
+    let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    return vec4<f32>(saturate(mapped), 1.0);
+}
+
+// params.x: darkening at the corners, params.y: distance from the center where
+// the darkening starts, relative to the corners
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = input_color(in.clip_position);
+    let radius = length(target_uv(in.clip_position) * 2.0 - 1.0) / sqrt(2.0);
+    let falloff = smoothstep(post.params.y, 1.0, radius);
+    return vec4<f32>(color.rgb * (1.0 - post.params.x * falloff), 1.0);
+}
+
+const MOTION_BLUR_SAMPLES: u32 = 16u;
+// Longest smear relative to the target width, fast turns stay readable
+const MOTION_BLUR_MAX_LENGTH: f32 = 0.05;
+
+// params.x: fraction of the frame the shutter is open. The screen motion is
+// rebuilt from the depth and both cameras, so only the camera motion blurs
+@fragment
+fn fs_motion_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(source));
+    let uv = target_uv(in.clip_position);
+    let depth = textureLoad(scene_depth, vec2<u32>(in.clip_position.xy), 0);
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let world = post_view.inverse_view_projection * vec4<f32>(ndc, depth, 1.0);
+    let previous = post_view.previous_view_projection * vec4<f32>(world.xyz / world.w, 1.0);
+    // Behind the last camera there is no motion to recover
+    if previous.w <= 0.0 {
+        return vec4<f32>(input_color(in.clip_position).rgb, 1.0);
+    }
+    let previous_ndc = previous.xy / previous.w;
+    let previous_uv = vec2<f32>(previous_ndc.x * 0.5 + 0.5, 0.5 - previous_ndc.y * 0.5);
+    // Pixels covered while the shutter is open, centered on this frame
+    var velocity = (uv - previous_uv) * size * post.params.x;
+    let max_length = MOTION_BLUR_MAX_LENGTH * size.x;
+    let velocity_length = length(velocity);
+    if velocity_length > max_length {
+        velocity *= max_length / velocity_length;
+    }
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < MOTION_BLUR_SAMPLES; i++) {
+        let t = (f32(i) + 0.5) / f32(MOTION_BLUR_SAMPLES) - 0.5;
+        let position = clamp(in.clip_position.xy + velocity * t, vec2<f32>(0.0), size - 1.0);
+        color += textureLoad(source, vec2<u32>(position), 0).rgb;
+    }
+    return vec4<f32>(color / f32(MOTION_BLUR_SAMPLES), 1.0);
+}
+
+// params.x: lift, params.y: gamma, params.z: gain, params.w: saturation. Expects
+// the displayable range the tonemap leaves, as color tables do
+@fragment
+fn fs_color_grade(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = saturate(input_color(in.clip_position).rgb);
+    // Lift raises the blacks while keeping white, gain scales towards white
+    let lifted = saturate((color * (1.0 - post.params.x) + post.params.x) * post.params.z);
+    let graded = pow(lifted, vec3<f32>(1.0 / max(post.params.y, 1e-3)));
+    let luma = dot(graded, vec3<f32>(0.2126, 0.7152, 0.0722));
+    let saturated = saturate(mix(vec3<f32>(luma), graded, post.params.w));
+    // Texel centers hold the table entries
+    let domain = (saturated - color_lut_domain.domain_min.xyz)
+        / (color_lut_domain.domain_max.xyz - color_lut_domain.domain_min.xyz);
+    let size = vec3<f32>(textureDimensions(color_lut));
+    let uvw = (saturate(domain) * (size - 1.0) + 0.5) / size;
+    return vec4<f32>(textureSampleLevel(color_lut, color_lut_sampler, uvw, 0.0).rgb, 1.0);
+}
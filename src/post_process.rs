@@ -0,0 +1,567 @@
+//! Full screen effects applied to the scene color before it is presented, in
+//! the order of `AppState::post_effects`.
+//!
+//! An effect implements `PostProcess` and is described by a `PostEffectKind`,
+//! which carries its parameters and creates it. `PostStack` owns two targets
+//! in the scene color format and runs the enabled effects between them,
+//! reading the scene color first. Effect shaders are appended to
+//! `post_process.wgsl`, which provides the full screen triangle, the input,
+//! the scene depth, the camera of this and the last frame, the parameters and
+//! the color table of the grading.
+
+use glam::Mat4;
+use wgpu::{util::DeviceExt, Device};
+
+use crate::{color_lut::CubeLut, texture};
+
+/// A full screen pass of the post-processing stack.
+pub trait PostProcess {
+    /// Set the pipeline and any bind groups past the three the stack binds, the
+    /// input at group 0, the parameters at group 1 and the color table at group
+    /// 2. The stack draws the full screen triangle afterwards.
+    fn bind(&self, render_pass: &mut wgpu::RenderPass);
+
+    /// Called after the stack reallocated its targets at `extent`.
+    fn resize(&mut self, _device: &Device, _extent: (u32, u32)) {}
+}
+
+/// An effect drawn by a single fragment shader entry point.
+struct FullscreenEffect {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl PostProcess for FullscreenEffect {
+    fn bind(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_pipeline(&self.pipeline);
+    }
+}
+
+/// Effect of one slot of `AppState::post_effects` and its parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostEffectKind {
+    /// Fitted ACES curve, maps the HDR scene color into the displayable range
+    Tonemap { exposure: f32 },
+    /// Darken towards the corners, starting at `start` relative to the corner
+    /// distance
+    Vignette { strength: f32, start: f32 },
+    /// Smear along the screen motion of the scene caused by the camera moving
+    /// since the last frame. The shutter stays open for `shutter_angle` degrees
+    /// of the 360 a frame lasts.
+    MotionBlur { shutter_angle: f32 },
+    /// Lift the blacks, bend the mid tones and scale the whites, then change the
+    /// saturation and look the result up in the color table, see
+    /// `PostStack::set_color_lut`. Meant to follow the tonemap.
+    ColorGrade {
+        lift: f32,
+        gamma: f32,
+        gain: f32,
+        saturation: f32,
+    },
+}
+
+impl PostEffectKind {
+    /// Kinds the Post Processing window can add.
+    pub const ALL: [Self; 4] = [
+        Self::Tonemap { exposure: 1.0 },
+        Self::Vignette {
+            strength: 0.5,
+            start: 0.4,
+        },
+        Self::MotionBlur {
+            shutter_angle: 180.0,
+        },
+        Self::ColorGrade {
+            lift: 0.0,
+            gamma: 1.0,
+            gain: 1.0,
+            saturation: 1.0,
+        },
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tonemap { .. } => "Tonemap",
+            Self::Vignette { .. } => "Vignette",
+            Self::MotionBlur { .. } => "Motion blur",
+            Self::ColorGrade { .. } => "Color grade",
+        }
+    }
+
+    /// Values the shader reads as `post.params`.
+    fn parameters(self) -> [f32; 4] {
+        match self {
+            Self::Tonemap { exposure } => [exposure, 0.0, 0.0, 0.0],
+            Self::Vignette { strength, start } => [strength, start, 0.0, 0.0],
+            Self::MotionBlur { shutter_angle } => [shutter_angle / 360.0, 0.0, 0.0, 0.0],
+            Self::ColorGrade {
+                lift,
+                gamma,
+                gain,
+                saturation,
+            } => [lift, gamma, gain, saturation],
+        }
+    }
+
+    fn create(self, device: &Device, stack: &PostStack) -> Box<dyn PostProcess> {
+        let source = include_str!("post_effects.wgsl");
+        let pipeline = match self {
+            Self::Tonemap { .. } => stack.create_pipeline(device, "Tonemap", source, "fs_tonemap"),
+            Self::Vignette { .. } => {
+                stack.create_pipeline(device, "Vignette", source, "fs_vignette")
+            }
+            Self::MotionBlur { .. } => {
+                stack.create_pipeline(device, "Motion Blur", source, "fs_motion_blur")
+            }
+            Self::ColorGrade { .. } => {
+                stack.create_pipeline(device, "Color Grade", source, "fs_color_grade")
+            }
+        };
+        Box::new(FullscreenEffect { pipeline })
+    }
+}
+
+/// One entry of the post-processing stack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostEffectSlot {
+    pub kind: PostEffectKind,
+    pub enabled: bool,
+}
+
+impl PostEffectSlot {
+    pub fn new(kind: PostEffectKind) -> Self {
+        Self {
+            kind,
+            enabled: true,
+        }
+    }
+}
+
+/// Texture the stack leaves its result in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostOutput {
+    /// No effect is enabled
+    SceneColor,
+    /// Index into `PostStack::targets`
+    Target(usize),
+}
+
+/// Matches `PostView` in `post_process.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct UniformPostView {
+    inverse_view_projection: Mat4,
+    previous_view_projection: Mat4,
+}
+
+/// Matches `ColorLut` in `post_process.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct UniformColorLut {
+    domain_min: [f32; 4],
+    domain_max: [f32; 4],
+}
+
+struct PostEffect {
+    name: &'static str,
+    effect: Box<dyn PostProcess>,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Runs the effects of `AppState::post_effects`, see the module docs.
+pub struct PostStack {
+    input_layout: wgpu::BindGroupLayout,
+    params_layout: wgpu::BindGroupLayout,
+    lut_layout: wgpu::BindGroupLayout,
+    /// Color table of the grading, see `set_color_lut`
+    lut_bind_group: wgpu::BindGroup,
+    format: wgpu::TextureFormat,
+    /// In the order of the slots
+    effects: Vec<PostEffect>,
+    /// Ping-pong targets, the size of the scene color
+    targets: Vec<texture::Texture>,
+    /// Reading the scene color and each target
+    input_bind_groups: Vec<wgpu::BindGroup>,
+    /// `UniformPostView` of the frame, bound with every input
+    view_buffer: wgpu::Buffer,
+    /// Camera of the last frame, none until one was rendered
+    previous_view_projection: Option<Mat4>,
+    extent: (u32, u32),
+}
+
+impl PostStack {
+    pub fn new(device: &Device, queue: &wgpu::Queue, format: wgpu::TextureFormat) -> Self {
+        let input_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                // scene depth
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Post Input Bind Group Layout"),
+        });
+        let view_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post View Buffer"),
+            contents: bytemuck::cast_slice(&[UniformPostView {
+                inverse_view_projection: Mat4::IDENTITY,
+                previous_view_projection: Mat4::IDENTITY,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let params_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Post Parameters Bind Group Layout"),
+        });
+        let lut_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Post Color Table Bind Group Layout"),
+        });
+        let lut_bind_group = create_color_lut(device, queue, &lut_layout, &CubeLut::default());
+        Self {
+            input_layout,
+            params_layout,
+            lut_layout,
+            lut_bind_group,
+            format,
+            effects: Vec::new(),
+            targets: Vec::new(),
+            input_bind_groups: Vec::new(),
+            view_buffer,
+            previous_view_projection: None,
+            extent: (0, 0),
+        }
+    }
+
+    /// Pipeline drawing the `entry_point` of `source`, a fragment shader
+    /// appended to `post_process.wgsl`, into the targets of the stack.
+    pub fn create_pipeline(
+        &self,
+        device: &Device,
+        label: &str,
+        source: &str,
+        entry_point: &str,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(
+                [include_str!("post_process.wgsl"), source].concat().into(),
+            ),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[&self.input_layout, &self.params_layout, &self.lut_layout],
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(entry_point),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// Create the effects again when effects were added, removed or moved.
+    /// Parameters and toggles are picked up every frame without this.
+    pub fn prepare(&mut self, device: &Device, slots: &[PostEffectSlot]) {
+        if self
+            .effects
+            .iter()
+            .map(|effect| effect.name)
+            .eq(slots.iter().map(|slot| slot.kind.name()))
+        {
+            return;
+        }
+        self.effects = slots
+            .iter()
+            .map(|slot| {
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Post Parameters Buffer"),
+                    size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &self.params_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                    label: Some("Post Parameters Bind Group"),
+                });
+                let mut effect = slot.kind.create(device, self);
+                effect.resize(device, self.extent);
+                PostEffect {
+                    name: slot.kind.name(),
+                    effect,
+                    buffer,
+                    bind_group,
+                }
+            })
+            .collect();
+    }
+
+    /// Allocate the targets at the size of `scene_color` and read it first,
+    /// `scene_depth` is read by every effect.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        scene_color: &texture::Texture,
+        scene_depth: &texture::Texture,
+    ) {
+        let size = scene_color.texture.size();
+        self.extent = (size.width, size.height);
+        self.targets = (0..2)
+            .map(|_| {
+                texture::Texture::create_color_target(device, self.extent, self.format, "post")
+            })
+            .collect();
+        self.input_bind_groups = std::iter::once(scene_color)
+            .chain(&self.targets)
+            .map(|input| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &self.input_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&input.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&scene_depth.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: self.view_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: Some("Post Input Bind Group"),
+                })
+            })
+            .collect();
+        for effect in &mut self.effects {
+            effect.effect.resize(device, self.extent);
+        }
+    }
+
+    /// Replace the color table the grading looks colors up in.
+    pub fn set_color_lut(&mut self, device: &Device, queue: &wgpu::Queue, lut: &CubeLut) {
+        self.lut_bind_group = create_color_lut(device, queue, &self.lut_layout, lut);
+    }
+
+    pub fn targets(&self) -> &[texture::Texture] {
+        &self.targets
+    }
+
+    /// Write the parameters of every slot and the camera the scene was rendered
+    /// with this frame, which becomes the previous one of the next frame.
+    pub fn update(&mut self, queue: &wgpu::Queue, slots: &[PostEffectSlot], view_projection: Mat4) {
+        let previous_view_projection = self
+            .previous_view_projection
+            .replace(view_projection)
+            .unwrap_or(view_projection);
+        queue.write_buffer(
+            &self.view_buffer,
+            0,
+            bytemuck::cast_slice(&[UniformPostView {
+                inverse_view_projection: view_projection.inverse(),
+                previous_view_projection,
+            }]),
+        );
+        for (effect, slot) in self.effects.iter().zip(slots) {
+            queue.write_buffer(
+                &effect.buffer,
+                0,
+                bytemuck::cast_slice(&slot.kind.parameters()),
+            );
+        }
+    }
+
+    /// Where `run` leaves the result for `slots`.
+    pub fn output(&self, slots: &[PostEffectSlot]) -> PostOutput {
+        let enabled = slots.iter().filter(|slot| slot.enabled).count();
+        if enabled == 0 || self.effects.len() != slots.len() {
+            PostOutput::SceneColor
+        } else {
+            PostOutput::Target((enabled - 1) % 2)
+        }
+    }
+
+    /// Run the enabled effects one after another, see `output` for the result.
+    pub fn run(&self, encoder: &mut wgpu::CommandEncoder, slots: &[PostEffectSlot]) {
+        if self.effects.len() != slots.len() {
+            return;
+        }
+        let mut input = 0;
+        let enabled = self
+            .effects
+            .iter()
+            .zip(slots)
+            .filter(|(_, slot)| slot.enabled);
+        for (i, (effect, _)) in enabled.enumerate() {
+            let output = i % 2;
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(effect.name),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.targets[output].view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_bind_group(0, &self.input_bind_groups[input], &[]);
+            render_pass.set_bind_group(1, &effect.bind_group, &[]);
+            render_pass.set_bind_group(2, &self.lut_bind_group, &[]);
+            effect.effect.bind(&mut render_pass);
+            render_pass.draw(0..3, 0..1);
+            input = output + 1;
+        }
+    }
+}
+
+/// Bind group of `lut` as a 3D texture and its domain.
+fn create_color_lut(
+    device: &Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    lut: &CubeLut,
+) -> wgpu::BindGroup {
+    let texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("Post Color Table"),
+            size: wgpu::Extent3d {
+                width: lut.size,
+                height: lut.size,
+                depth_or_array_layers: lut.size,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &lut.rgba8(),
+    );
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Post Color Table Sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Post Color Table Buffer"),
+        contents: bytemuck::cast_slice(&[UniformColorLut {
+            domain_min: lut.domain_min.extend(0.0).to_array(),
+            domain_max: lut.domain_max.extend(0.0).to_array(),
+        }]),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("Post Color Table Bind Group"),
+    })
+}
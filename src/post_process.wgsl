@@ -0,0 +1,59 @@
+// Shared part of every post-processing effect, the effect's fragment shader is
+// appended to this file
+
+struct Post {
+    // Parameters of the effect, see `PostEffectKind::parameters`
+    params: vec4<f32>,
+}
+
+struct PostView {
+    inverse_view_projection: mat4x4<f32>,
+    // Camera of the last frame, the current one on the first frame
+    previous_view_projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var scene_depth: texture_depth_2d;
+@group(0) @binding(2)
+var<uniform> post_view: PostView;
+@group(1) @binding(0)
+var<uniform> post: Post;
+
+struct ColorLut {
+    // Colors mapped to the first and last entry of each channel
+    domain_min: vec4<f32>,
+    domain_max: vec4<f32>,
+}
+
+// Color table of the grading, leaves every color as it is unless one is loaded
+@group(2) @binding(0)
+var color_lut: texture_3d<f32>;
+@group(2) @binding(1)
+var color_lut_sampler: sampler;
+@group(2) @binding(2)
+var<uniform> color_lut_domain: ColorLut;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// One triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// The input under a pixel, input and target have the same size
+fn input_color(position: vec4<f32>) -> vec4<f32> {
+    return textureLoad(source, vec2<u32>(position.xy), 0);
+}
+
+// Position of a pixel from 0 to 1 across the target
+fn target_uv(position: vec4<f32>) -> vec2<f32> {
+    return position.xy / vec2<f32>(textureDimensions(source));
+}
@@ -0,0 +1,131 @@
+// Copy the HDR scene color onto the surface
+
+struct Present {
+    // Paint NaN and Inf pixels magenta
+    nan_guard: u32,
+    // Scene color resolution relative to the surface
+    scale: f32,
+    // Draw the depth contour overlay
+    contours: u32,
+    // View depth between two contour lines
+    contour_spacing: f32,
+    focus_distance: f32,
+    focus_range: f32,
+    znear: f32,
+    zfar: f32,
+    // Depth is linear in view depth
+    orthographic: u32,
+    // `Upsampling` of the scene color
+    upsampling: u32,
+    // From 0 to 1, for UPSAMPLING_SHARPEN
+    sharpness: f32,
+}
+
+const UPSAMPLING_NEAREST: u32 = 0u;
+const UPSAMPLING_SHARPEN: u32 = 2u;
+
+@group(0) @binding(0)
+var scene_color: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> present: Present;
+@group(0) @binding(2)
+var scene_depth: texture_depth_2d;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// One triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Exponent all ones, `x != x` may be folded away by the driver
+fn is_not_finite(x: f32) -> bool {
+    return (bitcast<u32>(x) & 0x7f800000u) == 0x7f800000u;
+}
+
+// View space distance of a depth buffer value of a right handed perspective or
+// orthographic projection
+fn view_depth(depth: f32) -> f32 {
+    if present.orthographic != 0u {
+        return present.znear + depth * (present.zfar - present.znear);
+    }
+    return present.znear * present.zfar / (present.zfar - depth * (present.zfar - present.znear));
+}
+
+// Iso-depth lines a pixel wide and a tint over the focus band
+fn depth_contours(color: vec3<f32>, depth: f32) -> vec3<f32> {
+    let z = view_depth(depth);
+    let steps = z / present.contour_spacing;
+    // Distance to the closest contour in pixels
+    let line = abs(fract(steps + 0.5) - 0.5) / max(fwidth(steps), 1e-6);
+    let focus = abs(z - present.focus_distance) / max(fwidth(z), 1e-6);
+    var out = color;
+    if abs(z - present.focus_distance) < present.focus_range * 0.5 {
+        out = mix(out, vec3<f32>(0.0, 1.0, 0.0), 0.25);
+    }
+    out = mix(out, vec3<f32>(1.0), 0.6 * (1.0 - saturate(line)));
+    out = mix(out, vec3<f32>(0.0, 1.0, 0.0), 1.0 - saturate(focus - 0.5));
+    // Nothing was drawn at the far plane
+    return select(out, color, depth >= 1.0);
+}
+
+// Scene color at `position` in texels, interpolated between the four closest
+fn bilinear(position: vec2<f32>) -> vec3<f32> {
+    let last = vec2<i32>(textureDimensions(scene_color)) - 1;
+    let corner = position - 0.5;
+    let base = vec2<i32>(floor(corner));
+    let t = corner - floor(corner);
+    let c00 = textureLoad(scene_color, clamp(base, vec2<i32>(0), last), 0).rgb;
+    let c10 = textureLoad(scene_color, clamp(base + vec2<i32>(1, 0), vec2<i32>(0), last), 0).rgb;
+    let c01 = textureLoad(scene_color, clamp(base + vec2<i32>(0, 1), vec2<i32>(0), last), 0).rgb;
+    let c11 = textureLoad(scene_color, clamp(base + vec2<i32>(1, 1), vec2<i32>(0), last), 0).rgb;
+    return mix(mix(c00, c10, t.x), mix(c01, c11, t.x), t.y);
+}
+
+// Sharpen the bilinear color with its neighbours a texel away, the lobe is
+// limited so that no channel leaves the range of the neighbourhood, after RCAS
+// of FidelityFX Super Resolution 1
+fn sharpen(position: vec2<f32>) -> vec3<f32> {
+    let center = bilinear(position);
+    let north = bilinear(position + vec2<f32>(0.0, -1.0));
+    let south = bilinear(position + vec2<f32>(0.0, 1.0));
+    let west = bilinear(position + vec2<f32>(-1.0, 0.0));
+    let east = bilinear(position + vec2<f32>(1.0, 0.0));
+    let low = min(min(north, south), min(west, east));
+    let high = max(max(north, south), max(west, east));
+    // Negative weight of the neighbours that keeps the result between the
+    // darkest and brightest of them
+    let hit_low = min(low, center) / max(4.0 * high, vec3<f32>(1e-5));
+    let hit_high = (1.0 - max(high, center)) / min(4.0 * low - 4.0, vec3<f32>(-1e-5));
+    let limit = max(-hit_low, hit_high);
+    let lobe = max(-0.1875, min(max(limit.r, max(limit.g, limit.b)), 0.0)) * present.sharpness;
+    return (lobe * (north + south + west + east) + center) / (4.0 * lobe + 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = min(vec2<u32>(in.clip_position.xy * present.scale), textureDimensions(scene_color) - 1u);
+    let position = in.clip_position.xy * present.scale;
+    var color = textureLoad(scene_color, texel, 0);
+    if present.upsampling == UPSAMPLING_SHARPEN {
+        color = vec4<f32>(sharpen(position), color.a);
+    } else if present.upsampling != UPSAMPLING_NEAREST {
+        color = vec4<f32>(bilinear(position), color.a);
+    }
+    // Derivatives are taken before any branch on the pixel
+    let contoured = depth_contours(color.rgb, textureLoad(scene_depth, texel, 0));
+    if present.nan_guard != 0u
+        && (is_not_finite(color.r) || is_not_finite(color.g) || is_not_finite(color.b)) {
+        return vec4<f32>(1.0, 0.0, 1.0, 1.0);
+    }
+    if present.contours != 0u {
+        return vec4<f32>(contoured, 1.0);
+    }
+    return vec4<f32>(color.rgb, 1.0);
+}
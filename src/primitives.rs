@@ -1,16 +1,32 @@
+pub mod shapes;
+
 use std::{
     borrow::Borrow,
+    collections::HashMap,
     path::{Path, PathBuf},
     sync::Arc,
 };
 
 use bytemuck::{NoUninit, Pod, Zeroable};
-use glam::{mat2, vec2, vec3, Vec2, Vec3, Vec4};
-use log::warn;
+use glam::{vec2, vec3, vec4, Mat4, Vec2, Vec3, Vec4};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    logging,
+    material_file::{MaterialFile, MaterialPatch},
+    texture::{ImageCache, TextureImage},
+};
 
 // use crate::ASSETS_DIR;
 const RESOURCE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources");
 
+/// Resolve a path relative to the resources directory, absolute paths are kept as is.
+pub fn resource_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    PathBuf::from(RESOURCE_PATH).join(path)
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable, Default)]
 pub struct UniformLight {
@@ -34,6 +50,78 @@ where
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct UniformObject {
+    model: Mat4,
+    // inverse transpose of the model matrix
+    normal: Mat4,
+    material: UniformMaterial,
+    // which material maps are sampled, bit i for the i-th map in `shader.wgsl`
+    enable_bit: u32,
+    // first matrix of the skin in the joint buffer, `NO_SKIN` for rigid geoms
+    joint_offset: u32,
+    _padding: [u32; 2],
+}
+
+impl UniformObject {
+    const NO_SKIN: u32 = u32::MAX;
+
+    pub fn new(
+        model: Mat4,
+        material: UniformMaterial,
+        enable_bit: u32,
+        joint_offset: Option<u32>,
+    ) -> Self {
+        Self {
+            model,
+            normal: model.inverse().transpose(),
+            material,
+            enable_bit,
+            joint_offset: joint_offset.unwrap_or(Self::NO_SKIN),
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// One vertex of the interleaved vertex buffer, matches `VertexInput` in `shader.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct Vertex {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub normal: Vec3,
+    pub tangent: Vec3,
+    /// The bitangent is `tangent_sign * cross(normal, tangent)`, see `tangents`
+    pub tangent_sign: f32,
+    pub texcoord: Vec2,
+    /// Indices into the joints of the skin of the geom
+    pub joints: [u32; 4],
+    /// All zero for rigid meshes
+    pub weights: [f32; 4],
+}
+
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x3,
+        3 => Float32x3,
+        4 => Float32,
+        5 => Float32x2,
+        6 => Uint32x4,
+        7 => Float32x4,
+    ];
+
+    pub const fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
 pub struct UniformMaterial {
@@ -41,8 +129,32 @@ pub struct UniformMaterial {
     ambient: Vec4,
     diffuse: Vec4,
     specular: Vec4,
+    // xy: scale, zw: offset applied to color texture coordinates
+    color_uv_transform: Vec4,
+    // xyz: light given off, added to the lit color
+    emissive: Vec4,
     shininess: f32,
-    _padding: [u32; 3],
+    alpha: f32,
+    alpha_cutoff: f32,
+    // 0: opaque, 1: mask, 2: blend
+    alpha_mode: u32,
+    // scale of the tangent space bend of the normal map
+    normal_strength: f32,
+    // non-zero negates the green channel of the normal map
+    normal_flip_y: u32,
+    // texture repeats per world unit along the axes, 0: texture coordinates
+    triplanar_scale: f32,
+    _padding: u32,
+}
+
+impl UniformMaterial {
+    pub fn with_normal_mapping(self, normal_mapping: NormalMapping) -> Self {
+        Self {
+            normal_strength: normal_mapping.strength,
+            normal_flip_y: normal_mapping.flip_y as u32,
+            ..self
+        }
+    }
 }
 
 impl From<Option<Material>> for UniformMaterial {
@@ -66,20 +178,250 @@ where
             ambient: op_vec3_to_vec4(value.borrow().ambient),
             diffuse: op_vec3_to_vec4(value.borrow().diffuse),
             specular: op_vec3_to_vec4(value.borrow().specular),
+            color_uv_transform: value
+                .borrow()
+                .color_uv_transform
+                .unwrap_or(vec4(1.0, 1.0, 0.0, 0.0)),
+            emissive: value.borrow().emissive.unwrap_or(Vec3::ZERO).extend(0.0),
             shininess: value.borrow().shininess.unwrap_or(1.0),
-            _padding: [0; 3],
+            alpha: value.borrow().alpha.unwrap_or(1.0),
+            alpha_cutoff: match value.borrow().alpha_mode {
+                AlphaMode::Mask { cutoff } => cutoff,
+                _ => 0.0,
+            },
+            alpha_mode: match value.borrow().alpha_mode {
+                AlphaMode::Opaque => 0,
+                AlphaMode::Mask { .. } => 1,
+                AlphaMode::Blend => 2,
+            },
+            normal_strength: value.borrow().normal_mapping.strength,
+            normal_flip_y: value.borrow().normal_mapping.flip_y as u32,
+            triplanar_scale: value.borrow().triplanar_scale.unwrap_or(0.0),
+            _padding: 0,
+        }
+    }
+}
+
+/// Material standing in for every loaded one, matches `MaterialOverride` in `shader.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct UniformMaterialOverride {
+    material: UniformMaterial,
+    // non-zero replaces the colors and specular of every material
+    enabled: u32,
+    /// `ChannelView` shown instead of the lit color
+    channel: u32,
+    /// Clip planes the depth channel spans
+    znear: f32,
+    zfar: f32,
+}
+
+impl UniformMaterialOverride {
+    pub fn new(value: MaterialOverride, channel: ChannelView, (znear, zfar): (f32, f32)) -> Self {
+        let material = value.material();
+        Self {
+            enabled: material.is_some() as u32,
+            material: material.into(),
+            channel: channel as u32,
+            znear,
+            zfar,
+        }
+    }
+}
+
+/// Scene wide replacement of the materials, used to judge lighting without textures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaterialOverride {
+    #[default]
+    Off,
+    /// Matte white
+    Clay,
+    /// Matte mid gray
+    Gray,
+    /// Mirror like metal
+    Chrome,
+}
+
+impl MaterialOverride {
+    pub const ALL: [Self; 4] = [Self::Off, Self::Clay, Self::Gray, Self::Chrome];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Clay => "Clay",
+            Self::Gray => "Gray",
+            Self::Chrome => "Chrome",
+        }
+    }
+
+    /// Replacement material, only its colors and shininess are used.
+    pub fn material(self) -> Option<Material> {
+        let (diffuse, specular, shininess) = match self {
+            Self::Off => return None,
+            Self::Clay => (Vec3::ONE, Vec3::ZERO, 1.0),
+            Self::Gray => (Vec3::splat(0.5), Vec3::ZERO, 1.0),
+            Self::Chrome => (Vec3::splat(0.05), Vec3::ONE, 512.0),
+        };
+        Some(Material {
+            ambient: Some(diffuse),
+            diffuse: Some(diffuse),
+            specular: Some(specular),
+            shininess: Some(shininess),
+            ..Default::default()
+        })
+    }
+}
+
+/// Term of the shading shown instead of the lit color, to inspect the inputs of
+/// the lighting one at a time. The values match the `CHANNEL_` constants in
+/// `shader.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelView {
+    #[default]
+    Lit,
+    /// Surface color before lighting
+    Albedo,
+    /// World space shading normal after normal mapping
+    Normals,
+    /// Distance from the camera between the clip planes
+    Depth,
+    Roughness,
+    AmbientOcclusion,
+    /// Ambient and reflection probe lighting without the direct light
+    GiOnly,
+    /// How much the light reaches the surface
+    ShadowsOnly,
+    /// View space normal looked up in a lit sphere, see `matcap`
+    Matcap,
+}
+
+impl ChannelView {
+    pub const ALL: [Self; 9] = [
+        Self::Lit,
+        Self::Albedo,
+        Self::Normals,
+        Self::Depth,
+        Self::Roughness,
+        Self::AmbientOcclusion,
+        Self::GiOnly,
+        Self::ShadowsOnly,
+        Self::Matcap,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lit => "Lit",
+            Self::Albedo => "Albedo",
+            Self::Normals => "Normals",
+            Self::Depth => "Depth",
+            Self::Roughness => "Roughness",
+            Self::AmbientOcclusion => "AO",
+            Self::GiOnly => "GI only",
+            Self::ShadowsOnly => "Shadows only",
+            Self::Matcap => "Matcap",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    /// Fragments with alpha below `cutoff` are discarded
+    Mask { cutoff: f32 },
+    /// Blended over the opaque geometry, drawn back to front
+    Blend,
+}
+
+/// Faces a material does not render, the scene pipelines come in a variant
+/// for each.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub enum CullMode {
+    #[default]
+    Back,
+    Front,
+    /// Both faces are rendered
+    None,
+}
+
+impl CullMode {
+    /// Every mode, in the order of `CullMode::index`
+    pub const ALL: [Self; 3] = [Self::Back, Self::Front, Self::None];
+
+    /// Index of the pipeline variant drawing with this mode.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn face(self) -> Option<wgpu::Face> {
+        match self {
+            Self::Back => Some(wgpu::Face::Back),
+            Self::Front => Some(wgpu::Face::Front),
+            Self::None => None,
+        }
+    }
+
+    /// Name as given in an MTL file, see `ObjScene`.
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "back" => Some(Self::Back),
+            "front" => Some(Self::Front),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+}
+
+/// How a normal map bends the normal. Maps baked for DirectX point green down
+/// and need `flip_y`, OpenGL and glTF maps point it up.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalMapping {
+    /// Scale of the tangent space bend, zero leaves the normal flat
+    pub strength: f32,
+    pub flip_y: bool,
+}
+
+impl Default for NormalMapping {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            flip_y: false,
         }
     }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Material {
+    /// Name in the MTL or glTF file, keys the per material settings
+    pub name: String,
     pub ambient: Option<Vec3>,
     pub diffuse: Option<Vec3>,
     pub specular: Option<Vec3>,
     pub shininess: Option<f32>,
-    pub color_texture: Option<image::DynamicImage>,
-    pub normal_texture: Option<image::DynamicImage>,
+    pub color_texture: Option<Arc<TextureImage>>,
+    pub normal_texture: Option<Arc<TextureImage>>,
+    pub normal_mapping: NormalMapping,
+    pub specular_texture: Option<Arc<TextureImage>>,
+    /// Roughness is read from the green channel
+    pub roughness_texture: Option<Arc<TextureImage>>,
+    /// Occlusion is read from the red channel
+    pub ambient_occlusion_texture: Option<Arc<TextureImage>>,
+    pub alpha: Option<f32>,
+    /// Alpha is read from the red channel
+    pub alpha_texture: Option<Arc<TextureImage>>,
+    pub alpha_mode: AlphaMode,
+    /// Faces that are not rendered
+    pub cull_mode: CullMode,
+    /// Set when the color texture lives inside an atlas
+    pub color_uv_transform: Option<Vec4>,
+    /// Light given off by the surface, unaffected by the lighting
+    pub emissive: Option<Vec3>,
+    /// Project the textures along the world axes with this many repeats per
+    /// unit instead of using the texture coordinates, for meshes without them
+    pub triplanar_scale: Option<f32>,
 }
 
 pub trait Scene<V, C, N, T>
@@ -89,18 +431,32 @@ where
     N: NoUninit,
     T: NoUninit,
 {
-    fn vertex_descriptor(&self) -> wgpu::VertexBufferLayout<'static>;
     fn vertices(&self) -> Box<[V]>;
     fn vertex_colors(&self) -> Box<[C]>;
     fn normals(&self) -> Box<[N]>;
-    fn tbn(&self) -> (Box<[Vec3]>, Box<[Vec3]>, Box<[Vec3]>);
     fn texcoords(&self) -> Box<[T]>;
+    /// Tangent in xyz and the bitangent sign in w for every vertex, generated
+    /// by the loader when empty
+    fn tangents(&self) -> Box<[Vec4]> {
+        Box::from([])
+    }
+    /// Four joints of the skin moving every vertex, empty for rigid meshes
+    fn joints(&self) -> Box<[[u32; 4]]> {
+        Box::from([])
+    }
+    /// Weight of each of the `joints` of every vertex
+    fn weights(&self) -> Box<[Vec4]> {
+        Box::from([])
+    }
     fn indices(&self) -> Box<[u32]>;
     fn vertex_count(&self) -> u32;
     fn name(&self) -> &str;
-    fn material(&self) -> Option<Material>;
+    fn material(&self, images: &mut ImageCache) -> Option<Material>;
 }
 
+/// Any scene source the renderer can upload.
+pub type DynScene = dyn Scene<Vec3, Vec3, Vec3, Vec2> + Send;
+
 fn load_obj<P: AsRef<Path>>(obj_path: P) -> tobj::LoadResult {
     tobj::load_obj(
         PathBuf::from(RESOURCE_PATH).join(obj_path),
@@ -117,19 +473,45 @@ pub struct ObjScene {
     pub model: tobj::Model,
     pub obj_dir: PathBuf,
     pub materials: Option<Arc<tobj::Material>>,
+    /// Entry of the `material_file` for the MTL material
+    pub material_patch: Option<Arc<MaterialPatch>>,
 }
 
 impl ObjScene {
-    pub fn load<P, F>(
-        path: P,
-        light_predicate: F,
-    ) -> Result<(Vec<Self>, Option<Vec3>), tobj::LoadError>
+    pub fn load<P, F>(path: P, light_predicate: F) -> Result<(Vec<Self>, Option<Vec3>)>
     where
         P: AsRef<Path>,
         F: Fn(&tobj::Material) -> bool,
     {
+        if !resource_path(&path).exists() {
+            return Err(Error::ModelNotFound(path.as_ref().to_path_buf()));
+        }
         let (model, materials) = load_obj(&path)?;
         let materials = materials?.into_iter().map(Arc::new).collect::<Box<[_]>>();
+        let patches = MaterialFile::load(&path)
+            .inspect_err(|err| {
+                tracing::warn!(
+                    target: logging::SCENE,
+                    "failed to read material file: {:#}",
+                    err
+                )
+            })
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .materials
+            .into_iter()
+            .map(|(name, patch)| (name, Arc::new(patch)))
+            .collect::<HashMap<_, _>>();
+        for name in patches.keys() {
+            if !materials.iter().any(|material| &material.name == name) {
+                tracing::warn!(
+                    target: logging::SCENE,
+                    "material file names unknown material {}",
+                    name
+                );
+            }
+        }
         let light = model
             .iter()
             .filter_map(|md| {
@@ -157,7 +539,7 @@ impl ObjScene {
             model
                 .into_iter()
                 .map(|m| {
-                    let material_id = m.mesh.material_id;
+                    let material = m.mesh.material_id.and_then(|i| materials.get(i));
                     Self {
                         model: m,
                         obj_dir: PathBuf::from(RESOURCE_PATH)
@@ -165,56 +547,111 @@ impl ObjScene {
                             .parent()
                             .map(Path::to_path_buf)
                             .unwrap_or(RESOURCE_PATH.into()),
-                        materials: material_id.and_then(|i| materials.get(i).map(Clone::clone)),
+                        material_patch: material
+                            .and_then(|material| patches.get(&material.name).cloned()),
+                        materials: material.cloned(),
                     }
                 })
                 .collect(),
             light,
         ))
     }
-}
 
-impl Scene<Vec3, Vec3, Vec3, Vec2> for ObjScene {
-    fn vertex_descriptor(&self) -> wgpu::VertexBufferLayout<'static> {
-        use std::mem;
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<[f32; 17]>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
-                    shader_location: 2,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
-                    shader_location: 3,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
-                    shader_location: 4,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 15]>() as wgpu::BufferAddress,
-                    shader_location: 5,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-            ],
+    /// Replace the normals with area weighted smooth ones. Vertices at the same
+    /// position share them, so UV seams stay smooth. Faces meeting at more than
+    /// `hard_edge_angle` radians are not smoothed together, vertices on such
+    /// edges are split to keep them hard.
+    pub fn generate_normals(&mut self, hard_edge_angle: Option<f32>) {
+        let mesh = &mut self.model.mesh;
+        let vertex_count = mesh.positions.len() / 3;
+        let position = |i: u32| Vec3::from_slice(&mesh.positions[i as usize * 3..][..3]);
+        // OBJ faces wind counter-clockwise, twice the triangle area long
+        let faces = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|c| {
+                let [p0, p1, p2] = [c[0], c[1], c[2]].map(position);
+                (p1 - p0).cross(p2 - p0)
+            })
+            .collect::<Vec<_>>();
+        let units = faces
+            .iter()
+            .map(|n| n.normalize_or_zero())
+            .collect::<Vec<_>>();
+        let mut welded = HashMap::new();
+        let groups = (0..vertex_count as u32)
+            .map(|i| {
+                let next = welded.len() as u32;
+                *welded
+                    .entry(position(i).to_array().map(f32::to_bits))
+                    .or_insert(next)
+            })
+            .collect::<Vec<_>>();
+        let mut group_faces = vec![Vec::new(); welded.len()];
+        for (face, c) in mesh.indices.chunks_exact(3).enumerate() {
+            for &i in c {
+                let faces = &mut group_faces[groups[i as usize] as usize];
+                // Degenerate faces may touch a position twice
+                if faces.last() != Some(&face) {
+                    faces.push(face);
+                }
+            }
+        }
+        // Without a threshold every face around a position is smoothed together
+        let min_cos = hard_edge_angle.map_or(f32::NEG_INFINITY, f32::cos);
+        let corners = mesh
+            .indices
+            .par_chunks_exact(3)
+            .enumerate()
+            .flat_map_iter(|(face, c)| {
+                let unit = units[face];
+                [c[0], c[1], c[2]].map(|i| {
+                    group_faces[groups[i as usize] as usize]
+                        .iter()
+                        .filter(|&&other| units[other].dot(unit) >= min_cos)
+                        .map(|&other| faces[other])
+                        .sum::<Vec3>()
+                        .try_normalize()
+                        .or(unit.try_normalize())
+                        .unwrap_or(Vec3::Z)
+                })
+            })
+            .collect::<Vec<_>>();
+        // Corners disagreeing with the first normal of their vertex move to copies
+        let has_texcoords = mesh.texcoords.len() == vertex_count * 2;
+        let has_colors = mesh.vertex_color.len() == vertex_count * 3;
+        let mut normals = vec![None; vertex_count];
+        let mut copies = HashMap::new();
+        for (index, normal) in mesh.indices.iter_mut().zip(corners) {
+            let i = *index as usize;
+            let first = normals[i];
+            match first {
+                None => normals[i] = Some(normal),
+                Some(first) if first == normal => {}
+                Some(_) => {
+                    let key = (i, normal.to_array().map(f32::to_bits));
+                    *index = *copies.entry(key).or_insert_with(|| {
+                        normals.push(Some(normal));
+                        mesh.positions.extend_from_within(i * 3..i * 3 + 3);
+                        if has_texcoords {
+                            mesh.texcoords.extend_from_within(i * 2..i * 2 + 2);
+                        }
+                        if has_colors {
+                            mesh.vertex_color.extend_from_within(i * 3..i * 3 + 3);
+                        }
+                        normals.len() as u32 - 1
+                    });
+                }
+            }
         }
+        mesh.normals = normals
+            .into_iter()
+            .flat_map(|n| n.unwrap_or(Vec3::Z).to_array())
+            .collect();
     }
+}
 
+impl Scene<Vec3, Vec3, Vec3, Vec2> for ObjScene {
     fn vertices(&self) -> Box<[Vec3]> {
         self.model
             .mesh
@@ -242,117 +679,6 @@ impl Scene<Vec3, Vec3, Vec3, Vec2> for ObjScene {
             .collect()
     }
 
-    fn tbn(&self) -> (Box<[Vec3]>, Box<[Vec3]>, Box<[Vec3]>) {
-        let temp_vertices = self.vertices();
-        let temp_texcoords = {
-            let mut texcoords = self.texcoords();
-            if texcoords.len() != temp_vertices.len() {
-                texcoords = std::iter::repeat_n(Vec2::ZERO, temp_vertices.len()).collect();
-            }
-            texcoords
-        };
-        assert!(temp_vertices.len() == temp_texcoords.len());
-        let mut temp_tangents = vec![Vec3::ZERO; temp_vertices.len()];
-        let mut temp_bitangents = vec![Vec3::ZERO; temp_vertices.len()];
-        let mut temp_normal = vec![Vec3::ZERO; temp_vertices.len()];
-        let mut count_triangles_included = vec![0; temp_vertices.len()];
-        for c in self.indices().chunks(3) {
-            let pos0 = temp_vertices[c[0] as usize];
-            let pos1 = temp_vertices[c[1] as usize];
-            let pos2 = temp_vertices[c[2] as usize];
-
-            let uv0 = temp_texcoords[c[0] as usize];
-            let uv1 = temp_texcoords[c[1] as usize];
-            let uv2 = temp_texcoords[c[2] as usize];
-
-            // Calculate the edges of the triangle
-            let delta_pos1 = pos1 - pos0;
-            let delta_pos2 = pos2 - pos0;
-
-            // This will give us a direction to calculate the
-            // tangent and bitangent
-            let delta_uv1 = (uv1 - uv0) * 2.0f32.powi(11);
-            let delta_uv2 = (uv2 - uv0) * 2.0f32.powi(11);
-
-            // Solving the following system of equations will
-            // give us the tangent and bitangent.
-            //     delta_pos1 = delta_uv1.x * T + delta_u.y * B
-            //     delta_pos2 = delta_uv2.x * T + delta_uv2.y * B
-            // Luckily, the place I found this equation provided
-            // the solution!
-            let r = mat2(delta_uv1, delta_uv2).inverse();
-            let tangent = r.col(0).x * delta_pos1 - r.col(0).y * delta_pos2;
-            // We flip the bitangent to enable right-handed normal
-            // maps with wgpu texture coordinate system
-            let bitangent = -r.col(1).x * delta_pos1 + r.col(1).y * delta_pos2;
-
-            // construct normal
-            let normal = bitangent.cross(tangent).normalize();
-
-            // We'll use the same tangent/bitangent for each vertex in the triangle
-            if !tangent.is_nan() && !bitangent.is_nan() && !normal.is_nan() {
-                temp_tangents[c[0] as usize] += tangent;
-                temp_tangents[c[1] as usize] += tangent;
-                temp_tangents[c[2] as usize] += tangent;
-                temp_bitangents[c[0] as usize] += bitangent;
-                temp_bitangents[c[1] as usize] += bitangent;
-                temp_bitangents[c[2] as usize] += bitangent;
-                temp_normal[c[0] as usize] += normal;
-                temp_normal[c[1] as usize] += normal;
-                temp_normal[c[2] as usize] += normal;
-                // Used to average the tangents/bitangents
-                count_triangles_included[c[0] as usize] += 1;
-                count_triangles_included[c[1] as usize] += 1;
-                count_triangles_included[c[2] as usize] += 1;
-            }
-        }
-
-        (
-            temp_tangents
-                .iter()
-                .zip(count_triangles_included.iter())
-                .map(|(tangent, count)| {
-                    if *count > 0 {
-                        (tangent / (*count as f32)).normalize()
-                    } else {
-                        Vec3::X
-                    }
-                })
-                .collect(),
-            temp_bitangents
-                .iter()
-                .zip(count_triangles_included.iter())
-                .map(|(bitangent, count)| {
-                    if *count > 0 {
-/*
-                        if((bitangent / (*count as f32)).normalize().is_nan()){
-                            println!("1");
-                        }
-                        if((bitangent / (*count as f32)).normalize().is_nan()){
-                            println!("2");
-                        }*/
-
-                        (bitangent / (*count as f32)).normalize()
-                        //(bitangent / (*count as f32)).normalize()
-                    } else {
-                        Vec3::Y
-                    }
-                })
-                .collect(),
-            temp_normal
-                .iter()
-                .zip(count_triangles_included.iter())
-                .map(|(normal, count)| {
-                    if *count > 0 {
-                        (normal / (*count as f32)).normalize()
-                    } else {
-                        Vec3::Z
-                    }
-                })
-                .collect(),
-        )
-    }
-
     fn texcoords(&self) -> Box<[Vec2]> {
         if self.model.mesh.positions.len() / 3 == self.model.mesh.texcoords.len() / 2 {
             self.model
@@ -383,34 +709,86 @@ impl Scene<Vec3, Vec3, Vec3, Vec2> for ObjScene {
         &self.model.name
     }
 
-    fn material(&self) -> Option<Material> {
+    fn material(&self, images: &mut ImageCache) -> Option<Material> {
         self.materials.as_ref().map(|e| {
-            let color_texture = {
-                let path = e.diffuse_texture.clone().map(|dp| self.obj_dir.join(dp));
-                path.and_then(|p| {
-                    image::ImageReader::open(p)
-                        .inspect_err(|err| warn!("failed to open color texture: {}", err))
-                        .ok()
-                        .and_then(|img| img.decode().ok())
-                })
+            let mut open = |path: &Option<String>| {
+                path.as_ref()
+                    .and_then(|p| images.open(self.obj_dir.join(p)))
             };
-            let normal_texture = {
-                let path = e.normal_texture.clone().map(|dp| self.obj_dir.join(dp));
-                path.and_then(|p| {
-                    image::ImageReader::open(p)
-                        .inspect_err(|err| warn!("failed to open normal texture: {}", err))
-                        .ok()
-                        .and_then(|img| img.decode().ok())
-                })
+            let color_texture = open(&e.diffuse_texture);
+            let normal_texture = open(&e.normal_texture);
+            let specular_texture = open(&e.specular_texture);
+            let roughness_texture = open(&e.unknown_param.get("map_Pr").cloned());
+            // MTL has no occlusion map, the ambient map plays that role
+            let ambient_occlusion_texture = open(&e.ambient_texture);
+            let alpha_texture = open(&e.dissolve_texture);
+            // `Tr` is the inverse of `d`, some exporters only write that one
+            let alpha = e.dissolve.or_else(|| {
+                e.unknown_param
+                    .get("Tr")
+                    .and_then(|tr| tr.parse::<f32>().ok())
+                    .map(|tr| 1.0 - tr)
+            });
+            // MTL has no culling state, back faces are culled unless `cull none` or
+            // `cull front` is given as an extra material parameter. `double_sided 1`
+            // is read as `cull none`
+            let cull_mode = match e.unknown_param.get("cull") {
+                Some(name) => CullMode::parse(name).unwrap_or_else(|| {
+                    tracing::warn!(
+                        target: logging::SCENE,
+                        "unknown cull mode {:?} of material {:?}, culling back faces",
+                        name,
+                        e.name
+                    );
+                    CullMode::Back
+                }),
+                None if e
+                    .unknown_param
+                    .get("double_sided")
+                    .is_some_and(|value| value.trim() == "1") =>
+                {
+                    CullMode::None
+                }
+                None => CullMode::Back,
+            };
+            let alpha_mode = if alpha.is_some_and(|alpha| alpha < 1.0) {
+                AlphaMode::Blend
+            } else if alpha_texture.is_some() {
+                AlphaMode::Mask { cutoff: 0.5 }
+            } else {
+                AlphaMode::Opaque
             };
-            Material {
+            let emissive = e.unknown_param.get("Ke").and_then(|ke| {
+                let rgb = ke
+                    .split_whitespace()
+                    .map(|v| v.parse::<f32>().ok())
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Vec3::from_slice(rgb.get(..3)?))
+            });
+            let mut material = Material {
+                name: e.name.clone(),
                 ambient: e.ambient.map(Vec3::from_array),
                 diffuse: e.diffuse.map(Vec3::from_array),
                 specular: e.specular.map(Vec3::from_array),
                 shininess: e.shininess,
                 color_texture,
                 normal_texture,
+                normal_mapping: NormalMapping::default(),
+                specular_texture,
+                roughness_texture,
+                ambient_occlusion_texture,
+                alpha,
+                alpha_texture,
+                alpha_mode,
+                cull_mode,
+                color_uv_transform: None,
+                emissive: emissive.filter(|emissive| *emissive != Vec3::ZERO),
+                triplanar_scale: None,
+            };
+            if let Some(patch) = &self.material_patch {
+                patch.apply(&mut material, |path| images.open(self.obj_dir.join(path)));
             }
+            material
         })
     }
 }
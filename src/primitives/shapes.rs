@@ -0,0 +1,261 @@
+//! Meshes generated in code, for test scenes and gizmos that should not depend
+//! on model files.
+//!
+//! Every shape is built from parametric patches: a patch is sampled on a grid
+//! and each point brings its normal, texture coordinate and the directions in
+//! which the texture coordinate grows, which become the tangent frame. Shapes
+//! are centered on the origin with Y up, textures read upright and unmirrored
+//! when a face is looked at from outside.
+
+use std::f32::consts::{PI, TAU};
+
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::{
+    primitives::{Material, Scene},
+    texture::ImageCache,
+};
+
+/// A point of a patch.
+struct SurfacePoint {
+    position: Vec3,
+    normal: Vec3,
+    texcoord: Vec2,
+    /// Direction in which `texcoord.x` grows
+    tangent: Vec3,
+    /// Direction in which `texcoord.y` grows
+    bitangent: Vec3,
+}
+
+/// A generated mesh, see the module docs.
+#[derive(Debug, Clone)]
+pub struct Shape {
+    name: String,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    texcoords: Vec<Vec2>,
+    /// Tangent in xyz and the bitangent sign in w, like `tangents::TangentFrames`
+    tangents: Vec<Vec4>,
+    indices: Vec<u32>,
+    material: Option<Material>,
+}
+
+impl Shape {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            texcoords: Vec::new(),
+            tangents: Vec::new(),
+            indices: Vec::new(),
+            material: None,
+        }
+    }
+
+    /// UV sphere with `segments` around the Y axis and `rings` from pole to pole.
+    pub fn sphere(radius: f32, segments: u32, rings: u32) -> Self {
+        let mut shape = Self::new("Sphere");
+        shape.patch(segments.max(3), rings.max(2), |u, v| {
+            let (sin_phi, cos_phi) = (u * TAU).sin_cos();
+            let (sin_theta, cos_theta) = (v * PI).sin_cos();
+            let normal = Vec3::new(sin_theta * cos_phi, cos_theta, -sin_theta * sin_phi);
+            SurfacePoint {
+                position: normal * radius,
+                normal,
+                texcoord: Vec2::new(u, v),
+                tangent: Vec3::new(-sin_phi, 0.0, -cos_phi),
+                bitangent: Vec3::new(cos_theta * cos_phi, -sin_theta, -cos_theta * sin_phi),
+            }
+        });
+        shape
+    }
+
+    /// Plane in XZ facing up, split into `subdivisions` squares along each side.
+    pub fn plane(size: Vec2, subdivisions: u32) -> Self {
+        let mut shape = Self::new("Plane");
+        let subdivisions = subdivisions.max(1);
+        shape.patch(subdivisions, subdivisions, |u, v| SurfacePoint {
+            position: Vec3::new((u - 0.5) * size.x, 0.0, (v - 0.5) * size.y),
+            normal: Vec3::Y,
+            texcoord: Vec2::new(u, v),
+            tangent: Vec3::X,
+            bitangent: Vec3::Z,
+        });
+        shape
+    }
+
+    /// Axis aligned box reaching `half_extent` from the origin, each face covered
+    /// by the whole texture.
+    pub fn cuboid(half_extent: Vec3) -> Self {
+        let mut shape = Self::new("Box");
+        // Normal, then right and up as seen from outside
+        let faces = [
+            (Vec3::Z, Vec3::X, Vec3::Y),
+            (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+            (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+            (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+            (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+            (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        ];
+        for (normal, right, up) in faces {
+            shape.patch(1, 1, |u, v| SurfacePoint {
+                position: (normal + right * (u * 2.0 - 1.0) + up * (1.0 - v * 2.0)) * half_extent,
+                normal,
+                texcoord: Vec2::new(u, v),
+                tangent: right,
+                bitangent: -up,
+            });
+        }
+        shape
+    }
+
+    /// Capped cylinder along the Y axis with `segments` around it. The side is
+    /// wrapped by the whole texture once, each cap is projected onto it.
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Self {
+        let mut shape = Self::new("Cylinder");
+        let segments = segments.max(3);
+        shape.patch(segments, 1, |u, v| {
+            let (sin_phi, cos_phi) = (u * TAU).sin_cos();
+            let normal = Vec3::new(cos_phi, 0.0, -sin_phi);
+            SurfacePoint {
+                position: normal * radius + Vec3::Y * (0.5 - v) * height,
+                normal,
+                texcoord: Vec2::new(u, v),
+                tangent: Vec3::new(-sin_phi, 0.0, -cos_phi),
+                bitangent: Vec3::NEG_Y,
+            }
+        });
+        for side in [1.0, -1.0] {
+            // The bottom cap is seen from below, its texture runs the other way
+            // along Z to stay unmirrored
+            shape.patch(segments, 1, |u, v| {
+                let (sin_phi, cos_phi) = (u * TAU).sin_cos();
+                let (x, z) = (cos_phi * v, -sin_phi * v);
+                SurfacePoint {
+                    position: Vec3::new(x * radius, side * height * 0.5, z * radius),
+                    normal: Vec3::Y * side,
+                    texcoord: (Vec2::new(x, z * side) + 1.0) * 0.5,
+                    tangent: Vec3::X,
+                    bitangent: Vec3::Z * side,
+                }
+            });
+        }
+        shape
+    }
+
+    /// Torus around the Y axis, `segments` around it and `sides` around the tube.
+    pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> Self {
+        let mut shape = Self::new("Torus");
+        shape.patch(segments.max(3), sides.max(3), |u, v| {
+            let (sin_phi, cos_phi) = (u * TAU).sin_cos();
+            let (sin_psi, cos_psi) = (v * TAU).sin_cos();
+            let outward = Vec3::new(cos_phi, 0.0, -sin_phi);
+            let normal = outward * cos_psi + Vec3::Y * sin_psi;
+            SurfacePoint {
+                position: outward * major_radius + normal * minor_radius,
+                normal,
+                texcoord: Vec2::new(u, v),
+                tangent: Vec3::new(-sin_phi, 0.0, -cos_phi),
+                bitangent: Vec3::Y * cos_psi - outward * sin_psi,
+            }
+        });
+        shape
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    /// Sample `surface` at `columns` by `rows` cells of the unit square and
+    /// append the points and the triangles between them.
+    fn patch(&mut self, columns: u32, rows: u32, surface: impl Fn(f32, f32) -> SurfacePoint) {
+        let first = self.positions.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let point = surface(column as f32 / columns as f32, row as f32 / rows as f32);
+                let sign = point
+                    .normal
+                    .dot(point.tangent.cross(point.bitangent))
+                    .signum();
+                self.positions.push(point.position);
+                self.normals.push(point.normal);
+                self.texcoords.push(point.texcoord);
+                self.tangents.push(point.tangent.normalize().extend(sign));
+            }
+        }
+        let index = |column: u32, row: u32| first + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let [a, b] = [index(column, row), index(column + 1, row)];
+                let [c, d] = [index(column, row + 1), index(column + 1, row + 1)];
+                self.push_triangle([a, b, d]);
+                self.push_triangle([a, d, c]);
+            }
+        }
+    }
+
+    /// Wind the triangle clockwise as seen from the side its normals face, like
+    /// the front faces of the render pipelines. Triangles collapsed at a pole or
+    /// the center of a cap are dropped.
+    fn push_triangle(&mut self, [a, b, c]: [u32; 3]) {
+        let [pa, pb, pc] = [a, b, c].map(|i| self.positions[i as usize]);
+        let normal = [a, b, c]
+            .map(|i| self.normals[i as usize])
+            .into_iter()
+            .sum::<Vec3>();
+        let facing = (pc - pa).cross(pb - pa).dot(normal);
+        if facing.abs() <= f32::EPSILON {
+            return;
+        }
+        if facing > 0.0 {
+            self.indices.extend([a, b, c]);
+        } else {
+            self.indices.extend([a, c, b]);
+        }
+    }
+}
+
+impl Scene<Vec3, Vec3, Vec3, Vec2> for Shape {
+    fn vertices(&self) -> Box<[Vec3]> {
+        self.positions.as_slice().into()
+    }
+
+    fn vertex_colors(&self) -> Box<[Vec3]> {
+        Box::from([])
+    }
+
+    fn normals(&self) -> Box<[Vec3]> {
+        self.normals.as_slice().into()
+    }
+
+    fn texcoords(&self) -> Box<[Vec2]> {
+        self.texcoords.as_slice().into()
+    }
+
+    fn tangents(&self) -> Box<[Vec4]> {
+        self.tangents.as_slice().into()
+    }
+
+    fn indices(&self) -> Box<[u32]> {
+        self.indices.as_slice().into()
+    }
+
+    fn vertex_count(&self) -> u32 {
+        self.indices.len() as u32
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn material(&self, _images: &mut ImageCache) -> Option<Material> {
+        self.material.clone()
+    }
+}
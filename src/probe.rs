@@ -0,0 +1,709 @@
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use crate::{
+    camera::UniformCamera,
+    logging,
+    primitives::resource_path,
+    probe_prefilter::{self, ProbePrefilter},
+    probe_storage::ProbePacker,
+    texture,
+    texture_preview::CUBE_FACES,
+};
+
+pub const MAX_REFLECTION_PROBES: usize = 8;
+/// Face size unless the render settings scale it
+pub const DEFAULT_PROBE_RESOLUTION: u32 = 128;
+const PROBE_ZNEAR: f32 = 0.05;
+const PROBE_ZFAR: f32 = 100.0;
+// Each face camera lives in its own slice of one buffer
+const FACE_UNIFORM_STRIDE: u64 = 256;
+const CACHE_MAGIC: [u8; 4] = *b"RPC1";
+
+/// A local reflection capture, the cubemap is box projected onto `box_min`..`box_max`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReflectionProbe {
+    pub position: [f32; 3],
+    pub box_min: [f32; 3],
+    pub box_max: [f32; 3],
+}
+
+impl ReflectionProbe {
+    /// A probe centered at `position` whose projection box spans `half_extent` on every axis.
+    pub fn around(position: Vec3, half_extent: f32) -> Self {
+        Self {
+            position: position.to_array(),
+            box_min: (position - Vec3::splat(half_extent)).to_array(),
+            box_max: (position + Vec3::splat(half_extent)).to_array(),
+        }
+    }
+
+    fn face_cameras(&self) -> [UniformCamera; 6] {
+        cube_face_cameras(Vec3::from(self.position), PROBE_ZNEAR, PROBE_ZFAR)
+    }
+}
+
+/// View projection matrices of the six cube faces around `eye`, in +X, -X, +Y,
+/// -Y, +Z, -Z order.
+///
+/// Left handed look-at matrices line the rendered images up with the cubemap
+/// sampling convention.
+pub fn cube_face_cameras(eye: Vec3, znear: f32, zfar: f32) -> [UniformCamera; 6] {
+    let projection = Mat4::perspective_lh(std::f32::consts::FRAC_PI_2, 1.0, znear, zfar);
+    [
+        (Vec3::X, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Y),
+        (Vec3::Y, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::Z),
+        (Vec3::Z, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::Y),
+    ]
+    .map(|(dir, up)| UniformCamera::from_matrix(projection * Mat4::look_to_lh(eye, dir, up), eye))
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable, Default)]
+struct UniformProbe {
+    position: Vec4,
+    box_min: Vec4,
+    box_max: Vec4,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct UniformProbes {
+    probes: [UniformProbe; MAX_REFLECTION_PROBES],
+    count: u32,
+    intensity: f32,
+    _padding: [u32; 2],
+}
+
+impl UniformProbes {
+    pub fn new(probes: &[ReflectionProbe], intensity: f32) -> Self {
+        let mut uniform = Self {
+            probes: [UniformProbe::default(); MAX_REFLECTION_PROBES],
+            count: probes.len().min(MAX_REFLECTION_PROBES) as u32,
+            intensity,
+            _padding: [0; 2],
+        };
+        for (dst, src) in uniform.probes.iter_mut().zip(probes) {
+            *dst = UniformProbe {
+                position: Vec3::from(src.position).extend(1.0),
+                box_min: Vec3::from(src.box_min).extend(1.0),
+                box_max: Vec3::from(src.box_max).extend(1.0),
+            };
+        }
+        uniform
+    }
+}
+
+/// GPU side of the reflection probes: a cube array holding every probe and the
+/// resources needed to bake them.
+pub struct ReflectionProbes {
+    pub uniform_buffer: wgpu::Buffer,
+    cube_texture: wgpu::Texture,
+    pub cube_view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    face_texture: wgpu::Texture,
+    face_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    face_camera_buffer: wgpu::Buffer,
+    face_camera_bind_groups: Vec<wgpu::BindGroup>,
+    probe_count: usize,
+    /// Format of the cube array
+    format: wgpu::TextureFormat,
+    /// Format the faces are rendered in
+    face_format: wgpu::TextureFormat,
+    /// Encodes the faces when they are stored in another format
+    packer: Option<ProbePacker>,
+    /// Renders the rough reflection levels, unless the faces are packed
+    prefilter: Option<ProbePrefilter>,
+    resolution: u32,
+}
+
+/// The cube array and its prefilter when `prefiltered`, see `probe_prefilter`.
+fn create_cube_array(
+    device: &Device,
+    format: wgpu::TextureFormat,
+    resolution: u32,
+    prefiltered: bool,
+) -> (wgpu::Texture, wgpu::TextureView, Option<ProbePrefilter>) {
+    let usage = wgpu::TextureUsages::TEXTURE_BINDING
+        | wgpu::TextureUsages::COPY_DST
+        | wgpu::TextureUsages::COPY_SRC;
+    let cube_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Reflection Probe Cube Array"),
+        size: wgpu::Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: 6 * MAX_REFLECTION_PROBES as u32,
+        },
+        mip_level_count: probe_prefilter::mip_level_count(resolution, prefiltered),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: if prefiltered {
+            usage | wgpu::TextureUsages::RENDER_ATTACHMENT
+        } else {
+            usage
+        },
+        view_formats: &[],
+    });
+    let cube_view = cube_texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Reflection Probe Cube Array View"),
+        dimension: Some(wgpu::TextureViewDimension::CubeArray),
+        ..Default::default()
+    });
+    let prefilter = prefiltered.then(|| ProbePrefilter::new(device, &cube_texture));
+    (cube_texture, cube_view, prefilter)
+}
+
+impl ReflectionProbes {
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        resolution: u32,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reflection Probe Buffer"),
+            contents: bytemuck::cast_slice(&[UniformProbes::new(&[], 0.0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let face_size = wgpu::Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: 1,
+        };
+        let (cube_texture, cube_view, prefilter) =
+            create_cube_array(device, format, resolution, true);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            // Rough surfaces blend between the prefiltered levels
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        // Faces are rendered here and then copied into the cube array, so the
+        // array can stay bound for sampling while baking.
+        let face_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reflection Probe Face"),
+            size: face_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let face_view = face_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reflection Probe Depth"),
+            size: face_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: texture::Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let face_count = 6 * MAX_REFLECTION_PROBES as u64;
+        let face_camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reflection Probe Camera Buffer"),
+            size: face_count * FACE_UNIFORM_STRIDE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let face_camera_bind_groups = (0..face_count)
+            .map(|i| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: camera_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &face_camera_buffer,
+                            offset: i * FACE_UNIFORM_STRIDE,
+                            size: wgpu::BufferSize::new(
+                                std::mem::size_of::<UniformCamera>() as u64
+                            ),
+                        }),
+                    }],
+                    label: Some("Reflection Probe Camera Bind Group"),
+                })
+            })
+            .collect();
+
+        Self {
+            uniform_buffer,
+            cube_texture,
+            cube_view,
+            sampler,
+            face_texture,
+            face_view,
+            depth_view,
+            face_camera_buffer,
+            face_camera_bind_groups,
+            probe_count: 0,
+            format,
+            face_format: format,
+            packer: None,
+            prefilter,
+            resolution,
+        }
+    }
+
+    /// Replace the cube array with an empty one in `format`, the faces are still
+    /// rendered in the format given on creation and only prefiltered when stored
+    /// in it. Bind groups using `cube_view` have to be recreated and the probes
+    /// baked again.
+    pub fn set_storage(
+        &mut self,
+        device: &Device,
+        format: wgpu::TextureFormat,
+    ) -> anyhow::Result<()> {
+        self.packer = if format == self.face_format {
+            None
+        } else {
+            Some(ProbePacker::new(
+                device,
+                &self.face_view,
+                format,
+                self.resolution,
+            )?)
+        };
+        (self.cube_texture, self.cube_view, self.prefilter) =
+            create_cube_array(device, format, self.resolution, self.packer.is_none());
+        self.format = format;
+        Ok(())
+    }
+
+    pub fn update(&mut self, queue: &Queue, probes: &[ReflectionProbe], intensity: f32) {
+        if probes.len() > MAX_REFLECTION_PROBES {
+            tracing::warn!(
+                target: logging::PROBES,
+                "only {} of {} reflection probes are used",
+                MAX_REFLECTION_PROBES,
+                probes.len()
+            );
+        }
+        self.probe_count = probes.len().min(MAX_REFLECTION_PROBES);
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[UniformProbes::new(probes, intensity)]),
+        );
+        for (i, probe) in probes.iter().take(MAX_REFLECTION_PROBES).enumerate() {
+            for (face, camera) in probe.face_cameras().iter().enumerate() {
+                queue.write_buffer(
+                    &self.face_camera_buffer,
+                    (i * 6 + face) as u64 * FACE_UNIFORM_STRIDE,
+                    bytemuck::cast_slice(&[*camera]),
+                );
+            }
+        }
+    }
+
+    /// Number of cube faces a full refresh renders.
+    pub fn face_count(&self) -> usize {
+        self.probe_count * 6
+    }
+
+    /// Record the bake of the given cube faces, `draw` renders the scene with the given camera.
+    pub fn bake<F>(&self, encoder: &mut wgpu::CommandEncoder, faces: Range<usize>, draw: F)
+    where
+        F: Fn(&mut wgpu::RenderPass, &wgpu::BindGroup),
+    {
+        for layer in faces.start..faces.end.min(self.face_count()) {
+            encoder.push_debug_group(&format!("Probe {} {}", layer / 6, CUBE_FACES[layer % 6]));
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Render Pass: reflection probe face"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &self.face_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &self.depth_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                draw(&mut render_pass, &self.face_camera_bind_groups[layer]);
+            }
+            encoder.pop_debug_group();
+            if let Some(packer) = &self.packer {
+                packer.pack(encoder, &self.cube_texture, layer as u32);
+                continue;
+            }
+            encoder.copy_texture_to_texture(
+                self.face_texture.as_image_copy(),
+                wgpu::ImageCopyTexture {
+                    texture: &self.cube_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width: self.resolution,
+                    height: self.resolution,
+                    depth_or_array_layers: 1,
+                },
+            );
+            // The levels blur across the faces, so they wait for the last one
+            if let Some(prefilter) = self.prefilter.as_ref().filter(|_| layer % 6 == 5) {
+                prefilter.prefilter(encoder, (layer / 6) as u32);
+            }
+        }
+    }
+}
+
+impl ReflectionProbes {
+    /// Format of the cube array, see `set_storage`
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn face_format(&self) -> wgpu::TextureFormat {
+        self.face_format
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    /// Cube array holding every baked face, one layer per face.
+    pub fn cube_texture(&self) -> &wgpu::Texture {
+        &self.cube_texture
+    }
+
+    fn bytes_per_pixel(&self) -> u32 {
+        self.format.block_copy_size(None).unwrap_or(4)
+    }
+
+    /// Read the baked faces of `probes` back from the GPU.
+    pub fn read_cache(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        probes: &[ReflectionProbe],
+    ) -> anyhow::Result<ProbeCache> {
+        let count = probes.len().min(MAX_REFLECTION_PROBES);
+        let bytes_per_row = self.resolution * self.bytes_per_pixel();
+        // Rows of a buffer copy have to be aligned
+        let padded_row = bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let layers = (count * 6) as u32;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reflection Probe Readback Buffer"),
+            size: (padded_row * self.resolution * layers.max(1)) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Reflection Probe Readback"),
+        });
+        encoder.copy_texture_to_buffer(
+            self.cube_texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(self.resolution),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.resolution,
+                height: self.resolution,
+                depth_or_array_layers: layers,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+        let pixels = slice
+            .get_mapped_range()
+            .chunks(padded_row as usize)
+            .flat_map(|row| &row[..bytes_per_row as usize])
+            .copied()
+            .collect();
+        readback.unmap();
+        Ok(ProbeCache {
+            probes: probes[..count].to_vec(),
+            resolution: self.resolution,
+            bytes_per_pixel: self.bytes_per_pixel(),
+            pixels,
+        })
+    }
+
+    /// Upload previously baked faces and prefilter them, returns false when
+    /// `cache` was baked for other probes.
+    pub fn restore_cache(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        probes: &[ReflectionProbe],
+        cache: &ProbeCache,
+    ) -> bool {
+        let count = probes.len().min(MAX_REFLECTION_PROBES);
+        let bytes_per_row = self.resolution * self.bytes_per_pixel();
+        let layers = (count * 6) as u32;
+        if cache.resolution != self.resolution {
+            tracing::warn!(
+                target: logging::PROBES,
+                "ignoring probe cache baked at {}px",
+                cache.resolution
+            );
+            return false;
+        }
+        if count == 0
+            || cache.probes != probes[..count]
+            || cache.bytes_per_pixel != self.bytes_per_pixel()
+            || cache.pixels.len() != (bytes_per_row * self.resolution * layers) as usize
+        {
+            return false;
+        }
+        queue.write_texture(
+            self.cube_texture.as_image_copy(),
+            &cache.pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(self.resolution),
+            },
+            wgpu::Extent3d {
+                width: self.resolution,
+                height: self.resolution,
+                depth_or_array_layers: layers,
+            },
+        );
+        if let Some(prefilter) = &self.prefilter {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Reflection Probe Prefilter"),
+            });
+            for probe in 0..count as u32 {
+                prefilter.prefilter(&mut encoder, probe);
+            }
+            queue.submit(Some(encoder.finish()));
+        }
+        true
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct CacheHeader {
+    magic: [u8; 4],
+    resolution: u32,
+    bytes_per_pixel: u32,
+    probe_count: u32,
+}
+
+/// Baked probe faces stored next to the scene as `<name>.probes.bin`, lets a
+/// static scene skip baking on startup.
+#[derive(Debug, Clone)]
+pub struct ProbeCache {
+    probes: Vec<ReflectionProbe>,
+    resolution: u32,
+    bytes_per_pixel: u32,
+    pixels: Vec<u8>,
+}
+
+impl ProbeCache {
+    pub fn probes(&self) -> &[ReflectionProbe] {
+        &self.probes
+    }
+
+    /// Faces of every probe back to back, in the format the probes were baked in.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    pub fn path_for<P: AsRef<Path>>(scene_path: P) -> PathBuf {
+        resource_path(scene_path).with_extension("probes.bin")
+    }
+
+    /// Read the cache belonging to `scene_path`, a missing file is not an error.
+    pub fn load<P: AsRef<Path>>(scene_path: P) -> anyhow::Result<Option<Self>> {
+        let path = Self::path_for(scene_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(&path)?;
+        let header_size = std::mem::size_of::<CacheHeader>();
+        let probe_size = std::mem::size_of::<[f32; 9]>();
+        anyhow::ensure!(data.len() >= header_size, "truncated probe cache");
+        let header: CacheHeader = bytemuck::pod_read_unaligned(&data[..header_size]);
+        anyhow::ensure!(header.magic == CACHE_MAGIC, "not a probe cache");
+        let pixels_start = header_size + probe_size * header.probe_count as usize;
+        anyhow::ensure!(data.len() >= pixels_start, "truncated probe cache");
+        let probes = data[header_size..pixels_start]
+            .chunks_exact(probe_size)
+            .map(|chunk| {
+                let v: [f32; 9] = bytemuck::pod_read_unaligned(chunk);
+                ReflectionProbe {
+                    position: [v[0], v[1], v[2]],
+                    box_min: [v[3], v[4], v[5]],
+                    box_max: [v[6], v[7], v[8]],
+                }
+            })
+            .collect();
+        Ok(Some(Self {
+            probes,
+            resolution: header.resolution,
+            bytes_per_pixel: header.bytes_per_pixel,
+            pixels: data[pixels_start..].to_vec(),
+        }))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, scene_path: P) -> anyhow::Result<()> {
+        let path = Self::path_for(scene_path);
+        let header = CacheHeader {
+            magic: CACHE_MAGIC,
+            resolution: self.resolution,
+            bytes_per_pixel: self.bytes_per_pixel,
+            probe_count: self.probes.len() as u32,
+        };
+        let mut data = bytemuck::bytes_of(&header).to_vec();
+        for probe in &self.probes {
+            let v = [probe.position, probe.box_min, probe.box_max].concat();
+            data.extend_from_slice(bytemuck::cast_slice(&v));
+        }
+        data.extend_from_slice(&self.pixels);
+        fs::write(&path, data)?;
+        Ok(())
+    }
+}
+
+/// Spreads probe bakes over several frames so they fit in a per frame time budget.
+///
+/// The cost of a face is estimated from how much longer frames with bake work
+/// take compared to frames without.
+#[derive(Debug, Clone)]
+pub struct BakeSchedule {
+    /// Milliseconds per frame allowed for probe updates, zero bakes everything at once
+    pub budget_ms: f32,
+    cursor: usize,
+    pending: bool,
+    faces_last_frame: usize,
+    frames_this_refresh: usize,
+    baseline_ms: f32,
+    face_cost_ms: f32,
+    /// Frames the last completed refresh took
+    pub last_refresh_frames: usize,
+}
+
+impl Default for BakeSchedule {
+    fn default() -> Self {
+        Self {
+            budget_ms: 0.0,
+            cursor: 0,
+            pending: false,
+            faces_last_frame: 0,
+            frames_this_refresh: 0,
+            baseline_ms: 0.0,
+            face_cost_ms: 0.5,
+            last_refresh_frames: 0,
+        }
+    }
+}
+
+impl BakeSchedule {
+    const SMOOTHING: f32 = 0.1;
+
+    /// Restart a full refresh from the first face.
+    pub fn request(&mut self) {
+        self.cursor = 0;
+        self.frames_this_refresh = 0;
+        self.pending = true;
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    pub fn face_cost_ms(&self) -> f32 {
+        self.face_cost_ms
+    }
+
+    /// Faces baked per frame under the current budget.
+    pub fn faces_per_frame(&self, total: usize) -> usize {
+        if self.budget_ms <= 0.0 {
+            return total.max(1);
+        }
+        ((self.budget_ms / self.face_cost_ms.max(1e-3)) as usize).clamp(1, total.max(1))
+    }
+
+    /// Frames a full refresh of `total` faces takes under the current budget.
+    pub fn frames_per_refresh(&self, total: usize) -> usize {
+        total.div_ceil(self.faces_per_frame(total))
+    }
+
+    /// Feed the duration of the previous frame and pick the faces to bake this frame.
+    ///
+    /// A zero frame time leaves the cost estimate untouched, which keeps the
+    /// slicing reproducible in deterministic mode.
+    pub fn next_faces(&mut self, total: usize, frame_time_ms: f32) -> Option<Range<usize>> {
+        if frame_time_ms > 0.0 {
+            if self.faces_last_frame > 0 {
+                let sample =
+                    (frame_time_ms - self.baseline_ms).max(0.0) / self.faces_last_frame as f32;
+                self.face_cost_ms += (sample - self.face_cost_ms) * Self::SMOOTHING;
+            } else {
+                self.baseline_ms += (frame_time_ms - self.baseline_ms) * Self::SMOOTHING;
+            }
+        }
+        self.faces_last_frame = 0;
+        if !self.pending || total == 0 {
+            return None;
+        }
+        let start = self.cursor;
+        let end = (start + self.faces_per_frame(total)).min(total);
+        self.cursor = end;
+        self.faces_last_frame = end - start;
+        self.frames_this_refresh += 1;
+        if end == total {
+            self.pending = false;
+            self.last_refresh_frames = self.frames_this_refresh;
+        }
+        Some(start..end)
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use wgpu::Device;
+
+use crate::{
+    compute::{self, WorkgroupSize},
+    logging,
+    probe::ReflectionProbes,
+};
+
+/// Full refreshes after which a run gives up on converging.
+pub const MAX_CONVERGENCE_REFRESHES: u32 = 32;
+/// Matches `probe_convergence.wgsl`, a row of invocations per face
+const WORKGROUP_SIZE: WorkgroupSize = WorkgroupSize::new(64, 1, 1);
+
+/// Progress of re-baking the probes until another bounce stops changing them.
+/// Every refresh renders the scene lit by the previous one, adding a bounce.
+#[derive(Debug, Clone)]
+pub struct ConvergenceRun {
+    /// Refreshes measured so far
+    pub refreshes: u32,
+    /// Frames rendered since the run started
+    pub frames: u32,
+    pub started: Instant,
+    /// Wall clock time until convergence
+    pub elapsed_ms: Option<f32>,
+    /// Mean luminance change of the last refresh relative to the mean luminance
+    pub last_change: Option<f32>,
+    pub converged: bool,
+}
+
+impl Default for ConvergenceRun {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConvergenceRun {
+    pub fn new() -> Self {
+        Self {
+            refreshes: 0,
+            frames: 0,
+            started: Instant::now(),
+            elapsed_ms: None,
+            last_change: None,
+            converged: false,
+        }
+    }
+
+    /// Still baking, neither converged nor given up.
+    pub fn is_running(&self) -> bool {
+        self.elapsed_ms.is_none()
+    }
+}
+
+/// Compares the probes before and after a refresh on the GPU, read back without
+/// stalling like the frame statistics.
+pub struct ProbeConvergence {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    /// Probes as they were when the current refresh started
+    previous: wgpu::Texture,
+    sum_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Measured in the frame being recorded, mapped once submitted
+    measured: Cell<bool>,
+    /// A measurement is being read back
+    in_flight: Cell<bool>,
+    mapped: Arc<AtomicBool>,
+    /// Columns summed per face
+    width: u32,
+}
+
+impl ProbeConvergence {
+    pub fn new(device: &Device, probes: &ReflectionProbes) -> Self {
+        let cube_texture = probes.cube_texture();
+        let previous = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reflection Probe Previous Bake"),
+            size: cube_texture.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: cube_texture.format(),
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let width = cube_texture.width();
+        let size = (width * cube_texture.depth_or_array_layers()) as wgpu::BufferAddress
+            * std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress;
+        let sum_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Probe Convergence Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Probe Convergence Readback Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                compute::texture_entry(0, wgpu::TextureViewDimension::D2Array),
+                compute::texture_entry(1, wgpu::TextureViewDimension::D2Array),
+                compute::storage_entry(2, false),
+            ],
+            label: Some("Probe Convergence Bind Group Layout"),
+        });
+        let array_view = |texture: &wgpu::Texture| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            })
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&array_view(cube_texture)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&array_view(&previous)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: sum_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Probe Convergence Bind Group"),
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("probe_convergence.wgsl"));
+        let pipeline = compute::create_pipeline(
+            device,
+            "Probe Convergence",
+            &shader,
+            "cs_main",
+            &[&bind_group_layout],
+        );
+        Self {
+            pipeline,
+            bind_group,
+            previous,
+            sum_buffer,
+            readback_buffer,
+            measured: Cell::new(false),
+            in_flight: Cell::new(false),
+            mapped: Arc::new(AtomicBool::new(false)),
+            width,
+        }
+    }
+
+    /// Remember the probes before a refresh overwrites them.
+    pub fn snapshot(&self, encoder: &mut wgpu::CommandEncoder, probes: &ReflectionProbes) {
+        let cube_texture = probes.cube_texture();
+        encoder.copy_texture_to_texture(
+            cube_texture.as_image_copy(),
+            self.previous.as_image_copy(),
+            cube_texture.size(),
+        );
+    }
+
+    /// Compare the first `face_count` faces with the snapshot, call once a refresh
+    /// completed. Returns false when an earlier measurement is still read back.
+    pub fn measure(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        face_count: usize,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) -> bool {
+        if self.in_flight.get() {
+            return false;
+        }
+        encoder.clear_buffer(&self.sum_buffer, 0, None);
+        {
+            let mut compute_pass =
+                compute::begin_pass(encoder, "probe convergence", timestamp_writes);
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            WORKGROUP_SIZE.dispatch(&mut compute_pass, [self.width, face_count as u32, 1]);
+        }
+        encoder.copy_buffer_to_buffer(
+            &self.sum_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            self.sum_buffer.size(),
+        );
+        self.measured.set(true);
+        self.in_flight.set(true);
+        true
+    }
+
+    /// Start mapping the sums, call once the recorded frame was submitted.
+    pub fn submitted(&self) {
+        if !self.measured.take() {
+            return;
+        }
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(err) => tracing::error!(
+                    target: logging::PROBES,
+                    "failed to map probe convergence: {}",
+                    err
+                ),
+            });
+    }
+
+    /// Relative change of the last measured refresh once its readback finished,
+    /// the device has to be polled for this to make progress.
+    pub fn read(&self) -> Option<f32> {
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        let (change, total) = {
+            let view = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice::<u8, [f32; 2]>(&view)
+                .iter()
+                .fold((0.0, 0.0), |(change, total), [c, t]| {
+                    (change + c, total + t)
+                })
+        };
+        self.readback_buffer.unmap();
+        self.in_flight.set(false);
+        Some(change / f32::max(total, 1e-6))
+    }
+}
@@ -0,0 +1,30 @@
+// Sum the luminance change of every probe texel since the previous bake, one
+// column of one cube face per invocation
+
+@group(0) @binding(0)
+var current: texture_2d_array<f32>;
+@group(0) @binding(1)
+var previous: texture_2d_array<f32>;
+// x: absolute change, y: current luminance
+@group(0) @binding(2)
+var<storage, read_write> sums: array<vec2<f32>>;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(current);
+    if id.x >= size.x || id.y >= textureNumLayers(current) {
+        return;
+    }
+    var sum = vec2<f32>(0.0);
+    for (var y = 0u; y < size.y; y++) {
+        let texel = vec2<u32>(id.x, y);
+        let now = luminance(textureLoad(current, texel, id.y, 0).rgb);
+        let before = luminance(textureLoad(previous, texel, id.y, 0).rgb);
+        sum += vec2<f32>(abs(now - before), now);
+    }
+    sums[id.y * size.x + id.x] = sum;
+}
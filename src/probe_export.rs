@@ -0,0 +1,111 @@
+//! Export of baked reflection probes for use in other engines.
+//!
+//! An export is a JSON descriptor plus a binary file next to it with the same
+//! name and a `.bin` extension. The descriptor looks like
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "binary": "scene.probes.bin",
+//!   "resolution": 128,
+//!   "pixel_format": "rgba16_float",
+//!   "face_order": ["+X", "-X", "+Y", "-Y", "+Z", "-Z"],
+//!   "probes": [
+//!     {
+//!       "position": [0.0, 1.0, 0.0],
+//!       "box_min": [-5.0, -4.0, -5.0],
+//!       "box_max": [5.0, 6.0, 5.0],
+//!       "byte_offset": 0,
+//!       "byte_length": 786432
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Each probe owns six tightly packed `resolution` x `resolution` faces in
+//! `face_order`, rows go from top to bottom. `rgba16_float` pixels are little
+//! endian half floats in linear space, `rg11b10_ufloat` and `rgb9e5_ufloat`
+//! pixels are little endian 32 bit words laid out like the WebGPU formats. Faces follow the usual cubemap
+//! convention, so they can be uploaded as cube layers directly. Positions and
+//! boxes are in scene units, reflections are box projected onto
+//! `box_min`..`box_max`.
+
+use std::{fs, path::Path};
+
+use serde::Serialize;
+
+use crate::probe::ProbeCache;
+
+const EXPORT_VERSION: u32 = 1;
+const FACE_ORDER: [&str; 6] = ["+X", "-X", "+Y", "-Y", "+Z", "-Z"];
+
+#[derive(Serialize)]
+struct ExportedProbe {
+    position: [f32; 3],
+    box_min: [f32; 3],
+    box_max: [f32; 3],
+    byte_offset: usize,
+    byte_length: usize,
+}
+
+#[derive(Serialize)]
+struct ProbeExport {
+    version: u32,
+    binary: String,
+    resolution: u32,
+    pixel_format: &'static str,
+    face_order: [&'static str; 6],
+    probes: Vec<ExportedProbe>,
+}
+
+/// Write `cache` as `path` and its binary companion, `format` is the format the
+/// probes were baked in.
+pub fn export_probes<P: AsRef<Path>>(
+    cache: &ProbeCache,
+    format: wgpu::TextureFormat,
+    path: P,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let (pixel_format, swap_red_blue) = match format {
+        wgpu::TextureFormat::Rgba8UnormSrgb => ("rgba8_srgb", false),
+        wgpu::TextureFormat::Bgra8UnormSrgb => ("rgba8_srgb", true),
+        wgpu::TextureFormat::Rgba8Unorm => ("rgba8_unorm", false),
+        wgpu::TextureFormat::Bgra8Unorm => ("rgba8_unorm", true),
+        wgpu::TextureFormat::Rgba16Float => ("rgba16_float", false),
+        wgpu::TextureFormat::Rg11b10Ufloat => ("rg11b10_ufloat", false),
+        wgpu::TextureFormat::Rgb9e5Ufloat => ("rgb9e5_ufloat", false),
+        format => anyhow::bail!("cannot export probes baked as {:?}", format),
+    };
+    let mut pixels = cache.pixels().to_vec();
+    if swap_red_blue {
+        pixels.chunks_exact_mut(4).for_each(|px| px.swap(0, 2));
+    }
+
+    let probe_length = pixels.len() / cache.probes().len().max(1);
+    let binary_path = path.with_extension("bin");
+    let export = ProbeExport {
+        version: EXPORT_VERSION,
+        binary: binary_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        resolution: cache.resolution(),
+        pixel_format,
+        face_order: FACE_ORDER,
+        probes: cache
+            .probes()
+            .iter()
+            .enumerate()
+            .map(|(i, probe)| ExportedProbe {
+                position: probe.position,
+                box_min: probe.box_min,
+                box_max: probe.box_max,
+                byte_offset: i * probe_length,
+                byte_length: probe_length,
+            })
+            .collect(),
+    };
+    fs::write(&binary_path, &pixels)?;
+    fs::write(path, serde_json::to_string_pretty(&export)?)?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+// Packs a rendered probe face into the texel encoding of the probe storage
+// format, the words are then copied into the cube array. rgb9e5 and rg11b10
+// cannot be rendered to, so they are encoded here.
+
+struct Packing {
+    // 0 rgba16float, 1 rg11b10ufloat, 2 rgb9e5ufloat
+    mode: u32,
+    // Rows are padded to the buffer copy alignment
+    words_per_row: u32,
+    resolution: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0)
+var face: texture_2d<f32>;
+@group(0) @binding(1)
+var<storage, read_write> words: array<u32>;
+@group(0) @binding(2)
+var<uniform> packing: Packing;
+
+// Largest values the unsigned small floats can hold
+const MAX_FLOAT10: f32 = 64512.0;
+const MAX_RGB9E5: f32 = 65408.0;
+
+// The half float bits rounded to the top `bits` of exponent and mantissa
+fn small_float(value: f32, bits: u32) -> u32 {
+    let half = pack2x16float(vec2(value, 0.0)) & 0x7fffu;
+    let dropped = 15u - bits;
+    let rounded = (half + (1u << (dropped - 1u))) >> dropped;
+    return min(rounded, (1u << bits) - 1u);
+}
+
+fn pack_rg11b10(color: vec3<f32>) -> u32 {
+    let c = clamp(color, vec3(0.0), vec3(MAX_FLOAT10));
+    return small_float(c.r, 11u) | (small_float(c.g, 11u) << 11u) | (small_float(c.b, 10u) << 22u);
+}
+
+// See the shared exponent encoding of EXT_texture_shared_exponent
+fn pack_rgb9e5(color: vec3<f32>) -> u32 {
+    let c = clamp(color, vec3(0.0), vec3(MAX_RGB9E5));
+    let max_channel = max(c.r, max(c.g, c.b));
+    var exponent = i32(max(-16.0, floor(log2(max(max_channel, 1e-20))))) + 16;
+    if floor(max_channel / exp2(f32(exponent - 24)) + 0.5) >= 512.0 {
+        exponent += 1;
+    }
+    let mantissa = vec3<u32>(min(floor(c / exp2(f32(exponent - 24)) + 0.5), vec3(511.0)));
+    return mantissa.r | (mantissa.g << 9u) | (mantissa.b << 18u) | (u32(exponent) << 27u);
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= packing.resolution || id.y >= packing.resolution {
+        return;
+    }
+    let color = textureLoad(face, id.xy, 0);
+    let row = id.y * packing.words_per_row;
+    switch packing.mode {
+        case 0u: {
+            words[row + id.x * 2u] = pack2x16float(color.rg);
+            words[row + id.x * 2u + 1u] = pack2x16float(color.ba);
+        }
+        case 1u: {
+            words[row + id.x] = pack_rg11b10(color.rgb);
+        }
+        default: {
+            words[row + id.x] = pack_rgb9e5(color.rgb);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Roughness mip chain of the reflection probe cube array.
+//!
+//! Once the six faces of a probe are baked, `probe_prefilter.wgsl` renders
+//! every further mip level from the one above it, blurred by a GGX lobe of
+//! growing roughness. The shading samples the level matching the roughness of
+//! the surface. Filtering the level above keeps the sample count low, the
+//! lobe added per level is narrowed so the blurs sum up to the roughness of
+//! the level. Probes stored in a format other than the rendered one cannot be
+//! rendered to and keep a single sharp level.
+
+use wgpu::{util::DeviceExt, Device};
+
+/// Mip levels of a prefiltered cube array, the last one spans the roughest
+/// reflections
+const LEVELS: u32 = 5;
+// Each face and level lives in its own slice of one buffer
+const UNIFORM_STRIDE: u64 = 256;
+const FACES: u32 = 6;
+
+/// Mip levels of a cube array at `resolution`, one unless it is prefiltered.
+pub fn mip_level_count(resolution: u32, prefiltered: bool) -> u32 {
+    if prefiltered {
+        LEVELS.min(resolution.ilog2() + 1)
+    } else {
+        1
+    }
+}
+
+/// Matches `Prefilter` in `probe_prefilter.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct UniformPrefilter {
+    face: u32,
+    alpha: f32,
+    _padding: [u32; 2],
+}
+
+/// GGX alpha of mip `level`, the perceptual roughness grows linearly over the levels.
+fn level_alpha(level: u32, levels: u32) -> f32 {
+    let roughness = level as f32 / (levels - 1).max(1) as f32;
+    roughness * roughness
+}
+
+/// Renders the mip levels of the probes in one cube array.
+pub struct ProbePrefilter {
+    pipeline: wgpu::RenderPipeline,
+    /// Level above each rendered level as a cube, per probe and level
+    source_bind_groups: Vec<wgpu::BindGroup>,
+    /// Face and lobe, per level and face
+    prefilter_bind_groups: Vec<wgpu::BindGroup>,
+    /// One face of one level, per probe, face and level
+    target_views: Vec<wgpu::TextureView>,
+    levels: u32,
+}
+
+impl ProbePrefilter {
+    /// `cube_texture` needs `mip_level_count` levels and to be renderable.
+    pub fn new(device: &Device, cube_texture: &wgpu::Texture) -> Self {
+        let levels = cube_texture.mip_level_count();
+        let format = cube_texture.format();
+        let probes = cube_texture.depth_or_array_layers() / FACES;
+        // Level zero is the bake itself
+        let filtered = levels - 1;
+        let uniforms = (1..levels)
+            .flat_map(|level| {
+                let alpha = level_alpha(level, levels);
+                let previous = level_alpha(level - 1, levels);
+                // GGX lobes widen roughly like their squared alphas add up
+                let alpha = (alpha * alpha - previous * previous).max(1e-6).sqrt();
+                (0..FACES).map(move |face| UniformPrefilter {
+                    face,
+                    alpha,
+                    _padding: [0; 2],
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut contents = vec![0; uniforms.len() * UNIFORM_STRIDE as usize];
+        for (chunk, uniform) in contents
+            .chunks_exact_mut(UNIFORM_STRIDE as usize)
+            .zip(&uniforms)
+        {
+            chunk[..std::mem::size_of::<UniformPrefilter>()]
+                .copy_from_slice(bytemuck::bytes_of(uniform));
+        }
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reflection Probe Prefilter Buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let source_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("Reflection Probe Prefilter Source Bind Group Layout"),
+        });
+        let prefilter_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Reflection Probe Prefilter Bind Group Layout"),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let source_bind_groups = (0..probes)
+            .flat_map(|probe| (0..filtered).map(move |level| (probe, level)))
+            .map(|(probe, level)| {
+                let view = cube_texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Reflection Probe Prefilter Source View"),
+                    dimension: Some(wgpu::TextureViewDimension::Cube),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    base_array_layer: probe * FACES,
+                    array_layer_count: Some(FACES),
+                    ..Default::default()
+                });
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &source_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: Some("Reflection Probe Prefilter Source Bind Group"),
+                })
+            })
+            .collect();
+        let prefilter_bind_groups = (0..uniforms.len() as u64)
+            .map(|i| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &prefilter_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &uniform_buffer,
+                            offset: i * UNIFORM_STRIDE,
+                            size: wgpu::BufferSize::new(
+                                std::mem::size_of::<UniformPrefilter>() as u64
+                            ),
+                        }),
+                    }],
+                    label: Some("Reflection Probe Prefilter Bind Group"),
+                })
+            })
+            .collect();
+        let target_views = (0..probes * FACES)
+            .flat_map(|layer| (1..levels).map(move |level| (layer, level)))
+            .map(|(layer, level)| {
+                cube_texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Reflection Probe Prefilter Target View"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("probe_prefilter.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Reflection Probe Prefilter Pipeline Layout"),
+            bind_group_layouts: &[&source_layout, &prefilter_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Reflection Probe Prefilter Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            pipeline,
+            source_bind_groups,
+            prefilter_bind_groups,
+            target_views,
+            levels,
+        }
+    }
+
+    /// Render the levels below the first of `probe`, whose faces are baked.
+    pub fn prefilter(&self, encoder: &mut wgpu::CommandEncoder, probe: u32) {
+        let filtered = self.levels - 1;
+        for level in 1..self.levels {
+            for face in 0..FACES {
+                let target = ((probe * FACES + face) * filtered + level - 1) as usize;
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Render Pass: reflection probe prefilter"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &self.target_views[target],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                render_pass.set_pipeline(&self.pipeline);
+                render_pass.set_bind_group(
+                    0,
+                    &self.source_bind_groups[(probe * filtered + level - 1) as usize],
+                    &[],
+                );
+                render_pass.set_bind_group(
+                    1,
+                    &self.prefilter_bind_groups[((level - 1) * FACES + face) as usize],
+                    &[],
+                );
+                render_pass.draw(0..3, 0..1);
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// Prefilters a baked probe for rough reflections: every mip level below the
+// first holds the level above it convolved with a GGX lobe, so that the
+// shading can pick the level matching the roughness of the surface.
+
+struct Prefilter {
+    // Cube face rendered, in +X, -X, +Y, -Y, +Z, -Z order
+    face: u32,
+    // GGX alpha of the lobe added on top of the blur of the level above
+    alpha: f32,
+    _padding: vec2<u32>,
+}
+
+@group(0) @binding(0)
+var source: texture_cube<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(1) @binding(0)
+var<uniform> prefilter: Prefilter;
+
+const SAMPLE_COUNT: u32 = 32u;
+const PI: f32 = 3.14159265;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// One triangle covering the whole face
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Direction through `uv` of the face, after the cubemap sampling convention
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let s = uv.x * 2.0 - 1.0;
+    let t = uv.y * 2.0 - 1.0;
+    switch face {
+        case 0u: { return vec3<f32>(1.0, -t, -s); }
+        case 1u: { return vec3<f32>(-1.0, -t, s); }
+        case 2u: { return vec3<f32>(s, 1.0, t); }
+        case 3u: { return vec3<f32>(s, -1.0, -t); }
+        case 4u: { return vec3<f32>(s, -t, 1.0); }
+        default: { return vec3<f32>(-s, -t, -1.0); }
+    }
+}
+
+fn hammersley(i: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(SAMPLE_COUNT), f32(reverseBits(i)) * 2.3283064e-10);
+}
+
+// Half vector around +Z distributed like the GGX normal distribution
+fn importance_sample_ggx(xi: vec2<f32>, alpha: f32) -> vec3<f32> {
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+// The view is assumed to look along the normal, which loses the stretched
+// highlights at grazing angles but needs one lookup per level
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(face_direction(prefilter.face, in.uv));
+    let up = select(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(1.0, 0.0, 0.0), abs(normal.z) > 0.999);
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    var radiance = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let h = importance_sample_ggx(hammersley(i), prefilter.alpha);
+        let half_dir = tangent * h.x + bitangent * h.y + normal * h.z;
+        let light_dir = 2.0 * dot(normal, half_dir) * half_dir - normal;
+        let n_dot_l = dot(normal, light_dir);
+        if n_dot_l > 0.0 {
+            radiance += textureSampleLevel(source, source_sampler, light_dir, 0.0).xyz * n_dot_l;
+            total_weight += n_dot_l;
+        }
+    }
+    return vec4<f32>(radiance / max(total_weight, 1e-6), 1.0);
+}
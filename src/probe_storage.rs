@@ -0,0 +1,332 @@
+//! Storage formats of the reflection probe cube array.
+//!
+//! Faces are rendered in the reflection probe format of the render settings
+//! and stored as is, unless another storage format is picked at runtime. Faces
+//! are then encoded by `probe_pack.wgsl` and copied into the cube array, which
+//! also works for formats that cannot be rendered to such as rgb9e5.
+
+use image::{Rgb, RgbImage};
+use wgpu::{util::DeviceExt, Device};
+
+use crate::{
+    compute::{self, WorkgroupSize},
+    probe::ProbeCache,
+};
+
+/// Matches `probe_pack.wgsl`, one invocation per texel of a face
+const WORKGROUP_SIZE: WorkgroupSize = WorkgroupSize::new(8, 8, 1);
+
+/// Storage formats offered at runtime, all of them can be sampled with filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeStorage {
+    Rgba16Float,
+    Rg11b10Float,
+    Rgb9e5,
+}
+
+impl ProbeStorage {
+    pub const ALL: [Self; 3] = [Self::Rgba16Float, Self::Rg11b10Float, Self::Rgb9e5];
+
+    pub fn format(self) -> wgpu::TextureFormat {
+        match self {
+            Self::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+            Self::Rg11b10Float => wgpu::TextureFormat::Rg11b10Ufloat,
+            Self::Rgb9e5 => wgpu::TextureFormat::Rgb9e5Ufloat,
+        }
+    }
+
+    /// WebGPU name of the format
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rgba16Float => "rgba16float",
+            Self::Rg11b10Float => "rg11b10ufloat",
+            Self::Rgb9e5 => "rgb9e5ufloat",
+        }
+    }
+
+    fn mode(format: wgpu::TextureFormat) -> Option<u32> {
+        Self::ALL
+            .iter()
+            .position(|storage| storage.format() == format)
+            .map(|i| i as u32)
+    }
+}
+
+/// Mebibytes of a cube array of `layers` faces in `format`.
+pub fn cube_array_mib(format: wgpu::TextureFormat, resolution: u32, layers: u32) -> f32 {
+    let bytes_per_pixel = format.block_copy_size(None).unwrap_or(4);
+    (resolution * resolution * layers * bytes_per_pixel) as f32 / (1024.0 * 1024.0)
+}
+
+/// Matches `Packing` in `probe_pack.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct UniformPacking {
+    mode: u32,
+    words_per_row: u32,
+    resolution: u32,
+    _padding: u32,
+}
+
+/// Encodes rendered faces into a storage format other than the one they are
+/// rendered in.
+pub struct ProbePacker {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    buffer: wgpu::Buffer,
+    padded_row: u32,
+    resolution: u32,
+}
+
+impl ProbePacker {
+    pub fn new(
+        device: &Device,
+        face_view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        resolution: u32,
+    ) -> anyhow::Result<Self> {
+        let Some(mode) = ProbeStorage::mode(format) else {
+            anyhow::bail!("cannot store reflection probes as {:?}", format);
+        };
+        let bytes_per_pixel = format.block_copy_size(None).unwrap_or(4);
+        // Rows of a buffer copy have to be aligned
+        let padded_row =
+            (resolution * bytes_per_pixel).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reflection Probe Pack Buffer"),
+            size: (padded_row * resolution) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reflection Probe Pack Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[UniformPacking {
+                mode,
+                words_per_row: padded_row / 4,
+                resolution,
+                _padding: 0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                compute::texture_entry(0, wgpu::TextureViewDimension::D2),
+                compute::storage_entry(1, false),
+                compute::uniform_entry(2),
+            ],
+            label: Some("Reflection Probe Pack Bind Group Layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(face_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Reflection Probe Pack Bind Group"),
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("probe_pack.wgsl"));
+        let pipeline = compute::create_pipeline(
+            device,
+            "Reflection Probe Pack",
+            &shader,
+            "cs_main",
+            &[&bind_group_layout],
+        );
+        Ok(Self {
+            pipeline,
+            bind_group,
+            buffer,
+            padded_row,
+            resolution,
+        })
+    }
+
+    /// Encode the rendered face into `layer` of `cube_texture`.
+    pub fn pack(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        cube_texture: &wgpu::Texture,
+        layer: u32,
+    ) {
+        {
+            let mut compute_pass = compute::begin_pass(encoder, "reflection probe pack", None);
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            WORKGROUP_SIZE.dispatch(&mut compute_pass, [self.resolution, self.resolution, 1]);
+        }
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_row),
+                    rows_per_image: Some(self.resolution),
+                },
+            },
+            wgpu::ImageCopyTexture {
+                texture: cube_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width: self.resolution,
+                height: self.resolution,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// One storage format of a comparison capture.
+#[derive(Debug, Clone)]
+pub struct StorageComparison {
+    pub format: wgpu::TextureFormat,
+    /// Size of the whole cube array
+    pub mib: f32,
+    /// Against the faces as rendered, infinite when lossless and missing for
+    /// the reference itself
+    pub psnr_db: Option<f32>,
+}
+
+fn half_to_f32(bits: u16) -> f32 {
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f32;
+    let magnitude = match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f => f32::INFINITY,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent as i32 - 15),
+    };
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Unsigned float with a 5 bit exponent and `mantissa_bits` mantissa bits.
+fn small_float_to_f32(bits: u32, mantissa_bits: u32) -> f32 {
+    let exponent = bits >> mantissa_bits;
+    let mantissa = (bits & ((1 << mantissa_bits) - 1)) as f32 / (1 << mantissa_bits) as f32;
+    match exponent {
+        0 => mantissa * 2f32.powi(-14),
+        0x1f => f32::INFINITY,
+        _ => (1.0 + mantissa) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+/// RGB of tightly packed pixels in `format`.
+pub fn decode(format: wgpu::TextureFormat, pixels: &[u8]) -> anyhow::Result<Vec<[f32; 3]>> {
+    let word = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    Ok(match format {
+        wgpu::TextureFormat::Rgba32Float => pixels
+            .chunks_exact(16)
+            .map(|px| [word(&px[0..4]), word(&px[4..8]), word(&px[8..12])].map(f32::from_bits))
+            .collect(),
+        wgpu::TextureFormat::Rgba16Float => pixels
+            .chunks_exact(8)
+            .map(|px| [0, 2, 4].map(|i| half_to_f32(u16::from_le_bytes([px[i], px[i + 1]]))))
+            .collect(),
+        wgpu::TextureFormat::Rg11b10Ufloat => pixels
+            .chunks_exact(4)
+            .map(|px| {
+                let bits = word(px);
+                [
+                    small_float_to_f32(bits & 0x7ff, 6),
+                    small_float_to_f32((bits >> 11) & 0x7ff, 6),
+                    small_float_to_f32(bits >> 22, 5),
+                ]
+            })
+            .collect(),
+        wgpu::TextureFormat::Rgb9e5Ufloat => pixels
+            .chunks_exact(4)
+            .map(|px| {
+                let bits = word(px);
+                let scale = 2f32.powi((bits >> 27) as i32 - 24);
+                [0, 9, 18].map(|shift| ((bits >> shift) & 0x1ff) as f32 * scale)
+            })
+            .collect(),
+        format => anyhow::bail!("cannot read {:?} pixels", format),
+    })
+}
+
+/// Reinhard tone mapped, so the error of bright texels does not dominate.
+fn tonemap(color: [f32; 3]) -> [f32; 3] {
+    color.map(|c| {
+        let c = c.max(0.0);
+        if c.is_finite() {
+            c / (1.0 + c)
+        } else {
+            1.0
+        }
+    })
+}
+
+/// Compare bakes of the same probes in several formats against the first one
+/// and lay out the first probe of each bake in a capture. Rows show the six
+/// faces on the left and the difference to the reference, amplified 16 times,
+/// on the right.
+pub fn compare(
+    bakes: &[(wgpu::TextureFormat, ProbeCache)],
+    layers: u32,
+) -> anyhow::Result<(Vec<StorageComparison>, RgbImage)> {
+    let Some((_, reference)) = bakes.first() else {
+        anyhow::bail!("nothing to compare");
+    };
+    let resolution = reference.resolution();
+    let decoded = bakes
+        .iter()
+        .map(|(format, cache)| {
+            decode(*format, cache.pixels()).map(|pixels| pixels.into_iter().map(tonemap).collect())
+        })
+        .collect::<anyhow::Result<Vec<Vec<_>>>>()?;
+    let face_pixels = (resolution * resolution) as usize;
+    let mut capture = RgbImage::new(resolution * 12, resolution * bakes.len() as u32);
+    let to_byte = |value: f32| (value.powf(1.0 / 2.2).clamp(0.0, 1.0) * 255.0).round() as u8;
+    let comparisons = bakes
+        .iter()
+        .zip(&decoded)
+        .enumerate()
+        .map(|(row, ((format, _), pixels))| {
+            let error = pixels
+                .iter()
+                .zip(&decoded[0])
+                .map(|(a, b)| (0..3).map(|c| (a[c] - b[c]).powi(2)).sum::<f32>())
+                .sum::<f32>()
+                / (pixels.len() * 3).max(1) as f32;
+            for (i, (color, reference)) in pixels
+                .iter()
+                .zip(&decoded[0])
+                .take(6 * face_pixels)
+                .enumerate()
+            {
+                let face = (i / face_pixels) as u32;
+                let x = face * resolution + (i % face_pixels) as u32 % resolution;
+                let y = row as u32 * resolution + (i % face_pixels) as u32 / resolution;
+                capture.put_pixel(x, y, Rgb(color.map(to_byte)));
+                let difference = [0, 1, 2].map(|c| (color[c] - reference[c]).abs() * 16.0);
+                capture.put_pixel(x + 6 * resolution, y, Rgb(difference.map(to_byte)));
+            }
+            StorageComparison {
+                format: *format,
+                mib: cube_array_mib(*format, resolution, layers),
+                psnr_db: (row > 0).then(|| -10.0 * error.log10()),
+            }
+        })
+        .collect();
+    Ok((comparisons, capture))
+}
@@ -0,0 +1,320 @@
+//! A small render graph ordering the passes of a frame by the resources they
+//! hand to each other.
+//!
+//! Passes are declared once with the resources they read and write. Resources
+//! are either imported, owned by someone else such as the surface or the
+//! reflection probes, or textures the graph allocates itself from a
+//! description. Writes version a resource in declaration order, a pass reads
+//! the version written by the closest pass declared before it. Passes whose
+//! writes nothing reads are culled, unless they write an imported resource.
+//!
+//! The graph only decides what runs and in which order. Recording a pass is up
+//! to its owner, which matches on the pass values `RenderGraph::passes` yields.
+
+use std::collections::HashSet;
+
+use wgpu::Device;
+
+use crate::{logging, texture, texture_pool::TexturePool};
+
+/// Handle of a resource declared in a `RenderGraph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+/// Texture the graph allocates, sized relative to the extent given to
+/// `RenderGraph::allocate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDesc {
+    pub label: &'static str,
+    /// Depth formats are allocated as `texture::Texture::DEPTH_FORMAT`
+    pub format: wgpu::TextureFormat,
+    /// Size relative to the allocated extent
+    pub scale: f32,
+}
+
+impl TextureDesc {
+    fn extent(&self, extent: (u32, u32)) -> (u32, u32) {
+        texture::scaled_extent(extent, self.scale)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GraphError {
+    #[error("pass {pass} reads the texture {texture} before any pass writes it")]
+    ReadBeforeWrite {
+        pass: &'static str,
+        texture: &'static str,
+    },
+    #[error("pass {0} is declared twice")]
+    DuplicatePass(&'static str),
+}
+
+enum Resource {
+    Imported(&'static str),
+    Texture(TextureDesc),
+}
+
+impl Resource {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Imported(name) => name,
+            Self::Texture(desc) => desc.label,
+        }
+    }
+}
+
+struct PassNode<P> {
+    name: &'static str,
+    pass: P,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+}
+
+/// Passes of a frame and the resources between them, see the module docs.
+pub struct RenderGraph<P> {
+    resources: Vec<Resource>,
+    passes: Vec<PassNode<P>>,
+    /// Indices of the passes that run, in execution order
+    order: Vec<usize>,
+    /// Allocated textures, by resource index
+    textures: Vec<Option<texture::Texture>>,
+    extent: (u32, u32),
+}
+
+impl<P> Default for RenderGraph<P> {
+    fn default() -> Self {
+        Self {
+            resources: Vec::new(),
+            passes: Vec::new(),
+            order: Vec::new(),
+            textures: Vec::new(),
+            extent: (0, 0),
+        }
+    }
+}
+
+impl<P: Copy> RenderGraph<P> {
+    /// Declare a resource owned outside the graph.
+    pub fn import(&mut self, name: &'static str) -> ResourceId {
+        self.resources.push(Resource::Imported(name));
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Declare a texture the graph allocates.
+    pub fn texture(&mut self, desc: TextureDesc) -> ResourceId {
+        self.resources.push(Resource::Texture(desc));
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Declare a pass after every pass declared so far.
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        pass: P,
+        reads: &[ResourceId],
+        writes: &[ResourceId],
+    ) {
+        self.passes.push(PassNode {
+            name,
+            pass,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        });
+    }
+
+    /// Check the declarations and work out which passes run in which order.
+    pub fn compile(&mut self) -> Result<(), GraphError> {
+        let mut names = HashSet::new();
+        let mut written = HashSet::new();
+        for node in &self.passes {
+            if !names.insert(node.name) {
+                return Err(GraphError::DuplicatePass(node.name));
+            }
+            for &read in &node.reads {
+                let resource = &self.resources[read.0];
+                if matches!(resource, Resource::Texture(_)) && !written.contains(&read) {
+                    return Err(GraphError::ReadBeforeWrite {
+                        pass: node.name,
+                        texture: resource.name(),
+                    });
+                }
+            }
+            written.extend(node.writes.iter().copied());
+        }
+        // Walk back from the imported resources, which are always consumed. A
+        // live pass consumes what it reads and replaces what it writes.
+        let mut consumed = HashSet::new();
+        let mut live = vec![false; self.passes.len()];
+        for (i, node) in self.passes.iter().enumerate().rev() {
+            live[i] = node.writes.iter().any(|write| {
+                matches!(self.resources[write.0], Resource::Imported(_)) || consumed.contains(write)
+            });
+            if live[i] {
+                for write in &node.writes {
+                    consumed.remove(write);
+                }
+                consumed.extend(node.reads.iter().copied());
+            }
+        }
+        // Every read refers to a pass declared earlier, the declaration order
+        // already satisfies the dependencies
+        self.order = (0..self.passes.len()).filter(|&i| live[i]).collect();
+        self.textures.resize_with(self.resources.len(), || None);
+        Ok(())
+    }
+
+    /// Allocate the textures for `extent` from `pool`, again whenever their
+    /// size changes. The textures of the old size go back to `pool`.
+    pub fn allocate(&mut self, device: &Device, pool: &mut TexturePool, extent: (u32, u32)) {
+        if self.extent == extent {
+            return;
+        }
+        self.extent = extent;
+        for (resource, texture) in self.resources.iter().zip(&mut self.textures) {
+            if let Some(old) = texture.take() {
+                pool.release(old);
+            }
+            let Resource::Texture(desc) = resource else {
+                continue;
+            };
+            let size = desc.extent(extent);
+            let _span =
+                tracing::error_span!(target: logging::RENDER, "texture", label = desc.label)
+                    .entered();
+            *texture = Some(logging::validated(device, || {
+                pool.acquire(device, size, desc.format, desc.label)
+            }));
+        }
+    }
+
+    /// Texture allocated for `id`, which has to be declared with
+    /// `RenderGraph::texture` and allocated.
+    pub fn get(&self, id: ResourceId) -> &texture::Texture {
+        self.textures[id.0]
+            .as_ref()
+            .expect("render graph texture used before it was allocated")
+    }
+
+    /// The passes that run and their names, in execution order.
+    pub fn passes(&self) -> impl Iterator<Item = (&'static str, P)> + '_ {
+        self.order
+            .iter()
+            .map(|&i| (self.passes[i].name, self.passes[i].pass))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc(label: &'static str) -> TextureDesc {
+        TextureDesc {
+            label,
+            format: wgpu::TextureFormat::Rgba16Float,
+            scale: 1.0,
+        }
+    }
+
+    fn names(graph: &RenderGraph<u32>) -> Vec<&'static str> {
+        graph.passes().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn passes_run_in_declaration_order() {
+        let mut graph = RenderGraph::default();
+        let surface = graph.import("surface");
+        let color = graph.texture(desc("color"));
+        let bloom = graph.texture(desc("bloom"));
+        graph.add_pass("scene", 0, &[], &[color]);
+        graph.add_pass("bloom", 1, &[color], &[bloom]);
+        graph.add_pass("tonemap", 2, &[color, bloom], &[surface]);
+        graph.compile().unwrap();
+        assert_eq!(names(&graph), ["scene", "bloom", "tonemap"]);
+        assert_eq!(
+            graph.passes().map(|(_, pass)| pass).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn unread_passes_are_culled() {
+        let mut graph = RenderGraph::default();
+        let surface = graph.import("surface");
+        let color = graph.texture(desc("color"));
+        let unused = graph.texture(desc("unused"));
+        graph.add_pass("scene", 0, &[], &[color]);
+        graph.add_pass("debug", 1, &[color], &[unused]);
+        graph.add_pass("present", 2, &[color], &[surface]);
+        graph.compile().unwrap();
+        assert_eq!(names(&graph), ["scene", "present"]);
+    }
+
+    #[test]
+    fn overwritten_textures_cull_the_earlier_writer() {
+        let mut graph = RenderGraph::default();
+        let surface = graph.import("surface");
+        let color = graph.texture(desc("color"));
+        graph.add_pass("clear", 0, &[], &[color]);
+        graph.add_pass("scene", 1, &[], &[color]);
+        graph.add_pass("present", 2, &[color], &[surface]);
+        graph.compile().unwrap();
+        assert_eq!(names(&graph), ["scene", "present"]);
+    }
+
+    #[test]
+    fn imported_writes_are_kept() {
+        let mut graph = RenderGraph::default();
+        let probes = graph.import("probes");
+        graph.add_pass("bake", 0, &[probes], &[probes]);
+        graph.compile().unwrap();
+        assert_eq!(names(&graph), ["bake"]);
+    }
+
+    #[test]
+    fn reading_before_writing_is_an_error() {
+        let mut graph = RenderGraph::default();
+        let surface = graph.import("surface");
+        let color = graph.texture(desc("color"));
+        graph.add_pass("present", 0, &[color], &[surface]);
+        graph.add_pass("scene", 1, &[], &[color]);
+        assert!(matches!(
+            graph.compile(),
+            Err(GraphError::ReadBeforeWrite {
+                pass: "present",
+                texture: "color"
+            })
+        ));
+    }
+
+    #[test]
+    fn reading_imports_needs_no_writer() {
+        let mut graph = RenderGraph::default();
+        let probes = graph.import("probes");
+        let surface = graph.import("surface");
+        graph.add_pass("scene", 0, &[probes], &[surface]);
+        graph.compile().unwrap();
+        assert_eq!(names(&graph), ["scene"]);
+    }
+
+    #[test]
+    fn duplicate_passes_are_an_error() {
+        let mut graph = RenderGraph::default();
+        let surface = graph.import("surface");
+        graph.add_pass("scene", 0, &[], &[surface]);
+        graph.add_pass("scene", 1, &[], &[surface]);
+        assert!(matches!(
+            graph.compile(),
+            Err(GraphError::DuplicatePass("scene"))
+        ));
+    }
+
+    #[test]
+    fn textures_scale_with_the_extent() {
+        let half = TextureDesc {
+            scale: 0.5,
+            ..desc("half")
+        };
+        assert_eq!(half.extent((1920, 1081)), (960, 541));
+        assert_eq!(half.extent((1, 1)), (1, 1));
+    }
+}
@@ -0,0 +1,237 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dock::DockLayout, logging, primitives::resource_path, probe::DEFAULT_PROBE_RESOLUTION, schema,
+    texture,
+};
+
+/// Overrides for one pass, formats use their WebGPU names such as `rgba16float`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PassSettings {
+    pub format: Option<wgpu::TextureFormat>,
+    /// Resolution relative to the pass default
+    pub scale: Option<f32>,
+}
+
+/// Advanced per pass settings read from `resources/render_settings.json`, e.g.
+/// `{ "version": 1, "scene": { "scale": 0.5 }, "reflection_probes": { "format":
+/// "rg11b10ufloat" } }`. See `schema::RENDER_SETTINGS` for the versions.
+///
+/// The viewer also writes the arrangement of its panels here when it closes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    /// Color target of the main and transparent passes, scaled relative to the window
+    pub scene: PassSettings,
+    /// Probe faces, scaled relative to `DEFAULT_PROBE_RESOLUTION`
+    pub reflection_probes: PassSettings,
+    /// Docked panels of the viewer, the default layout when left out
+    pub layout: Option<DockLayout>,
+}
+
+/// Format and resolution scale a pass renders with once the overrides are applied.
+#[derive(Debug, Clone, Copy)]
+pub struct PassTarget {
+    pub format: wgpu::TextureFormat,
+    pub scale: f32,
+}
+
+impl PassTarget {
+    /// Size of the target for a pass whose default size is `width` x `height`.
+    pub fn extent(&self, width: u32, height: u32) -> (u32, u32) {
+        texture::scaled_extent((width, height), self.scale)
+    }
+}
+
+/// Filter stretching the scene color over the window when it renders at another
+/// resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Upsampling {
+    /// The closest texel, shows the rendered pixels as they are
+    Nearest,
+    #[default]
+    Bilinear,
+    /// Bilinear followed by contrast adaptive sharpening, after the RCAS pass of
+    /// FidelityFX Super Resolution 1
+    Sharpen,
+}
+
+impl Upsampling {
+    pub const ALL: [Self; 3] = [Self::Nearest, Self::Bilinear, Self::Sharpen];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Nearest => "Nearest",
+            Self::Bilinear => "Bilinear",
+            Self::Sharpen => "Sharpen",
+        }
+    }
+}
+
+/// Resolution the scene renders at while running, starts at the `scene.scale`
+/// of the settings file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderScale {
+    /// Scene color size relative to the window
+    pub scale: f32,
+    pub upsampling: Upsampling,
+    /// From 0 to 1, for `Upsampling::Sharpen`
+    pub sharpness: f32,
+}
+
+impl RenderScale {
+    /// Range the inspector panel offers, up to the largest scale the
+    /// settings file accepts for the scene
+    pub const RANGE: std::ops::RangeInclusive<f32> = 0.25..=2.0;
+}
+
+impl Default for RenderScale {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            upsampling: Upsampling::default(),
+            sharpness: 0.5,
+        }
+    }
+}
+
+/// Validated targets of every configurable pass.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderTargets {
+    pub scene: PassTarget,
+    pub reflection_probes: PassTarget,
+}
+
+impl Default for RenderTargets {
+    fn default() -> Self {
+        Self {
+            scene: PassTarget {
+                format: texture::Texture::HDR_FORMAT,
+                scale: 1.0,
+            },
+            reflection_probes: PassTarget {
+                format: texture::Texture::HDR_FORMAT,
+                scale: 1.0,
+            },
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn path() -> PathBuf {
+        resource_path("render_settings.json")
+    }
+
+    /// Read the settings file, a missing file means no overrides.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        schema::RENDER_SETTINGS.load(&path)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        schema::RENDER_SETTINGS.save(&Self::path(), self)
+    }
+
+    /// Check the overrides against what `adapter` supports. Invalid passes are
+    /// reported and fall back to their defaults.
+    pub fn resolve(&self, adapter: &wgpu::Adapter) -> RenderTargets {
+        let defaults = RenderTargets::default();
+        // Both passes draw with alpha blending and are read as float textures
+        let usages = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let scene = resolve_pass(
+            adapter,
+            "scene",
+            &self.scene,
+            defaults.scene,
+            2.0,
+            usages,
+            wgpu::TextureFormatFeatureFlags::BLENDABLE,
+        );
+        // Probes are copied into a cube array and sampled with filtering
+        let reflection_probes = resolve_pass(
+            adapter,
+            "reflection_probes",
+            &self.reflection_probes,
+            defaults.reflection_probes,
+            4.0,
+            usages | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+            wgpu::TextureFormatFeatureFlags::BLENDABLE
+                | wgpu::TextureFormatFeatureFlags::FILTERABLE,
+        );
+        let targets = RenderTargets {
+            scene,
+            reflection_probes,
+        };
+        if targets.reflection_probes.scale != 1.0 {
+            let (resolution, _) = targets
+                .reflection_probes
+                .extent(DEFAULT_PROBE_RESOLUTION, DEFAULT_PROBE_RESOLUTION);
+            tracing::info!(
+                target: logging::RENDER,
+                "reflection probes bake at {}px",
+                resolution
+            );
+        }
+        targets
+    }
+}
+
+fn resolve_pass(
+    adapter: &wgpu::Adapter,
+    name: &str,
+    settings: &PassSettings,
+    default: PassTarget,
+    max_scale: f32,
+    usages: wgpu::TextureUsages,
+    flags: wgpu::TextureFormatFeatureFlags,
+) -> PassTarget {
+    let target = PassTarget {
+        format: settings.format.unwrap_or(default.format),
+        scale: settings.scale.unwrap_or(default.scale),
+    };
+    let features = adapter.get_texture_format_features(target.format);
+    let error = if !(target.scale > 0.0 && target.scale <= max_scale) {
+        Some(format!(
+            "scale {} is outside of (0, {}]",
+            target.scale, max_scale
+        ))
+    } else if !matches!(
+        target.format.sample_type(None, None),
+        Some(wgpu::TextureSampleType::Float { .. })
+    ) {
+        Some(format!("{:?} is not a float color format", target.format))
+    } else if !features.allowed_usages.contains(usages) {
+        Some(format!(
+            "{:?} does not support {:?} on this adapter",
+            target.format,
+            usages - features.allowed_usages
+        ))
+    } else if !features.flags.contains(flags) {
+        Some(format!(
+            "{:?} is not {:?} on this adapter",
+            target.format,
+            flags - features.flags
+        ))
+    } else {
+        None
+    };
+    match error {
+        Some(error) => {
+            tracing::error!(
+                target: logging::RENDER,
+                "{}: ignoring the {} overrides, {}",
+                RenderSettings::path().display(),
+                name,
+                error
+            );
+            default
+        }
+        None => target,
+    }
+}
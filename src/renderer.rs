@@ -1,167 +1,865 @@
-use glam::{Vec2, Vec3};
-use itertools::{EitherOrBoth, Itertools};
+use std::{
+    cell::{Cell, OnceCell},
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::Path,
+    rc::Rc,
+    sync::{mpsc, Arc},
+    time::SystemTime,
+};
+
+use glam::{Mat4, Vec2, Vec3};
 use wgpu::{util::DeviceExt, Device, Queue, RenderPipeline, SurfaceConfiguration, TextureView};
 
 use crate::{
-    camera::UniformCamera,
-    primitives::{self, Material, ObjScene, Scene, UniformMaterial},
-    texture, AppState, RenderStage,
+    animation::SceneGraph,
+    benchmark::Benchmark,
+    camera::{ProjectionMode, UniformCamera},
+    color_lut::CubeLut,
+    culling::{GpuCulling, UniformBounds},
+    debug_draw::DebugDrawOverlay,
+    error,
+    frame_stats::{FrameStatistics, StatsStage},
+    gpu_timer::{PassProfiler, PassTimer},
+    ground_grid::GroundGridOverlay,
+    heatmap::{self, HeatDraw, HeatGeometry, Heatmap, HeatmapPass},
+    loader::{LoadedScene, MeshData},
+    logging,
+    matcap::MatcapTexture,
+    object_ids::{self, HoverReadback},
+    panorama::{self, CubeCapture},
+    picking::{self, PickedTriangle},
+    planar_reflection::{mirror_plane, PlanarReflection},
+    post_process::{PostOutput, PostStack},
+    primitives::{
+        self, AlphaMode, ChannelView, CullMode, UniformMaterial, UniformMaterialOverride,
+        UniformObject, Vertex,
+    },
+    probe::DEFAULT_PROBE_RESOLUTION,
+    probe::{self, ProbeCache, ReflectionProbes, UniformProbes, MAX_REFLECTION_PROBES},
+    probe_convergence::{ConvergenceRun, ProbeConvergence, MAX_CONVERGENCE_REFRESHES},
+    probe_export,
+    probe_storage::{self, ProbeStorage},
+    render_graph::{RenderGraph, ResourceId, TextureDesc},
+    render_settings::RenderTargets,
+    scene_pipelines::{MaterialPermutation, ScenePipelineCache, ScenePipelines},
+    scene_world::{self, Geom, GeomBundle, SceneWorld},
+    shader_preprocessor::{ShaderLibrary, SHADER_DIRECTORY},
+    sky::{SkyBackground, SkyTarget, UniformSky},
+    split_view::SplitView,
+    stages::StageKind,
+    staging::UploadBelt,
+    texture,
+    texture_pool::TexturePool,
+    texture_preview::{PreviewSources, TexturePreviews},
+    volumetric_fog::{FogView, FogVolume},
+    wireframe::WireframeOverlay,
+    AppState,
 };
 
-#[derive(Debug)]
-pub struct Geom {
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    material_bind_group: wgpu::BindGroup,
-    enable_bit: u32,
-    enable_bit_buffer: wgpu::Buffer,
-    model: ObjScene,
+/// Half size of the box drawn around the light
+const LIGHT_GIZMO_EXTENT: f32 = 0.1;
+
+/// Texture maps a material can bind, see the enable bits in `shader.wgsl`.
+const MATERIAL_MAP_COUNT: usize = 6;
+
+/// Enable bit of materials whose normal map has only x and y, which the shader
+/// rebuilds z for. After `planar_reflection::MIRROR_BIT`
+const TWO_CHANNEL_NORMALS_BIT: u32 = 1 << 7;
+
+/// GPU textures of the scene being uploaded, keyed by the image they come from and
+/// whether it is sampled as a normal map. The image is kept alive so its address
+/// cannot be reused by another image.
+type TextureCache = HashMap<
+    (*const texture::TextureImage, bool),
+    (Arc<texture::TextureImage>, Rc<texture::Texture>),
+>;
+
+/// Upload `img` unless an earlier mesh already did.
+fn cached_texture(
+    device: &Device,
+    queue: &Queue,
+    textures: &mut TextureCache,
+    img: Arc<texture::TextureImage>,
+    label: String,
+    is_normal_map: bool,
+) -> Option<Rc<texture::Texture>> {
+    let key = (Arc::as_ptr(&img), is_normal_map);
+    if let Some((_, texture)) = textures.get(&key) {
+        return Some(texture.clone());
+    }
+    let texture =
+        texture::Texture::from_texture_image(device, queue, &img, Some(&label), is_normal_map)
+            .inspect_err(|err| {
+                tracing::warn!(
+                    target: logging::SCENE,
+                    "failed to upload {}: {}",
+                    label,
+                    err
+                )
+            })
+            .ok()
+            .map(Rc::new)?;
+    textures.insert(key, (img, texture.clone()));
+    Some(texture)
+}
+
+/// Buffer holding `count` `UniformObject`s and its bind group.
+fn create_object_buffer(device: &Device, count: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Object Buffer"),
+        size: (count.max(1) * std::mem::size_of::<UniformObject>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_joint_buffer(device: &Device, count: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Joint Buffer"),
+        size: (count.max(1) * std::mem::size_of::<Mat4>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_object_bind_group(
+    device: &Device,
+    layout: &wgpu::BindGroupLayout,
+    object_buffer: &wgpu::Buffer,
+    joint_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: object_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: joint_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("Object Bind Group"),
+    })
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct UniformPresent {
+    // non-zero paints NaN and Inf pixels magenta
+    nan_guard: u32,
+    // scene color resolution relative to the surface
+    scale: f32,
+    // non-zero draws the depth contour overlay
+    contours: u32,
+    contour_spacing: f32,
+    focus_distance: f32,
+    focus_range: f32,
+    // planes the depth buffer was rendered with
+    znear: f32,
+    zfar: f32,
+    // non-zero when the depth is linear
+    orthographic: u32,
+    /// `Upsampling` of the scene color
+    upsampling: u32,
+    sharpness: f32,
+    _padding: u32,
+}
+
+impl UniformPresent {
+    fn new(state: &AppState, scale: f32) -> Self {
+        let contours = &state.depth_contours;
+        let (znear, zfar) = state.projection.planes();
+        Self {
+            nan_guard: state.nan_guard as u32,
+            scale,
+            contours: contours.enabled as u32,
+            contour_spacing: contours.spacing,
+            focus_distance: contours.focus_distance,
+            focus_range: contours.focus_range,
+            znear,
+            zfar,
+            orthographic: (state.projection.mode() == ProjectionMode::Orthographic) as u32,
+            upsampling: state.render_scale.upsampling as u32,
+            sharpness: state.render_scale.sharpness,
+            _padding: 0,
+        }
+    }
+}
+
+fn create_present_bind_group(
+    device: &Device,
+    layout: &wgpu::BindGroupLayout,
+    hdr_texture: &texture::Texture,
+    depth_texture: &texture::Texture,
+    present_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&hdr_texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: present_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&depth_texture.view),
+            },
+        ],
+        label: Some("Present Bind Group"),
+    })
+}
+
+/// Present bind groups reading each target of `post` instead of the scene color.
+fn create_post_present_bind_groups(
+    device: &Device,
+    layout: &wgpu::BindGroupLayout,
+    post: &PostStack,
+    depth_texture: &texture::Texture,
+    present_buffer: &wgpu::Buffer,
+) -> Vec<wgpu::BindGroup> {
+    post.targets()
+        .iter()
+        .map(|target| {
+            create_present_bind_group(device, layout, target, depth_texture, present_buffer)
+        })
+        .collect()
+}
+
+/// Color and depth a view of the scene renders into, seen through `camera_bind_group`
+/// and lit as set up in `scene_bind_group`.
+struct ViewTarget<'a> {
+    color: &'a TextureView,
+    depth: &'a TextureView,
+    /// Written by the opaque pass with `GeomPass::ObjectIds`, see `object_ids`
+    object_ids: Option<&'a TextureView>,
+    camera_bind_group: &'a wgpu::BindGroup,
+    scene_bind_group: &'a wgpu::BindGroup,
+    /// Draw the sky behind the opaque geometry
+    sky: bool,
+}
+
+/// Targets and lighting of the right side of the split view, see
+/// `split_view::SplitView`. It shares the camera of the main view.
+struct SplitTarget {
+    /// Surface size the targets were created for
+    surface: (u32, u32),
+    /// Splitter position in fractions of the surface width
+    split: f32,
+    hdr_texture: texture::Texture,
+    depth_texture: texture::Texture,
+    probe_buffer: wgpu::Buffer,
+    material_override_buffer: wgpu::Buffer,
+    scene_bind_group: wgpu::BindGroup,
+    present_bind_group: wgpu::BindGroup,
+}
+
+/// Probe and material override uniforms lighting the right side of the split view.
+fn split_uniforms(state: &AppState) -> (UniformProbes, UniformMaterialOverride) {
+    let right = state.split_view.right;
+    let intensity = if right.enable_reflection_probes {
+        state.reflection_probe_intensity
+    } else {
+        0.0
+    };
+    (
+        UniformProbes::new(&state.scene_file.reflection_probes, intensity),
+        UniformMaterialOverride::new(
+            right.material_override,
+            state.channel_view,
+            state.projection.planes(),
+        ),
+    )
+}
+
+/// Targets and camera of the region of interest, see `roi::RegionOfInterest`.
+struct RoiTarget {
+    extent: (u32, u32),
+    hdr_texture: texture::Texture,
+    depth_texture: texture::Texture,
+    /// The presented region in the surface format, shown by egui
+    output: texture::Texture,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    present_buffer: wgpu::Buffer,
+    present_bind_group: wgpu::BindGroup,
+}
+
+/// Buffers every scene bind group binds, whichever view it is for.
+struct SceneUniforms<'a> {
+    light: &'a wgpu::Buffer,
+    sky: &'a wgpu::Buffer,
+    /// Camera of the planar reflection, see `PlanarReflection::camera_buffer`
+    mirror_camera: &'a wgpu::Buffer,
+    matcap: &'a texture::Texture,
+}
+
+/// `reflection` is what the reflecting mirror samples, see `planar_reflection`.
+fn create_scene_bind_group(
+    device: &Device,
+    layout: &wgpu::BindGroupLayout,
+    uniforms: &SceneUniforms,
+    reflection_probes: &ReflectionProbes,
+    probe_buffer: &wgpu::Buffer,
+    material_override_buffer: &wgpu::Buffer,
+    reflection: &TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniforms.light.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: probe_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&reflection_probes.cube_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(&reflection_probes.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: material_override_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: uniforms.sky.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(reflection),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: uniforms.mirror_camera.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 8,
+                resource: wgpu::BindingResource::TextureView(&uniforms.matcap.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 9,
+                resource: wgpu::BindingResource::Sampler(&uniforms.matcap.sampler),
+            },
+        ],
+        label: Some("Scene Info Bind Group"),
+    })
+}
+
+/// Resources shared by the geoms of a scene while it is being uploaded, the
+/// vertices and indices of every mesh end up in one buffer each.
+#[derive(Default)]
+struct SceneUpload {
+    textures: TextureCache,
+    /// Bound in place of missing maps
+    empty_texture: OnceCell<Rc<texture::Texture>>,
+    /// Keyed by the textures of every map
+    material_bind_groups:
+        HashMap<[*const texture::Texture; MATERIAL_MAP_COUNT], Arc<wgpu::BindGroup>>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl SceneUpload {
+    /// Append a mesh, returns its base vertex, vertex count and index range.
+    fn push(&mut self, vertices: &[Vertex], indices: &[u32]) -> (i32, u32, Range<u32>) {
+        let base_vertex = self.vertices.len() as i32;
+        let vertex_count = vertices.len() as u32;
+        let first_index = self.indices.len() as u32;
+        self.vertices.extend_from_slice(vertices);
+        self.indices.extend_from_slice(indices);
+        (
+            base_vertex,
+            vertex_count,
+            first_index..self.indices.len() as u32,
+        )
+    }
+
+    /// Bind group of a material sampling `maps`. Meshes with the same maps share
+    /// one, which the meshes packed into a texture atlas do unless they have
+    /// other maps of their own.
+    fn material_bind_group(
+        &mut self,
+        device: &Device,
+        layout: &wgpu::BindGroupLayout,
+        maps: &[Rc<texture::Texture>; MATERIAL_MAP_COUNT],
+        name: &str,
+    ) -> Arc<wgpu::BindGroup> {
+        self.material_bind_groups
+            .entry(maps.each_ref().map(Rc::as_ptr))
+            .or_insert_with(|| {
+                Arc::new(
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        layout,
+                        entries: &maps
+                            .iter()
+                            .enumerate()
+                            .flat_map(|(i, map)| {
+                                [
+                                    wgpu::BindGroupEntry {
+                                        binding: i as u32 * 2,
+                                        resource: wgpu::BindingResource::TextureView(&map.view),
+                                    },
+                                    wgpu::BindGroupEntry {
+                                        binding: 1 + i as u32 * 2,
+                                        resource: wgpu::BindingResource::Sampler(&map.sampler),
+                                    },
+                                ]
+                            })
+                            .collect::<Vec<_>>(),
+                        label: Some(format!("Material Bind Group: {}", name).as_str()),
+                    }),
+                )
+            })
+            .clone()
+    }
 }
 
-pub struct DefaultDebugRenderer {
-    render_pipeline: RenderPipeline,
+struct SceneBuffers {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
-    bind_group: wgpu::BindGroup,
-    draw_count: u32,
+    /// `DrawIndexedIndirectArgs` of every geom, in the order of their object index
+    indirect_buffer: wgpu::Buffer,
+    /// `indirect_buffer` culled against the main camera, see `GpuCulling`
+    culled_indirect_buffer: wgpu::Buffer,
+    /// `UniformBounds` of every geom
+    bounds_buffer: wgpu::Buffer,
+    /// Missing without GPU culling support
+    culling_bind_group: Option<wgpu::BindGroup>,
 }
 
-impl DefaultDebugRenderer {
-    pub fn new(
+impl SceneBuffers {
+    /// Wrap the geometry of the geoms of `scene`, creating their draw arguments
+    /// and bounds.
+    fn new(
         device: &Device,
-        config: &SurfaceConfiguration,
-        _queue: &Queue,
-        _state: &mut AppState,
-        light_buffer: &wgpu::Buffer,
-        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        vertex_buffer: wgpu::Buffer,
+        index_buffer: wgpu::Buffer,
+        scene: &SceneWorld,
     ) -> Self {
-        let (light_vertex, _) = ObjScene::load("cube/cube.obj", |_| false).unwrap();
-        let draw_count: u32 = light_vertex[0].vertices().len() as u32;
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer: Light"),
-            contents: bytemuck::cast_slice(&(light_vertex[0].vertices())),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer: Light"),
-            contents: bytemuck::cast_slice(&(light_vertex[0].indices())),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-        let light_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("Light Bind Group Layout"),
-            });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
-            label: Some("Light Bind Group"),
-        });
-        let light_shader = device.create_shader_module(wgpu::include_wgsl!("light.wgsl"));
-        let light_render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Light Source Render Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout, &light_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-        let light_vertex_descriptor = {
-            use std::mem;
-            wgpu::VertexBufferLayout {
-                array_stride: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &[wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                }],
-            }
-        };
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Light Source Render Pipeline"),
-            layout: Some(&light_render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &light_shader,
-                entry_point: Some("vs_main"),
-                buffers: &[light_vertex_descriptor],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Cw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &light_shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
+        let draws = scene
+            .geoms()
+            .flat_map(|geom| {
+                wgpu::util::DrawIndexedIndirectArgs {
+                    index_count: geom.mesh.indices.len() as u32,
+                    instance_count: 1,
+                    first_index: geom.mesh.indices.start,
+                    base_vertex: geom.mesh.base_vertex,
+                    first_instance: geom.object_index,
+                }
+                .as_bytes()
+                .to_vec()
+            })
+            .collect::<Vec<_>>();
+        let bounds = scene
+            .geoms()
+            .map(|geom| UniformBounds::new(geom.mesh.center, geom.mesh.extent))
+            .collect::<Vec<_>>();
+        Self {
+            vertex_buffer,
+            index_buffer,
+            indirect_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Indirect Buffer: Scene"),
+                contents: &draws,
+                usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::STORAGE,
             }),
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
+            culled_indirect_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Culled Indirect Buffer: Scene"),
+                size: draws.len() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
             }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
+            bounds_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Bounds Buffer: Scene"),
+                contents: bytemuck::cast_slice(&bounds),
+                usage: wgpu::BufferUsages::STORAGE,
+            }),
+            culling_bind_group: None,
+        }
+    }
+
+    fn indirect_buffer(&self, culled: bool) -> &wgpu::Buffer {
+        if culled {
+            &self.culled_indirect_buffer
+        } else {
+            &self.indirect_buffer
+        }
+    }
+}
+
+/// How the geoms are submitted, see `Capabilities::indirect_first_instance`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DrawPath {
+    Direct,
+    /// One indirect draw per geom
+    Indirect,
+    /// One indirect draw per run of geoms sharing their pipeline and material
+    MultiDrawIndirect,
+}
+
+impl DrawPath {
+    fn new(state: &AppState) -> Self {
+        let capabilities = &state.capabilities;
+        if !state.indirect_draw || !capabilities.indirect_first_instance {
+            Self::Direct
+        } else if capabilities.multi_draw_indirect {
+            Self::MultiDrawIndirect
+        } else {
+            Self::Indirect
+        }
+    }
+}
+
+/// Which geometry a draw covers and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GeomPass {
+    /// Everything, single sided materials are back face culled
+    Main,
+    /// Like `Main`, also writing the object ids of the main view
+    ObjectIds,
+    /// Static geometry only and nothing culled, the mirrored cube face cameras
+    /// flip the winding
+    ProbeBake,
+    /// Everything but the mirrors and nothing culled, seen by the mirrored camera
+    Mirror,
+}
+
+/// Passes of `DefaultRenderer::render_stage`, run in the order of the frame
+/// graph.
+#[derive(Debug, Clone, Copy)]
+enum FramePass {
+    ProbeBake,
+    Culling,
+    PlanarReflection,
+    Opaque,
+    OpaqueStatistics,
+    GroundGrid,
+    Transparent,
+    VolumetricFog,
+    Heatmap,
+    Wireframe,
+    DebugDraw,
+    FinalStatistics,
+    PostProcess,
+    TexturePreviews,
+    SplitView,
+    RegionOfInterest,
+    Present,
+    PresentSplit,
+}
+
+impl FramePass {
+    /// Stage recording the pass. None of the debug views reads what the post
+    /// processing stage writes, so they go before it.
+    fn stage(self) -> StageKind {
+        match self {
+            Self::ProbeBake
+            | Self::Culling
+            | Self::PlanarReflection
+            | Self::Opaque
+            | Self::OpaqueStatistics
+            | Self::GroundGrid
+            | Self::Transparent
+            | Self::VolumetricFog => StageKind::Scene,
+            Self::Heatmap
+            | Self::Wireframe
+            | Self::DebugDraw
+            | Self::TexturePreviews
+            | Self::SplitView
+            | Self::RegionOfInterest => StageKind::DebugViews,
+            Self::FinalStatistics | Self::PostProcess => StageKind::PostProcess,
+            Self::Present | Self::PresentSplit => StageKind::Present,
+        }
+    }
+}
+
+/// The passes of a frame and the scene textures handed between them, which the
+/// graph allocates.
+struct FrameGraph {
+    graph: RenderGraph<FramePass>,
+    scene_color: ResourceId,
+    scene_depth: ResourceId,
+    /// Object under each pixel of the main view, see `object_ids`
+    object_ids: ResourceId,
+    /// Fragments per pixel of the overdraw heatmap, see `heatmap`
+    overdraw: ResourceId,
+    /// The scene mirrored about the reflecting mirror, see `planar_reflection`
+    mirror_color: ResourceId,
+    mirror_depth: ResourceId,
+}
+
+impl FrameGraph {
+    fn new(targets: &RenderTargets) -> Self {
+        let mut graph = RenderGraph::default();
+        let probes = graph.import("reflection probes");
+        let draws = graph.import("draw commands");
+        let statistics = graph.import("frame statistics");
+        let post = graph.import("post processed");
+        let previews = graph.import("texture previews");
+        let split = graph.import("split view");
+        let roi = graph.import("region of interest");
+        let surface = graph.import("surface");
+        let scene_color = graph.texture(TextureDesc {
+            label: "hdr_texture",
+            format: targets.scene.format,
+            scale: targets.scene.scale,
+        });
+        let scene_depth = graph.texture(TextureDesc {
+            label: "depth_texture",
+            format: texture::Texture::DEPTH_FORMAT,
+            scale: targets.scene.scale,
+        });
+        let object_ids = graph.texture(TextureDesc {
+            label: "object_id_texture",
+            format: object_ids::FORMAT,
+            scale: targets.scene.scale,
         });
+        let overdraw = graph.texture(TextureDesc {
+            label: "overdraw_texture",
+            format: heatmap::FORMAT,
+            scale: targets.scene.scale,
+        });
+        let mirror_color = graph.texture(TextureDesc {
+            label: "mirror_texture",
+            format: targets.scene.format,
+            scale: targets.scene.scale,
+        });
+        let mirror_depth = graph.texture(TextureDesc {
+            label: "mirror_depth_texture",
+            format: texture::Texture::DEPTH_FORMAT,
+            scale: targets.scene.scale,
+        });
+        let scene = [scene_color, scene_depth];
+        // Baking samples the probes refreshed earlier
+        graph.add_pass("probe bake", FramePass::ProbeBake, &[probes], &[probes]);
+        graph.add_pass("culling", FramePass::Culling, &[], &[draws]);
+        // Sees every geom, the culled draws do not cover it
+        graph.add_pass(
+            "planar reflection",
+            FramePass::PlanarReflection,
+            &[probes],
+            &[mirror_color, mirror_depth],
+        );
+        graph.add_pass(
+            "opaque",
+            FramePass::Opaque,
+            &[probes, draws, mirror_color],
+            &[scene_color, scene_depth, object_ids],
+        );
+        graph.add_pass(
+            "opaque statistics",
+            FramePass::OpaqueStatistics,
+            &[scene_color],
+            &[statistics],
+        );
+        // Blended geometry is drawn over the grid
+        graph.add_pass("ground grid", FramePass::GroundGrid, &scene, &scene);
+        graph.add_pass(
+            "transparent",
+            FramePass::Transparent,
+            &[probes, draws, mirror_color, scene_color, scene_depth],
+            &scene,
+        );
+        // Overlays stay clear of the fog
+        graph.add_pass(
+            "volumetric fog",
+            FramePass::VolumetricFog,
+            &[probes, scene_color, scene_depth],
+            &[scene_color],
+        );
+        // Overlays are drawn over the heatmap
+        graph.add_pass(
+            "heatmap",
+            FramePass::Heatmap,
+            &scene,
+            &[scene_color, overdraw],
+        );
+        graph.add_pass("wireframe", FramePass::Wireframe, &scene, &scene);
+        graph.add_pass("debug draw", FramePass::DebugDraw, &scene, &scene);
+        graph.add_pass(
+            "final statistics",
+            FramePass::FinalStatistics,
+            &[scene_color],
+            &[statistics],
+        );
+        graph.add_pass(
+            "post process",
+            FramePass::PostProcess,
+            &[scene_color, scene_depth],
+            &[post],
+        );
+        graph.add_pass(
+            "texture previews",
+            FramePass::TexturePreviews,
+            &[scene_color, scene_depth, probes],
+            &[previews],
+        );
+        // Same camera, so the culled draws of the main view cover it as well
+        graph.add_pass(
+            "split view",
+            FramePass::SplitView,
+            &[probes, draws, mirror_color],
+            &[split],
+        );
+        // The region lies inside the view frustum, the culled draws cover it
+        graph.add_pass(
+            "region of interest",
+            FramePass::RegionOfInterest,
+            &[probes, draws, mirror_color],
+            &[roi],
+        );
+        graph.add_pass(
+            "present",
+            FramePass::Present,
+            &[scene_color, scene_depth, post],
+            &[surface],
+        );
+        graph.add_pass(
+            "present split",
+            FramePass::PresentSplit,
+            &[split],
+            &[surface],
+        );
+        if let Err(err) = graph.compile() {
+            panic!("invalid frame graph: {}", err);
+        }
         Self {
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            bind_group,
-            draw_count,
+            graph,
+            scene_color,
+            scene_depth,
+            object_ids,
+            overdraw,
+            mirror_color,
+            mirror_depth,
         }
     }
 
-    fn render(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_bind_group(0, camera_bind_group, &[]);
-        render_pass.set_bind_group(1, &self.bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.draw_indexed(0..self.draw_count, 0, 0..1);
+    /// Scene color before it is presented, `texture::Texture::HDR_FORMAT` by default
+    fn scene_color(&self) -> &texture::Texture {
+        self.graph.get(self.scene_color)
+    }
+
+    fn scene_depth(&self) -> &texture::Texture {
+        self.graph.get(self.scene_depth)
+    }
+
+    fn object_ids(&self) -> &texture::Texture {
+        self.graph.get(self.object_ids)
+    }
+
+    fn overdraw(&self) -> &texture::Texture {
+        self.graph.get(self.overdraw)
+    }
+
+    fn mirror_color(&self) -> &texture::Texture {
+        self.graph.get(self.mirror_color)
+    }
+
+    fn mirror_depth(&self) -> &texture::Texture {
+        self.graph.get(self.mirror_depth)
     }
 }
 
 pub struct DefaultRenderer {
-    render_pipeline: RenderPipeline,
+    /// Prepared for the materials of the scene, see `DefaultRenderer::pipelines`
+    scene_pipelines: ScenePipelineCache,
+    /// Normal map toggle of this frame, picks the permutation of each material
+    normal_maps: bool,
+    /// Latest change of the shader files while they are watched
+    shaders_modified: Option<SystemTime>,
     pub camera_buffer: wgpu::Buffer,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
     camera_bind_group: wgpu::BindGroup,
     pub light_buffer: wgpu::Buffer,
+    /// `UniformSky` of `AppState::sky`, read by the shading and the sky
+    sky_buffer: wgpu::Buffer,
     scene_bind_group: wgpu::BindGroup,
-    depth_texture: texture::Texture,
-    debug_renderer: DefaultDebugRenderer,
-    pub geoms: Vec<Geom>,
+    /// Binds the placeholder of `planar_reflection` while the reflection is drawn
+    mirror_scene_bind_group: wgpu::BindGroup,
+    scene_bind_group_layout: wgpu::BindGroupLayout,
+    material_override_buffer: wgpu::Buffer,
+    reflection_probes: ReflectionProbes,
+    /// Passes of a frame and the scene color and depth they render into
+    frame_graph: FrameGraph,
+    present_pipeline: RenderPipeline,
+    present_bind_group_layout: wgpu::BindGroupLayout,
+    present_bind_group: wgpu::BindGroup,
+    /// Present the targets of `post` instead of the scene color
+    post_present_bind_groups: Vec<wgpu::BindGroup>,
+    present_buffer: wgpu::Buffer,
+    /// Present while the region of interest is enabled
+    roi_target: Option<RoiTarget>,
+    split_target: Option<SplitTarget>,
+    /// Formats and resolution scales of the passes, see `render_settings`
+    targets: RenderTargets,
+    /// Missing without compute shader support
+    frame_statistics: Option<FrameStatistics>,
+    /// Missing without compute shaders or indirect draws
+    culling: Option<GpuCulling>,
+    /// Missing without compute shader support
+    probe_convergence: Option<ProbeConvergence>,
+    /// Faces of a refresh whose convergence is measured in a separate submission
+    deferred_measure: Cell<Option<usize>>,
+    /// Missing without timestamp queries
+    pass_timer: Option<PassTimer>,
+    /// Times every pass while a benchmark records, missing without timestamps
+    /// inside encoders
+    pass_profiler: Option<PassProfiler>,
+    /// Missing without compute shader support
+    volumetric_fog: Option<FogVolume>,
+    heatmap: HeatmapPass,
+    material_bind_group_layout: wgpu::BindGroupLayout,
+    object_bind_group_layout: wgpu::BindGroupLayout,
+    /// `UniformObject` of every geom, in the order of their object index
+    object_buffer: wgpu::Buffer,
+    /// Joint matrices of every skin of the scene graph, see `SceneGraph::joint_matrices`
+    joint_buffer: wgpu::Buffer,
+    object_bind_group: wgpu::BindGroup,
+    /// Rewrite the object buffer during the next upload
+    objects_dirty: bool,
+    /// Carries the camera, light and objects into the frame's encoder, see `upload`
+    uploads: UploadBelt,
+    /// Render targets released by the graph, the split view, the region of
+    /// interest and captures
+    texture_pool: TexturePool,
+    scene_graph: Option<SceneGraph>,
+    /// Geometry of every geom, missing while no scene is loaded
+    scene_buffers: Option<SceneBuffers>,
+    /// Geometry and light entities, see `scene_world`
+    pub scene: SceneWorld,
+    /// A static geometry moved during the last update, the probes are outdated
+    static_geometry_moved: bool,
+    ground_grid: GroundGridOverlay,
+    sky: SkyBackground,
+    planar_reflection: PlanarReflection,
+    /// Object index of the mirror reflecting this frame
+    active_mirror: Option<u32>,
+    matcap: MatcapTexture,
+    wireframe: WireframeOverlay,
+    /// Lines of `AppState::debug_draw` uploaded for this frame, see `debug_draw`
+    debug_draw: DebugDrawOverlay,
+    /// Outlines the triangle picked for the inspector
+    picked_overlay: WireframeOverlay,
+    /// Outlines the object under the cursor
+    hover_overlay: WireframeOverlay,
+    hover_readback: HoverReadback,
+    /// Object index `hover_overlay` outlines
+    hovered_object: Option<usize>,
+    previews: TexturePreviews,
+    /// Effects between the scene color and the surface
+    post: PostStack,
 }
 
 impl DefaultRenderer {
@@ -170,41 +868,32 @@ impl DefaultRenderer {
         config: &SurfaceConfiguration,
         queue: &Queue,
         state: &mut AppState,
-        path: &str,
-    ) -> Self {
-        let mut geoms: Vec<Geom> = vec![];
-        let (models, light) = primitives::ObjScene::load(path, |mt| mt.name == "Light").unwrap();
-        state.given_light_position = light.is_some();
+        targets: RenderTargets,
+    ) -> error::Result<Self> {
         // Scene light
         let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Light Buffer"),
             contents: bytemuck::cast_slice::<_, u8>(&[Into::<primitives::UniformLight>::into(
-                light.unwrap_or_else(|| Vec3::from(state.light_position)),
+                Vec3::from(state.light_position),
             )]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
-        let scene_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("Scene Info Bind Group Layout"),
-            });
-        let scene_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &scene_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
-            label: Some("Camera Bind Group"),
+        let sky_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky Buffer"),
+            contents: bytemuck::cast_slice(&[UniformSky::new(&state.sky)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        // Stand-in for every material while an override is selected
+        let material_override_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Material Override Buffer"),
+                contents: bytemuck::cast_slice(&[UniformMaterialOverride::new(
+                    state.material_override,
+                    state.channel_view,
+                    state.projection.planes(),
+                )]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
         // Setup Camera
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Camera Buffer"),
@@ -237,13 +926,23 @@ impl DefaultRenderer {
             label: Some("Camera Bind Group"),
         });
 
-        // Material Description
-        let material_bind_group_layout =
+        // Scene wide information: light, reflection probes, material override and sky
+        let (probe_resolution, _) = targets
+            .reflection_probes
+            .extent(DEFAULT_PROBE_RESOLUTION, DEFAULT_PROBE_RESOLUTION);
+        let reflection_probes = ReflectionProbes::new(
+            device,
+            targets.reflection_probes.format,
+            probe_resolution,
+            &camera_bind_group_layout,
+        );
+        // Light and probes are also read by the volumetric fog
+        let scene_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
@@ -251,10 +950,10 @@ impl DefaultRenderer {
                         },
                         count: None,
                     },
-                    // enable bit
+                    // reflection probes
                     wgpu::BindGroupLayoutEntry {
                         binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
@@ -262,27 +961,47 @@ impl DefaultRenderer {
                         },
                         count: None,
                     },
-                    // color texture
                     wgpu::BindGroupLayoutEntry {
                         binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
+                            view_dimension: wgpu::TextureViewDimension::CubeArray,
                             sample_type: wgpu::TextureSampleType::Float { filterable: true },
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 3,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
-                    // normal texture
                     wgpu::BindGroupLayoutEntry {
                         binding: 4,
                         visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // sky
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // planar reflection and its camera
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
                             view_dimension: wgpu::TextureViewDimension::D2,
@@ -291,342 +1010,2782 @@ impl DefaultRenderer {
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 5,
+                        binding: 7,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // matcap
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 8,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 9,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
                 ],
-                label: Some("Material Bind Group Layout"),
+                label: Some("Scene Info Bind Group Layout"),
             });
+        // Adjusted at runtime from the scale of the settings file
+        state.render_scale.scale = targets.scene.scale;
+        let mut frame_graph = FrameGraph::new(&targets);
+        let mut texture_pool = TexturePool::default();
+        frame_graph
+            .graph
+            .allocate(device, &mut texture_pool, (config.width, config.height));
+        let planar_reflection =
+            PlanarReflection::new(device, targets.scene.format, &camera_bind_group_layout);
+        let matcap = MatcapTexture::new(device, queue);
+        let uniforms = SceneUniforms {
+            light: &light_buffer,
+            sky: &sky_buffer,
+            mirror_camera: &planar_reflection.camera_buffer,
+            matcap: &matcap.texture,
+        };
+        let scene_bind_group = create_scene_bind_group(
+            device,
+            &scene_bind_group_layout,
+            &uniforms,
+            &reflection_probes,
+            &reflection_probes.uniform_buffer,
+            &material_override_buffer,
+            &frame_graph.mirror_color().view,
+        );
+        let mirror_scene_bind_group = create_scene_bind_group(
+            device,
+            &scene_bind_group_layout,
+            &uniforms,
+            &reflection_probes,
+            &reflection_probes.uniform_buffer,
+            &material_override_buffer,
+            &planar_reflection.placeholder.view,
+        );
 
-        // Depth buffer
-        let depth_texture =
-            texture::Texture::create_depth_texture(&device, &config, "depth_texture");
-
-        // Summon shader
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[
-                    &camera_bind_group_layout,
-                    &material_bind_group_layout,
-                    &scene_bind_group_layout,
-                ],
-                push_constant_ranges: &[],
-            });
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[models
-                    .iter()
-                    .map(ObjScene::vertex_descriptor)
-                    .next()
-                    .unwrap()],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Cw,
-                cull_mode: None,
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
+        // Material Description
+        // color, normal, specular, roughness, ambient occlusion and alpha maps, each a
+        // texture followed by its sampler
+        let material_map_entries = (0..MATERIAL_MAP_COUNT as u32).flat_map(|i| {
+            [
+                wgpu::BindGroupLayoutEntry {
+                    binding: i * 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1 + i * 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ]
+        });
+        let material_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &material_map_entries.collect::<Vec<_>>(),
+                label: Some("Material Bind Group Layout"),
+            });
+
+        // Per object transform, material constants and enable bits of every object,
+        // the shader picks its object with the instance index. Skinned objects
+        // read their joint matrices next to them
+        let object_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<UniformObject>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<Mat4>() as u64
+                            ),
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("Object Bind Group Layout"),
+            });
+        let object_buffer = create_object_buffer(device, 1);
+        let joint_buffer = create_joint_buffer(device, 1);
+        let object_bind_group = create_object_bind_group(
+            device,
+            &object_bind_group_layout,
+            &object_buffer,
+            &joint_buffer,
+        );
+
+        let frame_statistics = state
+            .capabilities
+            .compute_shaders
+            .then(|| FrameStatistics::new(device, frame_graph.scene_color()));
+        let probe_convergence = state
+            .capabilities
+            .compute_shaders
+            .then(|| ProbeConvergence::new(device, &reflection_probes));
+        let pass_timer = (state.capabilities.timestamp_queries
+            && state.capabilities.compute_shaders)
+            .then(|| PassTimer::new(device, queue));
+        let pass_profiler = state
+            .capabilities
+            .encoder_timestamps
+            .then(|| PassProfiler::new(device, queue, frame_graph.graph.passes().count()));
+        let culling = (state.capabilities.compute_shaders
+            && state.capabilities.indirect_first_instance)
+            .then(|| GpuCulling::new(device, &state.capabilities));
+        let volumetric_fog = state.capabilities.compute_shaders.then(|| {
+            FogVolume::new(
+                device,
+                targets.scene.format,
+                &scene_bind_group_layout,
+                frame_graph.scene_depth(),
+            )
+        });
+
+        // Present the scene color on the surface
+        let present_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Present Buffer"),
+            contents: bytemuck::cast_slice(&[UniformPresent::new(state, targets.scene.scale)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let present_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("Present Bind Group Layout"),
+            });
+        let present_bind_group = create_present_bind_group(
+            device,
+            &present_bind_group_layout,
+            frame_graph.scene_color(),
+            frame_graph.scene_depth(),
+            &present_buffer,
+        );
+        let mut post = PostStack::new(device, queue, targets.scene.format);
+        post.resize(device, frame_graph.scene_color(), frame_graph.scene_depth());
+        let post_present_bind_groups = create_post_present_bind_groups(
+            device,
+            &present_bind_group_layout,
+            &post,
+            frame_graph.scene_depth(),
+            &present_buffer,
+        );
+        let present_shader = device.create_shader_module(wgpu::include_wgsl!("present.wgsl"));
+        let present_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Present Pipeline Layout"),
+                bind_group_layouts: &[&present_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let present_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Present Pipeline"),
+            layout: Some(&present_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &present_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
+            primitive: wgpu::PrimitiveState::default(),
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: &present_shader,
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    blend: None,
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             }),
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
             multiview: None,
             cache: None,
         });
 
-        for model in models {
-            let (vertex_tangents, vertex_bitangents, vertex_normal) = model.tbn();
-            let vertex_data = model
-                .vertices()
-                .iter()
-                .zip(
-                    model
-                        .vertex_colors()
-                        .iter()
-                        .chain(std::iter::repeat(&Vec3::ONE)),
-                )
-                .zip(
-                    model
-                        .normals()
-                        .iter()
-                        .zip_longest(vertex_normal.iter())
-                        .map(|z| match z {
-                            EitherOrBoth::Both(l, _) => l,
-                            EitherOrBoth::Left(l) => l,
-                            EitherOrBoth::Right(r) => r,
-                        })
-                        .chain(std::iter::repeat(&Vec3::Z)),
-                )
-                .zip(vertex_tangents.iter().chain(std::iter::repeat(&Vec3::X)))
-                .zip(vertex_bitangents.iter().chain(std::iter::repeat(&Vec3::Y)))
-                .zip(
-                    model
-                        .texcoords()
-                        .iter()
-                        .chain(std::iter::repeat(&Vec2::ZERO)),
-                )
-                .flat_map(|(((((a, b), c), d), e), f)| {
-                    a.to_array()
-                        .into_iter()
-                        .chain(b.to_array().into_iter())
-                        .chain(c.to_array().into_iter())
-                        .chain(d.to_array().into_iter())
-                        .chain(e.to_array().into_iter())
-                        .chain(f.to_array().into_iter())
-                })
-                .collect::<Box<[_]>>();
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(format!("Vertex Buffer: {}", model.name()).as_str()),
-                contents: bytemuck::cast_slice(&vertex_data),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(format!("Index Buffer: {}", model.name()).as_str()),
-                contents: bytemuck::cast_slice(&model.indices()),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-            let (material_buffer, color_texture, normal_texture, enable_bit_buffer, enable_bit) = {
-                let enable_bit_calc =
-                    |color: bool, normal: bool| -> u32 { (color as u32) | ((normal as u32) << 1) };
-                let unwrap_texture = |text: Option<texture::Texture>| -> texture::Texture {
-                    text.unwrap_or(texture::Texture::empty(
-                        &device,
-                        &queue,
-                        Some("Empty Texture"),
-                    ))
-                };
-                if let Some(material) = model.material() {
-                    let material_buffer =
-                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: Some(format!("Material Buffer: {}", model.name()).as_str()),
-                            contents: bytemuck::cast_slice(&[Into::<UniformMaterial>::into(
-                                &material,
-                            )]),
-                            usage: wgpu::BufferUsages::UNIFORM,
-                        });
-                    let color_texture = material.color_texture.map(|img| {
-                        texture::Texture::from_image(
-                            &device,
-                            &queue,
-                            &img,
-                            Some(format!("Color Texture: {}", model.name()).as_str()),
-                        )
-                        .unwrap()
-                    });
-                    let normal_texture = material.normal_texture.map(|img| {
-                        texture::Texture::from_image_internal(
-                            &device,
-                            &queue,
-                            &img,
-                            Some(format!("Normal Texture: {}", model.name()).as_str()),
-                            true,
-                        )
-                        .unwrap()
-                    });
-                    let enable_bit =
-                        enable_bit_calc(color_texture.is_some(), normal_texture.is_some());
-                    let enable_bit_buffer =
-                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: Some(format!("Enable Bit Buffer: {}", model.name()).as_str()),
-                            contents: bytemuck::cast_slice(&[enable_bit]),
-                            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                        });
-                    (
-                        material_buffer,
-                        unwrap_texture(color_texture),
-                        unwrap_texture(normal_texture),
-                        enable_bit_buffer,
-                        enable_bit,
-                    )
-                } else {
-                    let material_buffer =
-                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: Some(format!("Material Buffer: {}", model.name()).as_str()),
-                            contents: bytemuck::cast_slice(&[Into::<UniformMaterial>::into(
-                                Material::default(),
-                            )]),
-                            usage: wgpu::BufferUsages::UNIFORM,
-                        });
-                    let enable_bit_buffer =
-                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: Some(format!("Enable Bit Buffer: {}", model.name()).as_str()),
-                            contents: bytemuck::cast_slice(&[0u32]),
-                            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                        });
-                    (
-                        material_buffer,
-                        unwrap_texture(None),
-                        unwrap_texture(None),
-                        enable_bit_buffer,
-                        0u32,
-                    )
-                }
+        let scene_format = targets.scene.format;
+        let scene_pipelines = ScenePipelineCache::new(
+            device,
+            &state.capabilities,
+            &[
+                &camera_bind_group_layout,
+                &material_bind_group_layout,
+                &scene_bind_group_layout,
+                &object_bind_group_layout,
+            ],
+            scene_format,
+            targets.reflection_probes.format,
+        );
+
+        let ground_grid = GroundGridOverlay::new(
+            device,
+            scene_format,
+            &camera_bind_group_layout,
+            &state.ground_grid,
+        );
+        let sky = SkyBackground::new(
+            device,
+            scene_format,
+            targets.reflection_probes.format,
+            &camera_bind_group_layout,
+            &scene_bind_group_layout,
+        );
+        let wireframe = WireframeOverlay::new(
+            device,
+            &state.capabilities,
+            scene_format,
+            &camera_bind_group_layout,
+            &object_bind_group_layout,
+            [1.0, 0.55, 0.1],
+        );
+        let picked_overlay = WireframeOverlay::new(
+            device,
+            &state.capabilities,
+            scene_format,
+            &camera_bind_group_layout,
+            &object_bind_group_layout,
+            [0.1, 0.9, 1.0],
+        );
+        let hover_overlay = WireframeOverlay::new(
+            device,
+            &state.capabilities,
+            scene_format,
+            &camera_bind_group_layout,
+            &object_bind_group_layout,
+            [1.0, 1.0, 1.0],
+        );
+        let debug_draw = DebugDrawOverlay::new(device, scene_format, &camera_bind_group_layout);
+        let heatmap = HeatmapPass::new(
+            device,
+            &state.capabilities,
+            scene_format,
+            &camera_bind_group_layout,
+            &object_bind_group_layout,
+            frame_graph.overdraw(),
+            frame_graph.scene_depth(),
+        );
+        Ok(Self {
+            scene_pipelines,
+            normal_maps: state.enable_normal_map,
+            shaders_modified: None,
+            camera_bind_group_layout,
+            camera_bind_group,
+            camera_buffer,
+            light_buffer,
+            sky_buffer,
+            scene_bind_group,
+            mirror_scene_bind_group,
+            scene_bind_group_layout,
+            material_override_buffer,
+            reflection_probes,
+            frame_graph,
+            present_pipeline,
+            present_bind_group_layout,
+            present_bind_group,
+            post_present_bind_groups,
+            present_buffer,
+            roi_target: None,
+            split_target: None,
+            targets,
+            frame_statistics,
+            culling,
+            probe_convergence,
+            deferred_measure: Cell::new(None),
+            pass_timer,
+            pass_profiler,
+            volumetric_fog,
+            heatmap,
+            material_bind_group_layout,
+            object_bind_group_layout,
+            object_buffer,
+            joint_buffer,
+            object_bind_group,
+            objects_dirty: false,
+            uploads: UploadBelt::default(),
+            texture_pool,
+            scene_graph: None,
+            scene_buffers: None,
+            scene: SceneWorld::new(),
+            static_geometry_moved: false,
+            ground_grid,
+            sky,
+            planar_reflection,
+            active_mirror: None,
+            matcap,
+            wireframe,
+            debug_draw,
+            picked_overlay,
+            hover_overlay,
+            hover_readback: HoverReadback::new(device),
+            hovered_object: None,
+            previews: TexturePreviews::new(device, config.format),
+            post,
+        })
+    }
+
+    /// Drop every geometry of the current scene, camera and light are left untouched.
+    pub fn clear_scene(&mut self) {
+        self.scene.clear();
+        self.wireframe.clear();
+        self.picked_overlay.clear();
+        self.hover_overlay.clear();
+        self.hovered_object = None;
+        self.scene_buffers = None;
+        self.scene_graph = None;
+    }
+
+    /// Replace the currently displayed geometry with a freshly loaded scene.
+    pub fn set_scene(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        state: &mut AppState,
+        scene: LoadedScene,
+    ) {
+        tracing::info!(
+            target: logging::SCENE,
+            "loaded scene {} ({} meshes)",
+            scene.path.display(),
+            scene.meshes.len()
+        );
+        state.given_light_position = scene.light.is_some();
+        if let Some(light) = scene.light {
+            state.light_position = light.to_array();
+            state.light_input = state.light_position.map(|p| p.to_string());
+            state.light_orbit.recenter();
+        }
+        let node_matrices = scene
+            .scene_graph
+            .as_ref()
+            .map(|graph| graph.evaluate(0.0))
+            .unwrap_or_default();
+        let mut upload = SceneUpload::default();
+        let geoms = scene
+            .meshes
+            .into_iter()
+            .map(|mesh| {
+                let transform = mesh
+                    .node
+                    .and_then(|node| node_matrices.get(node).copied())
+                    .unwrap_or(Mat4::IDENTITY);
+                let joint_offset = mesh
+                    .node
+                    .zip(scene.scene_graph.as_ref())
+                    .and_then(|(node, graph)| graph.joint_offset(node));
+                self.create_geom(device, queue, &mut upload, transform, joint_offset, mesh)
+            })
+            .collect::<Vec<_>>();
+        self.scene.clear();
+        self.set_geoms(device, queue, state, upload, geoms, None);
+        state.scene_animated = scene
+            .scene_graph
+            .as_ref()
+            .is_some_and(SceneGraph::is_animated);
+        state.animation_time = 0.0;
+        state.animation_duration = scene.scene_graph.as_ref().map_or(0.0, SceneGraph::duration);
+        let joint_matrices = scene
+            .scene_graph
+            .as_ref()
+            .map(|graph| graph.joint_matrices(&node_matrices))
+            .unwrap_or_default();
+        let joint_size = std::mem::size_of_val(joint_matrices.as_slice()) as u64;
+        if self.joint_buffer.size() < joint_size {
+            self.joint_buffer = create_joint_buffer(device, joint_matrices.len());
+            self.object_bind_group = create_object_bind_group(
+                device,
+                &self.object_bind_group_layout,
+                &self.object_buffer,
+                &self.joint_buffer,
+            );
+        }
+        if !joint_matrices.is_empty() {
+            queue.write_buffer(&self.joint_buffer, 0, bytemuck::cast_slice(&joint_matrices));
+        }
+        self.scene_graph = scene.scene_graph;
+        state.scene_path = Some(scene.path);
+        state.scene_file = scene.scene_file;
+        state.reflection_probes_changed = true;
+        let restored = scene.probe_cache.is_some_and(|cache| {
+            self.reflection_probes.restore_cache(
+                device,
+                queue,
+                &state.scene_file.reflection_probes,
+                &cache,
+            )
+        });
+        if restored {
+            tracing::info!(
+                target: logging::PROBES,
+                "restored reflection probes from cache"
+            );
+        } else {
+            state.bake_reflection_probes = true;
+        }
+    }
+
+    /// Add the geometry of a streamed chunk of the scene, see `streaming::ChunkStreamer`.
+    pub fn add_chunk(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        state: &mut AppState,
+        chunk: usize,
+        scene: LoadedScene,
+    ) {
+        tracing::info!(
+            target: logging::SCENE,
+            "streamed in {} ({} meshes)",
+            scene.path.display(),
+            scene.meshes.len()
+        );
+        // Chunks are static, their nodes are placed once
+        let node_matrices = scene
+            .scene_graph
+            .as_ref()
+            .map(|graph| graph.evaluate(0.0))
+            .unwrap_or_default();
+        let mut upload = SceneUpload::default();
+        let added = scene
+            .meshes
+            .into_iter()
+            .map(|mesh| {
+                let transform = mesh
+                    .node
+                    .and_then(|node| node_matrices.get(node).copied())
+                    .unwrap_or(Mat4::IDENTITY);
+                let mesh = MeshData { node: None, ..mesh };
+                self.create_geom(device, queue, &mut upload, transform, None, mesh)
+            })
+            .collect::<Vec<_>>();
+        self.set_geoms(device, queue, state, upload, added, Some(chunk));
+        state.bake_reflection_probes = true;
+    }
+
+    /// Drop the geometry of a streamed chunk of the scene.
+    pub fn remove_chunk(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        state: &mut AppState,
+        chunk: usize,
+    ) {
+        if !self.scene.despawn_chunk(chunk) {
+            return;
+        }
+        self.set_geoms(
+            device,
+            queue,
+            state,
+            SceneUpload::default(),
+            Vec::new(),
+            None,
+        );
+        state.bake_reflection_probes = true;
+    }
+
+    /// Add `added` to the geoms of the scene, part of `chunk` if given. The
+    /// geometry of the geoms already in the scene is still in the current scene
+    /// buffers and is moved to the front of the new ones on the GPU, the meshes of
+    /// `upload` that `added` points into follow.
+    fn set_geoms(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        state: &mut AppState,
+        upload: SceneUpload,
+        added: Vec<GeomBundle>,
+        chunk: Option<usize>,
+    ) {
+        if !added.is_empty() {
+            tracing::info!(
+                target: logging::SCENE,
+                "{} meshes share {} material bind groups",
+                added.len(),
+                upload.material_bind_groups.len()
+            );
+        }
+        let vertex_size = std::mem::size_of::<Vertex>() as wgpu::BufferAddress;
+        let index_size = std::mem::size_of::<u32>() as wgpu::BufferAddress;
+        let old_buffers = self.scene_buffers.take();
+        let (kept_vertices, kept_indices) =
+            self.scene
+                .geoms()
+                .fold((0, 0), |(vertices, indices), geom| {
+                    (
+                        vertices + geom.mesh.vertex_count,
+                        indices + geom.mesh.indices.len() as u32,
+                    )
+                });
+        let vertex_count = kept_vertices as usize + upload.vertices.len();
+        let index_count = kept_indices as usize + upload.indices.len();
+        // Nothing to create for a scene without triangles, empty slices cannot be bound
+        let geometry = (index_count > 0).then(|| {
+            let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Vertex Buffer: Scene"),
+                size: vertex_count as wgpu::BufferAddress * vertex_size,
+                usage: wgpu::BufferUsages::VERTEX
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Index Buffer: Scene"),
+                size: index_count as wgpu::BufferAddress * index_size,
+                usage: wgpu::BufferUsages::INDEX
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            (vertex_buffer, index_buffer)
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Scene Repack Encoder"),
+        });
+        let (mut next_vertex, mut next_index) = (0, 0);
+        for mut mesh in self.scene.meshes_mut() {
+            if let Some((old, (vertex_buffer, index_buffer))) =
+                old_buffers.as_ref().zip(geometry.as_ref())
+            {
+                if mesh.vertex_count > 0 {
+                    encoder.copy_buffer_to_buffer(
+                        &old.vertex_buffer,
+                        mesh.base_vertex as wgpu::BufferAddress * vertex_size,
+                        vertex_buffer,
+                        next_vertex as wgpu::BufferAddress * vertex_size,
+                        mesh.vertex_count as wgpu::BufferAddress * vertex_size,
+                    );
+                }
+                if !mesh.indices.is_empty() {
+                    encoder.copy_buffer_to_buffer(
+                        &old.index_buffer,
+                        mesh.indices.start as wgpu::BufferAddress * index_size,
+                        index_buffer,
+                        next_index as wgpu::BufferAddress * index_size,
+                        mesh.indices.len() as wgpu::BufferAddress * index_size,
+                    );
+                }
+            }
+            mesh.base_vertex = next_vertex as i32;
+            mesh.indices = next_index..next_index + mesh.indices.len() as u32;
+            next_vertex += mesh.vertex_count;
+            next_index += mesh.indices.len() as u32;
+        }
+        queue.submit(Some(encoder.finish()));
+        if let Some((vertex_buffer, index_buffer)) = &geometry {
+            if !upload.vertices.is_empty() {
+                queue.write_buffer(
+                    vertex_buffer,
+                    kept_vertices as wgpu::BufferAddress * vertex_size,
+                    bytemuck::cast_slice(&upload.vertices),
+                );
+            }
+            if !upload.indices.is_empty() {
+                queue.write_buffer(
+                    index_buffer,
+                    kept_indices as wgpu::BufferAddress * index_size,
+                    bytemuck::cast_slice(&upload.indices),
+                );
+            }
+        }
+        let added = added
+            .into_iter()
+            .map(|(mut mesh, material, transform)| {
+                mesh.base_vertex += kept_vertices as i32;
+                mesh.indices = mesh.indices.start + kept_indices..mesh.indices.end + kept_indices;
+                (mesh, material, transform)
+            })
+            .collect();
+        self.scene.spawn(added, chunk);
+        let object_size = (self.scene.len() * std::mem::size_of::<UniformObject>()) as u64;
+        if self.object_buffer.size() < object_size {
+            self.object_buffer = create_object_buffer(device, self.scene.len());
+            self.object_bind_group = create_object_bind_group(
+                device,
+                &self.object_bind_group_layout,
+                &self.object_buffer,
+                &self.joint_buffer,
+            );
+        }
+        self.scene_buffers = geometry.map(|(vertex_buffer, index_buffer)| {
+            SceneBuffers::new(device, vertex_buffer, index_buffer, &self.scene)
+        });
+        if let Some((culling, buffers)) = self.culling.as_ref().zip(self.scene_buffers.as_mut()) {
+            buffers.culling_bind_group = Some(culling.create_bind_group(
+                device,
+                &self.camera_buffer,
+                &self.object_buffer,
+                &buffers.bounds_buffer,
+                &buffers.indirect_buffer,
+                &buffers.culled_indirect_buffer,
+            ));
+        }
+        // Both sides of the normal map toggle, so flipping it needs no new pipelines
+        let permutations = self
+            .scene
+            .geoms()
+            .flat_map(|geom| {
+                [true, false].map(|normal_maps| {
+                    MaterialPermutation::new(geom.material.enable_bit, normal_maps)
+                })
+            })
+            .collect::<HashSet<_>>();
+        let errors = self.scene_pipelines.prepare(device, permutations);
+        state.shader_errors.extend(errors);
+        self.objects_dirty = true;
+        // Respect the current normal map toggle for the new geometry
+        state.normal_map_changed = true;
+        state.objects = self
+            .scene
+            .geoms()
+            .map(|geom| geom.mesh.name.clone())
+            .collect();
+        let mut normal_mapped = self
+            .scene
+            .geoms()
+            .filter(|geom| geom.material.enable_bit & 2 != 0)
+            .map(|geom| (geom.material.name.clone(), geom.material.normal_mapping))
+            .collect::<Vec<_>>();
+        normal_mapped.sort_by(|a, b| a.0.cmp(&b.0));
+        normal_mapped.dedup_by(|a, b| a.0 == b.0);
+        state.normal_mapped_materials = normal_mapped;
+        // Flips are read from the scene file, which streamed chunks do not have
+        state.object_orientations = self
+            .scene
+            .geoms()
+            .filter(|geom| geom.chunk.is_none())
+            .map(|geom| (geom.mesh.name.clone(), geom.mesh.orientation))
+            .collect();
+        state.object_classes_changed = true;
+        self.set_wireframe_object(device, state.wireframe_object.as_deref());
+        // Geoms were renumbered, the pick may point at another triangle
+        state.picked_triangle = None;
+        self.picked_overlay.clear();
+        self.hover_overlay.clear();
+        self.hovered_object = None;
+    }
+
+    /// Pick the nearest triangle under `ndc` as seen by the main camera and
+    /// outline it, the pick is cleared when nothing is hit. The ray only tests
+    /// the object the id buffer shows there, unless it misses it or the pixel
+    /// has no id, as over blended geometry.
+    pub fn pick_triangle(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        state: &mut AppState,
+        ndc: Vec2,
+    ) {
+        let shown = object_ids::read(device, queue, self.frame_graph.object_ids(), ndc)
+            .unwrap_or_else(|err| {
+                tracing::warn!(
+                    target: logging::RENDER,
+                    "failed to read the object id under the cursor: {}",
+                    err
+                );
+                None
+            });
+        let view_projection = state.projection.calc_matrix() * state.camera.calc_matrix();
+        let (origin, direction) = picking::ray_from_ndc(view_projection, ndc);
+        let nearest = shown
+            .and_then(|object| self.nearest_triangle(origin, direction, Some(object)))
+            .or_else(|| self.nearest_triangle(origin, direction, None));
+        state.picked_triangle = nearest.map(|(i, triangle)| {
+            let geom = self.scene.geom(i);
+            let indices = [0, 1, 2].map(|k| geom.mesh.triangles[triangle * 3 + k]);
+            PickedTriangle {
+                object: geom.mesh.name.clone(),
+                geom: i,
+                triangle,
+                indices,
+                vertices: indices.map(|index| geom.mesh.vertices[index as usize]),
+                mirrored_transform: geom.transform.matrix.determinant() < 0.0,
+            }
+        });
+        match &state.picked_triangle {
+            Some(picked) => {
+                let geom = self.scene.geom(picked.geom);
+                self.picked_overlay.set_lines(
+                    device,
+                    [(
+                        &picked.indices[..],
+                        geom.mesh.base_vertex,
+                        geom.object_index,
+                    )],
+                );
+            }
+            None => {
+                self.picked_overlay.clear();
+                state.notifications.info("No triangle under the cursor");
+            }
+        }
+    }
+
+    /// Object index and triangle the ray hits first, among the geoms with
+    /// object index `only` or all of them.
+    fn nearest_triangle(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        only: Option<usize>,
+    ) -> Option<(usize, usize)> {
+        let mut nearest: Option<(f32, usize, usize)> = None;
+        for geom in self
+            .scene
+            .culled(true)
+            .filter(|geom| only.is_none_or(|object| geom.object_index as usize == object))
+        {
+            let (mesh, transform) = (geom.mesh, geom.transform.matrix);
+            let to_object = transform.inverse();
+            let object_origin = to_object.transform_point3(origin);
+            let object_direction = to_object.transform_vector3(direction);
+            if !picking::intersects_box(object_origin, object_direction, mesh.center, mesh.extent) {
+                continue;
+            }
+            for (triangle, indices) in mesh.triangles.chunks_exact(3).enumerate() {
+                let corners = [0, 1, 2].map(|k| mesh.vertices[indices[k] as usize].position);
+                let Some(t) = picking::intersect_triangle(object_origin, object_direction, corners)
+                else {
+                    continue;
+                };
+                // Distances along the object space ray are scaled by the transform
+                let hit = transform.transform_point3(object_origin + object_direction * t);
+                let distance = hit.distance(origin);
+                if nearest.is_none_or(|(nearest, ..)| distance < nearest) {
+                    nearest = Some((distance, geom.object_index as usize, triangle));
+                }
+            }
+        }
+        nearest.map(|(_, object, triangle)| (object, triangle))
+    }
+
+    /// Drop the outline of the triangle picked before.
+    pub fn clear_picked_triangle(&mut self) {
+        self.picked_overlay.clear();
+    }
+
+    /// Outline the object the id buffer showed under the cursor once its
+    /// readback arrived, call after `poll_frame_stats` polled the device.
+    pub fn poll_hovered_object(&mut self, device: &Device, state: &mut AppState) {
+        let hovered = if state.hover_at.is_some() {
+            match self.hover_readback.read() {
+                Some(hovered) => hovered,
+                None => return,
+            }
+        } else {
+            None
+        };
+        if hovered == self.hovered_object {
+            return;
+        }
+        self.hovered_object = hovered;
+        // The id may be older than a renumbering of the geoms
+        let geoms = self
+            .scene
+            .geoms()
+            .filter(|geom| Some(geom.object_index as usize) == hovered)
+            .collect::<Vec<_>>();
+        state.hovered_object = geoms.first().map(|geom| geom.mesh.name.clone());
+        self.hover_overlay.set_lines(
+            device,
+            geoms.iter().map(|geom| {
+                (
+                    &*geom.mesh.triangles,
+                    geom.mesh.base_vertex,
+                    geom.object_index,
+                )
+            }),
+        );
+    }
+
+    /// Outline the geoms named `name` over the shaded image, none when `None`.
+    pub fn set_wireframe_object(&mut self, device: &Device, name: Option<&str>) {
+        let geoms = self
+            .scene
+            .geoms()
+            .filter(|geom| Some(geom.mesh.name.as_str()) == name)
+            .map(|geom| {
+                (
+                    &*geom.mesh.triangles,
+                    geom.mesh.base_vertex,
+                    geom.object_index,
+                )
+            });
+        self.wireframe.set_lines(device, geoms);
+    }
+
+    /// Write the currently baked reflection probes next to the scene.
+    pub fn save_probe_cache(&self, device: &Device, queue: &Queue, state: &AppState) {
+        let Some(path) = &state.scene_path else {
+            return;
+        };
+        let result = self
+            .reflection_probes
+            .read_cache(device, queue, &state.scene_file.reflection_probes)
+            .and_then(|cache| cache.save(path));
+        match result {
+            Ok(()) => state.notifications.info(format!(
+                "Saved the probe cache {}",
+                ProbeCache::path_for(path).display()
+            )),
+            Err(err) => state
+                .notifications
+                .error(format!("Failed to save the probe cache: {}", err)),
+        }
+    }
+
+    /// Export the currently baked reflection probes, see `probe_export`.
+    pub fn export_probes(&self, device: &Device, queue: &Queue, state: &AppState, path: &Path) {
+        let result = self
+            .reflection_probes
+            .read_cache(device, queue, &state.scene_file.reflection_probes)
+            .and_then(|cache| {
+                probe_export::export_probes(&cache, self.reflection_probes.format(), path)
+            });
+        match result {
+            Ok(()) => state.notifications.info(format!(
+                "Exported {} reflection probes to {}",
+                state.scene_file.reflection_probes.len(),
+                path.display()
+            )),
+            Err(err) => state
+                .notifications
+                .error(format!("Failed to export the reflection probes: {}", err)),
+        }
+    }
+
+    /// Render the scene around the camera into a cube and write it to `path`
+    /// as an equirectangular panorama, see `panorama`. Blocks until the GPU is done.
+    pub fn capture_panorama(&self, device: &Device, queue: &Queue, state: &AppState, path: &Path) {
+        let resolution = state
+            .panorama_settings
+            .face_resolution
+            .min(device.limits().max_texture_dimension_2d);
+        let (znear, zfar) = state.projection.planes();
+        let eye = state.camera.position;
+        let capture = CubeCapture::new(
+            device,
+            self.reflection_probes.face_format(),
+            resolution,
+            &self.camera_bind_group_layout,
+            &probe::cube_face_cameras(eye, znear, zfar),
+        );
+        let draw_path = DrawPath::new(state);
+        let result = capture
+            .render(device, queue, |render_pass, camera_bind_group| {
+                self.draw_geoms(
+                    render_pass,
+                    camera_bind_group,
+                    &self.scene_bind_group,
+                    GeomPass::ProbeBake,
+                    draw_path,
+                    false,
+                );
+                if state.sky.enabled {
+                    self.sky.draw(
+                        render_pass,
+                        camera_bind_group,
+                        &self.scene_bind_group,
+                        SkyTarget::Bake,
+                    );
+                }
+            })
+            .and_then(|faces| {
+                let (yaw, _) = state.camera.orientation();
+                let (width, height, pixels) = panorama::equirectangular(&faces, resolution, yaw);
+                panorama::save(path, width, height, &pixels)
+            });
+        match result {
+            Ok(()) => state
+                .notifications
+                .info(format!("Saved a panorama to {}", path.display())),
+            Err(err) => state
+                .notifications
+                .error(format!("Failed to capture a panorama: {}", err)),
+        }
+    }
+
+    fn scene_uniforms(&self) -> SceneUniforms<'_> {
+        SceneUniforms {
+            light: &self.light_buffer,
+            sky: &self.sky_buffer,
+            mirror_camera: &self.planar_reflection.camera_buffer,
+            matcap: &self.matcap.texture,
+        }
+    }
+
+    /// Recreate the scene bind groups of the main view and the planar
+    /// reflection, after the probes or the reflection texture were replaced.
+    fn rebind_scene(&mut self, device: &Device) {
+        let uniforms = self.scene_uniforms();
+        let [scene_bind_group, mirror_scene_bind_group] = [
+            &self.frame_graph.mirror_color().view,
+            &self.planar_reflection.placeholder.view,
+        ]
+        .map(|reflection| {
+            create_scene_bind_group(
+                device,
+                &self.scene_bind_group_layout,
+                &uniforms,
+                &self.reflection_probes,
+                &self.reflection_probes.uniform_buffer,
+                &self.material_override_buffer,
+                reflection,
+            )
+        });
+        self.scene_bind_group = scene_bind_group;
+        self.mirror_scene_bind_group = mirror_scene_bind_group;
+    }
+
+    /// Store the probes in `format` and rebind everything sampling them.
+    fn replace_probe_storage(
+        &mut self,
+        device: &Device,
+        format: wgpu::TextureFormat,
+    ) -> anyhow::Result<()> {
+        self.reflection_probes.set_storage(device, format)?;
+        self.rebind_scene(device);
+        // Its scene bind group samples the replaced probes as well
+        self.release_split_target();
+        self.previews.clear();
+        if self.probe_convergence.is_some() {
+            self.probe_convergence = Some(ProbeConvergence::new(device, &self.reflection_probes));
+        }
+        Ok(())
+    }
+
+    /// Switch the probes to the storage picked in the UI and bake them again.
+    pub fn set_probe_storage(&mut self, device: &Device, state: &mut AppState) {
+        let format = state
+            .probe_storage
+            .map_or(self.reflection_probes.face_format(), ProbeStorage::format);
+        match self.replace_probe_storage(device, format) {
+            Ok(()) => tracing::info!(
+                target: logging::PROBES,
+                "reflection probes stored as {:?}, {:.1} MiB",
+                format,
+                probe_storage::cube_array_mib(
+                    format,
+                    self.reflection_probes.resolution(),
+                    6 * MAX_REFLECTION_PROBES as u32
+                )
+            ),
+            Err(err) => state
+                .notifications
+                .error(format!("Failed to change the probe storage: {}", err)),
+        }
+        state.bake_reflection_probes = true;
+    }
+
+    /// Bake the probes once as rendered and once per storage format, then write
+    /// a capture comparing them to `path`, see `probe_storage::compare`.
+    pub fn compare_probe_storage(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        state: &mut AppState,
+        path: &Path,
+    ) {
+        let face_format = self.reflection_probes.face_format();
+        let formats = std::iter::once(face_format)
+            .chain(ProbeStorage::ALL.map(ProbeStorage::format))
+            .collect::<Vec<_>>();
+        let probes = state.scene_file.reflection_probes.clone();
+        let mut bake = |format| -> anyhow::Result<_> {
+            self.replace_probe_storage(device, format)?;
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Probe Storage Comparison Encoder"),
+            });
+            let path = DrawPath::new(state);
+            self.reflection_probes.bake(
+                &mut encoder,
+                0..self.reflection_probes.face_count(),
+                |render_pass, camera_bind_group| {
+                    self.draw_geoms(
+                        render_pass,
+                        camera_bind_group,
+                        &self.scene_bind_group,
+                        GeomPass::ProbeBake,
+                        path,
+                        false,
+                    )
+                },
+            );
+            queue.submit(Some(encoder.finish()));
+            Ok((
+                format,
+                self.reflection_probes.read_cache(device, queue, &probes)?,
+            ))
+        };
+        let result = formats
+            .into_iter()
+            .map(&mut bake)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .and_then(|bakes| {
+                let layers = 6 * MAX_REFLECTION_PROBES as u32;
+                let (comparisons, capture) = probe_storage::compare(&bakes, layers)?;
+                capture.save(path)?;
+                Ok(comparisons)
+            });
+        match result {
+            Ok(comparisons) => {
+                for comparison in &comparisons {
+                    tracing::info!(
+                        target: logging::PROBES,
+                        "probes as {:?}: {:.1} MiB, PSNR {}",
+                        comparison.format,
+                        comparison.mib,
+                        comparison
+                            .psnr_db
+                            .map_or("reference".to_owned(), |psnr| format!("{:.1} dB", psnr))
+                    );
+                }
+                state.notifications.info(format!(
+                    "Saved the probe storage comparison {}",
+                    path.display()
+                ));
+                state.probe_storage_comparison = comparisons;
+            }
+            Err(err) => state
+                .notifications
+                .error(format!("Failed to compare probe storage formats: {}", err)),
+        }
+        self.set_probe_storage(device, state);
+    }
+
+    /// Resolve the scene color of `bind_group` onto `view`, which has the surface format.
+    fn present(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &TextureView,
+        bind_group: &wgpu::BindGroup,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass: present"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.present_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Create or resize the region of interest targets while it is enabled. Returns
+    /// the presented region when it was recreated and has to be shown anew.
+    pub fn prepare_roi(
+        &mut self,
+        device: &Device,
+        state: &AppState,
+        config: &SurfaceConfiguration,
+    ) -> Option<&TextureView> {
+        if !state.roi.enabled {
+            self.release_roi_target();
+            return None;
+        }
+        let extent = state.roi.extent(config.width, config.height);
+        if self
+            .roi_target
+            .as_ref()
+            .is_some_and(|roi| roi.extent == extent)
+        {
+            return None;
+        }
+        self.release_roi_target();
+        let hdr_texture =
+            self.texture_pool
+                .acquire(device, extent, self.targets.scene.format, "roi_hdr_texture");
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Region Of Interest Camera Buffer"),
+            size: std::mem::size_of::<UniformCamera>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("Region Of Interest Camera Bind Group"),
+        });
+        // The region target matches the presented size, nothing is scaled
+        let present_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Region Of Interest Present Buffer"),
+            contents: bytemuck::cast_slice(&[UniformPresent::new(state, 1.0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let depth_texture = self.texture_pool.acquire(
+            device,
+            extent,
+            texture::Texture::DEPTH_FORMAT,
+            "roi_depth_texture",
+        );
+        let present_bind_group = create_present_bind_group(
+            device,
+            &self.present_bind_group_layout,
+            &hdr_texture,
+            &depth_texture,
+            &present_buffer,
+        );
+        let roi = self.roi_target.insert(RoiTarget {
+            extent,
+            hdr_texture,
+            depth_texture,
+            output: texture::Texture::create_color_target(
+                device,
+                extent,
+                config.format,
+                "roi_output_texture",
+            ),
+            camera_buffer,
+            camera_bind_group,
+            present_buffer,
+            present_bind_group,
+        });
+        Some(&roi.output.view)
+    }
+
+    /// Drop the region of interest targets, the ones it renders into go back to
+    /// the pool. The presented region is registered with egui and dropped.
+    fn release_roi_target(&mut self) {
+        if let Some(roi) = self.roi_target.take() {
+            self.texture_pool.release(roi.hdr_texture);
+            self.texture_pool.release(roi.depth_texture);
+        }
+    }
+
+    /// Create the targets of new or changed texture previews, see
+    /// `TexturePreviews::prepare`.
+    pub fn prepare_previews(
+        &mut self,
+        device: &Device,
+        state: &AppState,
+        config: &SurfaceConfiguration,
+    ) -> Vec<(usize, &TextureView, [u32; 2])> {
+        let sources = PreviewSources {
+            scene_color: &self.frame_graph.scene_color().view,
+            scene_depth: &self.frame_graph.scene_depth().view,
+            probe_cube: self.reflection_probes.cube_texture(),
+            probe_resolution: self.reflection_probes.resolution(),
+            surface: (config.width, config.height),
+        };
+        self.previews
+            .prepare(device, &state.texture_previews, &sources)
+    }
+
+    /// Render the scene at the scale of `AppState::render_scale`, reallocating
+    /// every target sized after the scene color when it changed.
+    pub fn prepare_render_scale(
+        &mut self,
+        device: &Device,
+        state: &AppState,
+        config: &SurfaceConfiguration,
+    ) {
+        if state.render_scale.scale == self.targets.scene.scale {
+            return;
+        }
+        self.targets.scene.scale = state.render_scale.scale;
+        self.frame_graph = FrameGraph::new(&self.targets);
+        self.resize(device, config);
+    }
+
+    /// Create the post-processing effects again when the stack changed, see
+    /// `PostStack::prepare`, and load the color table once another was picked.
+    pub fn prepare_post(&mut self, device: &Device, queue: &Queue, state: &AppState) {
+        self.post.prepare(device, &state.post_effects);
+        if state.color_lut_changed {
+            let lut = match &state.color_lut {
+                Some(path) => CubeLut::open(path),
+                None => Ok(CubeLut::default()),
+            };
+            match lut {
+                Ok(lut) => self.post.set_color_lut(device, queue, &lut),
+                Err(err) => state
+                    .notifications
+                    .error(format!("Failed to load the color table: {}", err)),
+            }
+        }
+    }
+
+    /// Upload the lines recorded this frame together with the light gizmo and the
+    /// debug views, then start recording the next frame, see `debug_draw`.
+    /// Read the scene shader from `SHADER_DIRECTORY` again when asked or when
+    /// its files changed while watched. Errors go to the shader console and
+    /// the pipelines created before stay in use.
+    pub fn prepare_shaders(&mut self, device: &Device, state: &mut AppState) {
+        let directory = Path::new(SHADER_DIRECTORY);
+        let mut reload = std::mem::take(&mut state.shader_reload_requested);
+        if state.watch_shaders {
+            let modified = ShaderLibrary::modified(directory);
+            reload |= self.shaders_modified.is_some() && modified != self.shaders_modified;
+            self.shaders_modified = modified;
+        } else {
+            self.shaders_modified = None;
+        }
+        if !reload {
+            return;
+        }
+        let reloaded = ShaderLibrary::read(directory)
+            .map_err(|err| vec![err])
+            .and_then(|library| {
+                self.scene_pipelines
+                    .reload(device, &state.capabilities, &library)
+            });
+        match reloaded {
+            Ok(()) => {
+                state.shader_errors.clear();
+                state.notifications.info("Reloaded the scene shader");
+            }
+            Err(errors) => {
+                for err in &errors {
+                    tracing::error!(target: logging::SHADERS, "shader reload failed: {}", err);
+                }
+                state.shader_errors = errors;
+            }
+        }
+    }
+
+    pub fn prepare_debug_draw(&mut self, device: &Device, queue: &Queue, state: &mut AppState) {
+        let light = Vec3::from(state.light_position);
+        let extent = Vec3::splat(LIGHT_GIZMO_EXTENT);
+        state
+            .debug_draw
+            .aabb(light - extent, light + extent, [1.0, 1.0, 1.0]);
+        state.debug_views.record(&self.scene, &mut state.debug_draw);
+        self.debug_draw.prepare(device, queue, &state.debug_draw);
+        state.debug_draw.clear();
+    }
+
+    /// Create or resize the targets of the right side of the split view while it
+    /// is enabled.
+    pub fn prepare_split_view(
+        &mut self,
+        device: &Device,
+        state: &AppState,
+        config: &SurfaceConfiguration,
+    ) {
+        if !state.split_view.enabled {
+            self.release_split_target();
+            return;
+        }
+        let surface = (config.width, config.height);
+        if self
+            .split_target
+            .as_ref()
+            .is_some_and(|split| split.surface == surface)
+        {
+            return;
+        }
+        self.release_split_target();
+        let scene_size = self.targets.scene.extent(config.width, config.height);
+        let hdr_texture = self.texture_pool.acquire(
+            device,
+            scene_size,
+            self.targets.scene.format,
+            "split_hdr_texture",
+        );
+        let depth_texture = self.texture_pool.acquire(
+            device,
+            scene_size,
+            texture::Texture::DEPTH_FORMAT,
+            "split_depth_texture",
+        );
+        let (probes, material_override) = split_uniforms(state);
+        let probe_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Split View Probe Buffer"),
+            contents: bytemuck::cast_slice(&[probes]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let material_override_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Split View Material Override Buffer"),
+                contents: bytemuck::cast_slice(&[material_override]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let scene_bind_group = create_scene_bind_group(
+            device,
+            &self.scene_bind_group_layout,
+            &self.scene_uniforms(),
+            &self.reflection_probes,
+            &probe_buffer,
+            &material_override_buffer,
+            &self.frame_graph.mirror_color().view,
+        );
+        // Tone mapped like the main view so only the compared settings differ
+        let present_bind_group = create_present_bind_group(
+            device,
+            &self.present_bind_group_layout,
+            &hdr_texture,
+            &depth_texture,
+            &self.present_buffer,
+        );
+        self.split_target = Some(SplitTarget {
+            surface,
+            split: state.split_view.split,
+            hdr_texture,
+            depth_texture,
+            probe_buffer,
+            material_override_buffer,
+            scene_bind_group,
+            present_bind_group,
+        });
+    }
+
+    /// Drop the split view targets, their textures go back to the pool.
+    fn release_split_target(&mut self) {
+        if let Some(split) = self.split_target.take() {
+            self.texture_pool.release(split.hdr_texture);
+            self.texture_pool.release(split.depth_texture);
+        }
+    }
+
+    /// Present the right side of the split view over `view`, right of the splitter.
+    fn present_split(&self, encoder: &mut wgpu::CommandEncoder, view: &TextureView) {
+        let Some(split) = &self.split_target else {
+            return;
+        };
+        let (width, height) = split.surface;
+        let column = SplitView::column(split.split, width);
+        if column >= width {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass: present split view"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_scissor_rect(column, 0, width - column, height);
+        render_pass.set_pipeline(&self.present_pipeline);
+        render_pass.set_bind_group(0, &split.present_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Read back the last rendered frame as the surface would show it, without UI.
+    /// Blocks until the GPU finished the frame. Turntables and path captures call
+    /// this every frame, the target comes from the texture pool.
+    pub fn capture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        config: &SurfaceConfiguration,
+    ) -> anyhow::Result<image::RgbaImage> {
+        let bgra = match config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => anyhow::bail!("cannot capture {:?} surfaces", format),
+        };
+        let target = self.texture_pool.acquire(
+            device,
+            (config.width, config.height),
+            config.format,
+            "capture_texture",
+        );
+        let bytes_per_row = config.width * 4;
+        // Rows of a buffer copy have to be aligned
+        let padded_row = bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Readback Buffer"),
+            size: (padded_row * config.height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture"),
+        });
+        self.present(&mut encoder, &target.view, &self.present_bind_group);
+        self.present_split(&mut encoder, &target.view);
+        encoder.copy_texture_to_buffer(
+            target.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(config.height),
+                },
+            },
+            target.texture.size(),
+        );
+        queue.submit(Some(encoder.finish()));
+        self.texture_pool.release(target);
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+        let mut pixels = slice
+            .get_mapped_range()
+            .chunks(padded_row as usize)
+            .flat_map(|row| &row[..bytes_per_row as usize])
+            .copied()
+            .collect::<Vec<_>>();
+        readback.unmap();
+        if bgra {
+            pixels
+                .chunks_exact_mut(4)
+                .for_each(|pixel| pixel.swap(0, 2));
+        }
+        image::RgbaImage::from_raw(config.width, config.height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("capture size mismatch"))
+    }
+
+    /// Center of the world space bounds of the geoms named `object`, or of every geom.
+    pub fn pivot(&self, object: Option<&str>) -> Option<Vec3> {
+        let (min, max) = self.bounds(object)?;
+        Some((min + max) * 0.5)
+    }
+
+    /// World space bounds of the geoms named `object`, or of every geom.
+    pub fn bounds(&self, object: Option<&str>) -> Option<(Vec3, Vec3)> {
+        self.scene
+            .geoms()
+            .filter(|geom| object.is_none_or(|name| geom.mesh.name == name))
+            .map(|geom| geom.world_bounds())
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+    }
+
+    /// Let the frame statistics readback start and reclaim the staging chunks
+    /// and idle pooled textures, call after submitting a frame.
+    pub fn frame_submitted(&mut self) {
+        self.uploads.recall();
+        self.texture_pool.end_frame();
+        if let Some(frame_statistics) = &self.frame_statistics {
+            frame_statistics.submitted();
+        }
+        if let Some(probe_convergence) = &self.probe_convergence {
+            probe_convergence.submitted();
+        }
+        if let Some(pass_timer) = &self.pass_timer {
+            pass_timer.submitted();
+        }
+        if let Some(pass_profiler) = &self.pass_profiler {
+            pass_profiler.submitted();
+        }
+        self.hover_readback.submitted();
+    }
+
+    /// Submit the GI compute deferred by `AppState::async_compute` on its own,
+    /// call after the frame was submitted. wgpu has a single queue, so this only
+    /// lets the driver overlap it with the raster work of the frame before.
+    pub fn submit_gi_compute(&self, device: &Device, queue: &Queue) {
+        let Some((face_count, convergence)) = self
+            .deferred_measure
+            .take()
+            .zip(self.probe_convergence.as_ref())
+        else {
+            return;
+        };
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("GI Compute Encoder"),
+        });
+        let timer = self.pass_timer.as_ref();
+        encoder.push_debug_group("probe convergence");
+        let measured = convergence.measure(
+            &mut encoder,
+            face_count,
+            timer.and_then(PassTimer::compute_writes),
+        );
+        encoder.pop_debug_group();
+        if let Some(timer) = timer {
+            if measured {
+                timer.resolve(&mut encoder);
+            } else {
+                timer.disarm();
+            }
+        }
+        queue.submit(Some(encoder.finish()));
+        convergence.submitted();
+        if let Some(timer) = timer {
+            timer.submitted();
+        }
+    }
+
+    /// Pick up the GI compute and raster timings of an earlier frame, call after
+    /// `poll_frame_stats` polled the device.
+    pub fn poll_pass_timings(&self, state: &mut AppState) {
+        if let Some(timings) = self.pass_timer.as_ref().and_then(PassTimer::read) {
+            state.pass_timings = Some(timings);
+        }
+    }
+
+    /// Hand the pass timings of earlier frames to the running benchmark, call
+    /// after `poll_frame_stats` polled the device.
+    pub fn poll_pass_profiles(&self, state: &mut AppState) {
+        let Some(pass_profiler) = &self.pass_profiler else {
+            return;
+        };
+        for profile in pass_profiler.read() {
+            if let Some(benchmark) = &mut state.benchmark {
+                benchmark.record_gpu(profile);
+            }
+        }
+    }
+
+    /// Names of the render graph passes in execution order, the columns of the
+    /// benchmark pass breakdown.
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.frame_graph
+            .graph
+            .passes()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Judge the last measured probe refresh and keep baking until another bounce
+    /// changes less than the threshold, call after `poll_frame_stats` polled the device.
+    pub fn poll_probe_convergence(&self, state: &mut AppState) {
+        let Some(change) = self
+            .probe_convergence
+            .as_ref()
+            .and_then(ProbeConvergence::read)
+        else {
+            return;
+        };
+        let threshold = state.probe_convergence_threshold;
+        let Some(run) = state
+            .probe_convergence
+            .as_mut()
+            .filter(|run| run.is_running())
+        else {
+            return;
+        };
+        run.refreshes += 1;
+        run.last_change = Some(change);
+        run.converged = change <= threshold;
+        if run.converged || run.refreshes >= MAX_CONVERGENCE_REFRESHES {
+            let elapsed_ms = run.started.elapsed().as_secs_f32() * 1000.0;
+            run.elapsed_ms = Some(elapsed_ms);
+            if run.converged {
+                state.notifications.info(format!(
+                    "Reflection probes converged after {} bounces, {} frames / {:.0} ms",
+                    run.refreshes, run.frames, elapsed_ms
+                ));
+            } else {
+                state.notifications.warn(format!(
+                    "Reflection probes still change by {:.3}% after {} bounces",
+                    change * 100.0,
+                    run.refreshes
+                ));
+            }
+        } else {
+            state.bake_reflection_probes = true;
+        }
+    }
+
+    /// Pick up the statistics of an earlier frame if their readback finished.
+    pub fn poll_frame_stats(&self, device: &Device, state: &mut AppState) {
+        device.poll(wgpu::Maintain::Poll);
+        let Some(stats) = self
+            .frame_statistics
+            .as_ref()
+            .and_then(FrameStatistics::read)
+        else {
+            return;
+        };
+        let had_invalid = state
+            .frame_stats
+            .is_some_and(|last| last.nan_count + last.inf_count > 0);
+        if stats.nan_count + stats.inf_count > 0 && !had_invalid {
+            tracing::warn!(
+                target: logging::RENDER,
+                "rendered frames contain {} NaN and {} Inf pixels",
+                stats.nan_count,
+                stats.inf_count
+            );
+            if let Some((nan_count, inf_count)) = stats.opaque_invalid {
+                tracing::warn!(
+                    target: logging::RENDER,
+                    "{} NaN and {} Inf pixels already after the opaque pass",
+                    nan_count,
+                    inf_count
+                );
+            }
+        }
+        state.frame_stats = Some(stats);
+    }
+
+    /// Record the uniforms that change from frame to frame into `encoder`
+    /// through the staging belt: the camera, the light and, when they changed,
+    /// the objects with their enable bits. Call after `update` and before
+    /// `render`, then `frame_submitted` once the encoder was submitted.
+    pub fn upload(
+        &mut self,
+        device: &Device,
+        encoder: &mut wgpu::CommandEncoder,
+        state: &AppState,
+    ) {
+        let camera = UniformCamera::from_camera_project(&state.camera, &state.projection);
+        self.uploads
+            .write(device, encoder, &self.camera_buffer, &[camera]);
+        let light = match state.sky.light_direction() {
+            Some(sun) => primitives::UniformLight::new(sun.extend(0.0)),
+            None => self.scene.light(),
+        };
+        self.uploads
+            .write(device, encoder, &self.light_buffer, &[light]);
+        if std::mem::take(&mut self.objects_dirty) {
+            // Every `UniformObject` in a single upload
+            let objects = self.scene.objects(
+                state.enable_normal_map,
+                self.active_mirror,
+                &state.scene_file.normal_maps,
+            );
+            self.uploads
+                .write(device, encoder, &self.object_buffer, &objects);
+        }
+        self.uploads.finish();
+    }
+
+    /// `None` while the permutation of the geom failed to build.
+    fn pipelines(&self, geom: &Geom) -> Option<&ScenePipelines> {
+        self.scene_pipelines.get(MaterialPermutation::new(
+            geom.material.enable_bit,
+            self.normal_maps,
+        ))
+    }
+
+    /// Bind the shared scene geometry and objects, `None` while there is no geometry.
+    fn set_scene_buffers(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        scene_bind_group: &wgpu::BindGroup,
+    ) -> Option<&SceneBuffers> {
+        let buffers = self.scene_buffers.as_ref()?;
+        render_pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, scene_bind_group, &[]);
+        render_pass.set_bind_group(3, &self.object_bind_group, &[]);
+        Some(buffers)
+    }
+
+    /// Draw the opaque and alpha masked geometry.
+    fn draw_geoms(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        scene_bind_group: &wgpu::BindGroup,
+        pass: GeomPass,
+        path: DrawPath,
+        culled: bool,
+    ) {
+        let Some(buffers) =
+            self.set_scene_buffers(render_pass, camera_bind_group, scene_bind_group)
+        else {
+            return;
+        };
+        let indirect_buffer = buffers.indirect_buffer(culled);
+        let geoms = self
+            .scene
+            .culled(pass != GeomPass::ProbeBake)
+            .filter(|geom| !(geom.material.transparent || pass == GeomPass::Mirror && geom.mirror))
+            .collect::<Vec<_>>();
+        // Geoms are sorted by pipeline and material, a run only breaks where the
+        // state changes or a geom was culled
+        for batch in geoms.chunk_by(|a, b| {
+            a.material.cull_mode == b.material.cull_mode
+                && Arc::ptr_eq(&a.material.bind_group, &b.material.bind_group)
+                && a.object_index + 1 == b.object_index
+        }) {
+            let Some(pipelines) = self.pipelines(&batch[0]) else {
+                continue;
             };
-            let material_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &material_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: material_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: enable_bit_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&color_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 3,
-                        resource: wgpu::BindingResource::Sampler(&color_texture.sampler),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 4,
-                        resource: wgpu::BindingResource::TextureView(&normal_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 5,
-                        resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
-                    },
-                ],
-                label: Some(format!("Material Bind Group: {}", model.name()).as_str()),
-            });
-            geoms.push(Geom {
-                vertex_buffer,
-                index_buffer,
-                material_bind_group,
-                enable_bit,
-                enable_bit_buffer,
-                model,
-            });
+            let cull_mode = batch[0].material.cull_mode;
+            let pipeline = match pass {
+                GeomPass::Main => pipelines.main(false, cull_mode),
+                GeomPass::ObjectIds => pipelines.ids(cull_mode),
+                GeomPass::ProbeBake => &pipelines.probe,
+                GeomPass::Mirror => pipelines.main(false, CullMode::None),
+            };
+            Self::draw_batch(render_pass, indirect_buffer, pipeline, batch, path);
         }
-        let debug_renderer = DefaultDebugRenderer::new(
-            device,
-            config,
-            queue,
-            state,
-            &light_buffer,
-            &camera_bind_group_layout,
-        );
-        Self {
-            render_pipeline,
-            camera_bind_group,
-            camera_buffer,
-            light_buffer,
-            scene_bind_group,
-            depth_texture,
-            debug_renderer,
-            geoms,
+    }
+
+    /// Draw the blended geometry sorted back to front as seen from `eye`.
+    fn draw_transparent_geoms(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        scene_bind_group: &wgpu::BindGroup,
+        eye: Vec3,
+        path: DrawPath,
+        culled: bool,
+    ) {
+        let mut transparent = self
+            .scene
+            .culled(true)
+            .filter(|geom| geom.material.transparent)
+            .map(|geom| {
+                let center = geom.transform.matrix.transform_point3(geom.mesh.center);
+                (center.distance_squared(eye), geom)
+            })
+            .collect::<Vec<_>>();
+        transparent.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        let Some(buffers) =
+            self.set_scene_buffers(render_pass, camera_bind_group, scene_bind_group)
+        else {
+            return;
+        };
+        let indirect_buffer = buffers.indirect_buffer(culled);
+        for (_, geom) in transparent {
+            let Some(pipelines) = self.pipelines(&geom) else {
+                continue;
+            };
+            let pipeline = pipelines.main(true, geom.material.cull_mode);
+            Self::draw_batch(render_pass, indirect_buffer, pipeline, &[geom], path);
         }
     }
-}
 
-impl RenderStage<crate::AppState> for DefaultRenderer {
-    fn render(
+    /// Refresh the reflection probe faces due this frame and measure how far a
+    /// finished refresh converged.
+    fn bake_probes(
         &self,
-        _state: &mut AppState,
-        view: &TextureView,
+        state: &mut AppState,
         encoder: &mut wgpu::CommandEncoder,
+        path: DrawPath,
     ) {
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass: everything"),
-            color_attachments: &[
-                // This is what @location(0) in the fragment shader targets
-                Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+        let convergence = match state.probe_convergence.as_mut() {
+            Some(run) if run.is_running() => {
+                run.frames += 1;
+                self.probe_convergence.as_ref()
+            }
+            _ => None,
+        };
+        let total_faces = self.reflection_probes.face_count();
+        if let Some(faces) = state
+            .probe_bake
+            .next_faces(total_faces, state.frame_time_ms)
+        {
+            // Each refresh is compared with the one before, which lit it
+            if let Some(convergence) = convergence.filter(|_| faces.start == 0) {
+                convergence.snapshot(encoder, &self.reflection_probes);
+            }
+            let refresh_done = faces.end == total_faces;
+            self.reflection_probes
+                .bake(encoder, faces, |render_pass, camera_bind_group| {
+                    self.draw_geoms(
+                        render_pass,
+                        camera_bind_group,
+                        &self.scene_bind_group,
+                        GeomPass::ProbeBake,
+                        path,
+                        false,
+                    );
+                    if state.sky.enabled {
+                        self.sky.draw(
+                            render_pass,
+                            camera_bind_group,
+                            &self.scene_bind_group,
+                            SkyTarget::Bake,
+                        );
+                    }
+                });
+            if refresh_done && std::mem::take(&mut state.announce_probe_bake) {
+                state.notifications.info(format!(
+                    "Baked {} reflection probes in {} frames",
+                    state.scene_file.reflection_probes.len(),
+                    state.probe_bake.last_refresh_frames
+                ));
+            }
+            if let Some(convergence) = convergence.filter(|_| refresh_done) {
+                if let Some(pass_timer) = &self.pass_timer {
+                    pass_timer.arm();
+                }
+                if state.async_compute {
+                    self.deferred_measure.set(Some(total_faces));
+                } else if !convergence.measure(
+                    encoder,
+                    total_faces,
+                    self.pass_timer.as_ref().and_then(PassTimer::compute_writes),
+                ) {
+                    if let Some(pass_timer) = &self.pass_timer {
+                        pass_timer.disarm();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Opaque geometry, clearing the target first, and the sky behind it when
+    /// the target asks for it.
+    fn render_opaque(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &ViewTarget,
+        pass: GeomPass,
+        path: DrawPath,
+        culled: bool,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let color_attachments = [
+            // This is what @location(0) in the fragment shader targets
+            Some(wgpu::RenderPassColorAttachment {
+                view: target.color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.0,
+                        g: 0.0,
+                        b: 0.0,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            }),
+            // @location(1) of the object id entry points, zero means no object
+            target
+                .object_ids
+                .map(|view| wgpu::RenderPassColorAttachment {
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
                         store: wgpu::StoreOp::Store,
                     },
                 }),
-            ],
+        ];
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass: everything"),
+            color_attachments: &color_attachments[..1 + target.object_ids.is_some() as usize],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.view,
+                view: target.depth,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Clear(1.0),
                     store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
             }),
+            timestamp_writes,
+            occlusion_query_set: None,
+        });
+        self.draw_geoms(
+            &mut render_pass,
+            target.camera_bind_group,
+            target.scene_bind_group,
+            pass,
+            path,
+            culled,
+        );
+        // Last, so the depth test skips everything covered
+        if target.sky {
+            self.sky.draw(
+                &mut render_pass,
+                target.camera_bind_group,
+                target.scene_bind_group,
+                if target.object_ids.is_some() {
+                    SkyTarget::ObjectIds
+                } else {
+                    SkyTarget::View
+                },
+            );
+        }
+    }
+
+    /// Blended geometry on top of everything opaque, sorted back to front from `eye`.
+    fn render_transparent(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &ViewTarget,
+        eye: Vec3,
+        path: DrawPath,
+        culled: bool,
+    ) {
+        if !self
+            .scene
+            .culled(true)
+            .any(|geom| geom.material.transparent)
+        {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass: transparent"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: target.depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
             timestamp_writes: None,
             occlusion_query_set: None,
         });
-        render_pass.set_pipeline(&self.render_pipeline);
-        for Geom {
-            vertex_buffer,
-            index_buffer,
-            material_bind_group,
+        self.draw_transparent_geoms(
+            &mut render_pass,
+            target.camera_bind_group,
+            target.scene_bind_group,
+            eye,
+            path,
+            culled,
+        );
+    }
+
+    /// The ground grid over the opaque geometry, see `ground_grid`.
+    fn render_ground_grid(&self, encoder: &mut wgpu::CommandEncoder, target: &ViewTarget) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass: ground grid"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: target.depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        self.ground_grid
+            .draw(&mut render_pass, target.camera_bind_group);
+    }
+
+    /// Lines of the wireframe object, the hovered object and the picked triangle
+    /// over the shaded and blended geometry.
+    fn render_wireframe(&self, encoder: &mut wgpu::CommandEncoder, target: &ViewTarget) {
+        let Some(buffers) = self.scene_buffers.as_ref().filter(|_| {
+            !self.wireframe.is_empty()
+                || !self.picked_overlay.is_empty()
+                || !self.hover_overlay.is_empty()
+        }) else {
+            return;
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass: wireframe"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: target.depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+        render_pass.set_bind_group(0, target.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.object_bind_group, &[]);
+        self.wireframe.draw(&mut render_pass);
+        self.hover_overlay.draw(&mut render_pass);
+        self.picked_overlay.draw(&mut render_pass);
+    }
+
+    /// Count what the visible geoms cost to rasterize and color the main view
+    /// with it, see `heatmap`.
+    fn render_heatmap(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &ViewTarget,
+        heatmap: Heatmap,
+    ) {
+        let Some(buffers) = self
+            .scene_buffers
+            .as_ref()
+            .filter(|_| heatmap != Heatmap::Off)
+        else {
+            return;
+        };
+        let draws = self
+            .scene
+            .culled(true)
+            .map(|geom| HeatDraw {
+                indices: geom.mesh.indices.clone(),
+                base_vertex: geom.mesh.base_vertex,
+                object_index: geom.object_index,
+                cull_mode: geom.material.cull_mode,
+            })
+            .collect::<Vec<_>>();
+        let geometry = HeatGeometry {
+            vertex_buffer: &buffers.vertex_buffer,
+            index_buffer: &buffers.index_buffer,
+            camera_bind_group: target.camera_bind_group,
+            object_bind_group: &self.object_bind_group,
+        };
+        self.heatmap.record(
+            encoder,
+            heatmap,
+            &geometry,
+            &draws,
+            &self.frame_graph.overdraw().view,
+            target.color,
+        );
+    }
+
+    /// Lines recorded with `AppState::debug_draw` over everything else.
+    fn render_debug_draw(&self, encoder: &mut wgpu::CommandEncoder, target: &ViewTarget) {
+        if self.debug_draw.is_empty() {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass: debug draw"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: target.depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        self.debug_draw
+            .draw(&mut render_pass, target.camera_bind_group);
+    }
+
+    /// Draw consecutive geoms sharing `pipeline` and their material, indirect
+    /// paths read their draws from `indirect_buffer`. Captures show the batch
+    /// as a group named after the material, with a marker naming each geom.
+    fn draw_batch(
+        render_pass: &mut wgpu::RenderPass,
+        indirect_buffer: &wgpu::Buffer,
+        pipeline: &RenderPipeline,
+        batch: &[Geom],
+        path: DrawPath,
+    ) {
+        render_pass.push_debug_group(&format!("Material {}", batch[0].material.name));
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(1, batch[0].material.bind_group.as_ref(), &[]);
+        let indirect_offset = |geom: &Geom| {
+            geom.object_index as wgpu::BufferAddress
+                * std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as wgpu::BufferAddress
+        };
+        let marker = |geom: &Geom| format!("Geom {} {}", geom.object_index, geom.mesh.name);
+        match path {
+            DrawPath::Direct => {
+                for geom in batch {
+                    render_pass.insert_debug_marker(&marker(geom));
+                    let instance = geom.object_index..geom.object_index + 1;
+                    render_pass.draw_indexed(
+                        geom.mesh.indices.clone(),
+                        geom.mesh.base_vertex,
+                        instance,
+                    );
+                }
+            }
+            DrawPath::Indirect => {
+                for geom in batch {
+                    render_pass.insert_debug_marker(&marker(geom));
+                    render_pass.draw_indexed_indirect(indirect_buffer, indirect_offset(geom));
+                }
+            }
+            // A single draw covers the batch, the markers in front list its geoms
+            DrawPath::MultiDrawIndirect => {
+                for geom in batch {
+                    render_pass.insert_debug_marker(&marker(geom));
+                }
+                render_pass.multi_draw_indexed_indirect(
+                    indirect_buffer,
+                    indirect_offset(&batch[0]),
+                    batch.len() as u32,
+                );
+            }
+        }
+        render_pass.pop_debug_group();
+    }
+
+    fn create_geom(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        upload: &mut SceneUpload,
+        transform: Mat4,
+        joint_offset: Option<u32>,
+        mesh: MeshData,
+    ) -> GeomBundle {
+        let _span = tracing::error_span!(target: logging::SCENE, "geom", name = mesh.model.name())
+            .entered();
+        logging::validated(device, || {
+            self.upload_geom(device, queue, upload, transform, joint_offset, mesh)
+        })
+    }
+
+    fn upload_geom(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        upload: &mut SceneUpload,
+        transform: Mat4,
+        joint_offset: Option<u32>,
+        mesh: MeshData,
+    ) -> GeomBundle {
+        let MeshData {
             model,
-            ..
-        } in &self.geoms
+            node,
+            vertex_data,
+            indices,
+            material,
+            orientation,
+        } = mesh;
+        let triangles = indices;
+        let (base_vertex, vertex_count, indices) = upload.push(&vertex_data, &triangles);
+        // Meshes without material keep the old behaviour of showing both sides
+        let cull_mode = material.as_ref().map_or(CullMode::None, |m| m.cull_mode);
+        let material = material.unwrap_or_default();
+        let transparent = material.alpha_mode == AlphaMode::Blend;
+        let (min, max) = model.vertices().iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), v| (min.min(*v), max.max(*v)),
+        );
+        let center = (min + max) * 0.5;
+        let extent = (max - min) * 0.5;
+        let uniform_material = UniformMaterial::from(&material);
+        // Same order as the enable bits, data maps are sampled as linear values
+        let maps = [
+            (material.color_texture, "Color", false),
+            (material.normal_texture, "Normal", true),
+            (material.specular_texture, "Specular", false),
+            (material.roughness_texture, "Roughness", true),
+            (
+                material.ambient_occlusion_texture,
+                "Ambient Occlusion",
+                true,
+            ),
+            (material.alpha_texture, "Alpha", true),
+        ]
+        .map(|(img, kind, linear)| {
+            img.and_then(|img| {
+                let label = format!("{} Texture: {}", kind, model.name());
+                cached_texture(device, queue, &mut upload.textures, img, label, linear)
+            })
+        });
+        let mut enable_bit = maps
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, map)| bits | ((map.is_some() as u32) << i));
+        if maps[1]
+            .as_ref()
+            .is_some_and(|map| map.texture.format().components() == 2)
+        {
+            enable_bit |= TWO_CHANNEL_NORMALS_BIT;
+        }
+        let maps = maps.map(|map| {
+            map.unwrap_or_else(|| {
+                upload
+                    .empty_texture
+                    .get_or_init(|| {
+                        Rc::new(texture::Texture::empty(
+                            device,
+                            queue,
+                            Some("Empty Texture"),
+                        ))
+                    })
+                    .clone()
+            })
+        });
+        let material_bind_group = upload.material_bind_group(
+            device,
+            &self.material_bind_group_layout,
+            &maps,
+            model.name(),
+        );
+        (
+            scene_world::Mesh {
+                base_vertex,
+                vertex_count,
+                indices,
+                center,
+                extent,
+                vertices: vertex_data.into_boxed_slice(),
+                triangles,
+                name: model.name().to_owned(),
+                orientation,
+            },
+            scene_world::Material {
+                name: material.name,
+                bind_group: material_bind_group,
+                uniform: uniform_material,
+                normal_mapping: material.normal_mapping,
+                enable_bit,
+                transparent,
+                cull_mode,
+            },
+            scene_world::Transform {
+                node,
+                matrix: transform,
+                joint_offset,
+            },
+        )
+    }
+
+    /// Bookkeeping of a frame, call before any stage renders.
+    pub fn begin_frame(&self, state: &mut AppState) {
+        if std::mem::take(&mut state.converge_reflection_probes) && self.probe_convergence.is_some()
         {
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_bind_group(1, material_bind_group, &[]);
-            render_pass.set_bind_group(2, &self.scene_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.draw_indexed(0..model.vertex_count(), 0, 0..1);
+            state.probe_convergence = Some(ConvergenceRun::new());
+            state.bake_reflection_probes = true;
         }
+        // The probes see the sky behind the scene and are lit by it
+        if state.sky_changed {
+            state.bake_reflection_probes = true;
+        }
+        if self.static_geometry_moved && !state.probe_bake.is_pending() {
+            state.bake_reflection_probes = true;
+        }
+        if state.bake_reflection_probes && !self.scene.is_empty() {
+            state.probe_bake.request();
+            state.bake_reflection_probes = false;
+        }
+        if let Some(profiler) = self.profiler(state) {
+            profiler.begin(state.frame_index);
+        }
+    }
+
+    /// Call after every stage rendered.
+    pub fn end_frame(&self, state: &AppState, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(profiler) = self.profiler(state) {
+            profiler.resolve(encoder);
+        }
+    }
 
-        self.debug_renderer
-            .render(&mut render_pass, &self.camera_bind_group);
+    /// Times the passes of frames the benchmark records.
+    fn profiler(&self, state: &AppState) -> Option<&PassProfiler> {
+        self.pass_profiler
+            .as_ref()
+            .filter(|_| state.benchmark.as_ref().is_some_and(Benchmark::recording))
     }
 
-    fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
-        self.depth_texture =
-            texture::Texture::create_depth_texture(device, config, "depth_texture");
+    /// Culls the draws of the main passes on the GPU, probes see the scene from
+    /// their own cameras and are never culled.
+    fn gpu_culling(
+        &self,
+        state: &AppState,
+        path: DrawPath,
+    ) -> Option<(&GpuCulling, &wgpu::BindGroup)> {
+        let culling = self.culling.as_ref().zip(
+            self.scene_buffers
+                .as_ref()
+                .and_then(|buffers| buffers.culling_bind_group.as_ref()),
+        );
+        culling.filter(|_| state.gpu_culling && path != DrawPath::Direct)
+    }
+
+    /// Record the passes of the frame graph belonging to `stage`, in the order
+    /// of the graph.
+    pub fn render_stage(
+        &self,
+        stage: StageKind,
+        state: &mut AppState,
+        view: &TextureView,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let path = DrawPath::new(state);
+        let main_target = ViewTarget {
+            color: &self.frame_graph.scene_color().view,
+            depth: &self.frame_graph.scene_depth().view,
+            object_ids: Some(&self.frame_graph.object_ids().view),
+            camera_bind_group: &self.camera_bind_group,
+            scene_bind_group: &self.scene_bind_group,
+            sky: state.sky.enabled,
+        };
+        let culled = self.gpu_culling(state, path).is_some();
+        let profiler = self.profiler(state);
+        for (index, (name, pass)) in self.frame_graph.graph.passes().enumerate() {
+            if pass.stage() != stage {
+                continue;
+            }
+            // Error level so the span stays around whatever errors are logged
+            let _span = tracing::error_span!(target: logging::RENDER, "pass", name).entered();
+            // Groups the commands of the pass in RenderDoc, PIX and Xcode captures
+            encoder.push_debug_group(name);
+            if let Some(profiler) = profiler {
+                profiler.write(encoder, index, false);
+            }
+            match pass {
+                // Every refresh is lit by the one before, paused it would keep converging
+                FramePass::ProbeBake if state.time_frozen => {}
+                FramePass::ProbeBake => self.bake_probes(state, encoder, path),
+                FramePass::Culling => {
+                    if let Some((culling, bind_group)) = self.gpu_culling(state, path) {
+                        culling.record(encoder, bind_group, self.scene.len() as u32);
+                    }
+                }
+                FramePass::PlanarReflection => {
+                    if self.active_mirror.is_some() {
+                        let mirror_target = ViewTarget {
+                            color: &self.frame_graph.mirror_color().view,
+                            depth: &self.frame_graph.mirror_depth().view,
+                            object_ids: None,
+                            camera_bind_group: &self.planar_reflection.camera_bind_group,
+                            scene_bind_group: &self.mirror_scene_bind_group,
+                            sky: state.sky.enabled,
+                        };
+                        self.render_opaque(
+                            encoder,
+                            &mirror_target,
+                            GeomPass::Mirror,
+                            path,
+                            false,
+                            None,
+                        );
+                    }
+                }
+                FramePass::Opaque => {
+                    self.render_opaque(
+                        encoder,
+                        &main_target,
+                        GeomPass::ObjectIds,
+                        path,
+                        culled,
+                        self.pass_timer.as_ref().and_then(PassTimer::raster_writes),
+                    );
+                    if let Some(ndc) = state.hover_at {
+                        self.hover_readback
+                            .record(encoder, self.frame_graph.object_ids(), ndc);
+                    }
+                    // A deferred GI compute resolves in its own submission
+                    if let Some(pass_timer) = self
+                        .pass_timer
+                        .as_ref()
+                        .filter(|_| self.deferred_measure.get().is_none())
+                    {
+                        pass_timer.resolve(encoder);
+                    }
+                }
+                // Tell which pass produced invalid pixels
+                FramePass::OpaqueStatistics => {
+                    if let Some(frame_statistics) =
+                        self.frame_statistics.as_ref().filter(|_| state.nan_guard)
+                    {
+                        frame_statistics.record(
+                            encoder,
+                            self.frame_graph.scene_color(),
+                            StatsStage::Opaque,
+                        );
+                    }
+                }
+                FramePass::GroundGrid => {
+                    if state.ground_grid.enabled {
+                        self.render_ground_grid(encoder, &main_target);
+                    }
+                }
+                FramePass::Transparent => self.render_transparent(
+                    encoder,
+                    &main_target,
+                    state.camera.position,
+                    path,
+                    culled,
+                ),
+                FramePass::VolumetricFog => {
+                    if let Some(fog) = self
+                        .volumetric_fog
+                        .as_ref()
+                        .filter(|_| state.volumetric_fog.enabled)
+                    {
+                        fog.record(encoder, &self.scene_bind_group, main_target.color);
+                    }
+                }
+                FramePass::Heatmap => self.render_heatmap(encoder, &main_target, state.heatmap),
+                FramePass::Wireframe => self.render_wireframe(encoder, &main_target),
+                FramePass::DebugDraw => self.render_debug_draw(encoder, &main_target),
+                FramePass::FinalStatistics => {
+                    if let Some(frame_statistics) = self
+                        .frame_statistics
+                        .as_ref()
+                        .filter(|_| state.frame_stats_enabled || state.nan_guard)
+                    {
+                        frame_statistics.record(
+                            encoder,
+                            self.frame_graph.scene_color(),
+                            StatsStage::Final,
+                        );
+                    }
+                }
+                FramePass::PostProcess => self.post.run(encoder, &state.post_effects),
+                FramePass::TexturePreviews => self.previews.render(encoder),
+                FramePass::SplitView => {
+                    if let Some(split) = &self.split_target {
+                        let split_target = ViewTarget {
+                            color: &split.hdr_texture.view,
+                            depth: &split.depth_texture.view,
+                            object_ids: None,
+                            camera_bind_group: &self.camera_bind_group,
+                            scene_bind_group: &split.scene_bind_group,
+                            sky: state.sky.enabled,
+                        };
+                        self.render_opaque(
+                            encoder,
+                            &split_target,
+                            GeomPass::Main,
+                            path,
+                            culled,
+                            None,
+                        );
+                        self.render_transparent(
+                            encoder,
+                            &split_target,
+                            state.camera.position,
+                            path,
+                            culled,
+                        );
+                        self.render_wireframe(encoder, &split_target);
+                        self.render_debug_draw(encoder, &split_target);
+                    }
+                }
+                FramePass::RegionOfInterest => {
+                    if let Some(roi) = &self.roi_target {
+                        let roi_target = ViewTarget {
+                            color: &roi.hdr_texture.view,
+                            depth: &roi.depth_texture.view,
+                            object_ids: None,
+                            camera_bind_group: &roi.camera_bind_group,
+                            scene_bind_group: &self.scene_bind_group,
+                            sky: state.sky.enabled,
+                        };
+                        self.render_opaque(
+                            encoder,
+                            &roi_target,
+                            GeomPass::Main,
+                            path,
+                            culled,
+                            None,
+                        );
+                        self.render_transparent(
+                            encoder,
+                            &roi_target,
+                            state.camera.position,
+                            path,
+                            culled,
+                        );
+                        self.render_wireframe(encoder, &roi_target);
+                        self.render_debug_draw(encoder, &roi_target);
+                        self.present(encoder, &roi.output.view, &roi.present_bind_group);
+                    }
+                }
+                FramePass::Present => {
+                    let bind_group = match self.post.output(&state.post_effects) {
+                        PostOutput::SceneColor => &self.present_bind_group,
+                        PostOutput::Target(i) => &self.post_present_bind_groups[i],
+                    };
+                    self.present(encoder, view, bind_group);
+                }
+                FramePass::PresentSplit => self.present_split(encoder, view),
+            }
+            if let Some(profiler) = profiler {
+                profiler.write(encoder, index, true);
+            }
+            encoder.pop_debug_group();
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.frame_graph.graph.allocate(
+            device,
+            &mut self.texture_pool,
+            (config.width, config.height),
+        );
+        // The reflection texture was allocated again
+        self.rebind_scene(device);
+        self.present_bind_group = create_present_bind_group(
+            device,
+            &self.present_bind_group_layout,
+            self.frame_graph.scene_color(),
+            self.frame_graph.scene_depth(),
+            &self.present_buffer,
+        );
+        self.post.resize(
+            device,
+            self.frame_graph.scene_color(),
+            self.frame_graph.scene_depth(),
+        );
+        self.post_present_bind_groups = create_post_present_bind_groups(
+            device,
+            &self.present_bind_group_layout,
+            &self.post,
+            self.frame_graph.scene_depth(),
+            &self.present_buffer,
+        );
+        if let Some(frame_statistics) = &mut self.frame_statistics {
+            frame_statistics.resize(device, self.frame_graph.scene_color());
+        }
+        if let Some(fog) = &mut self.volumetric_fog {
+            fog.resize(device, self.frame_graph.scene_depth());
+        }
+        self.heatmap.resize(
+            device,
+            self.frame_graph.overdraw(),
+            self.frame_graph.scene_depth(),
+        );
+        // Recreated at the new size by `prepare_split_view` and `prepare_previews`
+        self.release_split_target();
+        self.previews.clear();
     }
 
-    fn update(&mut self, state: &crate::AppState, queue: &wgpu::Queue) {
-        if state.normal_map_changed {
-            for geom in &self.geoms {
-                let enable_bit = geom.enable_bit & ((state.enable_normal_map as u32) << 1 | 1);
+    pub fn update(&mut self, state: &crate::AppState, queue: &wgpu::Queue) {
+        self.post.update(
+            queue,
+            &state.post_effects,
+            state.projection.calc_matrix() * state.camera.calc_matrix(),
+        );
+        self.previews.update(
+            queue,
+            &state.texture_previews,
+            state.projection.planes(),
+            state.projection.mode() == ProjectionMode::Orthographic,
+        );
+        // The contours follow the clip planes, which move with the camera
+        let present_changed = state.nan_guard_changed
+            || state.depth_contours_changed
+            || state.depth_contours.enabled
+            || state.render_scale_changed;
+        if present_changed {
+            queue.write_buffer(
+                &self.present_buffer,
+                0,
+                bytemuck::cast_slice(&[UniformPresent::new(state, self.targets.scene.scale)]),
+            );
+        }
+        if let Some(roi) = &self.roi_target {
+            if present_changed {
+                queue.write_buffer(
+                    &roi.present_buffer,
+                    0,
+                    bytemuck::cast_slice(&[UniformPresent::new(state, 1.0)]),
+                );
+            }
+            let view_projection = state.projection.calc_matrix() * state.camera.calc_matrix();
+            queue.write_buffer(
+                &roi.camera_buffer,
+                0,
+                bytemuck::cast_slice(&[UniformCamera::from_matrix(
+                    state.roi.crop() * view_projection,
+                    state.camera.position,
+                )]),
+            );
+        }
+        if state.ground_grid.enabled {
+            self.ground_grid.update(queue, &state.ground_grid);
+        }
+        if let Some(fog) = self
+            .volumetric_fog
+            .as_ref()
+            .filter(|_| state.volumetric_fog.enabled)
+        {
+            let view = state.camera.calc_matrix();
+            fog.update(
+                queue,
+                &state.volumetric_fog,
+                &FogView {
+                    view_projection: state.projection.calc_matrix() * view,
+                    position: state.camera.position,
+                    forward: view.inverse().transform_vector3(Vec3::NEG_Z),
+                    planes: state.projection.planes(),
+                    orthographic: state.projection.mode() == ProjectionMode::Orthographic,
+                },
+            );
+        }
+        // The depth channel follows the clip planes, which move with the camera
+        let override_changed = state.material_override_changed
+            || state.channel_view_changed
+            || state.channel_view == ChannelView::Depth;
+        if override_changed {
+            queue.write_buffer(
+                &self.material_override_buffer,
+                0,
+                bytemuck::cast_slice(&[UniformMaterialOverride::new(
+                    state.material_override,
+                    state.channel_view,
+                    state.projection.planes(),
+                )]),
+            );
+        }
+        if state.matcap_changed {
+            if let Err(err) = self.matcap.set(queue, &state.matcap) {
+                state
+                    .notifications
+                    .error(format!("Failed to load the matcap: {}", err));
+            }
+        }
+        self.objects_dirty |= state.normal_map_changed;
+        self.normal_maps = state.enable_normal_map;
+        if state.object_classes_changed {
+            self.scene.classify(
+                &state.scene_file.dynamic_objects,
+                &state.hidden_objects,
+                &state.scene_file.mirrors,
+            );
+        }
+        if let Some(graph) = self.scene_graph.as_ref().filter(|g| g.is_animated()) {
+            if state.play_animation || state.animation_time_changed {
+                let node_matrices = graph.evaluate(state.animation_time);
+                let joint_matrices = graph.joint_matrices(&node_matrices);
+                if !joint_matrices.is_empty() {
+                    queue.write_buffer(
+                        &self.joint_buffer,
+                        0,
+                        bytemuck::cast_slice(&joint_matrices),
+                    );
+                }
+                self.scene.animate(node_matrices);
+            }
+        }
+        self.scene.set_light(Vec3::from(state.light_position));
+        let (moved, static_moved) = self.scene.run();
+        self.objects_dirty |= moved;
+        self.static_geometry_moved = static_moved;
+        if state.sky_changed {
+            queue.write_buffer(
+                &self.sky_buffer,
+                0,
+                bytemuck::cast_slice(&[UniformSky::new(&state.sky)]),
+            );
+        }
+        // The mirror follows the camera and animations, so it is placed every frame
+        let mirror = self
+            .scene
+            .mirror()
+            .filter(|_| state.planar_reflections)
+            .and_then(|geom| {
+                Some((
+                    geom.object_index,
+                    mirror_plane(&geom, state.camera.position)?,
+                ))
+            });
+        if let Some((_, plane)) = mirror {
+            self.planar_reflection.update(
+                queue,
+                state.camera.calc_matrix(),
+                state.projection.calc_matrix(),
+                state.camera.position,
+                plane,
+            );
+        }
+        let active_mirror = mirror.map(|(object_index, _)| object_index);
+        self.objects_dirty |= active_mirror != self.active_mirror;
+        self.active_mirror = active_mirror;
+        if let Some(split) = &mut self.split_target {
+            split.split = state.split_view.split;
+            if state.split_view_changed || state.reflection_probes_changed || override_changed {
+                let (probes, material_override) = split_uniforms(state);
+                queue.write_buffer(&split.probe_buffer, 0, bytemuck::cast_slice(&[probes]));
                 queue.write_buffer(
-                    &geom.enable_bit_buffer,
+                    &split.material_override_buffer,
                     0,
-                    bytemuck::cast_slice(&[enable_bit]),
+                    bytemuck::cast_slice(&[material_override]),
                 );
             }
         }
+        if state.reflection_probes_changed {
+            let intensity = if state.enable_reflection_probes {
+                state.reflection_probe_intensity
+            } else {
+                0.0
+            };
+            self.reflection_probes
+                .update(queue, &state.scene_file.reflection_probes, intensity);
+        }
     }
 }
@@ -0,0 +1,47 @@
+use glam::{vec2, Mat4, Vec2};
+
+/// Longest side of the region target, zoomed regions of large windows are capped.
+const MAX_ROI_EXTENT: u32 = 4096;
+
+/// Screen rectangle rendered again at a higher resolution, shown at 1:1 pixels
+/// to inspect artifacts up close.
+#[derive(Debug, Clone, Copy)]
+pub struct RegionOfInterest {
+    pub enabled: bool,
+    /// Top left corner in fractions of the window
+    pub min: Vec2,
+    /// Fractions of the window
+    pub size: Vec2,
+    /// Pixels rendered per window pixel along each axis
+    pub zoom: u32,
+}
+
+impl Default for RegionOfInterest {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min: Vec2::splat(0.4),
+            size: Vec2::splat(0.2),
+            zoom: 2,
+        }
+    }
+}
+
+impl RegionOfInterest {
+    /// Applied after the projection, stretches the rectangle over all of clip space.
+    pub fn crop(&self) -> Mat4 {
+        // Window y points down, clip space y up
+        let center = self.min + self.size * 0.5;
+        let center = vec2(center.x * 2.0 - 1.0, 1.0 - center.y * 2.0);
+        Mat4::from_scale(self.size.recip().extend(1.0))
+            * Mat4::from_translation((-center).extend(0.0))
+    }
+
+    /// Size of the region target for a `width` x `height` window.
+    pub fn extent(&self, width: u32, height: u32) -> (u32, u32) {
+        let scaled = |fraction: f32, size: u32| {
+            ((fraction * (size * self.zoom) as f32).round() as u32).clamp(1, MAX_ROI_EXTENT)
+        };
+        (scaled(self.size.x, width), scaled(self.size.y, height))
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::CameraBookmark,
+    flythrough::CameraPath,
+    guides::CompositionGuides,
+    primitives::{resource_path, NormalMapping},
+    probe::ReflectionProbe,
+    schema,
+    streaming::SceneChunk,
+};
+
+/// Per scene settings stored next to the OBJ file as `<name>.scene.json`, see
+/// `schema::SCENE_FILE`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneFile {
+    pub reflection_probes: Vec<ReflectionProbe>,
+    /// Mesh names left out of the reflection probe bakes, they only show up per frame
+    pub dynamic_objects: Vec<String>,
+    pub camera_bookmarks: Vec<CameraBookmark>,
+    /// Flythrough keyframes
+    pub camera_path: CameraPath,
+    /// Parts of the scene streamed in near the camera
+    pub chunks: Vec<SceneChunk>,
+    pub composition_guides: CompositionGuides,
+    /// Mesh names whose triangles are wound the other way when loaded
+    pub flipped_winding: Vec<String>,
+    /// Mesh names whose normals are negated when loaded
+    pub flipped_normals: Vec<String>,
+    /// Mesh names of flat meshes reflecting the scene, see `planar_reflection`
+    pub mirrors: Vec<String>,
+    /// Normal map settings by material name, replacing those it was loaded with
+    pub normal_maps: BTreeMap<String, NormalMapping>,
+}
+
+impl SceneFile {
+    pub fn path_for<P: AsRef<Path>>(scene_path: P) -> PathBuf {
+        resource_path(scene_path).with_extension("scene.json")
+    }
+
+    /// Read the scene file belonging to `scene_path`, a missing file is not an error.
+    pub fn load<P: AsRef<Path>>(scene_path: P) -> anyhow::Result<Option<Self>> {
+        let path = Self::path_for(scene_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(schema::SCENE_FILE.load(&path)?))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, scene_path: P) -> anyhow::Result<()> {
+        let path = Self::path_for(scene_path);
+        schema::SCENE_FILE.save(&path, self)
+    }
+}
@@ -0,0 +1,310 @@
+//! Pipelines drawing the scene geometry with `shader.wgsl`.
+//!
+//! Whether a material samples its color and normal maps is fixed by override
+//! constants rather than tested per fragment, every combination in use gets a
+//! set of pipelines of its own. The other maps stay behind `enable_bit`.
+//!
+//! The shader can be reloaded from disk while running, an invalid shader is
+//! reported and the pipelines created before stay in use.
+
+use std::collections::HashMap;
+
+use wgpu::{Device, RenderPipeline};
+
+use crate::{
+    capabilities::Capabilities,
+    object_ids,
+    primitives::{CullMode, Vertex},
+    shader_preprocessor::{self, ShaderDefs, ShaderError, ShaderLibrary},
+    texture,
+};
+
+/// `enable_bit` of the color map
+const COLOR_MAP_BIT: u32 = 1;
+/// `enable_bit` of the normal map
+const NORMAL_MAP_BIT: u32 = 2;
+
+/// Maps a pipeline samples, the override constants of `shader.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialPermutation {
+    pub color_map: bool,
+    pub normal_map: bool,
+}
+
+impl MaterialPermutation {
+    /// Permutation of a material with `enable_bit`, normal maps are skipped
+    /// while `normal_maps` is off.
+    pub fn new(enable_bit: u32, normal_maps: bool) -> Self {
+        Self {
+            color_map: enable_bit & COLOR_MAP_BIT != 0,
+            normal_map: normal_maps && enable_bit & NORMAL_MAP_BIT != 0,
+        }
+    }
+
+    fn constants(self) -> HashMap<String, f64> {
+        HashMap::from(
+            [
+                ("COLOR_MAP", self.color_map),
+                ("NORMAL_MAP", self.normal_map),
+            ]
+            .map(|(name, enabled)| (name.to_owned(), enabled as u32 as f64)),
+        )
+    }
+
+    fn label(self) -> String {
+        let maps = [("Color", self.color_map), ("Normal", self.normal_map)]
+            .into_iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        if maps.is_empty() {
+            "Untextured".to_owned()
+        } else {
+            format!("{} Mapped", maps.join(" "))
+        }
+    }
+}
+
+/// Every pipeline of one `MaterialPermutation`.
+pub struct ScenePipelines {
+    /// Opaque pipelines and then transparent ones, each indexed by
+    /// `CullMode::index`, see `ScenePipelines::main`
+    main: [[RenderPipeline; 3]; 2],
+    /// Opaque pipelines of the main view writing object ids, indexed by
+    /// `CullMode::index`
+    ids: [RenderPipeline; 3],
+    /// Draws into the reflection probe faces, which may use another format
+    pub probe: RenderPipeline,
+}
+
+impl ScenePipelines {
+    pub fn main(&self, transparent: bool, cull_mode: CullMode) -> &RenderPipeline {
+        &self.main[transparent as usize][cull_mode.index()]
+    }
+
+    pub fn ids(&self, cull_mode: CullMode) -> &RenderPipeline {
+        &self.ids[cull_mode.index()]
+    }
+}
+
+/// The scene pipelines of the permutations prepared so far.
+pub struct ScenePipelineCache {
+    shader: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    scene_format: wgpu::TextureFormat,
+    probe_format: wgpu::TextureFormat,
+    pipelines: HashMap<MaterialPermutation, ScenePipelines>,
+}
+
+impl ScenePipelineCache {
+    pub fn new(
+        device: &Device,
+        capabilities: &Capabilities,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        scene_format: wgpu::TextureFormat,
+        probe_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader = shader_preprocessor::create_shader_module(
+            device,
+            capabilities,
+            "shader.wgsl",
+            &ShaderDefs::default(),
+        );
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        Self {
+            shader,
+            layout,
+            scene_format,
+            probe_format,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Create the pipelines of the `permutations` not cached yet. Permutations
+    /// that failed are left out and their errors returned.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        permutations: impl IntoIterator<Item = MaterialPermutation>,
+    ) -> Vec<ShaderError> {
+        let mut errors = Vec::new();
+        for permutation in permutations {
+            if self.pipelines.contains_key(&permutation) {
+                continue;
+            }
+            match self.create(device, &self.shader, permutation) {
+                Ok(pipelines) => {
+                    self.pipelines.insert(permutation, pipelines);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        errors
+    }
+
+    /// Recreate every cached permutation with the shader of `library`. Nothing
+    /// is replaced unless all of them succeed.
+    pub fn reload(
+        &mut self,
+        device: &Device,
+        capabilities: &Capabilities,
+        library: &ShaderLibrary,
+    ) -> Result<(), Vec<ShaderError>> {
+        let shader = shader_preprocessor::try_create_shader_module(
+            device,
+            capabilities,
+            library,
+            "shader.wgsl",
+            &ShaderDefs::default(),
+        )
+        .map_err(|err| vec![err])?;
+        let mut pipelines = HashMap::new();
+        let mut errors = Vec::new();
+        for &permutation in self.pipelines.keys() {
+            match self.create(device, &shader, permutation) {
+                Ok(created) => {
+                    pipelines.insert(permutation, created);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        self.shader = shader;
+        self.pipelines = pipelines;
+        Ok(())
+    }
+
+    /// Pipelines of a permutation `prepare` created, `None` if it failed.
+    pub fn get(&self, permutation: MaterialPermutation) -> Option<&ScenePipelines> {
+        self.pipelines.get(&permutation)
+    }
+
+    fn create(
+        &self,
+        device: &Device,
+        shader: &wgpu::ShaderModule,
+        permutation: MaterialPermutation,
+    ) -> Result<ScenePipelines, ShaderError> {
+        let constants = permutation.constants();
+        let label = permutation.label();
+        // Transparent geometry is tested against depth but does not write it
+        // Probes are always baked lit, whatever channel the views show
+        // The object id target is only added to the opaque pass of the main view
+        let create_pipeline = |name: &str,
+                               format,
+                               fragment_entry: &str,
+                               depth_write_enabled: bool,
+                               cull_mode,
+                               object_ids: bool| {
+            let targets = [
+                Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+                Some(wgpu::ColorTargetState {
+                    format: object_ids::FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+            ];
+            let compilation_options = wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            };
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!("{} {}", label, name)),
+                layout: Some(&self.layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[Vertex::layout()],
+                    compilation_options: compilation_options.clone(),
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Cw,
+                    cull_mode,
+                    // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    // Requires Features::DEPTH_CLIP_CONTROL
+                    unclipped_depth: false,
+                    // Requires Features::CONSERVATIVE_RASTERIZATION
+                    conservative: false,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some(fragment_entry),
+                    targets: &targets[..1 + object_ids as usize],
+                    compilation_options,
+                }),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+        // Labeled like "Front Culled Transparent Render Pipeline"
+        let culled = |cull_mode: CullMode, name: &str| match cull_mode {
+            CullMode::None => name.to_owned(),
+            cull_mode => format!("{:?} Culled {}", cull_mode, name),
+        };
+        let pipelines = shader_preprocessor::capture_errors(device, || ScenePipelines {
+            main: [
+                ("Render Pipeline", true),
+                ("Transparent Render Pipeline", false),
+            ]
+            .map(|(name, depth_write_enabled)| {
+                CullMode::ALL.map(|cull_mode| {
+                    create_pipeline(
+                        &culled(cull_mode, name),
+                        self.scene_format,
+                        "fs_main",
+                        depth_write_enabled,
+                        cull_mode.face(),
+                        false,
+                    )
+                })
+            }),
+            ids: CullMode::ALL.map(|cull_mode| {
+                create_pipeline(
+                    &culled(cull_mode, "Object Id Render Pipeline"),
+                    self.scene_format,
+                    "fs_main_ids",
+                    true,
+                    cull_mode.face(),
+                    true,
+                )
+            }),
+            probe: create_pipeline(
+                "Reflection Probe Pipeline",
+                self.probe_format,
+                "fs_bake",
+                true,
+                None,
+                false,
+            ),
+        });
+        pipelines.map_err(|err| ShaderError {
+            message: format!("{} pipelines: {}", label, err.message),
+            ..err
+        })
+    }
+}
@@ -0,0 +1,41 @@
+// Camera and per object data shared by the shaders reading the scene buffers,
+// must match `UniformCamera` and `UniformObject`
+
+struct Camera {
+    view_matrix: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+
+struct Material {
+    ambient: vec4<f32>,
+    diffuse: vec4<f32>,
+    specular: vec4<f32>,
+    // xy: scale, zw: offset, used when the color texture is packed in an atlas
+    color_uv_transform: vec4<f32>,
+    // xyz: light given off, added to the lit color
+    emissive: vec4<f32>,
+    shininess: f32,
+    alpha: f32,
+    alpha_cutoff: f32,
+    // 0: opaque, 1: mask, 2: blend
+    alpha_mode: u32,
+    // scale of the tangent space bend of the normal map
+    normal_strength: f32,
+    // non-zero negates the green channel of the normal map, for DirectX maps
+    normal_flip_y: u32,
+    // texture repeats per world unit of the triplanar projection, zero samples
+    // the texture coordinates of the mesh
+    triplanar_scale: f32,
+    _padding: u32,
+}
+
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    material: Material,
+    enable_bit: u32,
+    // first matrix of the skin in `joints`, NO_SKIN for rigid objects
+    joint_offset: u32,
+}
+
+const NO_SKIN: u32 = 0xffffffffu;
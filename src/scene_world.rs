@@ -0,0 +1,480 @@
+//! Geometry and light of the scene as entities of a `bevy_ecs` world.
+//!
+//! Every uploaded mesh is an entity with a `Mesh`, `Material`, `Transform` and
+//! `Visibility`, the light is an entity with a `Light`. Dynamic geometry is
+//! tagged with `Dynamic`, planar mirrors with `Mirror` and the geometry of a
+//! streamed chunk with `Chunk`.
+//! Updates reach the world as resources and are applied by the systems of a
+//! `Schedule` run once per frame by `SceneWorld::run`: visibility tags and
+//! hides the geoms by model name, animation moves the transforms and the light
+//! is placed. Upload writes the `UniformObject`s, culling picks the geoms a
+//! pass draws and drawing walks them in `draw_order`.
+
+use std::{collections::BTreeMap, ops::Range, sync::Arc};
+
+use bevy_ecs::prelude::*;
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::{
+    orientation::Orientation,
+    planar_reflection::MIRROR_BIT,
+    primitives::{CullMode, NormalMapping, UniformLight, UniformMaterial, UniformObject, Vertex},
+};
+
+/// Where a mesh lives in the shared scene buffers and what it looked like
+/// when uploaded.
+#[derive(Component)]
+pub struct Mesh {
+    pub base_vertex: i32,
+    pub vertex_count: u32,
+    pub indices: Range<u32>,
+    /// Center of the bounding box in object space, used for sorting
+    pub center: Vec3,
+    /// Half size of the bounding box in object space, used for culling
+    pub extent: Vec3,
+    /// Uploaded vertices and triangle indices relative to `base_vertex`, kept for
+    /// picking and the wireframe overlay
+    pub vertices: Box<[Vertex]>,
+    pub triangles: Box<[u32]>,
+    /// Name of the model the mesh was loaded from
+    pub name: String,
+    pub orientation: Orientation,
+}
+
+#[derive(Component)]
+pub struct Material {
+    /// Name in the MTL or glTF file, see `SceneFile::normal_maps`
+    pub name: String,
+    /// Shared by the geoms using the same texture maps
+    pub bind_group: Arc<wgpu::BindGroup>,
+    pub uniform: UniformMaterial,
+    /// Normal mapping the material was loaded with
+    pub normal_mapping: NormalMapping,
+    pub enable_bit: u32,
+    /// Blended geometry is drawn after everything else, back to front
+    pub transparent: bool,
+    /// Faces the pipeline skips
+    pub cull_mode: CullMode,
+}
+
+#[derive(Component)]
+pub struct Transform {
+    /// Scene graph node driving `matrix` while animating
+    pub node: Option<usize>,
+    pub matrix: Mat4,
+    /// First matrix of the skin in the joint buffer. Skinned geoms are drawn
+    /// where their joints put them, culling and picking still use `matrix`
+    pub joint_offset: Option<u32>,
+}
+
+/// Hidden geometry is neither drawn nor baked into the probes.
+#[derive(Component)]
+pub struct Visibility {
+    pub visible: bool,
+}
+
+/// Dynamic geometry is drawn every frame but left out of the probe bakes.
+#[derive(Component)]
+pub struct Dynamic;
+
+/// Flat geometry reflecting the scene, see `planar_reflection`.
+#[derive(Component)]
+pub struct Mirror;
+
+/// Streamed scene file chunk the geometry belongs to, see `streaming`.
+#[derive(Component)]
+pub struct Chunk(pub usize);
+
+#[derive(Component)]
+pub struct Light {
+    pub position: Vec3,
+}
+
+/// Models to tag as `Dynamic` or `Mirror` and to hide, by name.
+#[derive(Resource, Default)]
+struct ObjectClasses {
+    dynamic: Vec<String>,
+    hidden: Vec<String>,
+    mirrors: Vec<String>,
+}
+
+/// Matrices of the scene graph nodes, taken by the next run.
+#[derive(Resource, Default)]
+struct NodeMatrices(Option<Vec<Mat4>>);
+
+#[derive(Resource, Default)]
+struct LightPosition(Vec3);
+
+/// Whether the last run moved any geom and whether a static one moved.
+#[derive(Resource, Default, Clone, Copy)]
+struct Motion {
+    moved: bool,
+    static_moved: bool,
+}
+
+/// A geom and the tags the visibility system sets.
+type Classified<'a> = (
+    Entity,
+    &'a Mesh,
+    &'a mut Visibility,
+    Has<Dynamic>,
+    Has<Mirror>,
+);
+
+/// Visibility system: tag the geoms whose model is named in `dynamic` as
+/// `Dynamic` and hide those named in `hidden`.
+fn update_visibility(
+    classes: Res<ObjectClasses>,
+    mut commands: Commands,
+    mut query: Query<Classified>,
+) {
+    if !classes.is_changed() {
+        return;
+    }
+    for (entity, mesh, mut visibility, is_dynamic, is_mirror) in &mut query {
+        visibility.visible = !classes.hidden.contains(&mesh.name);
+        let should_be_dynamic = classes.dynamic.contains(&mesh.name);
+        let should_be_mirror = classes.mirrors.contains(&mesh.name);
+        let mut entity = commands.entity(entity);
+        if should_be_dynamic != is_dynamic {
+            if should_be_dynamic {
+                entity.insert(Dynamic);
+            } else {
+                entity.remove::<Dynamic>();
+            }
+        }
+        if should_be_mirror != is_mirror {
+            if should_be_mirror {
+                entity.insert(Mirror);
+            } else {
+                entity.remove::<Mirror>();
+            }
+        }
+    }
+}
+
+/// Animation system: move the geoms attached to scene graph nodes.
+fn animate_transforms(
+    mut node_matrices: ResMut<NodeMatrices>,
+    mut motion: ResMut<Motion>,
+    mut query: Query<(&mut Transform, Has<Dynamic>)>,
+) {
+    *motion = Motion::default();
+    let Some(node_matrices) = node_matrices.0.take() else {
+        return;
+    };
+    for (mut transform, dynamic) in &mut query {
+        // The joints of skinned geoms follow the animation
+        motion.static_moved |= transform.joint_offset.is_some() && !dynamic;
+        let Some(&matrix) = transform.node.and_then(|node| node_matrices.get(node)) else {
+            continue;
+        };
+        if matrix == transform.matrix {
+            continue;
+        }
+        transform.matrix = matrix;
+        motion.moved = true;
+        motion.static_moved |= !dynamic;
+    }
+}
+
+fn place_light(position: Res<LightPosition>, mut lights: Query<&mut Light>) {
+    if position.is_changed() {
+        for mut light in &mut lights {
+            light.position = position.0;
+        }
+    }
+}
+
+/// Components of a geometry entity, as read by the draws.
+#[derive(Clone, Copy)]
+pub struct Geom<'a> {
+    /// Index of the `UniformObject` in `DefaultRenderer::object_buffer` and of the
+    /// indirect draw in `SceneBuffers::indirect_buffer`
+    pub object_index: u32,
+    pub mesh: &'a Mesh,
+    pub material: &'a Material,
+    pub transform: &'a Transform,
+    pub visible: bool,
+    pub dynamic: bool,
+    pub mirror: bool,
+    pub chunk: Option<usize>,
+}
+
+impl Geom<'_> {
+    /// World space bounds of the mesh, as tested by the culling.
+    pub fn world_bounds(&self) -> (Vec3, Vec3) {
+        let matrix = self.transform.matrix;
+        let center = matrix.transform_point3(self.mesh.center);
+        let axes = Mat3::from_mat4(matrix);
+        let extent = Mat3::from_cols(axes.x_axis.abs(), axes.y_axis.abs(), axes.z_axis.abs())
+            * self.mesh.extent;
+        (center - extent, center + extent)
+    }
+}
+
+/// A freshly uploaded mesh, see `SceneWorld::spawn`.
+pub type GeomBundle = (Mesh, Material, Transform);
+
+pub struct SceneWorld {
+    world: World,
+    /// Systems applying the updates, see `SceneWorld::run`
+    schedule: Schedule,
+    /// Geometry entities in the order of their object index. Geoms drawn with the
+    /// same pipeline and material are next to each other so their draws can be
+    /// merged
+    draw_order: Vec<Entity>,
+    light: Entity,
+}
+
+impl Default for SceneWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneWorld {
+    pub fn new() -> Self {
+        let mut world = World::new();
+        world.init_resource::<ObjectClasses>();
+        world.init_resource::<NodeMatrices>();
+        world.init_resource::<LightPosition>();
+        world.init_resource::<Motion>();
+        let light = world
+            .spawn(Light {
+                position: Vec3::ZERO,
+            })
+            .id();
+        let mut schedule = Schedule::default();
+        // The tags are applied before the animation tells static geoms apart
+        schedule.add_systems((update_visibility, animate_transforms, place_light).chain());
+        Self {
+            world,
+            schedule,
+            draw_order: Vec::new(),
+            light,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.draw_order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draw_order.is_empty()
+    }
+
+    /// The geom with object index `index`.
+    pub fn geom(&self, index: usize) -> Geom<'_> {
+        let entity = self.world.entity(self.draw_order[index]);
+        Geom {
+            object_index: index as u32,
+            mesh: entity.get::<Mesh>().unwrap(),
+            material: entity.get::<Material>().unwrap(),
+            transform: entity.get::<Transform>().unwrap(),
+            visible: entity.get::<Visibility>().unwrap().visible,
+            dynamic: entity.contains::<Dynamic>(),
+            mirror: entity.contains::<Mirror>(),
+            chunk: entity.get::<Chunk>().map(|chunk| chunk.0),
+        }
+    }
+
+    /// Every geom in the order of their object index.
+    pub fn geoms(&self) -> impl Iterator<Item = Geom<'_>> {
+        (0..self.len()).map(|index| self.geom(index))
+    }
+
+    /// Culling system: the visible geoms, dynamic ones only if `dynamic` is set.
+    pub fn culled(&self, dynamic: bool) -> impl Iterator<Item = Geom<'_>> {
+        self.geoms()
+            .filter(move |geom| geom.visible && (dynamic || !geom.dynamic))
+    }
+
+    /// The first visible mirror, the one that reflects.
+    pub fn mirror(&self) -> Option<Geom<'_>> {
+        self.culled(true).find(|geom| geom.mirror)
+    }
+
+    /// Drop every geometry entity, the light is kept.
+    pub fn clear(&mut self) {
+        for entity in self.draw_order.drain(..) {
+            self.world.despawn(entity);
+        }
+    }
+
+    /// Drop the geometry of `chunk`, returns whether there was any.
+    pub fn despawn_chunk(&mut self, chunk: usize) -> bool {
+        let mut query = self.world.query::<(Entity, &Chunk)>();
+        let removed = query
+            .iter(&self.world)
+            .filter(|(_, c)| c.0 == chunk)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        for &entity in &removed {
+            self.world.despawn(entity);
+        }
+        self.draw_order.retain(|entity| !removed.contains(entity));
+        !removed.is_empty()
+    }
+
+    /// Meshes of every geom in no particular order, for moving their geometry
+    /// within the scene buffers.
+    pub fn meshes_mut(&mut self) -> Vec<Mut<'_, Mesh>> {
+        self.world
+            .query::<&mut Mesh>()
+            .iter_mut(&mut self.world)
+            .collect()
+    }
+
+    /// Add the geoms, part of `chunk` if given, and number every geom again.
+    pub fn spawn(&mut self, geoms: Vec<GeomBundle>, chunk: Option<usize>) {
+        for geom in geoms {
+            let mut entity = self.world.spawn((geom, Visibility { visible: true }));
+            if let Some(chunk) = chunk {
+                entity.insert(Chunk(chunk));
+            }
+            self.draw_order.push(entity.id());
+        }
+        // Stable, the kept geoms stay in their order
+        let world = &self.world;
+        self.draw_order.sort_by_cached_key(|&entity| {
+            let material = world.get::<Material>(entity).unwrap();
+            (
+                material.transparent,
+                material.cull_mode,
+                Arc::as_ptr(&material.bind_group),
+            )
+        });
+    }
+
+    /// Tag the geoms whose model is named in `dynamic` as `Dynamic` and hide those
+    /// named in `hidden` on the next run.
+    pub fn classify(&mut self, dynamic: &[String], hidden: &[String], mirrors: &[String]) {
+        *self.world.resource_mut::<ObjectClasses>() = ObjectClasses {
+            dynamic: dynamic.to_vec(),
+            hidden: hidden.to_vec(),
+            mirrors: mirrors.to_vec(),
+        };
+    }
+
+    /// Move the geoms attached to scene graph nodes to `node_matrices` on the
+    /// next run.
+    pub fn animate(&mut self, node_matrices: Vec<Mat4>) {
+        self.world.resource_mut::<NodeMatrices>().0 = Some(node_matrices);
+    }
+
+    /// Place the light on the next run.
+    pub fn set_light(&mut self, position: Vec3) {
+        let mut light = self.world.resource_mut::<LightPosition>();
+        // Left unchanged the light is not placed again
+        if light.0 != position {
+            light.0 = position;
+        }
+    }
+
+    /// Run the systems on the updates given since the last run. Returns whether
+    /// any geom moved and whether a static one did.
+    pub fn run(&mut self) -> (bool, bool) {
+        self.schedule.run(&mut self.world);
+        let motion = *self.world.resource::<Motion>();
+        (motion.moved, motion.static_moved)
+    }
+
+    /// Upload system: the `UniformObject` of every geom in the order of their
+    /// object index, `mirror` is the object index of the reflecting mirror.
+    /// `normal_maps` replaces the normal mapping by material name.
+    pub fn objects(
+        &self,
+        enable_normal_map: bool,
+        mirror: Option<u32>,
+        normal_maps: &BTreeMap<String, NormalMapping>,
+    ) -> Vec<UniformObject> {
+        self.geoms()
+            .map(|geom| {
+                let material = geom.material;
+                let normal_mapping = normal_maps
+                    .get(&material.name)
+                    .copied()
+                    .unwrap_or(material.normal_mapping);
+                let mut enable_bit = material.enable_bit & !((!enable_normal_map as u32) << 1);
+                if mirror == Some(geom.object_index) {
+                    enable_bit |= MIRROR_BIT;
+                }
+                UniformObject::new(
+                    geom.transform.matrix,
+                    material.uniform.with_normal_mapping(normal_mapping),
+                    enable_bit,
+                    geom.transform.joint_offset,
+                )
+            })
+            .collect()
+    }
+
+    pub fn light(&self) -> UniformLight {
+        self.world.get::<Light>(self.light).unwrap().position.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A geom without material, which the systems do not read.
+    fn spawn(scene: &mut SceneWorld, name: &str, node: usize) -> Entity {
+        let mesh = Mesh {
+            base_vertex: 0,
+            vertex_count: 0,
+            indices: 0..0,
+            center: Vec3::ZERO,
+            extent: Vec3::ZERO,
+            vertices: Box::new([]),
+            triangles: Box::new([]),
+            name: name.to_owned(),
+            orientation: Orientation::default(),
+        };
+        let transform = Transform {
+            node: Some(node),
+            matrix: Mat4::IDENTITY,
+            joint_offset: None,
+        };
+        scene
+            .world
+            .spawn((mesh, transform, Visibility { visible: true }))
+            .id()
+    }
+
+    #[test]
+    fn classes_apply_before_the_animation() {
+        let mut scene = SceneWorld::new();
+        let wall = spawn(&mut scene, "wall", 0);
+        let door = spawn(&mut scene, "door", 1);
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        scene.classify(&names(&["door"]), &names(&["wall"]), &[]);
+        let moved = Mat4::from_translation(Vec3::X);
+        scene.animate(vec![Mat4::IDENTITY, moved]);
+        assert_eq!(scene.run(), (true, false));
+        assert!(!scene.world.get::<Visibility>(wall).unwrap().visible);
+        assert!(scene.world.entity(door).contains::<Dynamic>());
+        assert_eq!(scene.world.get::<Transform>(door).unwrap().matrix, moved);
+
+        // Nothing changes without new updates
+        assert_eq!(scene.run(), (false, false));
+        scene.animate(vec![moved, moved]);
+        assert_eq!(scene.run(), (true, true));
+    }
+
+    #[test]
+    fn light_is_placed() {
+        let mut scene = SceneWorld::new();
+        scene.set_light(Vec3::ONE);
+        let position = |scene: &SceneWorld| scene.world.get::<Light>(scene.light).unwrap().position;
+        assert_eq!(position(&scene), Vec3::ZERO);
+        scene.run();
+        assert_eq!(position(&scene), Vec3::ONE);
+    }
+}
@@ -0,0 +1,237 @@
+//! Versioned JSON files. Every file carries a `version` field, older files are
+//! upgraded step by step by the migrations of their schema before they are
+//! deserialized, so files written by earlier builds keep loading. Files
+//! written before versioning have no `version` field and count as version 0.
+//!
+//! Changing the layout of a file means appending a migration that rewrites
+//! the previous version into the new one and documenting it in the README.
+
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use crate::logging;
+
+/// Rewrites a file of one version into the layout of the next.
+pub type Migration = fn(&mut Map<String, Value>) -> anyhow::Result<()>;
+
+pub struct Schema {
+    pub name: &'static str,
+    /// `migrations[i]` upgrades version `i` to `i + 1`
+    pub migrations: &'static [Migration],
+}
+
+/// Version 1 only added the `version` field, the layout stayed the same.
+fn add_version(_: &mut Map<String, Value>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Version 2 added the camera bookmarks.
+fn add_camera_bookmarks(fields: &mut Map<String, Value>) -> anyhow::Result<()> {
+    fields.insert("camera_bookmarks".to_owned(), Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// Version 3 added the flythrough keyframes.
+fn add_camera_path(fields: &mut Map<String, Value>) -> anyhow::Result<()> {
+    fields.insert("camera_path".to_owned(), Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// Version 4 added the streamed chunks.
+fn add_chunks(fields: &mut Map<String, Value>) -> anyhow::Result<()> {
+    fields.insert("chunks".to_owned(), Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// Version 5 added the composition guides, their fields default when left out.
+fn add_composition_guides(fields: &mut Map<String, Value>) -> anyhow::Result<()> {
+    fields.insert("composition_guides".to_owned(), Value::Object(Map::new()));
+    Ok(())
+}
+
+/// Version 6 added the meshes whose winding or normals are flipped.
+fn add_orientation_flips(fields: &mut Map<String, Value>) -> anyhow::Result<()> {
+    fields.insert("flipped_winding".to_owned(), Value::Array(Vec::new()));
+    fields.insert("flipped_normals".to_owned(), Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// Version 7 added the planar mirrors.
+fn add_mirrors(fields: &mut Map<String, Value>) -> anyhow::Result<()> {
+    fields.insert("mirrors".to_owned(), Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// Version 8 added the normal map settings per material.
+fn add_normal_maps(fields: &mut Map<String, Value>) -> anyhow::Result<()> {
+    fields.insert("normal_maps".to_owned(), Value::Object(Map::new()));
+    Ok(())
+}
+
+/// Version 2 added the panel layout of the viewer, left out it is the default.
+fn add_layout(_: &mut Map<String, Value>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// `<name>.scene.json`, see `SceneFile`.
+pub const SCENE_FILE: Schema = Schema {
+    name: "scene file",
+    migrations: &[
+        add_version,
+        add_camera_bookmarks,
+        add_camera_path,
+        add_chunks,
+        add_composition_guides,
+        add_orientation_flips,
+        add_mirrors,
+        add_normal_maps,
+    ],
+};
+
+/// `resources/render_settings.json`, see `RenderSettings`.
+pub const RENDER_SETTINGS: Schema = Schema {
+    name: "render settings",
+    migrations: &[add_version, add_layout],
+};
+
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    version: u32,
+    #[serde(flatten)]
+    value: &'a T,
+}
+
+impl Schema {
+    /// Version new files are written with.
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+
+    /// Read a file of this schema, migrating it from the version it was written with.
+    pub fn load<T: DeserializeOwned>(&self, path: &Path) -> anyhow::Result<T> {
+        let content = fs::read_to_string(path)?;
+        let Value::Object(mut fields) = serde_json::from_str(&content)? else {
+            anyhow::bail!("{} is not a JSON object", self.name);
+        };
+        let version = match fields.remove("version") {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .with_context(|| format!("invalid {} version {}", self.name, version))?,
+        };
+        anyhow::ensure!(
+            version <= self.version(),
+            "{} version {} is newer than the supported version {}",
+            self.name,
+            version,
+            self.version()
+        );
+        for (from, migrate) in self.migrations.iter().enumerate().skip(version as usize) {
+            migrate(&mut fields).with_context(|| {
+                format!("failed to migrate {} from version {}", self.name, from)
+            })?;
+        }
+        if version < self.version() {
+            tracing::info!(
+                target: logging::SCENE,
+                "{}: migrated {} from version {} to {}",
+                path.display(),
+                self.name,
+                version,
+                self.version()
+            );
+        }
+        serde_json::from_value(Value::Object(fields))
+            .with_context(|| format!("invalid {} version {}", self.name, self.version()))
+    }
+
+    /// Write `value` with the current version.
+    pub fn save<T: Serialize>(&self, path: &Path, value: &T) -> anyhow::Result<()> {
+        let versioned = Versioned {
+            version: self.version(),
+            value,
+        };
+        fs::write(path, serde_json::to_string_pretty(&versioned)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `content` to a file of its own in the temporary directory.
+    fn file(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "radiance_cascade_schema_{}_{}.json",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn unversioned_files_are_migrated() {
+        let path = file("unversioned", r#"{"models": []}"#);
+        let fields: Map<String, Value> = SCENE_FILE.load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        for key in [
+            "models",
+            "camera_bookmarks",
+            "camera_path",
+            "chunks",
+            "composition_guides",
+            "flipped_winding",
+            "flipped_normals",
+            "mirrors",
+            "normal_maps",
+        ] {
+            assert!(fields.contains_key(key), "{} missing", key);
+        }
+        assert!(!fields.contains_key("version"));
+    }
+
+    #[test]
+    fn migrations_start_at_the_file_version() {
+        let path = file("version_7", r#"{"version": 7, "mirrors": [1]}"#);
+        let fields: Map<String, Value> = SCENE_FILE.load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(fields["mirrors"], serde_json::json!([1]));
+        assert_eq!(fields["normal_maps"], serde_json::json!({}));
+        assert!(!fields.contains_key("chunks"));
+    }
+
+    #[test]
+    fn saved_files_load_unchanged() {
+        let path = file("saved", "");
+        let mut fields = Map::new();
+        fields.insert("chunks".to_owned(), serde_json::json!([1, 2]));
+        SCENE_FILE.save(&path, &fields).unwrap();
+        let saved: Map<String, Value> =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let loaded: Map<String, Value> = SCENE_FILE.load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(saved["version"], SCENE_FILE.version());
+        assert_eq!(loaded, fields);
+    }
+
+    #[test]
+    fn invalid_versions_are_errors() {
+        let newer = format!(r#"{{"version": {}}}"#, RENDER_SETTINGS.version() + 1);
+        for (name, content) in [
+            ("newer", newer.as_str()),
+            ("negative", r#"{"version": -1}"#),
+            ("array", "[]"),
+        ] {
+            let path = file(name, content);
+            let result = RENDER_SETTINGS.load::<Map<String, Value>>(&path);
+            fs::remove_file(&path).unwrap();
+            assert!(result.is_err(), "{} loaded", name);
+        }
+    }
+}
@@ -1,9 +1,7 @@
 // Vertex shader
 
-struct Camera {
-    view_matrix: mat4x4<f32>,
-    view_position: vec4<f32>,
-}
+#define OBJECT_GROUP 3
+#include "skinning.wgsl"
 
 @group(0) @binding(0)
 var<uniform> camera: Camera;
@@ -13,8 +11,10 @@ struct VertexInput {
     @location(1) color: vec3<f32>,
     @location(2) normal: vec3<f32>,
     @location(3) tangent: vec3<f32>,
-    @location(4) bitangent: vec3<f32>,
+    @location(4) tangent_sign: f32,
     @location(5) texcoord: vec2<f32>,
+    @location(6) joints: vec4<u32>,
+    @location(7) weights: vec4<f32>,
 }
 
 struct VertexOutput {
@@ -23,79 +23,450 @@ struct VertexOutput {
     @location(1) color: vec3<f32>,
     @location(2) normal: vec3<f32>,
     @location(3) tangent: vec3<f32>,
-    @location(4) bitangent: vec3<f32>,
+    // Equal on every vertex of a triangle, see `tangents.rs`
+    @location(4) @interpolate(flat) tangent_sign: f32,
     @location(5) texcoord: vec2<f32>,
+    @location(6) @interpolate(flat) object_index: u32,
 }
 
 @vertex
 fn vs_main(
     model: VertexInput,
+    @builtin(instance_index) object_index: u32,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = camera.view_matrix * vec4<f32>(model.position, 1.0); 
-    out.world_position = model.position;
+    let object = objects[object_index];
+    out.object_index = object_index;
+    let transform = object_transform(object, model.joints, model.weights);
+    var normal_transform = object.normal;
+    if object.joint_offset != NO_SKIN {
+        // Joints rarely scale unevenly, the normals go along with the skin
+        normal_transform = transform;
+    }
+    let world_position = transform * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_matrix * world_position;
+    out.world_position = world_position.xyz;
     out.color = model.color;
-    out.normal = model.normal;
+    out.normal = (normal_transform * vec4<f32>(model.normal, 0.0)).xyz;
     out.texcoord = model.texcoord;
-    out.tangent = model.tangent;
-    out.bitangent = model.bitangent;
+    out.tangent = (transform * vec4<f32>(model.tangent, 0.0)).xyz;
+    out.tangent_sign = model.tangent_sign;
     return out;
 }
 
 // Fragment shader
 
-struct Material {
-    ambient: vec4<f32>,
-    diffuse: vec4<f32>,
-    specular: vec4<f32>,
-    shininess: f32,
-    // _padding: array<u32, 3>,
-}
-
 struct Light {
-    position: vec3<f32>,
+    // w: zero for a directional light, xyz points towards it then
+    position: vec4<f32>,
 }
 
+// Maps sampled by the pipeline, set per `MaterialPermutation` rather than read
+// from the enable bits 0 and 1
+override COLOR_MAP: bool = true;
+override NORMAL_MAP: bool = true;
+
 @group(1) @binding(0)
-var<uniform> material: Material;
+var color_texture: texture_2d<f32>;
 @group(1) @binding(1)
-var<uniform> enable_bit: u32;
+var color_sampler: sampler;
 @group(1) @binding(2)
-var color_texture: texture_2d<f32>;
+var normal_texture: texture_2d<f32>;
 @group(1) @binding(3)
-var color_sampler: sampler;
+var normal_sampler: sampler;
 @group(1) @binding(4)
-var normal_texture: texture_2d<f32>;
+var specular_texture: texture_2d<f32>;
 @group(1) @binding(5)
-var normal_sampler: sampler;
+var specular_sampler: sampler;
+@group(1) @binding(6)
+var roughness_texture: texture_2d<f32>;
+@group(1) @binding(7)
+var roughness_sampler: sampler;
+@group(1) @binding(8)
+var ambient_occlusion_texture: texture_2d<f32>;
+@group(1) @binding(9)
+var ambient_occlusion_sampler: sampler;
+@group(1) @binding(10)
+var alpha_texture: texture_2d<f32>;
+@group(1) @binding(11)
+var alpha_sampler: sampler;
+
+struct ReflectionProbe {
+    position: vec4<f32>,
+    box_min: vec4<f32>,
+    box_max: vec4<f32>,
+}
+
+struct ReflectionProbes {
+    probes: array<ReflectionProbe, 8>,
+    count: u32,
+    intensity: f32,
+}
 
 @group(2) @binding(0)
 var<uniform> light: Light;
+@group(2) @binding(1)
+var<uniform> reflection_probes: ReflectionProbes;
+@group(2) @binding(2)
+var probe_texture: texture_cube_array<f32>;
+@group(2) @binding(3)
+var probe_sampler: sampler;
+
+struct MaterialOverride {
+    material: Material,
+    // non-zero replaces the colors and specular of every material
+    enabled: u32,
+    // `ChannelView` shown instead of the lit color
+    channel: u32,
+    // Clip planes the depth channel spans
+    znear: f32,
+    zfar: f32,
+}
+
+const CHANNEL_LIT: u32 = 0u;
+const CHANNEL_ALBEDO: u32 = 1u;
+const CHANNEL_NORMALS: u32 = 2u;
+const CHANNEL_DEPTH: u32 = 3u;
+const CHANNEL_ROUGHNESS: u32 = 4u;
+const CHANNEL_AMBIENT_OCCLUSION: u32 = 5u;
+const CHANNEL_GI_ONLY: u32 = 6u;
+const CHANNEL_SHADOWS_ONLY: u32 = 7u;
+const CHANNEL_MATCAP: u32 = 8u;
+
+@group(2) @binding(4)
+var<uniform> material_override: MaterialOverride;
+
+struct Sky {
+    // Perez coefficients A to E, xyz: luminance Y and chromaticity x and y
+    a: vec4<f32>,
+    b: vec4<f32>,
+    c: vec4<f32>,
+    d: vec4<f32>,
+    e: vec4<f32>,
+    // zenith values divided by their distribution at the zenith
+    zenith: vec4<f32>,
+    // xyz: unit direction towards the sun, w: cosine of the disk radius
+    sun: vec4<f32>,
+    intensity: f32,
+    // scale of the sky light on the surfaces, zero while the sky is off
+    environment: f32,
+}
+
+@group(2) @binding(5)
+var<uniform> sky: Sky;
+
+// The scene seen through the reflecting mirror and the camera it was rendered
+// with, see `planar_reflection.rs`
+@group(2) @binding(6)
+var mirror_texture: texture_2d<f32>;
+@group(2) @binding(7)
+var<uniform> mirror_camera: Camera;
+
+// Lit sphere of the matcap channel, see `matcap.rs`
+@group(2) @binding(8)
+var matcap_texture: texture_2d<f32>;
+@group(2) @binding(9)
+var matcap_sampler: sampler;
+
+const GROUND_ALBEDO: f32 = 0.3;
+const SUN_DISK: f32 = 20.0;
+
+// Copy of `sky_radiance` in `sky.wgsl`
+fn perez(theta: f32, gamma: f32) -> vec3<f32> {
+    let cos_gamma = cos(gamma);
+    return (1.0 + sky.a.xyz * exp(sky.b.xyz / max(cos(theta), 1e-3)))
+        * (1.0 + sky.c.xyz * exp(sky.d.xyz * gamma) + sky.e.xyz * cos_gamma * cos_gamma);
+}
+
+fn sky_radiance(dir: vec3<f32>) -> vec3<f32> {
+    let above = vec3<f32>(dir.x, abs(dir.y), dir.z);
+    let theta = acos(clamp(above.y, -1.0, 1.0));
+    let cos_gamma = clamp(dot(above, sky.sun.xyz), -1.0, 1.0);
+    let value = sky.zenith.xyz * perez(theta, acos(cos_gamma));
+    let luminance = value.x;
+    let chromaticity = value.yz;
+    let xyz = vec3<f32>(
+        chromaticity.x / chromaticity.y * luminance,
+        luminance,
+        (1.0 - chromaticity.x - chromaticity.y) / chromaticity.y * luminance,
+    );
+    let rgb = max(mat3x3<f32>(
+        3.2406, -0.9689, 0.0557,
+        -1.5372, 1.8758, -0.2040,
+        -0.4986, 0.0415, 1.0570,
+    ) * xyz, vec3<f32>(0.0)) * sky.intensity;
+    if dir.y < 0.0 {
+        return rgb * GROUND_ALBEDO;
+    }
+    return select(rgb, rgb * SUN_DISK, cos_gamma > sky.sun.w);
+}
+
+// Intersect the reflection ray with the probe box and return the lookup direction
+fn box_project(probe: ReflectionProbe, position: vec3<f32>, dir: vec3<f32>) -> vec3<f32> {
+    let first = (probe.box_max.xyz - position) / dir;
+    let second = (probe.box_min.xyz - position) / dir;
+    let furthest = max(first, second);
+    let dist = min(min(furthest.x, furthest.y), furthest.z);
+    return position + dir * dist - probe.position.xyz;
+}
+
+// Blend every probe by proximity to the shaded point, rougher surfaces read
+// blurrier levels of the prefiltered probes
+fn sample_reflection_probes(position: vec3<f32>, dir: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let lod = roughness * f32(textureNumLevels(probe_texture) - 1u);
+    var radiance = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    for (var i = 0u; i < reflection_probes.count; i++) {
+        let probe = reflection_probes.probes[i];
+        let to_probe = probe.position.xyz - position;
+        let weight = 1.0 / (1.0 + dot(to_probe, to_probe));
+        let lookup = box_project(probe, position, dir);
+        radiance += textureSampleLevel(probe_texture, probe_sampler, lookup, i, lod).xyz * weight;
+        total_weight += weight;
+    }
+    return radiance / max(total_weight, 1e-6);
+}
+
+// Texture coordinates of the planes facing the x, y and z axes and how much each
+// shows, for materials projected along the axes instead of using the texture
+// coordinates of the mesh
+struct Planes {
+    x: vec2<f32>,
+    y: vec2<f32>,
+    z: vec2<f32>,
+    weights: vec3<f32>,
+    enabled: bool,
+}
 
+fn triplanar_planes(position: vec3<f32>, normal: vec3<f32>, scale: f32) -> Planes {
+    var planes: Planes;
+    // Texture rows run down the world on the side planes
+    planes.x = vec2<f32>(position.z, -position.y) * scale;
+    planes.y = position.xz * scale;
+    planes.z = vec2<f32>(position.x, -position.y) * scale;
+    // Sharpened so the planes only blend near the diagonals
+    let weights = pow(abs(normalize(normal)), vec3<f32>(4.0));
+    planes.weights = weights / (weights.x + weights.y + weights.z);
+    planes.enabled = scale > 0.0;
+    return planes;
+}
+
+// Sample `map` at `uv` through the atlas `transform`, or blend the planes when
+// they are enabled
+fn sample_map(map: texture_2d<f32>, map_sampler: sampler, uv: vec2<f32>, planes: Planes, transform: vec4<f32>) -> vec4<f32> {
+    // Gradients of the unwrapped coordinates, taken before branching, keep the
+    // mip level steady where the wrapped ones jump
+    let x_dx = dpdx(planes.x) * transform.xy;
+    let x_dy = dpdy(planes.x) * transform.xy;
+    let y_dx = dpdx(planes.y) * transform.xy;
+    let y_dy = dpdy(planes.y) * transform.xy;
+    let z_dx = dpdx(planes.z) * transform.xy;
+    let z_dy = dpdy(planes.z) * transform.xy;
+    let sample = textureSample(map, map_sampler, uv * transform.xy + transform.zw);
+    if !planes.enabled {
+        return sample;
+    }
+    let x = textureSampleGrad(map, map_sampler, fract(planes.x) * transform.xy + transform.zw, x_dx, x_dy);
+    let y = textureSampleGrad(map, map_sampler, fract(planes.y) * transform.xy + transform.zw, y_dx, y_dy);
+    let z = textureSampleGrad(map, map_sampler, fract(planes.z) * transform.xy + transform.zw, z_dx, z_dy);
+    return x * planes.weights.x + y * planes.weights.y + z * planes.weights.z;
+}
+
+// Tangent space bend of the normal map, see `Material`
+fn normal_bend(sample: vec4<f32>, material: Material) -> vec2<f32> {
+    let xy = sample.xy * 2.0 - 1.0;
+    return vec2<f32>(xy.x, select(xy.y, -xy.y, material.normal_flip_y != 0u)) * material.normal_strength;
+}
+
+// The normal map blended over the planes, each bend is added onto the surface
+// normal along the axes of its plane. `normal` is kept while the planes are off.
+// The bends mirror on the planes facing down the negative axes.
+fn triplanar_normal(planes: Planes, normal: vec3<f32>, material: Material) -> vec3<f32> {
+    let x_dx = dpdx(planes.x);
+    let x_dy = dpdy(planes.x);
+    let y_dx = dpdx(planes.y);
+    let y_dy = dpdy(planes.y);
+    let z_dx = dpdx(planes.z);
+    let z_dy = dpdy(planes.z);
+    if !planes.enabled {
+        return normal;
+    }
+    let x = normal_bend(textureSampleGrad(normal_texture, normal_sampler, fract(planes.x), x_dx, x_dy), material);
+    let y = normal_bend(textureSampleGrad(normal_texture, normal_sampler, fract(planes.y), y_dx, y_dy), material);
+    let z = normal_bend(textureSampleGrad(normal_texture, normal_sampler, fract(planes.z), z_dx, z_dy), material);
+    // Green points up the texture, against the rows
+    let x_normal = normal + vec3<f32>(0.0, x.y, x.x);
+    let y_normal = normal + vec3<f32>(y.x, 0.0, -y.y);
+    let z_normal = normal + vec3<f32>(z.x, z.y, 0.0);
+    return normalize(x_normal * planes.weights.x + y_normal * planes.weights.y + z_normal * planes.weights.z);
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in, material_override.channel, true);
+}
+
+struct ObjectIdOutput {
+    @location(0) color: vec4<f32>,
+    // Object index plus one, the target is cleared to zero, see `object_ids.rs`
+    @location(1) object_id: u32,
+}
+
+// The opaque pass of the main view also records which object covers each pixel
+@fragment
+fn fs_main_ids(in: VertexOutput) -> ObjectIdOutput {
+    return ObjectIdOutput(shade(in, material_override.channel, true), in.object_index + 1u);
+}
+
+// Probes are always baked lit, they are seen through the reflections
+@fragment
+fn fs_bake(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in, CHANNEL_LIT, false);
+}
+
+// The mirror reflection only matches the main camera, probes keep to the probes
+fn shade(in: VertexOutput, channel: u32, mirrors: bool) -> vec4<f32> {
+    let object = objects[in.object_index];
+    var material = object.material;
+    var enable_bit = object.enable_bit;
+    // Overrides keep cutouts and normal maps so the shape stays intact
+    let override_enabled = material_override.enabled != 0u;
+    if override_enabled {
+        material.ambient = material_override.material.ambient;
+        material.diffuse = material_override.material.diffuse;
+        material.specular = material_override.material.specular;
+        material.shininess = material_override.material.shininess;
+        enable_bit &= ~(4u | 8u | 16u);
+    }
     let texcoord = vec2<f32>(in.texcoord.x, 1.0 - in.texcoord.y);
+    let planes = triplanar_planes(in.world_position, in.normal, material.triplanar_scale);
+    let identity = vec4<f32>(1.0, 1.0, 0.0, 0.0);
+
+    var material_color = in.color;
+    var alpha = material.alpha;
+    if COLOR_MAP {
+        let color_sample = sample_map(color_texture, color_sampler, texcoord, planes, material.color_uv_transform);
+        material_color = color_sample.xyz;
+        alpha *= color_sample.w;
+    }
+    let color = select(material_color, vec3<f32>(1.0), override_enabled);
+
+    // enable bit 5: alpha map
+    alpha *= select(1.0, sample_map(alpha_texture, alpha_sampler, texcoord, planes, identity).x, (enable_bit & 32) != 0);
+    if material.alpha_mode == 1 && alpha < material.alpha_cutoff {
+        discard;
+    }
 
-    let color = (in.color * f32(~(enable_bit & 1) & 1)) + (textureSample(color_texture, color_sampler, texcoord).xyz * f32(enable_bit & 1));
+    // enable bit 2: specular map, 3: roughness map, 4: ambient occlusion map
+    let specular_map = sample_map(specular_texture, specular_sampler, texcoord, planes, identity).xyz;
+    let specular = material.specular.xyz * select(vec3<f32>(1.0), specular_map, (enable_bit & 4) != 0);
+    let roughness = sample_map(roughness_texture, roughness_sampler, texcoord, planes, identity).y;
+    // Blinn-Phong exponent matching the roughness
+    let roughness_alpha = max(roughness * roughness, 1e-3);
+    let shininess = select(material.shininess, 2.0 / (roughness_alpha * roughness_alpha) - 2.0, (enable_bit & 8) != 0);
+    let reflectivity = select(1.0, 1.0 - roughness, (enable_bit & 8) != 0);
+    let occlusion = select(1.0, sample_map(ambient_occlusion_texture, ambient_occlusion_sampler, texcoord, planes, identity).x, (enable_bit & 16) != 0);
 
     var light_color = vec3<f32>(0.0, 0.0, 0.0);
-    light_color += material.ambient.xyz * 0.05 * material.ambient.w;
+    let ambient = material.ambient.xyz * 0.05 * material.ambient.w * occlusion;
+    light_color += ambient;
 
-    let coef = (textureSample(normal_texture, normal_sampler, texcoord).xyz * 2 - 1);
-    let raw_normal = (normalize(in.normal) * f32(((~(enable_bit & 2)) >> 1) & 1)) + (normalize(coef.x * normalize(in.tangent) + coef.y * normalize(in.bitangent) + coef.z * in.normal) * f32((enable_bit & 2) >> 1));
+    var raw_normal = normalize(in.normal);
+    if NORMAL_MAP {
+        let normal_sample = textureSample(normal_texture, normal_sampler, texcoord);
+        let normal_xy = normal_sample.xy * 2 - 1;
+        // Two channel (BC5, RG8) normal maps get z rebuilt, the strength only scales
+        // the bend so the normal stays above the surface
+        let rebuilt_z = sqrt(max(1.0 - dot(normal_xy, normal_xy), 0.0));
+        let normal_z = select(normal_sample.z * 2 - 1, rebuilt_z, (enable_bit & 128u) != 0u);
+        let coef = vec3<f32>(normal_bend(normal_sample, material), normal_z);
+        // MikkTSpace rebuilds the bitangent per pixel from the unnormalized interpolated vectors
+        let bitangent = in.tangent_sign * cross(in.normal, in.tangent);
+        let mapped_normal = normalize(coef.x * in.tangent + coef.y * bitangent + coef.z * in.normal);
+        // The mesh tangents follow texture coordinates the planes do not use
+        let planar_normal = triplanar_normal(planes, raw_normal, material);
+        raw_normal = select(mapped_normal, planar_normal, planes.enabled);
+    }
     let view_dir = normalize(camera.view_position.xyz - in.world_position);
     let nDotV = dot(view_dir, raw_normal);
     let normal = f32(i32(nDotV < 0.0) * -2 + 1 ) * raw_normal;
 
-    let light_dir = normalize(light.position - in.world_position);
+    let light_dir = normalize(light.position.xyz - in.world_position * light.position.w);
     let nDotL = max(dot(light_dir, normal), 0.0);
     light_color += material.diffuse.xyz * 0.7 * nDotL * material.diffuse.w;
 
     let half_dir = normalize(view_dir + light_dir);
-    let strength = pow(max(dot(normal, half_dir), 0.0), material.shininess);
-    light_color += material.specular.xyz * strength * 1.0 * material.specular.w * f32(i32(nDotV > 1e-6));
+    let strength = pow(max(dot(normal, half_dir), 0.0), shininess);
+    light_color += specular * strength * 1.0 * material.specular.w * f32(i32(nDotV > 1e-6));
+
+    // The sky lights every surface, the ground below the horizon bounces some back
+    var sky_ambient = vec3<f32>(0.0);
+    if sky.environment > 0.0 {
+        sky_ambient = sky_radiance(normal) * sky.environment * material.diffuse.xyz * occlusion;
+    }
+    light_color += sky_ambient;
+
+    let reflect_dir = reflect(-view_dir, normal);
+    // Perceptual roughness the Blinn-Phong exponent stands for without a roughness map
+    let probe_roughness = select(pow(2.0 / (shininess + 2.0), 0.25), roughness, (enable_bit & 8) != 0);
+    var environment = sample_reflection_probes(in.world_position, reflect_dir, probe_roughness) * reflection_probes.intensity;
+    // Without probes the sky is reflected directly, the probes bake it in otherwise
+    if reflection_probes.count == 0u && sky.environment > 0.0 {
+        environment = sky_radiance(reflect_dir) * sky.environment;
+    }
+    // enable bit 6: the reflecting mirror, which looks up its mirrored view
+    if mirrors && (enable_bit & 64u) != 0u {
+        let clip = mirror_camera.view_matrix * vec4<f32>(in.world_position, 1.0);
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        environment = textureSampleLevel(mirror_texture, probe_sampler, uv, 0.0).xyz;
+    }
+    let reflection = specular * material.specular.w * reflectivity * occlusion * environment;
+    light_color += reflection;
+
+    let channel_alpha = select(1.0, alpha, material.alpha_mode == 2);
+    switch channel {
+        case CHANNEL_ALBEDO: {
+            return vec4<f32>(color, channel_alpha);
+        }
+        case CHANNEL_NORMALS: {
+            return vec4<f32>(normal * 0.5 + 0.5, channel_alpha);
+        }
+        case CHANNEL_DEPTH: {
+            // Logarithmic so near and far detail both show, near is bright
+            let znear = max(material_override.znear, 1e-4);
+            let distance = max(length(camera.view_position.xyz - in.world_position), znear);
+            let depth = log(distance / znear) / log(max(material_override.zfar, znear * 2.0) / znear);
+            return vec4<f32>(vec3<f32>(1.0 - saturate(depth)), channel_alpha);
+        }
+        case CHANNEL_ROUGHNESS: {
+            // Roughness matching the Blinn-Phong exponent without a roughness map
+            let derived = pow(2.0 / (shininess + 2.0), 0.25);
+            return vec4<f32>(vec3<f32>(select(derived, roughness, (enable_bit & 8) != 0)), channel_alpha);
+        }
+        case CHANNEL_AMBIENT_OCCLUSION: {
+            return vec4<f32>(vec3<f32>(occlusion), channel_alpha);
+        }
+        case CHANNEL_GI_ONLY: {
+            return vec4<f32>((ambient + sky_ambient + reflection) * color, channel_alpha);
+        }
+        case CHANNEL_SHADOWS_ONLY: {
+            // Nothing casts shadows yet, only the falloff towards the terminator
+            return vec4<f32>(vec3<f32>(nDotL), channel_alpha);
+        }
+        case CHANNEL_MATCAP: {
+            // The first two rows of the view projection point along the camera
+            // right and up axes, up to their scale
+            let right = normalize(vec3<f32>(camera.view_matrix[0].x, camera.view_matrix[1].x, camera.view_matrix[2].x));
+            let up = normalize(vec3<f32>(camera.view_matrix[0].y, camera.view_matrix[1].y, camera.view_matrix[2].y));
+            let view_normal = vec2<f32>(dot(normal, right), dot(normal, up));
+            let uv = view_normal * vec2<f32>(0.5, -0.5) + 0.5;
+            return vec4<f32>(textureSampleLevel(matcap_texture, matcap_sampler, uv, 0.0).xyz, channel_alpha);
+        }
+        default: {}
+    }
 
     let pred = (material.ambient.xyz - vec3<f32>(1e-5)) + (material.diffuse.xyz - vec3<f32>(1e-5)) + (material.specular.xyz - vec3<f32>(1e-5));
-    return vec4<f32>((light_color + f32((pred.x + pred.y + pred.z) <= 0)) * color, 1.0);
+    let lit = (light_color + f32((pred.x + pred.y + pred.z) <= 0)) * color;
+    return vec4<f32>(lit + material.emissive.xyz, channel_alpha);
 }
\ No newline at end of file
@@ -0,0 +1,504 @@
+//! A small preprocessor for the WGSL shaders, so declarations shared by several
+//! shaders live in one file and pipelines can pick features when they are
+//! created.
+//!
+//! Directives take a line of their own:
+//!
+//! - `#include "name.wgsl"` pastes another shader of `SOURCES`. Each file is
+//!   pasted once per module, so the structs it declares are not declared twice.
+//! - `#define NAME value` replaces the identifier `NAME` in the lines below it,
+//!   `#define NAME` without a value only sets a flag.
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` keep or drop the lines
+//!   between them, and may nest.
+//!
+//! The definitions given with `ShaderDefs` come first, as if defined at the top
+//! of the shader. The result remembers the file and line each of its lines came
+//! from, naga errors are reported there rather than in the pasted source.
+//!
+//! The shaders are embedded in the binary, a `ShaderLibrary` read from
+//! `SHADER_DIRECTORY` replaces them while they are edited.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::Path,
+    time::SystemTime,
+};
+
+use wgpu::{naga, Device};
+
+use crate::capabilities::Capabilities;
+
+/// Where the embedded shaders were written, read again when reloading
+pub const SHADER_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+
+/// Shaders that can be included or turned into modules, by name
+const SOURCES: &[(&str, &str)] = &[
+    ("culling.wgsl", include_str!("culling.wgsl")),
+    ("heatmap.wgsl", include_str!("heatmap.wgsl")),
+    ("scene_types.wgsl", include_str!("scene_types.wgsl")),
+    ("shader.wgsl", include_str!("shader.wgsl")),
+    ("skinning.wgsl", include_str!("skinning.wgsl")),
+    ("wireframe.wgsl", include_str!("wireframe.wgsl")),
+];
+
+/// Text of the shaders, the embedded `SOURCES` unless read from disk.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderLibrary {
+    /// Replaces the embedded text of a file
+    files: HashMap<&'static str, String>,
+}
+
+impl ShaderLibrary {
+    /// Read every file of `SOURCES` from `directory`.
+    pub fn read(directory: &Path) -> Result<Self, ShaderError> {
+        let files = SOURCES
+            .iter()
+            .map(|(name, _)| {
+                let path = directory.join(name);
+                std::fs::read_to_string(&path)
+                    .map(|text| (*name, text))
+                    .map_err(|err| ShaderError {
+                        location: None,
+                        message: format!("failed to read {}: {}", path.display(), err),
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { files })
+    }
+
+    /// Latest modification of the files of `SOURCES` in `directory`, `None`
+    /// when none of them can be found.
+    pub fn modified(directory: &Path) -> Option<SystemTime> {
+        SOURCES
+            .iter()
+            .filter_map(|(name, _)| directory.join(name).metadata().ok()?.modified().ok())
+            .max()
+    }
+
+    fn source(&self, name: &str) -> Option<(&'static str, &str)> {
+        let (file, text) = SOURCES.iter().copied().find(|(file, _)| *file == name)?;
+        Some((file, self.files.get(file).map_or(text, String::as_str)))
+    }
+}
+
+/// Definitions a shader is preprocessed with, see the module docs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefs(BTreeMap<String, String>);
+
+impl ShaderDefs {
+    /// Set the flag `name`, tested with `#ifdef`.
+    pub fn flag(mut self, name: &str) -> Self {
+        self.0.insert(name.to_owned(), String::new());
+        self
+    }
+
+    /// Set the flag `name` if `enabled`.
+    pub fn flag_if(self, name: &str, enabled: bool) -> Self {
+        if enabled {
+            self.flag(name)
+        } else {
+            self
+        }
+    }
+
+    /// Replace the identifier `name` with `value`.
+    pub fn value(mut self, name: &str, value: impl ToString) -> Self {
+        self.0.insert(name.to_owned(), value.to_string());
+        self
+    }
+}
+
+/// Line and column in one of `SOURCES`, counted from one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: &'static str,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShaderError {
+    /// Missing when naga could not tell where the error is
+    pub location: Option<SourceLocation>,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{}: {}", location, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl ShaderError {
+    fn at(location: SourceLocation, message: impl ToString) -> Self {
+        Self {
+            location: Some(location),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// An `#ifdef` or `#ifndef` whose `#endif` was not reached yet.
+struct Condition {
+    /// Lines are kept in this branch
+    active: bool,
+    in_else: bool,
+    location: SourceLocation,
+}
+
+fn is_identifier(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// WGSL of a shader and its includes, ready to become a module.
+pub struct ShaderSource {
+    pub name: &'static str,
+    pub code: String,
+    /// File and line of each line of `code`
+    lines: Vec<(&'static str, usize)>,
+}
+
+impl ShaderSource {
+    pub fn preprocess(
+        library: &ShaderLibrary,
+        name: &str,
+        defs: &ShaderDefs,
+    ) -> Result<Self, ShaderError> {
+        let (name, text) = library.source(name).ok_or_else(|| ShaderError {
+            location: None,
+            message: format!("unknown shader {}", name),
+        })?;
+        let mut preprocessor = Preprocessor {
+            library,
+            defines: defs.0.clone(),
+            included: HashSet::new(),
+            source: Self {
+                name,
+                code: String::new(),
+                lines: Vec::new(),
+            },
+        };
+        preprocessor.file(name, text)?;
+        Ok(preprocessor.source)
+    }
+
+    /// Where a location in `code` was written, naga counts lines from one.
+    fn locate(&self, location: naga::SourceLocation) -> Option<SourceLocation> {
+        let (file, line) = *self
+            .lines
+            .get((location.line_number as usize).checked_sub(1)?)?;
+        Some(SourceLocation {
+            file,
+            line,
+            column: location.line_position as usize,
+        })
+    }
+
+    /// Parse and validate `code` with naga, the way wgpu does when the module
+    /// is created, so errors can be reported in the original files. See
+    /// `Capabilities::shader_capabilities` for `capabilities`.
+    pub fn validate(&self, capabilities: naga::valid::Capabilities) -> Result<(), ShaderError> {
+        let module = naga::front::wgsl::parse_str(&self.code).map_err(|err| ShaderError {
+            location: err.location(&self.code).and_then(|l| self.locate(l)),
+            message: err.message().to_owned(),
+        })?;
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), capabilities)
+            .validate(&module)
+            .map_err(|err| ShaderError {
+                location: err.location(&self.code).and_then(|l| self.locate(l)),
+                message: format!("{:#}", anyhow::Error::from(err.into_inner())),
+            })?;
+        Ok(())
+    }
+}
+
+struct Preprocessor<'a> {
+    library: &'a ShaderLibrary,
+    defines: BTreeMap<String, String>,
+    included: HashSet<&'static str>,
+    source: ShaderSource,
+}
+
+impl Preprocessor<'_> {
+    fn file(&mut self, file: &'static str, text: &str) -> Result<(), ShaderError> {
+        self.included.insert(file);
+        let mut conditions: Vec<Condition> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let location = SourceLocation {
+                file,
+                line: index + 1,
+                column: 1,
+            };
+            let active = conditions.iter().all(|condition| condition.active);
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    self.push_line(line, location);
+                }
+                continue;
+            };
+            let keyword = directive.split_whitespace().next().unwrap_or_default();
+            let argument = directive[directive.find(keyword).unwrap_or(0) + keyword.len()..].trim();
+            let name = || {
+                argument
+                    .split_whitespace()
+                    .next()
+                    .filter(|name| is_identifier(name))
+                    .ok_or_else(|| {
+                        ShaderError::at(location, format!("#{} expects a name", keyword))
+                    })
+            };
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let defined = self.defines.contains_key(name()?);
+                    conditions.push(Condition {
+                        active: defined == (keyword == "ifdef"),
+                        in_else: false,
+                        location,
+                    });
+                }
+                "else" => {
+                    let condition = conditions
+                        .last_mut()
+                        .filter(|condition| !condition.in_else)
+                        .ok_or_else(|| ShaderError::at(location, "#else without #ifdef"))?;
+                    condition.in_else = true;
+                    condition.active = !condition.active;
+                }
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or_else(|| ShaderError::at(location, "#endif without #ifdef"))?;
+                }
+                // The branches not taken may use directives of later versions
+                _ if !active => {}
+                "define" => {
+                    let name = name()?;
+                    let value = argument[name.len()..].trim();
+                    self.defines.insert(name.to_owned(), value.to_owned());
+                }
+                "include" => {
+                    let included = argument
+                        .strip_prefix('"')
+                        .and_then(|rest| rest.strip_suffix('"'))
+                        .ok_or_else(|| {
+                            ShaderError::at(location, "#include expects a quoted file name")
+                        })?;
+                    let (included, text) = self.library.source(included).ok_or_else(|| {
+                        ShaderError::at(location, format!("unknown shader {}", included))
+                    })?;
+                    if !self.included.contains(included) {
+                        self.file(included, text)?;
+                    }
+                }
+                _ => {
+                    return Err(ShaderError::at(
+                        location,
+                        format!("unknown directive #{}", keyword),
+                    ))
+                }
+            }
+        }
+        match conditions.last() {
+            Some(condition) => Err(ShaderError::at(condition.location, "#endif missing")),
+            None => Ok(()),
+        }
+    }
+
+    /// Append `line` with its defined identifiers replaced, comments are kept
+    /// as they are.
+    fn push_line(&mut self, line: &str, location: SourceLocation) {
+        let (code, comment) = line.split_at(line.find("//").unwrap_or(line.len()));
+        let output = &mut self.source.code;
+        let mut rest = code;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            // Numbers such as 0x10u are not identifiers
+            match self.defines.get(word).filter(|value| !value.is_empty()) {
+                Some(value) if is_identifier(word) => output.push_str(value),
+                _ => output.push_str(word),
+            }
+            rest = &rest[end..];
+        }
+        output.push_str(rest);
+        output.push_str(comment);
+        output.push('\n');
+        self.source.lines.push((location.file, location.line));
+    }
+}
+
+/// Report the wgpu validation errors raised by `create` rather than panicking
+/// in the uncaptured error handler.
+pub fn capture_errors<T>(device: &Device, create: impl FnOnce() -> T) -> Result<T, ShaderError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let created = create();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(err) => Err(ShaderError {
+            location: None,
+            message: format!("{:#}", anyhow::Error::from(err)),
+        }),
+        None => Ok(created),
+    }
+}
+
+/// Preprocess the shader `name` of `library` into a module, naga errors are
+/// located in the original files.
+pub fn try_create_shader_module(
+    device: &Device,
+    capabilities: &Capabilities,
+    library: &ShaderLibrary,
+    name: &str,
+    defs: &ShaderDefs,
+) -> Result<wgpu::ShaderModule, ShaderError> {
+    let source = ShaderSource::preprocess(library, name, defs)?;
+    source.validate(capabilities.shader_capabilities())?;
+    capture_errors(device, || {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(source.name),
+            source: wgpu::ShaderSource::Wgsl(source.code.as_str().into()),
+        })
+    })
+}
+
+/// Preprocess the embedded shader `name` into a module. Panics with the error
+/// in the original file when the shader is invalid.
+pub fn create_shader_module(
+    device: &Device,
+    capabilities: &Capabilities,
+    name: &str,
+    defs: &ShaderDefs,
+) -> wgpu::ShaderModule {
+    try_create_shader_module(device, capabilities, &ShaderLibrary::default(), name, defs)
+        .unwrap_or_else(|err| panic!("invalid shader {}: {}", name, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The embedded shaders with `files` replacing some of them.
+    fn library(files: &[(&'static str, &str)]) -> ShaderLibrary {
+        ShaderLibrary {
+            files: files
+                .iter()
+                .map(|(name, text)| (*name, text.to_string()))
+                .collect(),
+        }
+    }
+
+    fn preprocess(library: &ShaderLibrary, defs: &ShaderDefs) -> Result<ShaderSource, ShaderError> {
+        ShaderSource::preprocess(library, "shader.wgsl", defs)
+    }
+
+    #[test]
+    fn includes_are_pasted_once() {
+        let library = library(&[
+            (
+                "shader.wgsl",
+                "#include \"skinning.wgsl\"\n#include \"scene_types.wgsl\"\nmain",
+            ),
+            ("skinning.wgsl", "#include \"scene_types.wgsl\"\nskinning"),
+            ("scene_types.wgsl", "types"),
+        ]);
+        let source = preprocess(&library, &ShaderDefs::default()).unwrap();
+        assert_eq!(source.code, "types\nskinning\nmain\n");
+        assert_eq!(
+            source.lines,
+            [
+                ("scene_types.wgsl", 1),
+                ("skinning.wgsl", 2),
+                ("shader.wgsl", 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_include_is_located() {
+        let library = library(&[("shader.wgsl", "main\n#include \"missing.wgsl\"")]);
+        let error = preprocess(&library, &ShaderDefs::default()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "shader.wgsl:2:1: unknown shader missing.wgsl"
+        );
+    }
+
+    #[test]
+    fn include_cycles_end() {
+        let library = library(&[
+            ("shader.wgsl", "#include \"skinning.wgsl\"\nmain"),
+            ("skinning.wgsl", "#include \"shader.wgsl\"\nskinning"),
+        ]);
+        let source = preprocess(&library, &ShaderDefs::default()).unwrap();
+        assert_eq!(source.code, "skinning\nmain\n");
+    }
+
+    #[test]
+    fn defines_replace_identifiers() {
+        let library = library(&[(
+            "shader.wgsl",
+            "#define SIZE COUNT * 2\n\
+             #ifdef FAST\n\
+             let a = COUNT + COUNT_2 + SIZE; // COUNT\n\
+             #else\n\
+             slow\n\
+             #endif\n\
+             #ifndef FAST\n\
+             slow\n\
+             #endif",
+        )]);
+        let defs = ShaderDefs::default().flag("FAST").value("COUNT", 4);
+        let source = preprocess(&library, &defs).unwrap();
+        assert_eq!(source.code, "let a = 4 + COUNT_2 + COUNT * 2; // COUNT\n");
+        assert_eq!(source.lines, [("shader.wgsl", 3)]);
+    }
+
+    #[test]
+    fn unbalanced_conditions_are_errors() {
+        for (text, message) in [
+            ("#ifdef FAST\nmain", "shader.wgsl:1:1: #endif missing"),
+            ("main\n#endif", "shader.wgsl:2:1: #endif without #ifdef"),
+            (
+                "#ifdef FAST\n#else\n#else\n#endif",
+                "shader.wgsl:3:1: #else without #ifdef",
+            ),
+        ] {
+            let library = library(&[("shader.wgsl", text)]);
+            let error = preprocess(&library, &ShaderDefs::default()).err().unwrap();
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn embedded_shaders_validate() {
+        for name in [
+            "culling.wgsl",
+            "heatmap.wgsl",
+            "shader.wgsl",
+            "wireframe.wgsl",
+        ] {
+            let source =
+                ShaderSource::preprocess(&ShaderLibrary::default(), name, &ShaderDefs::default())
+                    .unwrap();
+            if let Err(error) = source.validate(naga::valid::Capabilities::CUBE_ARRAY_TEXTURES) {
+                panic!("{}: {}", name, error);
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Fixed timestep of everything that moves on its own.
+//!
+//! Animations, the light orbit, camera path playback and the camera motion
+//! advance in steps of `TIMESTEP` however long the frames take, so they behave
+//! the same with or without vsync and at any frame rate. A frame runs as many
+//! steps as its time covers and carries the rest over to the next one. The
+//! camera, light and animation time it renders are interpolated between the
+//! last two steps by that rest, rendering trails the simulation by up to a step.
+//!
+//! Pausing stops the steps until one is requested, for inspecting what a
+//! single step changes. Frames in between show the last step as it is.
+
+use std::time::Duration;
+
+use glam::Vec3;
+
+use crate::{camera::Camera, input::InputState, AppState};
+
+/// 60 steps a second.
+pub const TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+/// Steps a single frame catches up at most, after a longer stall such as a
+/// scene load the simulation falls behind the clock rather than spiraling.
+const MAX_STEPS: u32 = 8;
+
+/// A simulated value after the last two steps and as it was last rendered.
+#[derive(Debug, Clone, Default)]
+struct Interpolated<T> {
+    previous: T,
+    current: T,
+    /// Written into the state for the last frame, a different value there was
+    /// set from outside, by the UI, a bookmark or a capture
+    rendered: T,
+}
+
+impl<T: Clone + PartialEq> Interpolated<T> {
+    /// Put the simulated value back into `value` before stepping, or take the
+    /// value set from outside since the last frame.
+    fn restore(&mut self, value: &mut T) {
+        if *value == self.rendered {
+            *value = self.current.clone();
+        } else {
+            self.previous = value.clone();
+            self.current = value.clone();
+        }
+    }
+
+    fn record(&mut self, value: &T) {
+        self.previous = std::mem::replace(&mut self.current, value.clone());
+    }
+
+    /// Write the value `alpha` of the way from the previous to the current step
+    /// into `value`.
+    fn present(&mut self, value: &mut T, alpha: f32, lerp: impl Fn(&T, &T, f32) -> T) {
+        self.rendered = lerp(&self.previous, &self.current, alpha);
+        *value = self.rendered.clone();
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Simulation {
+    /// Time not simulated yet, less than a step once a frame advanced
+    accumulator: Duration,
+    camera: Interpolated<Camera>,
+    light_position: Interpolated<[f32; 3]>,
+    animation_time: Interpolated<f32>,
+}
+
+impl Simulation {
+    /// Run the steps `frame_time` covers and leave `state` interpolated for
+    /// rendering. Deterministic runs pass `TIMESTEP` for every frame.
+    pub fn advance(&mut self, state: &mut AppState, frame_time: Duration) {
+        let steps = if state.paused {
+            // The time spent paused is not caught up with
+            self.accumulator = Duration::ZERO;
+            std::mem::take(&mut state.step_frame) as u32
+        } else {
+            self.accumulator = (self.accumulator + frame_time).min(TIMESTEP * MAX_STEPS);
+            let steps = (self.accumulator.as_nanos() / TIMESTEP.as_nanos()) as u32;
+            self.accumulator -= TIMESTEP * steps;
+            steps
+        };
+        state.time_frozen = state.paused && steps == 0;
+        self.camera.restore(&mut state.camera);
+        self.light_position.restore(&mut state.light_position);
+        self.animation_time.restore(&mut state.animation_time);
+        // Look and scroll wait for the next frame that steps, paused they would
+        // all land on the next single step
+        if state.time_frozen {
+            state.input.clear_motion();
+        } else if steps > 0 {
+            let input = state.input.take_steps(steps);
+            for _ in 0..steps {
+                step(state, &input);
+                self.camera.record(&state.camera);
+                self.light_position.record(&state.light_position);
+                self.animation_time.record(&state.animation_time);
+            }
+        }
+        // Paused frames show the last step rather than trail it
+        let alpha = if state.paused {
+            1.0
+        } else {
+            self.accumulator.as_secs_f32() / TIMESTEP.as_secs_f32()
+        };
+        self.camera.present(&mut state.camera, alpha, Camera::lerp);
+        self.light_position
+            .present(&mut state.light_position, alpha, |previous, current, t| {
+                Vec3::from(*previous)
+                    .lerp(Vec3::from(*current), t)
+                    .to_array()
+            });
+        // Not blended across the end of a loop
+        self.animation_time
+            .present(&mut state.animation_time, alpha, |previous, current, t| {
+                if current < previous {
+                    *current
+                } else {
+                    previous + (current - previous) * t
+                }
+            });
+    }
+}
+
+/// Move everything by one `TIMESTEP`, with the share of the look and scroll of
+/// `input` that falls on this step.
+fn step(state: &mut AppState, input: &InputState) {
+    let dt = TIMESTEP.as_secs_f32();
+    if state.scene_animated && state.play_animation {
+        // One timeline for every animation, looping after the longest
+        let duration = state.animation_duration;
+        let time = state.animation_time + dt;
+        state.animation_time = if duration > 0.0 { time % duration } else { 0.0 };
+        // Start the next refresh once the previous time sliced one completed
+        if state.animate_reflection_probes && !state.probe_bake.is_pending() {
+            state.bake_reflection_probes = true;
+        }
+    }
+    if state.light_orbit.advance(&mut state.light_position, dt) {
+        state.light_input = state.light_position.map(|p| format!("{:.2}", p));
+        // The probes keep up with the light like with an animated scene
+        if state.animate_reflection_probes && !state.probe_bake.is_pending() {
+            state.bake_reflection_probes = true;
+        }
+    }
+    state
+        .camera_controller
+        .update_camera(&mut state.camera, input, TIMESTEP);
+    let camera_path = &state.scene_file.camera_path;
+    let playback = &mut state.path_playback;
+    if playback.playing {
+        playback.time += dt;
+        if playback.time >= camera_path.duration() {
+            playback.time = camera_path.duration();
+            playback.playing = false;
+        }
+    }
+    if playback.following {
+        camera_path.place_camera(playback.time, &mut state.camera);
+    }
+}
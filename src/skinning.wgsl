@@ -0,0 +1,23 @@
+// The object and joint buffers bound at OBJECT_GROUP, which the including
+// shader defines
+
+#include "scene_types.wgsl"
+
+// Indexed by the instance, the draw of a geom starts at its object
+@group(OBJECT_GROUP) @binding(0)
+var<storage, read> objects: array<Object>;
+
+// Mesh to world matrix of every joint of every skin
+@group(OBJECT_GROUP) @binding(1)
+var<storage, read> joints: array<mat4x4<f32>>;
+
+// Mesh to world matrix of a vertex, blended from its joints on skinned objects
+fn object_transform(object: Object, joint_indices: vec4<u32>, weights: vec4<f32>) -> mat4x4<f32> {
+    if object.joint_offset == NO_SKIN {
+        return object.model;
+    }
+    return joints[object.joint_offset + joint_indices.x] * weights.x
+        + joints[object.joint_offset + joint_indices.y] * weights.y
+        + joints[object.joint_offset + joint_indices.z] * weights.z
+        + joints[object.joint_offset + joint_indices.w] * weights.w;
+}
@@ -0,0 +1,284 @@
+//! Analytic daylight sky after Preetham, Shirley and Smits, "A Practical
+//! Analytic Model for Daylight" (1999). The sun follows a day long arc set by
+//! the time of day, the Perez coefficients of the sky are fitted to it here and
+//! evaluated per pixel by `sky.wgsl`, and by `shader.wgsl` where the sky lights
+//! the scene.
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec3, Vec4};
+use wgpu::Device;
+
+use crate::{object_ids, texture};
+
+/// Zenith luminance of the model is in kcd/m², scaled to the range the scene
+/// lighting works in
+const LUMINANCE_SCALE: f32 = 0.05;
+/// Sun elevations, as the sine, over which day turns into night
+const TWILIGHT: (f32, f32) = (-0.1, 0.05);
+
+/// Sky drawn behind the scene with a sun moving over the day, see the module
+/// docs.
+#[derive(Debug, Clone, Copy)]
+pub struct Sky {
+    pub enabled: bool,
+    /// Hours from midnight, the sun rises at 6 and sets at 18
+    pub time_of_day: f32,
+    /// Degrees above the horizon the sun reaches at noon
+    pub noon_elevation: f32,
+    /// Degrees the path of the sun is turned around the up axis, at zero it
+    /// rises towards +X and culminates towards +Z
+    pub azimuth: f32,
+    /// Haze of the atmosphere, 2 is a clear sky and 10 a hazy one
+    pub turbidity: f32,
+    pub intensity: f32,
+    /// Scale of the sky light reaching the surfaces as ambient light, and as
+    /// reflections where no reflection probe is placed
+    pub environment_intensity: f32,
+    /// The scene light shines from the sun instead of its position
+    pub sun_light: bool,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            time_of_day: 10.0,
+            noon_elevation: 60.0,
+            azimuth: 0.0,
+            turbidity: 3.0,
+            intensity: 1.0,
+            environment_intensity: 0.5,
+            sun_light: true,
+        }
+    }
+}
+
+impl Sky {
+    /// Unit direction towards the sun, below the horizon at night.
+    pub fn sun_direction(&self) -> Vec3 {
+        // Hour angle, zero at noon and growing towards the evening
+        let hour_angle = (self.time_of_day - 12.0) / 24.0 * TAU;
+        let noon_elevation = self.noon_elevation.to_radians();
+        let noon = Vec3::new(0.0, noon_elevation.sin(), noon_elevation.cos());
+        let direction = noon * hour_angle.cos() - Vec3::X * hour_angle.sin();
+        glam::Quat::from_rotation_y(self.azimuth.to_radians()) * direction
+    }
+
+    /// Direction the scene light shines from while the sky drives it.
+    pub fn light_direction(&self) -> Option<Vec3> {
+        (self.enabled && self.sun_light).then(|| self.sun_direction())
+    }
+
+    /// Brightness of the day, one once the sun is up and zero at night.
+    pub fn daylight(&self) -> f32 {
+        let t = (self.sun_direction().y - TWILIGHT.0) / (TWILIGHT.1 - TWILIGHT.0);
+        let t = t.clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// Perez distribution coefficients A to E of luminance Y and chromaticity x
+/// and y, linear in the turbidity.
+fn perez_coefficients(turbidity: f32) -> [Vec3; 5] {
+    let t = turbidity;
+    [
+        Vec3::new(
+            0.1787 * t - 1.4630,
+            -0.0193 * t - 0.2592,
+            -0.0167 * t - 0.2608,
+        ),
+        Vec3::new(
+            -0.3554 * t + 0.4275,
+            -0.0665 * t + 0.0008,
+            -0.0950 * t + 0.0092,
+        ),
+        Vec3::new(
+            -0.0227 * t + 5.3251,
+            -0.0004 * t + 0.2125,
+            -0.0079 * t + 0.2102,
+        ),
+        Vec3::new(
+            0.1206 * t - 2.5771,
+            -0.0641 * t - 0.8989,
+            -0.0441 * t - 1.6537,
+        ),
+        Vec3::new(
+            -0.0670 * t + 0.3703,
+            -0.0033 * t + 0.0452,
+            -0.0109 * t + 0.0529,
+        ),
+    ]
+}
+
+/// Relative distribution of the sky at zenith angle `theta` and angle `gamma`
+/// from the sun.
+fn perez(coefficients: &[Vec3; 5], theta: f32, gamma: f32) -> Vec3 {
+    let [a, b, c, d, e] = *coefficients;
+    let cos_gamma = gamma.cos();
+    (Vec3::ONE + a * (b / theta.cos().max(1e-3)).exp())
+        * (Vec3::ONE + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+/// Luminance Y and chromaticity x and y straight up, for the sun at zenith
+/// angle `theta_sun`.
+fn zenith(turbidity: f32, theta_sun: f32) -> Vec3 {
+    let t = turbidity;
+    let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta_sun);
+    let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+    // Cubic polynomials of the sun angle per power of the turbidity
+    let theta = Vec3::new(theta_sun.powi(3), theta_sun.powi(2), theta_sun);
+    let x = t * t * (0.00166 * theta.x - 0.00375 * theta.y + 0.00209 * theta.z)
+        + t * (-0.02903 * theta.x + 0.06377 * theta.y - 0.03202 * theta.z + 0.00394)
+        + (0.11693 * theta.x - 0.21196 * theta.y + 0.06052 * theta.z + 0.25886);
+    let y = t * t * (0.00275 * theta.x - 0.00610 * theta.y + 0.00317 * theta.z)
+        + t * (-0.04214 * theta.x + 0.08970 * theta.y - 0.04153 * theta.z + 0.00516)
+        + (0.15346 * theta.x - 0.26756 * theta.y + 0.06670 * theta.z + 0.26688);
+    Vec3::new(luminance.max(0.0), x, y)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct UniformSky {
+    /// Perez coefficients A to E, xyz: luminance Y and chromaticity x and y
+    perez: [Vec4; 5],
+    /// Zenith values divided by their distribution at the zenith
+    zenith: Vec4,
+    /// xyz: unit direction towards the sun, kept above the horizon, w: cosine
+    /// of the angular radius of its disk
+    sun: Vec4,
+    /// Scale of the sky radiance, zero at night
+    intensity: f32,
+    /// Scale of the sky light on the surfaces, zero while the sky is off
+    environment: f32,
+    _padding: [u32; 2],
+}
+
+impl UniformSky {
+    /// Angular radius of the drawn sun disk, larger than the real one
+    const SUN_RADIUS: f32 = 0.01;
+
+    pub fn new(sky: &Sky) -> Self {
+        let sun = sky.sun_direction();
+        // The model holds for a sun above the horizon only
+        let model_sun = Vec3::new(sun.x, sun.y.max(0.0), sun.z).normalize_or(Vec3::Y);
+        let theta_sun = model_sun.y.clamp(-1.0, 1.0).acos().min(FRAC_PI_2);
+        let coefficients = perez_coefficients(sky.turbidity);
+        let zenith = zenith(sky.turbidity, theta_sun) / perez(&coefficients, 0.0, theta_sun);
+        Self {
+            perez: coefficients.map(|coefficient| coefficient.extend(0.0)),
+            zenith: zenith.extend(0.0),
+            sun: model_sun.extend(Self::SUN_RADIUS.cos()),
+            intensity: sky.intensity * sky.daylight() * LUMINANCE_SCALE,
+            environment: if sky.enabled {
+                sky.environment_intensity
+            } else {
+                0.0
+            },
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// Pass the sky is drawn into, each has its own pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkyTarget {
+    /// A view with a color target only
+    View,
+    /// The opaque pass of the main view, which also writes object ids
+    ObjectIds,
+    /// A reflection probe face
+    Bake,
+}
+
+/// Draws the `Sky` wherever the depth buffer is still clear, behind the
+/// geometry of the main view and of the probe bakes.
+pub struct SkyBackground {
+    pipeline: wgpu::RenderPipeline,
+    /// Also clears the object id of the pixels it covers
+    id_pipeline: wgpu::RenderPipeline,
+    /// For the format the reflection probe faces are rendered in
+    bake_pipeline: wgpu::RenderPipeline,
+}
+
+impl SkyBackground {
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        bake_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        scene_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("sky.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, scene_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label: &str, format, object_ids: bool| {
+            let targets = [
+                Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+                Some(wgpu::ColorTargetState {
+                    format: object_ids::FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+            ];
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(if object_ids { "fs_main_ids" } else { "fs_main" }),
+                    targets: &targets[..1 + object_ids as usize],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                // Drawn on the far plane, only where no geometry was drawn
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        Self {
+            pipeline: create_pipeline("Sky Pipeline", format, false),
+            id_pipeline: create_pipeline("Sky Object Id Pipeline", format, true),
+            bake_pipeline: create_pipeline("Sky Probe Bake Pipeline", bake_format, false),
+        }
+    }
+
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        scene_bind_group: &wgpu::BindGroup,
+        target: SkyTarget,
+    ) {
+        render_pass.set_pipeline(match target {
+            SkyTarget::View => &self.pipeline,
+            SkyTarget::ObjectIds => &self.id_pipeline,
+            SkyTarget::Bake => &self.bake_pipeline,
+        });
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, scene_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,107 @@
+// Preetham daylight sky drawn behind the scene, see `sky.rs`
+
+struct Camera {
+    view_matrix: mat4x4<f32>,
+    view_position: vec4<f32>,
+    inverse_view_matrix: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Sky {
+    // Perez coefficients A to E, xyz: luminance Y and chromaticity x and y
+    a: vec4<f32>,
+    b: vec4<f32>,
+    c: vec4<f32>,
+    d: vec4<f32>,
+    e: vec4<f32>,
+    // zenith values divided by their distribution at the zenith
+    zenith: vec4<f32>,
+    // xyz: unit direction towards the sun, w: cosine of the disk radius
+    sun: vec4<f32>,
+    intensity: f32,
+    environment: f32,
+}
+
+// The scene bind group of `shader.wgsl`
+@group(1) @binding(5)
+var<uniform> sky: Sky;
+
+// Part of the sky the ground below the horizon reflects
+const GROUND_ALBEDO: f32 = 0.3;
+// Radiance of the sun disk relative to the sky around it
+const SUN_DISK: f32 = 20.0;
+
+// Keep in sync with `sky_radiance` in `shader.wgsl`
+fn perez(theta: f32, gamma: f32) -> vec3<f32> {
+    let cos_gamma = cos(gamma);
+    return (1.0 + sky.a.xyz * exp(sky.b.xyz / max(cos(theta), 1e-3)))
+        * (1.0 + sky.c.xyz * exp(sky.d.xyz * gamma) + sky.e.xyz * cos_gamma * cos_gamma);
+}
+
+fn sky_radiance(dir: vec3<f32>) -> vec3<f32> {
+    // Below the horizon a dim mirror image of the sky stands in for the ground
+    let above = vec3<f32>(dir.x, abs(dir.y), dir.z);
+    let theta = acos(clamp(above.y, -1.0, 1.0));
+    let cos_gamma = clamp(dot(above, sky.sun.xyz), -1.0, 1.0);
+    let value = sky.zenith.xyz * perez(theta, acos(cos_gamma));
+    // xyY to XYZ to linear sRGB
+    let luminance = value.x;
+    let chromaticity = value.yz;
+    let xyz = vec3<f32>(
+        chromaticity.x / chromaticity.y * luminance,
+        luminance,
+        (1.0 - chromaticity.x - chromaticity.y) / chromaticity.y * luminance,
+    );
+    let rgb = max(mat3x3<f32>(
+        3.2406, -0.9689, 0.0557,
+        -1.5372, 1.8758, -0.2040,
+        -0.4986, 0.0415, 1.0570,
+    ) * xyz, vec3<f32>(0.0)) * sky.intensity;
+    if dir.y < 0.0 {
+        return rgb * GROUND_ALBEDO;
+    }
+    return select(rgb, rgb * SUN_DISK, cos_gamma > sky.sun.w);
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // The view ray through the pixel on the near and far plane
+    @location(0) near: vec4<f32>,
+    @location(1) far: vec4<f32>,
+}
+
+// One triangle covering the whole screen on the far plane
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    // Linear in screen space, divided per pixel
+    out.near = camera.inverse_view_matrix * vec4<f32>(ndc, 0.0, 1.0);
+    out.far = camera.inverse_view_matrix * vec4<f32>(ndc, 1.0, 1.0);
+    return out;
+}
+
+fn view_radiance(in: VertexOutput) -> vec4<f32> {
+    let dir = normalize(in.far.xyz / in.far.w - in.near.xyz / in.near.w);
+    return vec4<f32>(sky_radiance(dir), 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return view_radiance(in);
+}
+
+struct ObjectIdOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) object_id: u32,
+}
+
+// The sky is no object, it leaves the id at zero
+@fragment
+fn fs_main_ids(in: VertexOutput) -> ObjectIdOutput {
+    return ObjectIdOutput(view_radiance(in), 0u);
+}
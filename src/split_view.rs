@@ -0,0 +1,54 @@
+use crate::primitives::MaterialOverride;
+
+/// Renderer settings that can differ between the two sides of the split view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplitSettings {
+    pub material_override: MaterialOverride,
+    pub enable_reflection_probes: bool,
+}
+
+impl SplitSettings {
+    /// Short description shown above each side of the viewport.
+    pub fn caption(&self) -> String {
+        format!(
+            "Material {}, probes {}",
+            self.material_override.name(),
+            if self.enable_reflection_probes {
+                "on"
+            } else {
+                "off"
+            }
+        )
+    }
+}
+
+/// The same camera rendered twice side by side, the current settings left of the
+/// splitter and `right` right of it, to compare renderer configurations directly.
+#[derive(Debug, Clone, Copy)]
+pub struct SplitView {
+    pub enabled: bool,
+    /// Splitter position in fractions of the window width
+    pub split: f32,
+    pub right: SplitSettings,
+}
+
+impl Default for SplitView {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            split: 0.5,
+            // Direct lighting only against the probe lit default
+            right: SplitSettings {
+                material_override: MaterialOverride::Off,
+                enable_reflection_probes: false,
+            },
+        }
+    }
+}
+
+impl SplitView {
+    /// First surface column of the right side for a window `width` pixels wide.
+    pub fn column(split: f32, width: u32) -> u32 {
+        ((split.clamp(0.0, 1.0) * width as f32).round() as u32).min(width)
+    }
+}
@@ -0,0 +1,155 @@
+//! Stages the window renders every frame, in the order of
+//! `AppState::render_stages`. Every stage is created from its slot and boxed as
+//! a `RenderStage`.
+//!
+//! The scene, debug view, post processing and present stages each record their
+//! passes of the frame graph of one `DefaultRenderer`, which the window also
+//! uses for loading and picking. The window resizes and updates that renderer
+//! once per frame, whichever of its stages are enabled.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{renderer::DefaultRenderer, AppState, RenderStage};
+
+/// The renderer of the window, shared with the stages recording its passes.
+pub type SharedRenderer = Rc<RefCell<DefaultRenderer>>;
+
+/// What a slot of `AppState::render_stages` renders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StageKind {
+    /// The loaded scene, from the probe bake to the volumetric fog
+    Scene,
+    /// Heatmap, wireframe and debug draw overlays, texture previews, the split
+    /// view and the region of interest
+    DebugViews,
+    /// Frame statistics and the post effects
+    PostProcess,
+    /// Tone maps the scene or the post effects into the window
+    Present,
+    /// Fill the whole frame with a color, linear RGB
+    Clear { color: [f32; 3] },
+}
+
+impl StageKind {
+    /// Kinds the Passes panel can insert.
+    pub const INSERTABLE: [Self; 1] = [Self::Clear {
+        color: [0.0, 0.0, 0.0],
+    }];
+
+    /// Stages of the renderer in the order they are recorded by default, there
+    /// is always exactly one of each.
+    pub const RENDERER: [Self; 4] = [
+        Self::Scene,
+        Self::DebugViews,
+        Self::PostProcess,
+        Self::Present,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Scene => "Scene",
+            Self::DebugViews => "Debug Views",
+            Self::PostProcess => "Post Process",
+            Self::Present => "Present",
+            Self::Clear { .. } => "Clear",
+        }
+    }
+
+    /// Stages of the renderer can only be disabled.
+    pub fn removable(self) -> bool {
+        matches!(self, Self::Clear { .. })
+    }
+
+    /// The stage rendering this kind.
+    pub fn create(
+        self,
+        renderer: &SharedRenderer,
+        _device: &wgpu::Device,
+        _config: &wgpu::SurfaceConfiguration,
+    ) -> Box<dyn RenderStage<AppState>> {
+        match self {
+            Self::Clear { color } => Box::new(ClearStage { color }),
+            kind => Box::new(RendererStage {
+                kind,
+                renderer: renderer.clone(),
+            }),
+        }
+    }
+}
+
+/// One entry of the stage list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StageSlot {
+    pub kind: StageKind,
+    /// Disabled stages are skipped when rendering but kept up to date
+    pub enabled: bool,
+}
+
+impl StageSlot {
+    pub fn new(kind: StageKind) -> Self {
+        Self {
+            kind,
+            enabled: true,
+        }
+    }
+}
+
+/// Records the passes of the shared renderer belonging to `kind`, see the
+/// module documentation.
+struct RendererStage {
+    kind: StageKind,
+    renderer: SharedRenderer,
+}
+
+impl RenderStage<AppState> for RendererStage {
+    fn render(
+        &self,
+        state: &mut AppState,
+        view: &wgpu::TextureView,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        self.renderer
+            .borrow()
+            .render_stage(self.kind, state, view, encoder);
+    }
+
+    // The window resizes and updates the shared renderer
+    fn resize(&mut self, _device: &wgpu::Device, _config: &wgpu::SurfaceConfiguration) {}
+
+    fn update(&mut self, _state: &AppState, _queue: &wgpu::Queue) {}
+}
+
+/// Overwrites everything stages before it rendered, useful in front of the
+/// scene when the scene stage is disabled.
+struct ClearStage {
+    color: [f32; 3],
+}
+
+impl RenderStage<AppState> for ClearStage {
+    fn render(
+        &self,
+        _state: &mut AppState,
+        view: &wgpu::TextureView,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let [r, g, b] = self.color.map(f64::from);
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear Stage Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a: 1.0 }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+
+    fn resize(&mut self, _device: &wgpu::Device, _config: &wgpu::SurfaceConfiguration) {}
+
+    fn update(&mut self, _state: &AppState, _queue: &wgpu::Queue) {}
+}
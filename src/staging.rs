@@ -0,0 +1,52 @@
+//! Uniforms written every frame, copied through a staging belt recorded into
+//! the frame's encoder. `queue.write_buffer` allocates staging memory for every
+//! call, the belt keeps reusing its chunks once the GPU is done with them.
+
+use wgpu::{util::StagingBelt, Device};
+
+/// Bytes of a belt chunk, large enough for the per-frame uniforms of most
+/// scenes. Bigger writes get a chunk of their own size.
+const CHUNK_SIZE: wgpu::BufferAddress = 64 * 1024;
+
+/// Per-frame uploads of a renderer, see the module documentation.
+pub struct UploadBelt {
+    belt: StagingBelt,
+}
+
+impl Default for UploadBelt {
+    fn default() -> Self {
+        Self {
+            belt: StagingBelt::new(CHUNK_SIZE),
+        }
+    }
+}
+
+impl UploadBelt {
+    /// Copy `data` to the start of `target` before the commands recorded into
+    /// `encoder` afterwards. Empty writes are skipped.
+    pub fn write<T: bytemuck::Pod>(
+        &mut self,
+        device: &Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Buffer,
+        data: &[T],
+    ) {
+        let bytes = bytemuck::cast_slice(data);
+        let Some(size) = wgpu::BufferSize::new(bytes.len() as wgpu::BufferAddress) else {
+            return;
+        };
+        self.belt
+            .write_buffer(encoder, target, 0, size, device)
+            .copy_from_slice(bytes);
+    }
+
+    /// Close the chunks written this frame, call before the encoder is finished.
+    pub fn finish(&mut self) {
+        self.belt.finish();
+    }
+
+    /// Reuse the chunks once the GPU read them, call after the frame was submitted.
+    pub fn recall(&mut self) {
+        self.belt.recall();
+    }
+}
@@ -0,0 +1,153 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::loader::{LoadOptions, LoadedScene, SceneLoader};
+
+/// Chunks loading at once, further chunks in range wait for their turn.
+const MAX_CONCURRENT_LOADS: usize = 2;
+
+/// Resident chunks are unloaded beyond the load distance times this, so chunks
+/// right at the border do not load and unload every frame.
+const UNLOAD_FACTOR: f32 = 1.25;
+
+/// Part of a large scene listed in the scene file, resident only while the
+/// camera is near its bounds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneChunk {
+    /// Model file relative to the resources directory
+    pub path: String,
+    pub box_min: [f32; 3],
+    pub box_max: [f32; 3],
+}
+
+impl SceneChunk {
+    /// Distance from `eye` to the bounds, zero inside them.
+    pub fn distance(&self, eye: Vec3) -> f32 {
+        eye.clamp(
+            Vec3::from_array(self.box_min),
+            Vec3::from_array(self.box_max),
+        )
+        .distance(eye)
+    }
+}
+
+/// Streaming options edited in the UI.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamingSettings {
+    /// Keep every chunk unloaded when off
+    pub enabled: bool,
+    /// Chunks whose bounds are closer to the camera are loaded
+    pub load_distance: f32,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            load_distance: 50.0,
+        }
+    }
+}
+
+enum ChunkState {
+    Unloaded,
+    Loading(SceneLoader),
+    Resident,
+    /// Not retried until the scene is loaded again
+    Failed,
+}
+
+/// A change of the resident chunks the renderer has to follow.
+pub enum ChunkEvent {
+    Loaded(usize, Box<LoadedScene>),
+    Unloaded(usize),
+    /// Not retried until the scene is loaded again
+    Failed(usize, anyhow::Error),
+}
+
+/// Loads and unloads the chunks of the current scene file in the background
+/// as the camera moves, keeping only the geometry near it resident.
+#[derive(Default)]
+pub struct ChunkStreamer {
+    states: Vec<ChunkState>,
+}
+
+impl ChunkStreamer {
+    pub fn new(chunk_count: usize) -> Self {
+        Self {
+            states: (0..chunk_count).map(|_| ChunkState::Unloaded).collect(),
+        }
+    }
+
+    /// Start loading the chunks that came in range, nearest first, and pick up
+    /// finished loads. With `blocking` the loads are waited for, so deterministic
+    /// runs do not depend on the loader threads.
+    pub fn update(
+        &mut self,
+        chunks: &[SceneChunk],
+        eye: Vec3,
+        settings: &StreamingSettings,
+        options: LoadOptions,
+        blocking: bool,
+    ) -> Vec<ChunkEvent> {
+        let mut events = Vec::new();
+        let mut loading = self.loading();
+        let mut order = (0..chunks.len().min(self.states.len())).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| chunks[a].distance(eye).total_cmp(&chunks[b].distance(eye)));
+        for i in order {
+            let distance = chunks[i].distance(eye);
+            let wanted = settings.enabled && distance <= settings.load_distance;
+            let kept = settings.enabled && distance <= settings.load_distance * UNLOAD_FACTOR;
+            let state = &mut self.states[i];
+            match state {
+                ChunkState::Unloaded if wanted && loading < MAX_CONCURRENT_LOADS => {
+                    *state = ChunkState::Loading(SceneLoader::spawn(&chunks[i].path, options));
+                    loading += 1;
+                }
+                // The loader thread finishes on its own, its result is dropped
+                ChunkState::Loading(_) if !kept => {
+                    *state = ChunkState::Unloaded;
+                    loading -= 1;
+                }
+                ChunkState::Loading(loader) => {
+                    let result = if blocking {
+                        Some(loader.wait())
+                    } else {
+                        loader.poll()
+                    };
+                    match result {
+                        Some(Ok(scene)) => {
+                            events.push(ChunkEvent::Loaded(i, Box::new(scene)));
+                            *state = ChunkState::Resident;
+                            loading -= 1;
+                        }
+                        Some(Err(err)) => {
+                            events.push(ChunkEvent::Failed(i, err));
+                            *state = ChunkState::Failed;
+                            loading -= 1;
+                        }
+                        None => {}
+                    }
+                }
+                ChunkState::Resident if !kept => {
+                    events.push(ChunkEvent::Unloaded(i));
+                    *state = ChunkState::Unloaded;
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+
+    pub fn resident(&self) -> usize {
+        self.count(|state| matches!(state, ChunkState::Resident))
+    }
+
+    pub fn loading(&self) -> usize {
+        self.count(|state| matches!(state, ChunkState::Loading(_)))
+    }
+
+    fn count(&self, predicate: impl Fn(&ChunkState) -> bool) -> usize {
+        self.states.iter().filter(|state| predicate(state)).count()
+    }
+}
@@ -0,0 +1,275 @@
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+
+use crate::{
+    capabilities::Capabilities,
+    contact_sheet::{self, Cell},
+    primitives::MaterialOverride,
+    probe_storage::ProbeStorage,
+    AppState,
+};
+
+/// Frames rendered after the probes of a cell finished baking that still pay for
+/// the bake or the capture of the previous cell, left out of the frame time.
+const WARMUP_FRAMES: u32 = 2;
+/// Frames averaged into the frame time of a cell before it is captured
+const MEASURED_FRAMES: u32 = 8;
+
+/// Setting varied along one side of the sweep matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepAxis {
+    MaterialOverride,
+    NormalMap,
+    ReflectionProbes,
+    ProbeStorage,
+}
+
+impl SweepAxis {
+    pub const ALL: [Self; 4] = [
+        Self::MaterialOverride,
+        Self::NormalMap,
+        Self::ReflectionProbes,
+        Self::ProbeStorage,
+    ];
+
+    /// Also the name taken by `--sweep`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MaterialOverride => "material_override",
+            Self::NormalMap => "normal_map",
+            Self::ReflectionProbes => "reflection_probes",
+            Self::ProbeStorage => "probe_storage",
+        }
+    }
+
+    pub fn value_count(self) -> usize {
+        match self {
+            Self::MaterialOverride => MaterialOverride::ALL.len(),
+            Self::NormalMap | Self::ReflectionProbes => 2,
+            Self::ProbeStorage => ProbeStorage::ALL.len() + 1,
+        }
+    }
+
+    /// Probe storage formats are packed by a compute pass.
+    pub fn is_supported(self, capabilities: &Capabilities) -> bool {
+        self != Self::ProbeStorage || capabilities.compute_shaders
+    }
+
+    fn label(self, value: usize) -> &'static str {
+        match self {
+            Self::MaterialOverride => MaterialOverride::ALL[value].name(),
+            Self::NormalMap => ["normal map", "no normal map"][value],
+            Self::ReflectionProbes => ["probes", "no probes"][value],
+            Self::ProbeStorage => value
+                .checked_sub(1)
+                .map_or("as rendered", |i| ProbeStorage::ALL[i].name()),
+        }
+    }
+
+    /// Switch the setting to `value` and flag it as changed.
+    fn apply(self, value: usize, state: &mut AppState) {
+        match self {
+            Self::MaterialOverride => {
+                state.material_override = MaterialOverride::ALL[value];
+                state.material_override_changed = true;
+            }
+            Self::NormalMap => {
+                state.enable_normal_map = value == 0;
+                state.normal_map_changed = true;
+            }
+            Self::ReflectionProbes => {
+                state.enable_reflection_probes = value == 0;
+                state.reflection_probes_changed = true;
+            }
+            Self::ProbeStorage => {
+                state.probe_storage = value.checked_sub(1).map(|i| ProbeStorage::ALL[i]);
+                state.probe_storage_changed = true;
+            }
+        }
+    }
+}
+
+/// Sweep options edited in the UI or given with `--sweep=ROWS,COLUMNS`.
+#[derive(Debug, Clone, Copy)]
+pub struct SweepSettings {
+    pub rows: SweepAxis,
+    pub columns: SweepAxis,
+}
+
+impl Default for SweepSettings {
+    fn default() -> Self {
+        Self {
+            rows: SweepAxis::MaterialOverride,
+            columns: SweepAxis::ProbeStorage,
+        }
+    }
+}
+
+impl SweepSettings {
+    /// Read `ROWS,COLUMNS` as given to `--sweep`.
+    pub fn parse(axes: &str) -> Result<Self, String> {
+        let axis = |name: &str| {
+            SweepAxis::ALL
+                .into_iter()
+                .find(|axis| axis.name() == name)
+                .ok_or_else(|| {
+                    let names = SweepAxis::ALL.map(SweepAxis::name).join(", ");
+                    format!("unknown sweep axis {:?}, expected one of {}", name, names)
+                })
+        };
+        let (rows, columns) = axes
+            .split_once(',')
+            .ok_or_else(|| format!("expected --sweep=ROWS,COLUMNS, got {:?}", axes))?;
+        let settings = Self {
+            rows: axis(rows)?,
+            columns: axis(columns)?,
+        };
+        if settings.rows == settings.columns {
+            return Err(format!("cannot sweep {} against itself", rows));
+        }
+        Ok(settings)
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.rows.value_count() * self.columns.value_count()
+    }
+}
+
+/// The settings a sweep changes, restored once it ended.
+#[derive(Debug, Clone, Copy)]
+struct SweptSettings {
+    material_override: MaterialOverride,
+    enable_normal_map: bool,
+    enable_reflection_probes: bool,
+    probe_storage: Option<ProbeStorage>,
+}
+
+impl SweptSettings {
+    fn read(state: &AppState) -> Self {
+        Self {
+            material_override: state.material_override,
+            enable_normal_map: state.enable_normal_map,
+            enable_reflection_probes: state.enable_reflection_probes,
+            probe_storage: state.probe_storage,
+        }
+    }
+
+    fn restore(self, state: &mut AppState) {
+        state.material_override = self.material_override;
+        state.enable_normal_map = self.enable_normal_map;
+        state.enable_reflection_probes = self.enable_reflection_probes;
+        state.probe_storage = self.probe_storage;
+        state.material_override_changed = true;
+        state.normal_map_changed = true;
+        state.reflection_probes_changed = true;
+        state.probe_storage_changed = true;
+        state.bake_reflection_probes = true;
+    }
+}
+
+/// A running capture of the same view for every combination of two settings,
+/// composed into a contact sheet captioned with the settings and frame times.
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    settings: SweepSettings,
+    /// Cell being rendered, row by row
+    pub cell: usize,
+    /// Frames rendered since the probes of the cell finished baking
+    settled_frames: u32,
+    frame_time_ms: f32,
+    cells: Vec<Cell>,
+    output: PathBuf,
+    original: SweptSettings,
+}
+
+impl Sweep {
+    /// Start with the settings of the first cell.
+    pub fn new(settings: SweepSettings, state: &mut AppState, output: &Path) -> Self {
+        let sweep = Self {
+            settings,
+            cell: 0,
+            settled_frames: 0,
+            frame_time_ms: 0.0,
+            cells: Vec::new(),
+            output: output.to_owned(),
+            original: SweptSettings::read(state),
+        };
+        sweep.apply(state);
+        sweep
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.settings.cell_count()
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Row and column value of the current cell.
+    fn values(&self) -> (usize, usize) {
+        let columns = self.settings.columns.value_count();
+        (self.cell / columns, self.cell % columns)
+    }
+
+    fn apply(&self, state: &mut AppState) {
+        let (row, column) = self.values();
+        self.settings.rows.apply(row, state);
+        self.settings.columns.apply(column, state);
+        // Reflections should show the swept settings as well
+        state.bake_reflection_probes = true;
+    }
+
+    /// Call after each submitted frame, true once the current cell is ready to
+    /// be captured.
+    pub fn frame_rendered(&mut self, state: &AppState) -> bool {
+        if state.bake_reflection_probes || state.probe_bake.is_pending() {
+            self.settled_frames = 0;
+            self.frame_time_ms = 0.0;
+            return false;
+        }
+        self.settled_frames += 1;
+        if self.settled_frames > WARMUP_FRAMES {
+            self.frame_time_ms += state.frame_time_ms;
+        }
+        self.settled_frames >= WARMUP_FRAMES + MEASURED_FRAMES
+    }
+
+    /// Keep `image` as the current cell and move on to the next, false once
+    /// every cell is captured.
+    pub fn capture(&mut self, image: RgbaImage, state: &mut AppState) -> bool {
+        let (row, column) = self.values();
+        let mut caption = format!(
+            "{} / {}",
+            self.settings.rows.label(row),
+            self.settings.columns.label(column)
+        );
+        // Deterministic runs do not measure frame times
+        if self.frame_time_ms > 0.0 {
+            let frame_time_ms = self.frame_time_ms / MEASURED_FRAMES as f32;
+            caption += &format!("  {:.2} ms", frame_time_ms);
+        }
+        self.cells.push(Cell { image, caption });
+        self.cell += 1;
+        self.settled_frames = 0;
+        self.frame_time_ms = 0.0;
+        if self.cell < self.cell_count() {
+            self.apply(state);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Restore the swept settings and write the contact sheet of the captured cells.
+    pub fn finish(self, state: &mut AppState) -> anyhow::Result<usize> {
+        self.original.restore(state);
+        if self.cells.is_empty() {
+            return Ok(0);
+        }
+        let sheet = contact_sheet::compose(&self.cells, self.settings.columns.value_count());
+        sheet.save(&self.output)?;
+        Ok(self.cells.len())
+    }
+}
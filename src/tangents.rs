@@ -0,0 +1,224 @@
+//! Tangent frames generated with MikkTSpace through `bevy_mikktspace`, the
+//! convention DCC tools bake normal maps against.
+//!
+//! Bitangents are not stored, shaders rebuild them as
+//! `sign * cross(normal, tangent)`. MikkTSpace gives every triangle corner a
+//! frame of its own, vertices whose corners disagree are split, so seams of
+//! mirrored UV islands keep a frame per side.
+
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec3, Vec4};
+use rayon::prelude::*;
+
+/// Triangles handled by one task, smaller meshes stay on a single thread.
+const TRIANGLES_PER_TASK: usize = 4096;
+
+/// Area weighted vertex normals for meshes without any. Indices wind clockwise
+/// like the front faces of the render pipelines.
+pub fn vertex_normals(positions: &[Vec3], indices: &[u32]) -> Box<[Vec3]> {
+    let sums = indices
+        .par_chunks(3 * TRIANGLES_PER_TASK)
+        .fold(
+            || vec![Vec3::ZERO; positions.len()],
+            |mut sums, triangles| {
+                for c in triangles.chunks_exact(3) {
+                    let [p0, p1, p2] = [c[0], c[1], c[2]].map(|i| positions[i as usize]);
+                    // Twice the triangle area long
+                    let normal = (p2 - p0).cross(p1 - p0);
+                    for &i in c {
+                        sums[i as usize] += normal;
+                    }
+                }
+                sums
+            },
+        )
+        .reduce_with(|mut sums, other| {
+            sums.iter_mut().zip(other).for_each(|(sum, n)| *sum += n);
+            sums
+        })
+        .unwrap_or_else(|| vec![Vec3::ZERO; positions.len()]);
+    sums.into_iter()
+        .map(|sum| sum.try_normalize().unwrap_or(Vec3::Z))
+        .collect()
+}
+
+/// Vertices and indices of a mesh after the vertices whose corners got
+/// different tangents were split.
+pub struct TangentFrames {
+    /// Input vertex of every output vertex, the input vertices come first in
+    /// their order and split copies are appended
+    pub sources: Vec<u32>,
+    /// Tangent in xyz and the bitangent sign in w
+    pub tangents: Vec<Vec4>,
+    pub indices: Box<[u32]>,
+}
+
+/// An indexed triangle mesh as `bevy_mikktspace` reads it, collecting the
+/// tangent of every triangle corner.
+struct MikkMesh<'a> {
+    positions: &'a [Vec3],
+    normals: &'a [Vec3],
+    texcoords: &'a [Vec2],
+    indices: &'a [u32],
+    corner_tangents: Vec<Vec4>,
+}
+
+impl MikkMesh<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.indices[face * 3 + vert] as usize
+    }
+}
+
+impl bevy_mikktspace::Geometry for MikkMesh<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.vertex(face, vert)].to_array()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.vertex(face, vert)].to_array()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.texcoords[self.vertex(face, vert)].to_array()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.corner_tangents[face * 3 + vert] = Vec4::from_array(tangent);
+    }
+}
+
+/// MikkTSpace tangent frames of an indexed mesh, `normals` and `texcoords`
+/// have one entry per position. Corners of a vertex that got different frames,
+/// such as on mirrored UV seams, are split into copies of the vertex.
+pub fn generate(
+    positions: &[Vec3],
+    normals: &[Vec3],
+    texcoords: &[Vec2],
+    indices: &[u32],
+) -> TangentFrames {
+    let normals = normals
+        .iter()
+        .map(|n| n.try_normalize().unwrap_or(Vec3::Z))
+        .collect::<Vec<_>>();
+    let mut mesh = MikkMesh {
+        positions,
+        normals: &normals,
+        texcoords,
+        indices,
+        corner_tangents: vec![Vec4::ZERO; indices.len()],
+    };
+    // Fails only for meshes without triangles, whose corners keep the fallback
+    bevy_mikktspace::generate_tangents(&mut mesh);
+    let fallback = |i: usize| normals[i].any_orthonormal_vector().extend(1.0);
+    let mut sources = (0..positions.len() as u32).collect::<Vec<_>>();
+    let mut tangents = (0..positions.len()).map(fallback).collect::<Vec<_>>();
+    let mut assigned = vec![false; positions.len()];
+    // Copies of every vertex by the bits of their tangent
+    let mut copies = HashMap::new();
+    let indices = indices
+        .iter()
+        .zip(&mesh.corner_tangents)
+        .map(|(&i, &tangent)| {
+            let vertex = i as usize;
+            // Degenerate corners take whatever frame their vertex ends up with
+            if tangent.truncate() == Vec3::ZERO {
+                return i;
+            }
+            if !assigned[vertex] {
+                assigned[vertex] = true;
+                tangents[vertex] = tangent;
+                return i;
+            }
+            if tangents[vertex] == tangent {
+                return i;
+            }
+            *copies
+                .entry((i, tangent.to_array().map(f32::to_bits)))
+                .or_insert_with(|| {
+                    sources.push(i);
+                    tangents.push(tangent);
+                    sources.len() as u32 - 1
+                })
+        })
+        .collect();
+    TangentFrames {
+        sources,
+        tangents,
+        indices,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Quad in the xy plane facing +z with u along +x and v along +y.
+    fn quad() -> ([Vec3; 4], [Vec3; 4], [Vec2; 4], [u32; 6]) {
+        let positions = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let texcoords = positions.map(|p| p.truncate());
+        (positions, [Vec3::Z; 4], texcoords, [0, 1, 2, 0, 2, 3])
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        let (positions, normals, texcoords, indices) = quad();
+        let frames = generate(&positions, &normals, &texcoords, &indices);
+        assert_eq!(frames.sources, [0, 1, 2, 3]);
+        assert_eq!(&*frames.indices, &indices);
+        for tangent in &frames.tangents {
+            assert!(tangent.truncate().abs_diff_eq(Vec3::X, 1e-5), "{tangent}");
+            assert_eq!(tangent.w, 1.0);
+        }
+    }
+
+    #[test]
+    fn mirrored_seam_is_split() {
+        // A second quad sharing the edge 1-2 whose u runs back towards -x
+        let positions = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(2.0, 1.0, 0.0),
+        ];
+        let texcoords = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 1.0),
+        ];
+        let indices = [0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2];
+        let frames = generate(&positions, &[Vec3::Z; 6], &texcoords, &indices);
+        // The seam vertices got a copy for the mirrored side
+        assert_eq!(frames.sources, [0, 1, 2, 3, 4, 5, 1, 2]);
+        for (&index, &source) in frames.indices.iter().zip(&indices) {
+            assert_eq!(frames.sources[index as usize], source);
+        }
+        for (vertex, tangent) in frames.tangents.iter().enumerate() {
+            let mirrored = matches!(vertex, 4..=7);
+            let expected = if mirrored { -Vec3::X } else { Vec3::X };
+            assert!(
+                tangent.truncate().abs_diff_eq(expected, 1e-5),
+                "{vertex}: {tangent}"
+            );
+        }
+        assert_eq!(frames.tangents[0].w, -frames.tangents[4].w);
+    }
+}
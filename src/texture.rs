@@ -1,6 +1,185 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
 use anyhow::*;
 use image::GenericImageView;
 
+use crate::logging;
+
+/// CPU side texture content, either a decoded image or GPU ready compressed blocks.
+#[derive(Debug, Clone)]
+pub enum TextureImage {
+    Decoded(image::DynamicImage),
+    Compressed(CompressedImage),
+}
+
+impl TextureImage {
+    /// Open an image file, KTX2 and DDS containers are kept block compressed.
+    pub fn open<P: AsRef<Path>>(path: P) -> crate::error::Result<Self> {
+        let path = path.as_ref();
+        Self::decode(path).map_err(|reason| crate::error::Error::Texture {
+            path: path.to_path_buf(),
+            reason,
+        })
+    }
+
+    fn decode(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("ktx2") => Ok(Self::Compressed(CompressedImage::from_ktx2(
+                &std::fs::read(path)?,
+            )?)),
+            Some("dds") => Ok(Self::Compressed(CompressedImage::from_dds(
+                &std::fs::read(path)?,
+            )?)),
+            _ => Ok(Self::Decoded(image::ImageReader::open(path)?.decode()?)),
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Self::Decoded(img) => img.dimensions(),
+            Self::Compressed(img) => (img.width, img.height),
+        }
+    }
+}
+
+/// Images opened while loading a scene, keyed by their resolved path so a
+/// texture shared by many meshes is decoded once.
+#[derive(Default)]
+pub struct ImageCache {
+    images: HashMap<PathBuf, Option<Arc<TextureImage>>>,
+}
+
+impl ImageCache {
+    /// Open `path` or reuse the image opened before, failures are reported once.
+    pub fn open<P: AsRef<Path>>(&mut self, path: P) -> Option<Arc<TextureImage>> {
+        let path = path.as_ref();
+        let key = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.images
+            .entry(key)
+            .or_insert_with(|| {
+                TextureImage::open(path)
+                    .inspect_err(|err| tracing::warn!(target: logging::SCENE, "{}", err))
+                    .ok()
+                    .map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+/// Block compressed texture with its mip chain, uploaded without decoding.
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Mip levels, largest first
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self> {
+        use ktx2::Format;
+        let reader = ktx2::Reader::new(bytes).map_err(|err| anyhow!("invalid KTX2: {:?}", err))?;
+        let header = reader.header();
+        if let Some(scheme) = header.supercompression_scheme {
+            bail!("KTX2 supercompression {:?} is not supported", scheme);
+        }
+        let format = match header.format {
+            Some(Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK) => {
+                wgpu::TextureFormat::Bc1RgbaUnorm
+            }
+            Some(Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK) => {
+                wgpu::TextureFormat::Bc1RgbaUnormSrgb
+            }
+            Some(Format::BC2_UNORM_BLOCK) => wgpu::TextureFormat::Bc2RgbaUnorm,
+            Some(Format::BC2_SRGB_BLOCK) => wgpu::TextureFormat::Bc2RgbaUnormSrgb,
+            Some(Format::BC3_UNORM_BLOCK) => wgpu::TextureFormat::Bc3RgbaUnorm,
+            Some(Format::BC3_SRGB_BLOCK) => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+            Some(Format::BC4_UNORM_BLOCK) => wgpu::TextureFormat::Bc4RUnorm,
+            Some(Format::BC5_UNORM_BLOCK) => wgpu::TextureFormat::Bc5RgUnorm,
+            Some(Format::BC7_UNORM_BLOCK) => wgpu::TextureFormat::Bc7RgbaUnorm,
+            Some(Format::BC7_SRGB_BLOCK) => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+            Some(Format::R8G8B8A8_UNORM) => wgpu::TextureFormat::Rgba8Unorm,
+            Some(Format::R8G8B8A8_SRGB) => wgpu::TextureFormat::Rgba8UnormSrgb,
+            format => bail!("unsupported KTX2 format {:?}", format),
+        };
+        Ok(Self {
+            format,
+            width: header.pixel_width,
+            height: header.pixel_height.max(1),
+            levels: reader.levels().map(|level| level.data.to_vec()).collect(),
+        })
+    }
+
+    pub fn from_dds(bytes: &[u8]) -> Result<Self> {
+        use ddsfile::{D3DFormat, DxgiFormat};
+        let dds = ddsfile::Dds::read(bytes)?;
+        let format = match (dds.get_dxgi_format(), dds.get_d3d_format()) {
+            (Some(DxgiFormat::BC1_UNorm), _) | (_, Some(D3DFormat::DXT1)) => {
+                wgpu::TextureFormat::Bc1RgbaUnorm
+            }
+            (Some(DxgiFormat::BC1_UNorm_sRGB), _) => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+            (Some(DxgiFormat::BC2_UNorm), _) | (_, Some(D3DFormat::DXT3)) => {
+                wgpu::TextureFormat::Bc2RgbaUnorm
+            }
+            (Some(DxgiFormat::BC2_UNorm_sRGB), _) => wgpu::TextureFormat::Bc2RgbaUnormSrgb,
+            (Some(DxgiFormat::BC3_UNorm), _) | (_, Some(D3DFormat::DXT5)) => {
+                wgpu::TextureFormat::Bc3RgbaUnorm
+            }
+            (Some(DxgiFormat::BC3_UNorm_sRGB), _) => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+            (Some(DxgiFormat::BC4_UNorm), _) => wgpu::TextureFormat::Bc4RUnorm,
+            (Some(DxgiFormat::BC5_UNorm), _) => wgpu::TextureFormat::Bc5RgUnorm,
+            (Some(DxgiFormat::BC7_UNorm), _) => wgpu::TextureFormat::Bc7RgbaUnorm,
+            (Some(DxgiFormat::BC7_UNorm_sRGB), _) => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+            (Some(DxgiFormat::R8G8B8A8_UNorm), _) => wgpu::TextureFormat::Rgba8Unorm,
+            (Some(DxgiFormat::R8G8B8A8_UNorm_sRGB), _) => wgpu::TextureFormat::Rgba8UnormSrgb,
+            (dxgi, d3d) => bail!("unsupported DDS format {:?} {:?}", dxgi, d3d),
+        };
+        let (width, height) = (dds.get_width(), dds.get_height());
+        // DDS stores the whole mip chain of a layer back to back
+        let mut data = dds.get_data(0)?;
+        let mut levels = vec![];
+        for level in 0..dds.get_num_mipmap_levels() {
+            let size = Self::level_size(format, width >> level, height >> level);
+            if data.len() < size {
+                break;
+            }
+            let (head, tail) = data.split_at(size);
+            levels.push(head.to_vec());
+            data = tail;
+        }
+        ensure!(!levels.is_empty(), "DDS has no image data");
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    fn level_size(format: wgpu::TextureFormat, width: u32, height: u32) -> usize {
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format.block_copy_size(None).unwrap_or(4);
+        let blocks_x = width.max(1).div_ceil(block_width);
+        let blocks_y = height.max(1).div_ceil(block_height);
+        (blocks_x * blocks_y * block_size) as usize
+    }
+}
+
+/// `width` x `height` scaled by `scale`, rounded and at least one texel.
+pub fn scaled_extent((width, height): (u32, u32), scale: f32) -> (u32, u32) {
+    let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
 pub struct Texture {
     #[allow(unused)]
     pub texture: wgpu::Texture,
@@ -82,6 +261,101 @@ impl Texture {
         Self::from_image_internal(device, queue, img, label, false)
     }
 
+    /// Create a texture from any CPU side texture content.
+    pub fn from_texture_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &TextureImage,
+        label: Option<&str>,
+        is_normal_map: bool,
+    ) -> Result<Self> {
+        match img {
+            TextureImage::Decoded(img) => {
+                Self::from_image_internal(device, queue, img, label, is_normal_map)
+            }
+            TextureImage::Compressed(img) => {
+                Self::from_compressed(device, queue, img, label, is_normal_map)
+            }
+        }
+    }
+
+    pub fn from_compressed(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &CompressedImage,
+        label: Option<&str>,
+        is_normal_map: bool,
+    ) -> Result<Self> {
+        let required = img.format.required_features();
+        ensure!(
+            device.features().contains(required),
+            "device lacks {:?} for {:?} textures",
+            required,
+            img.format
+        );
+        // Color data is sampled as sRGB and normal data as linear, whatever the file says
+        let format = if is_normal_map {
+            img.format.remove_srgb_suffix()
+        } else {
+            img.format.add_srgb_suffix()
+        };
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format.block_copy_size(None).unwrap_or(4);
+        // Block compressed textures need a size that is a multiple of the block size
+        let size = wgpu::Extent3d {
+            width: img.width.next_multiple_of(block_width),
+            height: img.height.next_multiple_of(block_height),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: img.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (mip_level, data) in img.levels.iter().enumerate() {
+            let mip_size = size.mip_level_size(mip_level as u32, wgpu::TextureDimension::D2);
+            let physical_size = mip_size.physical_size(format);
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(physical_size.width / block_width * block_size),
+                    rows_per_image: Some(physical_size.height / block_height),
+                },
+                physical_size,
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::MirrorRepeat,
+            address_mode_v: wgpu::AddressMode::MirrorRepeat,
+            address_mode_w: wgpu::AddressMode::MirrorRepeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
     pub fn from_image_internal(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -147,15 +421,58 @@ impl Texture {
     }
 
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// Scene color is kept in half floats so out of range values survive until present
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    pub fn create_color_target(
+        device: &wgpu::Device,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // Copied from for readbacks such as the object id under the cursor
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
+        (width, height): (u32, u32),
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
-            width: config.width.max(1),
-            height: config.height.max(1),
+            width: width.max(1),
+            height: height.max(1),
             depth_or_array_layers: 1,
         };
         let desc = wgpu::TextureDescriptor {
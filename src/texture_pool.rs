@@ -0,0 +1,72 @@
+//! Render targets kept around after their owner let go of them, so resizing
+//! the window, toggling the split view or the region of interest and capturing
+//! frames reuse textures instead of allocating new ones every time.
+//!
+//! Textures are matched by size and format only. A reused texture keeps the
+//! label it was created with, graphics debuggers may show the label of an
+//! earlier owner.
+
+use wgpu::Device;
+
+use crate::texture;
+
+/// Frames a released texture is kept without being asked for again.
+const MAX_IDLE_FRAMES: u32 = 120;
+
+struct PooledTexture {
+    texture: texture::Texture,
+    /// Frames since it was released
+    idle_frames: u32,
+}
+
+/// Released render targets, see the module documentation.
+#[derive(Default)]
+pub struct TexturePool {
+    free: Vec<PooledTexture>,
+}
+
+impl TexturePool {
+    /// A released texture of `extent` and `format`, or a new one labeled
+    /// `label`. Depth formats get a depth texture, others a color target.
+    pub fn acquire(
+        &mut self,
+        device: &Device,
+        extent: (u32, u32),
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> texture::Texture {
+        let size = wgpu::Extent3d {
+            width: extent.0,
+            height: extent.1,
+            depth_or_array_layers: 1,
+        };
+        let matching = self.free.iter().position(|pooled| {
+            pooled.texture.texture.size() == size && pooled.texture.texture.format() == format
+        });
+        if let Some(index) = matching {
+            return self.free.swap_remove(index).texture;
+        }
+        if format.is_depth_stencil_format() {
+            texture::Texture::create_depth_texture(device, extent, label)
+        } else {
+            texture::Texture::create_color_target(device, extent, format, label)
+        }
+    }
+
+    /// Keep `texture` for the next `acquire` of the same size and format.
+    pub fn release(&mut self, texture: texture::Texture) {
+        self.free.push(PooledTexture {
+            texture,
+            idle_frames: 0,
+        });
+    }
+
+    /// Drop the textures nobody asked for in a while, call once per frame.
+    pub fn end_frame(&mut self) {
+        for pooled in &mut self.free {
+            pooled.idle_frames += 1;
+        }
+        self.free
+            .retain(|pooled| pooled.idle_frames <= MAX_IDLE_FRAMES);
+    }
+}
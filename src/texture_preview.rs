@@ -0,0 +1,351 @@
+use wgpu::Device;
+
+use crate::texture;
+
+/// Longest side of a preview, larger sources are scaled down.
+const PREVIEW_EXTENT: u32 = 320;
+
+/// Names of the reflection probe cube faces, in layer order.
+pub const CUBE_FACES: [&str; 6] = ["+X", "-X", "+Y", "-Y", "+Z", "-Z"];
+
+/// Texture of the renderer a preview shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewSource {
+    /// Scene color before it is presented
+    SceneColor,
+    SceneDepth,
+    /// One face of a baked reflection probe, `face` indexes `CUBE_FACES`
+    ProbeFace {
+        probe: usize,
+        face: usize,
+    },
+}
+
+impl PreviewSource {
+    pub fn name(self) -> String {
+        match self {
+            Self::SceneColor => "Scene color".to_owned(),
+            Self::SceneDepth => "Scene depth".to_owned(),
+            Self::ProbeFace { probe, face } => format!("Probe {} {}", probe, CUBE_FACES[face]),
+        }
+    }
+
+    pub fn is_depth(self) -> bool {
+        self == Self::SceneDepth
+    }
+
+    /// Size of the preview, the aspect of the source within `PREVIEW_EXTENT`.
+    fn extent(self, sources: &PreviewSources) -> (u32, u32) {
+        let (width, height) = match self {
+            Self::SceneColor | Self::SceneDepth => sources.surface,
+            Self::ProbeFace { .. } => (sources.probe_resolution, sources.probe_resolution),
+        };
+        let scale = (PREVIEW_EXTENT as f32 / width.max(height).max(1) as f32).min(1.0);
+        texture::scaled_extent((width, height), scale)
+    }
+}
+
+/// A texture of the renderer shown in its own window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TexturePreview {
+    pub source: PreviewSource,
+    /// Scale applied to color sources
+    pub exposure: f32,
+    /// Cleared when the window is closed, which removes the preview
+    pub open: bool,
+}
+
+impl TexturePreview {
+    pub fn new(source: PreviewSource) -> Self {
+        Self {
+            source,
+            exposure: 1.0,
+            open: true,
+        }
+    }
+}
+
+/// Textures the previews read from.
+pub struct PreviewSources<'a> {
+    pub scene_color: &'a wgpu::TextureView,
+    pub scene_depth: &'a wgpu::TextureView,
+    pub probe_cube: &'a wgpu::Texture,
+    pub probe_resolution: u32,
+    /// Window size, the scene targets may be scaled against it
+    pub surface: (u32, u32),
+}
+
+/// Matches `Preview` in `texture_preview.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct UniformPreview {
+    exposure: f32,
+    znear: f32,
+    zfar: f32,
+    orthographic: u32,
+    size: [f32; 2],
+    _padding: [u32; 2],
+}
+
+struct PreviewTarget {
+    source: PreviewSource,
+    extent: (u32, u32),
+    /// The preview in the surface format, shown by egui
+    output: texture::Texture,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Draws textures of the renderer into small targets egui shows as images, one
+/// per entry of `AppState::texture_previews`. Color sources are scaled by the
+/// exposure of their preview and depth is shown from white at the near plane to
+/// black at the far plane.
+pub struct TexturePreviews {
+    color_pipeline: wgpu::RenderPipeline,
+    depth_pipeline: wgpu::RenderPipeline,
+    color_layout: wgpu::BindGroupLayout,
+    depth_layout: wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+    /// In the order of the previews
+    targets: Vec<PreviewTarget>,
+}
+
+impl TexturePreviews {
+    pub fn new(device: &Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("texture_preview.wgsl"));
+        let uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let create_layout = |label, binding, sample_type| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    uniform_entry,
+                    wgpu::BindGroupLayoutEntry {
+                        binding,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some(label),
+            })
+        };
+        let color_layout = create_layout(
+            "Texture Preview Color Bind Group Layout",
+            1,
+            wgpu::TextureSampleType::Float { filterable: false },
+        );
+        let depth_layout = create_layout(
+            "Texture Preview Depth Bind Group Layout",
+            2,
+            wgpu::TextureSampleType::Depth,
+        );
+        let create_pipeline = |label, layout: &wgpu::BindGroupLayout, entry_point| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let color_pipeline =
+            create_pipeline("Texture Preview Color Pipeline", &color_layout, "fs_color");
+        let depth_pipeline =
+            create_pipeline("Texture Preview Depth Pipeline", &depth_layout, "fs_depth");
+        Self {
+            color_pipeline,
+            depth_pipeline,
+            color_layout,
+            depth_layout,
+            format,
+            targets: Vec::new(),
+        }
+    }
+
+    /// Forget the targets after their sources were replaced, `prepare` creates
+    /// them again.
+    pub fn clear(&mut self) {
+        self.targets.clear();
+    }
+
+    /// Create the targets of new previews and of those whose source or size
+    /// changed. Returns the index, view and size of every created target, which
+    /// has to be registered with egui again.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        previews: &[TexturePreview],
+        sources: &PreviewSources,
+    ) -> Vec<(usize, &wgpu::TextureView, [u32; 2])> {
+        self.targets.truncate(previews.len());
+        let mut created = Vec::new();
+        for (i, preview) in previews.iter().enumerate() {
+            let extent = preview.source.extent(sources);
+            let current = self.targets.get(i);
+            if current
+                .is_some_and(|target| target.source == preview.source && target.extent == extent)
+            {
+                continue;
+            }
+            let target = self.create_target(device, preview.source, extent, sources);
+            if i < self.targets.len() {
+                self.targets[i] = target;
+            } else {
+                self.targets.push(target);
+            }
+            created.push(i);
+        }
+        created
+            .into_iter()
+            .map(|i| {
+                let target = &self.targets[i];
+                let (width, height) = target.extent;
+                (i, &target.output.view, [width, height])
+            })
+            .collect()
+    }
+
+    fn create_target(
+        &self,
+        device: &Device,
+        source: PreviewSource,
+        extent: (u32, u32),
+        sources: &PreviewSources,
+    ) -> PreviewTarget {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture Preview Buffer"),
+            size: std::mem::size_of::<UniformPreview>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // The bind group keeps the face view alive
+        let face_view;
+        let (binding, layout, view) = match source {
+            PreviewSource::SceneColor => (1, &self.color_layout, sources.scene_color),
+            PreviewSource::SceneDepth => (2, &self.depth_layout, sources.scene_depth),
+            PreviewSource::ProbeFace { probe, face } => {
+                face_view = sources
+                    .probe_cube
+                    .create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("Texture Preview Probe Face View"),
+                        dimension: Some(wgpu::TextureViewDimension::D2),
+                        base_array_layer: (probe * CUBE_FACES.len() + face) as u32,
+                        array_layer_count: Some(1),
+                        ..Default::default()
+                    });
+                (1, &self.color_layout, &face_view)
+            }
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+            ],
+            label: Some("Texture Preview Bind Group"),
+        });
+        PreviewTarget {
+            source,
+            extent,
+            output: texture::Texture::create_color_target(
+                device,
+                extent,
+                self.format,
+                "texture_preview",
+            ),
+            buffer,
+            bind_group,
+        }
+    }
+
+    /// Write the exposure and clip planes of every preview.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        previews: &[TexturePreview],
+        (znear, zfar): (f32, f32),
+        orthographic: bool,
+    ) {
+        for (target, preview) in self.targets.iter().zip(previews) {
+            let (width, height) = target.extent;
+            let uniform = UniformPreview {
+                exposure: preview.exposure,
+                znear,
+                zfar,
+                orthographic: orthographic as u32,
+                size: [width as f32, height as f32],
+                _padding: [0; 2],
+            };
+            queue.write_buffer(&target.buffer, 0, bytemuck::cast_slice(&[uniform]));
+        }
+    }
+
+    /// Draw every preview from the current contents of its source.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder) {
+        for target in &self.targets {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Texture Preview Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.output.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            let pipeline = if target.source.is_depth() {
+                &self.depth_pipeline
+            } else {
+                &self.color_pipeline
+            };
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &target.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+// Draw a texture of the renderer into a preview shown by egui
+
+struct Preview {
+    // Scale applied to color sources
+    exposure: f32,
+    // Planes the depth buffer was rendered with
+    znear: f32,
+    zfar: f32,
+    // Depth is linear in view depth
+    orthographic: u32,
+    // Preview size in pixels
+    size: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> preview: Preview;
+@group(0) @binding(1)
+var source_color: texture_2d<f32>;
+@group(0) @binding(2)
+var source_depth: texture_depth_2d;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// One triangle covering the whole preview
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Source texel under a preview pixel, the source is scaled to the preview
+fn source_texel(position: vec2<f32>, size: vec2<u32>) -> vec2<u32> {
+    let texel = vec2<u32>(position / preview.size * vec2<f32>(size));
+    return min(texel, size - 1u);
+}
+
+@fragment
+fn fs_color(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = textureDimensions(source_color);
+    let color = textureLoad(source_color, source_texel(in.clip_position.xy, size), 0);
+    return vec4<f32>(color.rgb * preview.exposure, 1.0);
+}
+
+// Near is white and the far plane black, perspective depth is spread evenly over
+// the orders of magnitude between the planes
+@fragment
+fn fs_depth(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = textureDimensions(source_depth);
+    let depth = textureLoad(source_depth, source_texel(in.clip_position.xy, size), 0);
+    var t = depth;
+    if preview.orthographic == 0u {
+        let z = preview.znear * preview.zfar / (preview.zfar - depth * (preview.zfar - preview.znear));
+        t = log(z / preview.znear) / log(preview.zfar / preview.znear);
+    }
+    return vec4<f32>(vec3<f32>(1.0 - saturate(t)), 1.0);
+}
@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+
+use glam::Vec3;
+
+use crate::camera::Camera;
+
+/// Turntable options edited in the UI.
+#[derive(Debug, Clone)]
+pub struct TurntableSettings {
+    /// Images per full revolution
+    pub frames: u32,
+    /// Mesh to orbit around, the whole scene when `None`
+    pub pivot_object: Option<String>,
+}
+
+impl Default for TurntableSettings {
+    fn default() -> Self {
+        Self {
+            frames: 120,
+            pivot_object: None,
+        }
+    }
+}
+
+/// A running capture orbiting the camera once around `pivot` at its current
+/// distance and height, every frame is written as a numbered PNG.
+#[derive(Debug, Clone)]
+pub struct Turntable {
+    pivot: Vec3,
+    radius: f32,
+    height: f32,
+    start_angle: f32,
+    pub frames: u32,
+    /// Frame being rendered
+    pub frame: u32,
+    output: PathBuf,
+    /// Camera before the capture, restored afterwards
+    camera: Camera,
+}
+
+impl Turntable {
+    pub fn new(settings: &TurntableSettings, pivot: Vec3, camera: &Camera, output: &Path) -> Self {
+        let offset = camera.position - pivot;
+        Self {
+            pivot,
+            // Keep some distance when the camera starts right above the pivot
+            radius: offset.with_y(0.0).length().max(1e-2),
+            height: offset.y,
+            start_angle: offset.z.atan2(offset.x),
+            frames: settings.frames.max(1),
+            frame: 0,
+            output: output.to_owned(),
+            camera: camera.clone(),
+        }
+    }
+
+    /// Put `camera` at the position of the current frame, facing the pivot.
+    pub fn place_camera(&self, camera: &mut Camera) {
+        let angle =
+            self.start_angle + std::f32::consts::TAU * self.frame as f32 / self.frames as f32;
+        let (sin, cos) = angle.sin_cos();
+        camera.position = self.pivot + Vec3::new(cos * self.radius, self.height, sin * self.radius);
+        camera.look_at(self.pivot);
+    }
+
+    pub fn frame_path(&self) -> PathBuf {
+        self.output.join(format!("frame_{:04}.png", self.frame))
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Move on to the next frame, false once the revolution is complete.
+    pub fn advance(&mut self) -> bool {
+        self.frame += 1;
+        self.frame < self.frames
+    }
+
+    /// Camera to return to once the capture ended.
+    pub fn into_camera(self) -> Camera {
+        self.camera
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use crate::{
+    loader::{LoadOptions, MeshData, SceneLoader},
+    primitives::resource_path,
+    probe::ProbeCache,
+    render_settings::RenderSettings,
+    scene_file::SceneFile,
+};
+
+/// `--validate-scene`: load the render settings and every file of each scene
+/// without opening a window, printing one line per file. Returns whether all of
+/// them are valid.
+pub fn validate_scenes(scenes: &[&str]) -> bool {
+    let mut valid = check(
+        &RenderSettings::path(),
+        RenderSettings::path()
+            .exists()
+            .then(RenderSettings::load)
+            .transpose()
+            .map(|settings| settings.is_some()),
+    );
+    for scene in scenes {
+        let loaded = SceneLoader::spawn(*scene, LoadOptions::default()).wait();
+        let meshes = match &loaded {
+            Ok(loaded) => orientation_report(scene, &loaded.meshes),
+            Err(_) => Vec::new(),
+        };
+        valid &= check(&resource_path(scene), loaded.map(|_| true));
+        // Suspect orientations are reported but do not make the scene invalid
+        for line in meshes {
+            println!("{line}");
+        }
+        let scene_file = SceneFile::load(scene);
+        let chunks = match &scene_file {
+            Ok(Some(file)) => file.chunks.clone(),
+            _ => Vec::new(),
+        };
+        valid &= check(
+            &SceneFile::path_for(scene),
+            scene_file.map(|file| file.is_some()),
+        );
+        for chunk in chunks {
+            valid &= check(
+                &resource_path(&chunk.path),
+                SceneLoader::spawn(chunk.path, LoadOptions::default())
+                    .wait()
+                    .map(|_| true),
+            );
+        }
+        valid &= check(
+            &ProbeCache::path_for(scene),
+            ProbeCache::load(scene).map(|cache| cache.is_some()),
+        );
+    }
+    valid
+}
+
+/// Print the outcome of loading `path`, `Ok(false)` means the optional file is absent.
+fn check(path: &Path, result: anyhow::Result<bool>) -> bool {
+    match &result {
+        Ok(true) => println!("ok      {}", path.display()),
+        Ok(false) => println!("absent  {}", path.display()),
+        Err(err) => println!("invalid {}: {:#}", path.display(), err),
+    }
+    result.is_ok()
+}
+
+/// One line for every mesh of `scene` that looks inside out or mixes windings.
+fn orientation_report(scene: &str, meshes: &[MeshData]) -> Vec<String> {
+    meshes
+        .iter()
+        .filter(|mesh| {
+            mesh.orientation.suggested_fix().is_some() || mesh.orientation.inconsistent_edges > 0
+        })
+        .map(|mesh| {
+            let name = mesh.model.name();
+            let problems = mesh.orientation.problems().join(", ");
+            match mesh.orientation.suggested_fix() {
+                Some(fix) => format!(
+                    "suspect {scene}: mesh {name}: {problems}, {} ({})",
+                    fix.name(),
+                    match (fix.flips_winding(), fix.flips_normals()) {
+                        (true, true) => "list it in flipped_winding and flipped_normals",
+                        (true, false) => "list it in flipped_winding",
+                        _ => "list it in flipped_normals",
+                    }
+                ),
+                None => format!("suspect {scene}: mesh {name}: {problems}"),
+            }
+        })
+        .collect()
+}
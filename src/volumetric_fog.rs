@@ -0,0 +1,455 @@
+//! Froxel based volumetric fog. The view frustum is divided into `FROXELS`
+//! cells, exponentially spaced in depth, and each frame three passes run:
+//!
+//! - inject: density and the light scattered towards the camera per froxel,
+//!   from the scene light and optionally the reflection probes
+//! - integrate: accumulate scattering and transmittance front to back along
+//!   every column of froxels
+//! - composite: look up the integral at the depth of each pixel and blend it
+//!   over the scene color
+//!
+//! The fog needs compute shaders and covers the main view only.
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3, Vec4};
+use wgpu::{util::DeviceExt, Device};
+
+use crate::{
+    compute::{self, WorkgroupSize},
+    texture,
+};
+
+/// Froxels across, down and into the screen, must match `volumetric_fog.wgsl`
+const FROXELS: [u32; 3] = [160, 90, 64];
+const FROXEL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Matches `volumetric_fog.wgsl`, inject runs per froxel and integrate per column
+const WORKGROUP_SIZE: WorkgroupSize = WorkgroupSize::new(8, 8, 1);
+
+/// Height fog lit by the scene light, see the module docs.
+#[derive(Debug, Clone, Copy)]
+pub struct VolumetricFog {
+    pub enabled: bool,
+    /// Extinction per world unit below `height`
+    pub density: f32,
+    /// World height the fog thins out above
+    pub height: f32,
+    /// Density e-folds every `1 / height_falloff` world units above `height`
+    pub height_falloff: f32,
+    /// Scattered part of the extinction per color channel
+    pub albedo: [f32; 3],
+    /// Henyey-Greenstein asymmetry, positive values glow around the light
+    pub anisotropy: f32,
+    pub light_intensity: f32,
+    /// Scale of the light scattered in from the reflection probes, zero skips
+    /// sampling them
+    pub ambient_intensity: f32,
+    /// View distance the froxels reach, everything further is fogged as if it
+    /// were there
+    pub max_distance: f32,
+}
+
+impl Default for VolumetricFog {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            density: 0.05,
+            height: 0.0,
+            height_falloff: 0.5,
+            albedo: [1.0; 3],
+            anisotropy: 0.3,
+            light_intensity: 1.0,
+            ambient_intensity: 0.0,
+            max_distance: 100.0,
+        }
+    }
+}
+
+/// Camera of the main view the froxels are aligned with.
+#[derive(Debug, Clone, Copy)]
+pub struct FogView {
+    pub view_projection: Mat4,
+    pub position: Vec3,
+    pub forward: Vec3,
+    pub planes: (f32, f32),
+    pub orthographic: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct UniformFog {
+    inverse_view_projection: Mat4,
+    camera_position: Vec4,
+    forward: Vec4,
+    albedo: Vec4,
+    density: f32,
+    height: f32,
+    height_falloff: f32,
+    anisotropy: f32,
+    light_intensity: f32,
+    ambient_intensity: f32,
+    near: f32,
+    far: f32,
+    znear: f32,
+    zfar: f32,
+    orthographic: u32,
+    _padding: u32,
+}
+
+impl UniformFog {
+    fn new(fog: &VolumetricFog, view: &FogView) -> Self {
+        let (znear, zfar) = view.planes;
+        let near = znear.max(0.01);
+        Self {
+            inverse_view_projection: view.view_projection.inverse(),
+            camera_position: view.position.extend(1.0),
+            forward: view.forward.normalize().extend(0.0),
+            albedo: Vec3::from(fog.albedo).extend(1.0),
+            density: fog.density,
+            height: fog.height,
+            height_falloff: fog.height_falloff,
+            anisotropy: fog.anisotropy.clamp(-0.99, 0.99),
+            light_intensity: fog.light_intensity,
+            ambient_intensity: fog.ambient_intensity,
+            near,
+            far: fog.max_distance.max(near * 2.0),
+            znear,
+            zfar,
+            orthographic: view.orthographic as u32,
+            _padding: 0,
+        }
+    }
+}
+
+fn storage_texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: FROXEL_FORMAT,
+            view_dimension: wgpu::TextureViewDimension::D3,
+        },
+        count: None,
+    }
+}
+
+fn uniform_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        visibility,
+        ..compute::uniform_entry(binding)
+    }
+}
+
+fn create_froxel_texture(device: &Device, label: &str) -> wgpu::TextureView {
+    let [width, height, depth] = FROXELS;
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: depth,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: FROXEL_FORMAT,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Froxel volumes and the passes filling and compositing them.
+pub struct FogVolume {
+    inject_pipeline: wgpu::ComputePipeline,
+    integrate_pipeline: wgpu::ComputePipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    inject_bind_group: wgpu::BindGroup,
+    integrate_bind_group: wgpu::BindGroup,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    /// Follows the scene depth texture, see `resize`
+    composite_bind_group: wgpu::BindGroup,
+    integrated: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+}
+
+impl FogVolume {
+    /// `scene_bind_group_layout` is the layout of the light and reflection probes
+    /// of `shader.wgsl`, visible to compute shaders.
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        scene_bind_group_layout: &wgpu::BindGroupLayout,
+        scene_depth: &texture::Texture,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("volumetric_fog.wgsl"));
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Volumetric Fog Buffer"),
+            contents: bytemuck::cast_slice(&[UniformFog::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let scattering = create_froxel_texture(device, "Volumetric Fog Scattering");
+        let integrated = create_froxel_texture(device, "Volumetric Fog Integrated");
+
+        let inject_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    uniform_entry(0, wgpu::ShaderStages::COMPUTE),
+                    storage_texture_entry(1),
+                ],
+                label: Some("Volumetric Fog Inject Bind Group Layout"),
+            });
+        let inject_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &inject_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&scattering),
+                },
+            ],
+            label: Some("Volumetric Fog Inject Bind Group"),
+        });
+        let inject_pipeline = compute::create_pipeline(
+            device,
+            "Volumetric Fog Inject",
+            &shader,
+            "cs_inject",
+            &[&inject_bind_group_layout, scene_bind_group_layout],
+        );
+
+        let integrate_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    uniform_entry(0, wgpu::ShaderStages::COMPUTE),
+                    compute::texture_entry(2, wgpu::TextureViewDimension::D3),
+                    storage_texture_entry(3),
+                ],
+                label: Some("Volumetric Fog Integrate Bind Group Layout"),
+            });
+        let integrate_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &integrate_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&scattering),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&integrated),
+                },
+            ],
+            label: Some("Volumetric Fog Integrate Bind Group"),
+        });
+        let integrate_pipeline = compute::create_pipeline(
+            device,
+            "Volumetric Fog Integrate",
+            &shader,
+            "cs_integrate",
+            &[&integrate_bind_group_layout],
+        );
+
+        let composite_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    uniform_entry(0, wgpu::ShaderStages::FRAGMENT),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("Volumetric Fog Composite Bind Group Layout"),
+            });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Volumetric Fog Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let composite_bind_group = Self::create_composite_bind_group(
+            device,
+            &composite_bind_group_layout,
+            &buffer,
+            &integrated,
+            &sampler,
+            scene_depth,
+        );
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Volumetric Fog Composite Pipeline Layout"),
+            bind_group_layouts: &[&composite_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let composite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Volumetric Fog Composite Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_composite"),
+                // Scattered light added to the scene dimmed by the transmittance
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::SrcAlpha,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            inject_pipeline,
+            integrate_pipeline,
+            composite_pipeline,
+            buffer,
+            inject_bind_group,
+            integrate_bind_group,
+            composite_bind_group_layout,
+            composite_bind_group,
+            integrated,
+            sampler,
+        }
+    }
+
+    fn create_composite_bind_group(
+        device: &Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        integrated: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        scene_depth: &texture::Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(integrated),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&scene_depth.view),
+                },
+            ],
+            label: Some("Volumetric Fog Composite Bind Group"),
+        })
+    }
+
+    /// Follow the scene depth texture after it was recreated.
+    pub fn resize(&mut self, device: &Device, scene_depth: &texture::Texture) {
+        self.composite_bind_group = Self::create_composite_bind_group(
+            device,
+            &self.composite_bind_group_layout,
+            &self.buffer,
+            &self.integrated,
+            &self.sampler,
+            scene_depth,
+        );
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, fog: &VolumetricFog, view: &FogView) {
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[UniformFog::new(fog, view)]),
+        );
+    }
+
+    /// Fill the froxels and blend the fog over `color`, whose depth is the scene
+    /// depth given to `new` or `resize`.
+    pub fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene_bind_group: &wgpu::BindGroup,
+        color: &wgpu::TextureView,
+    ) {
+        {
+            let mut compute_pass = compute::begin_pass(encoder, "volumetric fog inject", None);
+            compute_pass.set_pipeline(&self.inject_pipeline);
+            compute_pass.set_bind_group(0, &self.inject_bind_group, &[]);
+            compute_pass.set_bind_group(1, scene_bind_group, &[]);
+            WORKGROUP_SIZE.dispatch(&mut compute_pass, FROXELS);
+        }
+        {
+            let mut compute_pass = compute::begin_pass(encoder, "volumetric fog integrate", None);
+            compute_pass.set_pipeline(&self.integrate_pipeline);
+            compute_pass.set_bind_group(0, &self.integrate_bind_group, &[]);
+            WORKGROUP_SIZE.dispatch(&mut compute_pass, [FROXELS[0], FROXELS[1], 1]);
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass: volumetric fog"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &self.composite_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
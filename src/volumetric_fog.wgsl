@@ -0,0 +1,232 @@
+// Froxel volumetric fog: scattering is injected into a view aligned volume,
+// integrated front to back along each column and composited over the scene.
+
+struct Fog {
+    inverse_view_projection: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    // xyz: unit view direction of the camera
+    forward: vec4<f32>,
+    // rgb: scattered part of the extinction
+    albedo: vec4<f32>,
+    // extinction per world unit below `height`
+    density: f32,
+    height: f32,
+    // density e-folds per world unit above `height`
+    height_falloff: f32,
+    // Henyey-Greenstein asymmetry, positive scatters forward
+    anisotropy: f32,
+    light_intensity: f32,
+    ambient_intensity: f32,
+    // view depth range the slices span, exponentially spaced
+    near: f32,
+    far: f32,
+    // planes the depth buffer was rendered with
+    znear: f32,
+    zfar: f32,
+    // non-zero when the depth is linear
+    orthographic: u32,
+    _padding: u32,
+}
+
+// Must match `FROXELS` in `volumetric_fog.rs`
+const FROXELS: vec3<u32> = vec3<u32>(160u, 90u, 64u);
+const PI: f32 = 3.14159265;
+
+@group(0) @binding(0)
+var<uniform> fog: Fog;
+
+struct Light {
+    // w: zero for a directional light, xyz points towards it then
+    position: vec4<f32>,
+}
+
+struct ReflectionProbe {
+    position: vec4<f32>,
+    box_min: vec4<f32>,
+    box_max: vec4<f32>,
+}
+
+struct ReflectionProbes {
+    probes: array<ReflectionProbe, 8>,
+    count: u32,
+    intensity: f32,
+}
+
+// The scene bind group of `shader.wgsl`
+@group(1) @binding(0)
+var<uniform> light: Light;
+@group(1) @binding(1)
+var<uniform> reflection_probes: ReflectionProbes;
+@group(1) @binding(2)
+var probe_texture: texture_cube_array<f32>;
+@group(1) @binding(3)
+var probe_sampler: sampler;
+
+// View depth at `w` of the volume depth, 0 at `near` and 1 at `far`
+fn slice_depth(w: f32) -> f32 {
+    return fog.near * pow(fog.far / fog.near, w);
+}
+
+fn slice_coordinate(depth: f32) -> f32 {
+    return log(max(depth, fog.near) / fog.near) / log(fog.far / fog.near);
+}
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let position = fog.inverse_view_projection * vec4<f32>(ndc, 1.0);
+    return position.xyz / position.w;
+}
+
+struct Froxel {
+    position: vec3<f32>,
+    // world length of the view ray per unit of view depth
+    ray_scale: f32,
+}
+
+// Point of the column through `texel` at view depth `depth`, for perspective and
+// orthographic projections alike
+fn froxel(texel: vec2<u32>, depth: f32) -> Froxel {
+    let uv = (vec2<f32>(texel) + 0.5) / vec2<f32>(FROXELS.xy);
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let near_point = unproject(vec3<f32>(ndc, 0.0));
+    let far_point = unproject(vec3<f32>(ndc, 1.0));
+    let near_depth = dot(near_point - fog.camera_position.xyz, fog.forward.xyz);
+    let far_depth = dot(far_point - fog.camera_position.xyz, fog.forward.xyz);
+    let t = (depth - near_depth) / (far_depth - near_depth);
+    var out: Froxel;
+    out.position = mix(near_point, far_point, t);
+    out.ray_scale = length(far_point - near_point) / abs(far_depth - near_depth);
+    return out;
+}
+
+fn extinction(position: vec3<f32>) -> f32 {
+    return fog.density * exp(-fog.height_falloff * max(position.y - fog.height, 0.0));
+}
+
+fn henyey_greenstein(cos_theta: f32) -> f32 {
+    let g = fog.anisotropy;
+    let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+    return (1.0 - g * g) / (4.0 * PI * pow(denominator, 1.5));
+}
+
+// Mean radiance the probes saw around `position`, weighted like the reflections
+fn probe_ambient(position: vec3<f32>) -> vec3<f32> {
+    let directions = array<vec3<f32>, 6>(
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(-1.0, 0.0, 0.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(0.0, -1.0, 0.0),
+        vec3<f32>(0.0, 0.0, 1.0),
+        vec3<f32>(0.0, 0.0, -1.0),
+    );
+    var radiance = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    for (var i = 0u; i < reflection_probes.count; i++) {
+        let to_probe = reflection_probes.probes[i].position.xyz - position;
+        let weight = 1.0 / (1.0 + dot(to_probe, to_probe));
+        for (var d = 0u; d < 6u; d++) {
+            let sample = textureSampleLevel(probe_texture, probe_sampler, directions[d], i, 0.0);
+            radiance += sample.xyz * weight / 6.0;
+        }
+        total_weight += weight;
+    }
+    return radiance / max(total_weight, 1e-6) * reflection_probes.intensity;
+}
+
+// rgb: light scattered towards the camera per world unit, a: extinction
+@group(0) @binding(1)
+var scattering_out: texture_storage_3d<rgba16float, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_inject(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id >= FROXELS) {
+        return;
+    }
+    let depth = slice_depth((f32(id.z) + 0.5) / f32(FROXELS.z));
+    let position = froxel(id.xy, depth).position;
+    let sigma_t = extinction(position);
+    let to_camera = normalize(fog.camera_position.xyz - position);
+    let to_light = normalize(light.position.xyz - position * light.position.w);
+    // Unshadowed like the surfaces, the light has no falloff either
+    var radiance = vec3<f32>(fog.light_intensity * henyey_greenstein(dot(-to_light, to_camera)));
+    if fog.ambient_intensity > 0.0 {
+        // An isotropic phase function integrates the mean radiance
+        radiance += probe_ambient(position) * fog.ambient_intensity;
+    }
+    textureStore(
+        scattering_out,
+        id,
+        vec4<f32>(radiance * fog.albedo.xyz * sigma_t, sigma_t),
+    );
+}
+
+@group(0) @binding(2)
+var scattering_in: texture_3d<f32>;
+// rgb: light scattered towards the camera up to the far end of the froxel,
+// a: transmittance to it
+@group(0) @binding(3)
+var integrated_out: texture_storage_3d<rgba16float, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_integrate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id.xy >= FROXELS.xy) {
+        return;
+    }
+    var scattered = vec3<f32>(0.0);
+    var transmittance = 1.0;
+    for (var z = 0u; z < FROXELS.z; z++) {
+        let start = slice_depth(f32(z) / f32(FROXELS.z));
+        let end = slice_depth(f32(z + 1u) / f32(FROXELS.z));
+        let center = froxel(id.xy, (start + end) * 0.5);
+        let thickness = (end - start) * center.ray_scale;
+        let cell = textureLoad(scattering_in, vec3<u32>(id.xy, z), 0);
+        let sigma_t = max(cell.a, 1e-6);
+        let cell_transmittance = exp(-sigma_t * thickness);
+        // Scattering integrated over the froxel, energy conserving for thick froxels
+        scattered += transmittance * (cell.xyz - cell.xyz * cell_transmittance) / sigma_t;
+        transmittance *= cell_transmittance;
+        textureStore(integrated_out, vec3<u32>(id.xy, z), vec4<f32>(scattered, transmittance));
+    }
+}
+
+@group(0) @binding(4)
+var integrated: texture_3d<f32>;
+@group(0) @binding(5)
+var integrated_sampler: sampler;
+@group(0) @binding(6)
+var scene_depth: texture_depth_2d;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// One triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// View space distance of a depth buffer value, like in `present.wgsl`
+fn view_depth(depth: f32) -> f32 {
+    if fog.orthographic != 0u {
+        return fog.znear + depth * (fog.zfar - fog.znear);
+    }
+    return fog.znear * fog.zfar / (fog.zfar - depth * (fog.zfar - fog.znear));
+}
+
+// Blended as `scattered + color * transmittance`
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = vec2<u32>(in.clip_position.xy);
+    let size = vec2<f32>(textureDimensions(scene_depth));
+    let depth = view_depth(textureLoad(scene_depth, texel, 0));
+    // Froxels hold the integral up to their far end, half a slice on
+    let w = slice_coordinate(depth) - 0.5 / f32(FROXELS.z);
+    let uvw = vec3<f32>(in.clip_position.xy / size, clamp(w, 0.0, 1.0));
+    let fog_sample = textureSampleLevel(integrated, integrated_sampler, uvw, 0.0);
+    // In front of the first slice nothing was scattered yet
+    let amount = saturate(slice_coordinate(depth) * f32(FROXELS.z));
+    return vec4<f32>(fog_sample.xyz * amount, mix(1.0, fog_sample.a, amount));
+}
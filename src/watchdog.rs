@@ -0,0 +1,110 @@
+use crate::AppState;
+
+/// Consecutive slow frames before the watchdog steps in, single hitches such as
+/// a texture upload are not worth a backoff.
+const SLOW_FRAMES: u32 = 3;
+/// Frames ignored after a backoff, the frame after a change pays for it.
+const COOLDOWN_FRAMES: u32 = 30;
+/// Probe update budget a pending bake is limited to.
+const BAKE_BUDGET_MS: f32 = 4.0;
+
+/// Turns the GI down one notch at a time when frames keep taking longer than
+/// `threshold_ms`, so an underpowered adapter does not leave the app
+/// unresponsive. Every backoff is explained in a toast.
+#[derive(Debug, Clone, Copy)]
+pub struct Watchdog {
+    pub enabled: bool,
+    pub threshold_ms: f32,
+    /// Slow frames in a row
+    slow_frames: u32,
+    /// Frames left to ignore after a backoff
+    cooldown: u32,
+    /// Frames stayed slow with nothing left to turn down, reported once
+    exhausted: bool,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold_ms: 200.0,
+            slow_frames: 0,
+            cooldown: 0,
+            exhausted: false,
+        }
+    }
+}
+
+impl Watchdog {
+    /// Count the previous frame and back off after `SLOW_FRAMES` slow ones.
+    /// Frames are not counted while a scene loads or a capture runs, those are
+    /// slow on purpose.
+    pub fn update(state: &mut AppState) {
+        let watchdog = &mut state.watchdog;
+        let busy = state.loading.is_some()
+            || state.turntable.is_some()
+            || state.path_capture.is_some()
+            || state.sweep.is_some();
+        // Deterministic runs do not measure frame times
+        if !watchdog.enabled || busy || state.frame_time_ms == 0.0 {
+            watchdog.slow_frames = 0;
+            return;
+        }
+        if watchdog.cooldown > 0 {
+            watchdog.cooldown -= 1;
+            return;
+        }
+        if state.frame_time_ms <= watchdog.threshold_ms {
+            watchdog.slow_frames = 0;
+            return;
+        }
+        watchdog.slow_frames += 1;
+        if watchdog.slow_frames < SLOW_FRAMES {
+            return;
+        }
+        watchdog.slow_frames = 0;
+        let threshold_ms = watchdog.threshold_ms;
+        match Self::back_off(state) {
+            Some(change) => {
+                state.watchdog.cooldown = COOLDOWN_FRAMES;
+                state.watchdog.exhausted = false;
+                state.notifications.warn(format!(
+                    "Frames took over {:.0} ms, {}. The GPU watchdog can be disabled in \
+                     Frame Statistics",
+                    threshold_ms, change
+                ));
+            }
+            None if !state.watchdog.exhausted => {
+                state.watchdog.exhausted = true;
+                state.notifications.warn(format!(
+                    "Frames still take over {:.0} ms with the GI turned down as far as the \
+                     GPU watchdog goes",
+                    threshold_ms
+                ));
+            }
+            None => {}
+        }
+    }
+
+    /// Turn down the most expensive GI setting still on, returns what changed.
+    fn back_off(state: &mut AppState) -> Option<String> {
+        if state.scene_animated && state.play_animation && state.animate_reflection_probes {
+            state.animate_reflection_probes = false;
+            return Some("stopped re-baking the reflection probes while animating".to_owned());
+        }
+        let budget_ms = state.probe_bake.budget_ms;
+        if state.probe_bake.is_pending() && (budget_ms == 0.0 || budget_ms > BAKE_BUDGET_MS) {
+            state.probe_bake.budget_ms = BAKE_BUDGET_MS;
+            return Some(format!(
+                "limited reflection probe updates to {} ms per frame",
+                BAKE_BUDGET_MS
+            ));
+        }
+        if state.enable_reflection_probes {
+            state.enable_reflection_probes = false;
+            state.reflection_probes_changed = true;
+            return Some("turned off the reflection probes".to_owned());
+        }
+        None
+    }
+}
@@ -1,31 +1,2324 @@
-use egui::{Checkbox, TextEdit};
+use std::{collections::HashSet, time::Instant};
 
-use crate::{window::egui_tools::EguiRenderer, AppState};
+use egui::{Checkbox, DragValue, ProgressBar, Slider, TextEdit};
+use egui_dock::{DockArea, Style, TabViewer};
+
+use crate::{
+    camera::{self, CameraBookmark, ProjectionMode},
+    dock::{DockLayout, Panel},
+    guides::CompositionGuides,
+    heatmap::Heatmap,
+    help::KEY_BINDINGS,
+    light_orbit::OrbitShape,
+    logging::{self, LogView},
+    matcap::Matcap,
+    notifications::{Level, MAX_TOASTS},
+    panorama::PanoramaSettings,
+    picking::{self, PickedTriangle},
+    post_process::{PostEffectKind, PostEffectSlot},
+    primitives::{resource_path, ChannelView, MaterialOverride},
+    probe::{ReflectionProbe, MAX_REFLECTION_PROBES},
+    probe_convergence::ConvergenceRun,
+    probe_storage::ProbeStorage,
+    render_settings::{RenderScale, Upsampling},
+    split_view::SplitSettings,
+    stages::{StageKind, StageSlot},
+    sweep::SweepAxis,
+    texture_preview::{PreviewSource, TexturePreview, CUBE_FACES},
+    window::egui_tools::EguiRenderer,
+    AppState,
+};
 
 pub fn widget_show(state: &mut AppState, renderer: &EguiRenderer) {
-    egui::Window::new("Camera Control")
+    // egui would show the cursor again whenever the hovered widget changes
+    if state.mouse_captured {
+        renderer.context().set_cursor_icon(egui::CursorIcon::None);
+    }
+    if !state.ui_visible() {
+        return;
+    }
+    menu_bar_show(state, renderer);
+    dock_show(state, renderer);
+    help_show(state, renderer);
+    if let Some(progress) = &state.loading {
+        egui::Window::new("Loading Scene")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(renderer.context(), |ui| {
+                ui.label(progress.stage);
+                ui.add(
+                    ProgressBar::new(progress.fraction())
+                        .text(format!("{} / {}", progress.done, progress.total)),
+                );
+            });
+    }
+    shader_errors_show(state, renderer);
+    if let Some(error) = &state.error {
+        let mut dismissed = false;
+        egui::Window::new("Error")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(renderer.context(), |ui| {
+                ui.label(error);
+                dismissed = ui.button("OK").clicked();
+            });
+        if dismissed {
+            state.error = None;
+        }
+    }
+    reflection_probe_show(state, renderer);
+    objects_show(state, renderer);
+    triangle_inspector_show(state, renderer);
+    turntable_show(state, renderer);
+    panorama_show(state, renderer);
+    sweep_show(state, renderer);
+    camera_path_show(state, renderer);
+    roi_show(state, renderer);
+    texture_previews_show(state, renderer);
+    post_process_show(state, renderer);
+    split_view_show(state, renderer);
+    depth_contours_show(state, renderer);
+    ground_grid_show(state, renderer);
+    volumetric_fog_show(state, renderer);
+    sky_show(state, renderer);
+    if state.scene_animated {
+        animation_show(state, renderer);
+    }
+    if !state.scene_file.chunks.is_empty() {
+        streaming_show(state, renderer);
+    }
+    guides_show(state, renderer);
+    guides_paint(state, renderer);
+    axis_gizmo_paint(state, renderer);
+    splitter_show(state, renderer);
+    toasts_show(state, renderer);
+}
+
+/// Docked panels filling the window below the menu bar, see `dock`.
+fn dock_show(state: &mut AppState, renderer: &EguiRenderer) {
+    let context = renderer.context();
+    // The viewer borrows the state, the layout is put back once shown
+    let mut layout = std::mem::take(&mut state.dock_layout);
+    state.viewport = None;
+    // No frame, the scene shows through the viewport panel
+    egui::CentralPanel::default()
+        .frame(egui::Frame::none())
+        .show(context, |ui| {
+            DockArea::new(&mut layout.0)
+                .style(Style::from_egui(context.style().as_ref()))
+                .show_inside(ui, &mut PanelViewer { state });
+        });
+    state.dock_layout = layout;
+}
+
+struct PanelViewer<'a> {
+    state: &'a mut AppState,
+}
+
+impl TabViewer for PanelViewer<'_> {
+    type Tab = Panel;
+
+    fn title(&mut self, panel: &mut Panel) -> egui::WidgetText {
+        panel.name().into()
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, panel: &mut Panel) {
+        let state = &mut *self.state;
+        match panel {
+            Panel::Viewport => state.viewport = Some(ui.max_rect()),
+            Panel::Inspector => inspector_show(state, ui),
+            Panel::Materials => materials_show(state, ui),
+            Panel::Lights => lights_show(state, ui),
+            Panel::Passes => passes_show(state, ui),
+            Panel::Log => log_show(state, ui),
+            Panel::Stats => frame_stats_show(state, ui),
+        }
+    }
+
+    fn closeable(&mut self, panel: &mut Panel) -> bool {
+        *panel != Panel::Viewport
+    }
+
+    fn clear_background(&self, panel: &Panel) -> bool {
+        *panel != Panel::Viewport
+    }
+
+    fn scroll_bars(&self, panel: &Panel) -> [bool; 2] {
+        match panel {
+            // The log scrolls its rows itself
+            Panel::Viewport | Panel::Log => [false, false],
+            _ => [false, true],
+        }
+    }
+}
+
+/// Whether `position` is over the viewport panel with no window or menu above
+/// it, the scene takes the input there. The whole window is the viewport while
+/// the UI is hidden.
+pub fn over_viewport(state: &AppState, context: &egui::Context, position: egui::Pos2) -> bool {
+    if !state.ui_visible() {
+        return true;
+    }
+    state
+        .viewport
+        .is_some_and(|viewport| viewport.contains(position))
+        && context
+            .layer_id_at(position)
+            .is_none_or(|layer| layer.order == egui::Order::Background)
+}
+
+fn split_view_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Split View")
         .default_open(false)
         .show(renderer.context(), |ui| {
+            let split_view = &mut state.split_view;
+            ui.add(Checkbox::new(&mut split_view.enabled, "Enabled"))
+                .on_hover_text(AppState::setting_help("split_view.enabled"));
+            ui.add(Slider::new(&mut split_view.split, 0.0..=1.0).text("Splitter"))
+                .on_hover_text(AppState::setting_help("split_view.split"));
+            ui.label("Right side");
+            let right = &mut split_view.right;
+            let previous = *right;
+            egui::ComboBox::from_label("Material override")
+                .selected_text(right.material_override.name())
+                .show_ui(ui, |ui| {
+                    for mode in MaterialOverride::ALL {
+                        ui.selectable_value(&mut right.material_override, mode, mode.name());
+                    }
+                })
+                .response
+                .on_hover_text(AppState::setting_help("split_view.right.material_override"));
+            ui.add(Checkbox::new(
+                &mut right.enable_reflection_probes,
+                "Reflection probes",
+            ))
+            .on_hover_text(AppState::setting_help(
+                "split_view.right.enable_reflection_probes",
+            ));
+            state.split_view_changed |= *right != previous;
+        });
+}
+
+/// Captions of both sides of the split view and the splitter between them, which
+/// can be dragged.
+fn splitter_show(state: &mut AppState, renderer: &EguiRenderer) {
+    if !state.split_view.enabled {
+        return;
+    }
+    let context = renderer.context();
+    let screen = context.screen_rect();
+    let x = egui::lerp(screen.x_range(), state.split_view.split);
+    let painter = context.layer_painter(egui::LayerId::background());
+    painter.vline(
+        x,
+        screen.y_range(),
+        egui::Stroke::new(2.0, egui::Color32::WHITE),
+    );
+    let left = SplitSettings {
+        material_override: state.material_override,
+        enable_reflection_probes: state.enable_reflection_probes,
+    };
+    for (settings, anchor, offset) in [
+        (left, egui::Align2::RIGHT_TOP, -8.0),
+        (state.split_view.right, egui::Align2::LEFT_TOP, 8.0),
+    ] {
+        let galley = painter.layout_no_wrap(
+            settings.caption(),
+            egui::FontId::proportional(14.0),
+            egui::Color32::WHITE,
+        );
+        let rect = anchor.anchor_size(egui::pos2(x + offset, screen.min.y + 8.0), galley.size());
+        painter.rect_filled(rect.expand(4.0), 2.0, egui::Color32::from_black_alpha(160));
+        painter.galley(rect.min, galley, egui::Color32::WHITE);
+    }
+    let splitter = egui::Area::new(egui::Id::new("split_view_splitter"))
+        .fixed_pos(egui::pos2(x - 4.0, screen.min.y))
+        .show(context, |ui| {
+            ui.allocate_response(egui::vec2(8.0, screen.height()), egui::Sense::drag())
+        })
+        .inner;
+    if splitter.hovered() || splitter.dragged() {
+        context.set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+    }
+    if let Some(pointer) = splitter.interact_pointer_pos() {
+        state.split_view.split = ((pointer.x - screen.min.x) / screen.width()).clamp(0.0, 1.0);
+    }
+}
+
+fn guides_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Composition Guides")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let guides = &mut state.scene_file.composition_guides;
+            ui.add(Checkbox::new(&mut guides.thirds, "Rule of thirds"))
+                .on_hover_text(AppState::setting_help("composition_guides.thirds"));
+            ui.add(Checkbox::new(&mut guides.center, "Center cross"))
+                .on_hover_text(AppState::setting_help("composition_guides.center"));
             ui.horizontal(|ui| {
-                ui.label("Light position");
-                ui.add_enabled_ui(!state.given_light_position, |ui| {
-                    state
-                        .light_input
-                        .iter_mut()
-                        .zip(state.light_position.iter_mut())
-                        .for_each(|(input, position)| {
-                            if ui.add(TextEdit::singleline(input).char_limit(5)).changed() {
-                                *position = input.parse().unwrap_or(*position);
-                            }
-                        });
+                ui.add(Checkbox::new(&mut guides.safe_area, "Title safe"))
+                    .on_hover_text(AppState::setting_help("composition_guides.safe_area"));
+                ui.add_enabled(
+                    guides.safe_area,
+                    Slider::new(&mut guides.safe_fraction, 0.5..=1.0),
+                );
+            });
+            let aspect_name = match guides.aspect {
+                None => "Window".to_owned(),
+                Some(aspect) => CompositionGuides::ASPECTS
+                    .iter()
+                    .find(|(_, preset)| (preset - aspect).abs() < 1e-3)
+                    .map_or(format!("{:.2}:1", aspect), |(name, _)| name.to_string()),
+            };
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Frame")
+                    .selected_text(aspect_name)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut guides.aspect, None, "Window");
+                        for (name, aspect) in CompositionGuides::ASPECTS {
+                            ui.selectable_value(&mut guides.aspect, Some(aspect), name);
+                        }
+                    })
+                    .response
+                    .on_hover_text(AppState::setting_help("composition_guides.aspect"));
+                if let Some(aspect) = &mut guides.aspect {
+                    ui.add(
+                        DragValue::new(aspect)
+                            .range(0.1..=10.0)
+                            .speed(0.01)
+                            .suffix(":1"),
+                    );
+                }
+            });
+            if ui
+                .add_enabled(state.scene_path.is_some(), egui::Button::new("Save"))
+                .on_hover_text("Store the guides in the scene file")
+                .clicked()
+            {
+                state.save_scene_file();
+            }
+        });
+}
+
+/// Draw the enabled composition guides over the scene, below every window.
+fn guides_paint(state: &AppState, renderer: &EguiRenderer) {
+    let guides = &state.scene_file.composition_guides;
+    if !guides.is_visible() {
+        return;
+    }
+    let screen = renderer.context().screen_rect();
+    let painter = renderer
+        .context()
+        .layer_painter(egui::LayerId::background());
+    let ([x, y], [width, height]) = guides.frame(screen.width(), screen.height());
+    let frame = egui::Rect::from_min_size(screen.min + egui::vec2(x, y), egui::vec2(width, height));
+    let stroke = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(160));
+    if guides.aspect.is_some() {
+        let mask = egui::Color32::from_black_alpha(170);
+        let (min, max) = (screen.min, screen.max);
+        for outside in [
+            egui::Rect::from_min_max(min, egui::pos2(max.x, frame.min.y)),
+            egui::Rect::from_min_max(egui::pos2(min.x, frame.max.y), max),
+            egui::Rect::from_x_y_ranges(min.x..=frame.min.x, frame.y_range()),
+            egui::Rect::from_x_y_ranges(frame.max.x..=max.x, frame.y_range()),
+        ] {
+            painter.rect_filled(outside, 0.0, mask);
+        }
+        painter.rect_stroke(frame, 0.0, stroke);
+    }
+    if guides.thirds {
+        for third in [1.0 / 3.0, 2.0 / 3.0] {
+            let x = egui::lerp(frame.x_range(), third);
+            let y = egui::lerp(frame.y_range(), third);
+            painter.vline(x, frame.y_range(), stroke);
+            painter.hline(frame.x_range(), y, stroke);
+        }
+    }
+    if guides.center {
+        let center = frame.center();
+        let arm = frame.size().min_elem() * 0.04;
+        painter.hline(center.x - arm..=center.x + arm, center.y, stroke);
+        painter.vline(center.x, center.y - arm..=center.y + arm, stroke);
+    }
+    if guides.safe_area {
+        let margin = frame.size() * (1.0 - guides.safe_fraction) * 0.5;
+        painter.rect_stroke(frame.shrink2(margin), 0.0, stroke);
+    }
+}
+
+/// Stack the queued notifications in the bottom right corner, newest at the
+/// bottom, until they expire or are clicked away.
+fn toasts_show(state: &mut AppState, renderer: &EguiRenderer) {
+    let now = Instant::now();
+    state.toasts.extend(state.notifications.take());
+    for toast in &mut state.toasts {
+        toast.shown.get_or_insert(now);
+    }
+    state.toasts.retain(|toast| !toast.expired(now));
+    let overflow = state.toasts.len().saturating_sub(MAX_TOASTS);
+    state.toasts.drain(..overflow);
+    if state.toasts.is_empty() {
+        return;
+    }
+    let mut dismissed = None;
+    egui::Area::new(egui::Id::new("Toasts"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+        .order(egui::Order::Foreground)
+        .show(renderer.context(), |ui| {
+            for (i, toast) in state.toasts.iter().enumerate() {
+                let color = match toast.level {
+                    Level::Info => ui.visuals().text_color(),
+                    Level::Warning => ui.visuals().warn_fg_color,
+                    Level::Error => ui.visuals().error_fg_color,
+                };
+                let response = egui::Frame::popup(ui.style())
+                    .show(ui, |ui| {
+                        ui.set_max_width(320.0);
+                        ui.colored_label(color, &toast.message);
+                    })
+                    .response
+                    .interact(egui::Sense::click())
+                    .on_hover_text("Click to dismiss");
+                if response.clicked() {
+                    dismissed = Some(i);
+                }
+            }
+        });
+    if let Some(i) = dismissed {
+        state.toasts.remove(i);
+    }
+}
+
+fn log_show(state: &mut AppState, ui: &mut egui::Ui) {
+    let log = &state.log;
+    let view = &mut state.log_view;
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("Level")
+            .selected_text(view.level.as_str())
+            .show_ui(ui, |ui| {
+                for level in LogView::LEVELS {
+                    ui.selectable_value(&mut view.level, level, level.as_str());
+                }
+            });
+        egui::ComboBox::from_label("Target")
+            .selected_text(view.target.unwrap_or("All"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut view.target, None, "All");
+                for target in logging::TARGETS {
+                    ui.selectable_value(&mut view.target, Some(target), target);
+                }
+            });
+        ui.add(TextEdit::singleline(&mut view.search).hint_text("Search"));
+        if ui.button("Clear").clicked() {
+            log.clear();
+        }
+    });
+    ui.separator();
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    log.read(|records| {
+        let shown = records
+            .iter()
+            .filter(|record| view.shows(record))
+            .collect::<Vec<_>>();
+        egui::ScrollArea::vertical()
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, shown.len(), |ui, rows| {
+                for record in &shown[rows] {
+                    let color = match record.level {
+                        tracing::Level::ERROR => ui.visuals().error_fg_color,
+                        tracing::Level::WARN => ui.visuals().warn_fg_color,
+                        _ => ui.visuals().text_color(),
+                    };
+                    let mut line = format!(
+                        "{:>9.3} {:<5} {}",
+                        record.time.as_secs_f32(),
+                        record.level,
+                        record.target
+                    );
+                    if !record.spans.is_empty() {
+                        line = format!("{} {}", line, record.spans);
+                    }
+                    let text = format!("{}: {}", line, record.message);
+                    // Truncated lines show in full when hovered
+                    ui.add(
+                        egui::Label::new(egui::RichText::new(text).monospace().color(color))
+                            .truncate(),
+                    );
+                }
+            });
+    });
+}
+
+/// Console of the shader and pipeline errors, open while there are any.
+fn shader_errors_show(state: &mut AppState, renderer: &EguiRenderer) {
+    if state.shader_errors.is_empty() {
+        return;
+    }
+    let mut dismissed = false;
+    egui::Window::new("Shader Errors")
+        .default_width(480.0)
+        .show(renderer.context(), |ui| {
+            ui.label("The last working pipelines stay in use until the shader is fixed.");
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(320.0)
+                .show(ui, |ui| {
+                    for err in &state.shader_errors {
+                        if let Some(location) = &err.location {
+                            ui.monospace(location.to_string());
+                        }
+                        ui.add(
+                            egui::Label::new(egui::RichText::new(&err.message).monospace()).wrap(),
+                        );
+                        ui.separator();
+                    }
+                });
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Reload")
+                    .on_hover_text(AppState::setting_help("shader_reload_requested"))
+                    .clicked()
+                {
+                    state.shader_reload_requested = true;
+                }
+                dismissed = ui.button("Dismiss").clicked();
+            });
+        });
+    if dismissed {
+        state.shader_errors.clear();
+    }
+}
+
+fn help_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Help")
+        .open(&mut state.show_help)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(renderer.context(), |ui| {
+            ui.label("Press F2 to toggle this window, hover any setting for details.");
+            ui.separator();
+            egui::Grid::new("Key Bindings")
+                .striped(true)
+                .show(ui, |ui| {
+                    for (key, action) in KEY_BINDINGS {
+                        ui.strong(*key);
+                        ui.label(*action);
+                        ui.end_row();
+                    }
+                });
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    for help in AppState::SETTINGS_HELP {
+                        ui.strong(help.label);
+                        ui.label(help.effect);
+                        ui.weak(format!("Cost: {}", help.cost));
+                        ui.add_space(4.0);
+                    }
+                });
+        });
+}
+
+/// Camera, clip planes and bookmarks, then how the frame is drawn.
+fn inspector_show(state: &mut AppState, ui: &mut egui::Ui) {
+    projection_show(state, ui);
+    motion_show(state, ui);
+    let clip_planes = &mut state.clip_planes;
+    ui.horizontal(|ui| {
+        ui.add(Checkbox::new(&mut clip_planes.auto, "Auto clip planes"))
+            .on_hover_text(AppState::setting_help("clip_planes.auto"));
+        let (znear_speed, zfar_speed) = (clip_planes.znear * 0.01, clip_planes.zfar * 0.01);
+        ui.add_enabled_ui(!clip_planes.auto, |ui| {
+            ui.add(
+                DragValue::new(&mut clip_planes.znear)
+                    .range(1e-5..=clip_planes.zfar * 0.5)
+                    .speed(znear_speed)
+                    .prefix("near "),
+            );
+            ui.add(
+                DragValue::new(&mut clip_planes.zfar)
+                    .range(clip_planes.znear * 2.0..=1e7)
+                    .speed(zfar_speed)
+                    .prefix("far "),
+            );
+        });
+    });
+    if let Some((min, max)) = state.scene_bounds {
+        let radius = (max - min).length() * 0.5;
+        let far_side = state.camera.position.distance((min + max) * 0.5) + radius;
+        let resolution = state
+            .projection
+            .depth_resolution(far_side.min(clip_planes.zfar));
+        if resolution > radius * camera::POOR_DEPTH_RESOLUTION {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!(
+                    "Depth steps of {:.2e} at the far side of the scene, expect \
+                     z-fighting. Raise the near plane.",
+                    resolution
+                ),
+            );
+        }
+    }
+    ui.separator();
+    bookmarks_show(state, ui);
+    ui.separator();
+    ui.add(Checkbox::new(&mut state.deterministic, "Deterministic"))
+        .on_hover_text(AppState::setting_help("deterministic"));
+    ui.add_enabled(
+        state.capabilities.indirect_first_instance,
+        Checkbox::new(&mut state.indirect_draw, "Indirect draws"),
+    )
+    .on_hover_text(AppState::setting_help("indirect_draw"))
+    .on_disabled_hover_text("Needs indirect draws with a first instance");
+    ui.add_enabled(
+        state.indirect_draw && state.capabilities.compute_shaders,
+        Checkbox::new(&mut state.gpu_culling, "GPU culling"),
+    )
+    .on_hover_text(AppState::setting_help("gpu_culling"))
+    .on_disabled_hover_text("Needs indirect draws and compute shaders");
+    render_scale_show(state, ui);
+    ui.horizontal(|ui| {
+        ui.add(Checkbox::new(&mut state.paused, "Pause (P)"))
+            .on_hover_text(AppState::setting_help("paused"));
+        if ui
+            .button("Step (.)")
+            .on_hover_text(AppState::setting_help("step_frame"))
+            .clicked()
+        {
+            state.paused = true;
+            state.step_frame = true;
+        }
+        ui.label(format!("Frame {}", state.frame_index));
+    });
+}
+
+fn materials_show(state: &mut AppState, ui: &mut egui::Ui) {
+    state.normal_map_changed |= ui
+        .add(Checkbox::new(
+            &mut state.enable_normal_map,
+            "Enable normal map",
+        ))
+        .on_hover_text(AppState::setting_help("enable_normal_map"))
+        .changed();
+    ui.horizontal(|ui| {
+        ui.add(Checkbox::new(&mut state.hard_edges, "Hard edges"))
+            .on_hover_text(AppState::setting_help("hard_edge_angle"));
+        ui.add_enabled(
+            state.hard_edges,
+            egui::DragValue::new(&mut state.hard_edge_angle)
+                .range(0.0..=180.0)
+                .suffix("°"),
+        );
+        if ui
+            .add_enabled(
+                state.scene_path.is_some(),
+                egui::Button::new("Recompute normals"),
+            )
+            .on_hover_text("Reload the scene with generated normals for every OBJ mesh")
+            .clicked()
+        {
+            state.recompute_normals = true;
+        }
+    });
+    let previous_override = state.material_override;
+    egui::ComboBox::from_label("Material override")
+        .selected_text(state.material_override.name())
+        .show_ui(ui, |ui| {
+            for mode in MaterialOverride::ALL {
+                ui.selectable_value(&mut state.material_override, mode, mode.name());
+            }
+        })
+        .response
+        .on_hover_text(AppState::setting_help("material_override"));
+    state.material_override_changed = state.material_override != previous_override;
+    // Reflections should show the overridden materials as well
+    state.bake_reflection_probes |= state.material_override_changed;
+    normal_maps_show(state, ui);
+}
+
+fn lights_show(state: &mut AppState, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        ui.label("Light position")
+            .on_hover_text(AppState::setting_help("light_position"));
+        let editable = !state.given_light_position && !state.light_orbit.enabled;
+        ui.add_enabled_ui(editable, |ui| {
+            state
+                .light_input
+                .iter_mut()
+                .zip(state.light_position.iter_mut())
+                .for_each(|(input, position)| {
+                    if ui.add(TextEdit::singleline(input).char_limit(5)).changed() {
+                        *position = input.parse().unwrap_or(*position);
+                    }
+                });
+        });
+    });
+    light_orbit_show(state, ui);
+}
+
+fn projection_show(state: &mut AppState, ui: &mut egui::Ui) {
+    let projection = &mut state.projection;
+    let previous_mode = projection.mode();
+    let mut mode = previous_mode;
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut mode, ProjectionMode::Perspective, "Perspective");
+        ui.selectable_value(&mut mode, ProjectionMode::Orthographic, "Orthographic");
+    })
+    .response
+    .on_hover_text(AppState::setting_help("projection.mode"));
+    if mode != previous_mode {
+        // Keep the scene center framed as it was
+        if let Some((min, max)) = state.scene_bounds {
+            let distance = state.camera.position.distance((min + max) * 0.5);
+            projection.set_ortho_height(projection.matching_ortho_height(distance));
+        }
+        projection.set_mode(mode);
+    }
+    match mode {
+        ProjectionMode::Perspective => {
+            let mut fovy = projection.fovy().to_degrees();
+            if ui
+                .add(
+                    Slider::new(&mut fovy, 10.0..=120.0)
+                        .text("Field of view")
+                        .suffix("°"),
+                )
+                .on_hover_text(AppState::setting_help("projection.fovy"))
+                .changed()
+            {
+                projection.set_fovy(fovy.to_radians());
+            }
+        }
+        ProjectionMode::Orthographic => {
+            let mut height = projection.ortho_height();
+            let speed = height * 0.01;
+            if ui
+                .add(
+                    DragValue::new(&mut height)
+                        .range(1e-3..=1e6)
+                        .speed(speed)
+                        .prefix("height "),
+                )
+                .on_hover_text(AppState::setting_help("projection.ortho_height"))
+                .changed()
+            {
+                projection.set_ortho_height(height);
+            }
+        }
+    }
+}
+
+fn render_scale_show(state: &mut AppState, ui: &mut egui::Ui) {
+    let previous = state.render_scale;
+    let render_scale = &mut state.render_scale;
+    ui.add(
+        Slider::new(&mut render_scale.scale, RenderScale::RANGE)
+            .custom_formatter(|scale, _| format!("{:.0}%", scale * 100.0))
+            .text("Render scale"),
+    )
+    .on_hover_text(AppState::setting_help("render_scale.scale"));
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("Upsampling")
+            .selected_text(render_scale.upsampling.name())
+            .show_ui(ui, |ui| {
+                for upsampling in Upsampling::ALL {
+                    ui.selectable_value(
+                        &mut render_scale.upsampling,
+                        upsampling,
+                        upsampling.name(),
+                    );
+                }
+            })
+            .response
+            .on_hover_text(AppState::setting_help("render_scale.upsampling"));
+        ui.add_enabled(
+            render_scale.upsampling == Upsampling::Sharpen,
+            Slider::new(&mut render_scale.sharpness, 0.0..=1.0).text("Sharpness"),
+        )
+        .on_hover_text(AppState::setting_help("render_scale.sharpness"));
+    });
+    state.render_scale_changed |= state.render_scale != previous;
+}
+
+fn motion_show(state: &mut AppState, ui: &mut egui::Ui) {
+    let controller = &mut state.camera_controller;
+    ui.add(
+        Slider::new(&mut controller.acceleration, 0.0..=1.0)
+            .text("Acceleration")
+            .suffix(" s"),
+    )
+    .on_hover_text(AppState::setting_help("camera_controller.acceleration"));
+    ui.add(
+        Slider::new(&mut controller.damping, 0.0..=2.0)
+            .text("Damping")
+            .suffix(" s"),
+    )
+    .on_hover_text(AppState::setting_help("camera_controller.damping"));
+    ui.add(
+        Slider::new(&mut controller.look_smoothing, 0.0..=0.5)
+            .text("Look smoothing")
+            .suffix(" s"),
+    )
+    .on_hover_text(AppState::setting_help("camera_controller.look_smoothing"));
+}
+
+fn light_orbit_show(state: &mut AppState, ui: &mut egui::Ui) {
+    let orbit = &mut state.light_orbit;
+    ui.horizontal(|ui| {
+        ui.add(Checkbox::new(&mut orbit.enabled, "Orbit light"))
+            .on_hover_text(AppState::setting_help("light_orbit.enabled"));
+        for shape in OrbitShape::ALL {
+            ui.selectable_value(&mut orbit.shape, shape, shape.name());
+        }
+    });
+    ui.add(
+        Slider::new(&mut orbit.radius, 0.01..=100.0)
+            .logarithmic(true)
+            .text("Radius"),
+    )
+    .on_hover_text(AppState::setting_help("light_orbit.radius"));
+    ui.add_enabled(
+        orbit.shape == OrbitShape::Ellipse,
+        Slider::new(&mut orbit.minor_radius, 0.01..=100.0)
+            .logarithmic(true)
+            .text("Minor radius"),
+    )
+    .on_hover_text(AppState::setting_help("light_orbit.minor_radius"));
+    ui.add(
+        Slider::new(&mut orbit.speed, -360.0..=360.0)
+            .text("Speed")
+            .suffix("°/s"),
+    )
+    .on_hover_text(AppState::setting_help("light_orbit.speed"));
+}
+
+fn bookmarks_show(state: &mut AppState, ui: &mut egui::Ui) {
+    ui.label("Bookmarks")
+        .on_hover_text(AppState::setting_help("scene_file.camera_bookmarks"));
+    let bookmarks = &mut state.scene_file.camera_bookmarks;
+    let mut restored = None;
+    let mut removed = None;
+    for (i, bookmark) in bookmarks.iter_mut().enumerate() {
+        ui.push_id(i, |ui| {
+            ui.horizontal(|ui| {
+                // Only the first nine have a key
+                let key = if i < 9 {
+                    format!("{}", i + 1)
+                } else {
+                    "-".to_owned()
+                };
+                ui.label(key);
+                if ui.button(&bookmark.name).clicked() {
+                    restored = Some(i);
+                }
+                if ui
+                    .button("Update")
+                    .on_hover_text("Replace the pose with the current view")
+                    .clicked()
+                {
+                    *bookmark = CameraBookmark::capture(
+                        std::mem::take(&mut bookmark.name),
+                        &state.camera,
+                        &state.projection,
+                        &state.clip_planes,
+                    );
+                }
+                if ui.button("Remove").clicked() {
+                    removed = Some(i);
+                }
+            });
+        });
+    }
+    if let Some(i) = removed {
+        bookmarks.remove(i);
+    }
+    ui.horizontal(|ui| {
+        ui.add(
+            TextEdit::singleline(&mut state.bookmark_name)
+                .hint_text("Name")
+                .desired_width(120.0),
+        );
+        if ui.button("Add").clicked() {
+            let name = match std::mem::take(&mut state.bookmark_name) {
+                name if name.is_empty() => {
+                    format!("View {}", state.scene_file.camera_bookmarks.len() + 1)
+                }
+                name => name,
+            };
+            let bookmark =
+                CameraBookmark::capture(name, &state.camera, &state.projection, &state.clip_planes);
+            state.scene_file.camera_bookmarks.push(bookmark);
+        }
+        if ui
+            .add_enabled(state.scene_path.is_some(), egui::Button::new("Save"))
+            .on_hover_text("Store the bookmarks in the scene file")
+            .clicked()
+        {
+            state.save_scene_file();
+        }
+    });
+    if let Some(i) = restored {
+        state.restore_bookmark(i);
+    }
+}
+
+fn frame_stats_show(state: &mut AppState, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        ui.add(Checkbox::new(&mut state.watchdog.enabled, "GPU watchdog"))
+            .on_hover_text(AppState::setting_help("watchdog.enabled"));
+        ui.add_enabled(
+            state.watchdog.enabled,
+            DragValue::new(&mut state.watchdog.threshold_ms)
+                .range(16.0..=2000.0)
+                .suffix(" ms"),
+        )
+        .on_hover_text(AppState::setting_help("watchdog.threshold_ms"));
+    });
+    if !state.capabilities.compute_shaders {
+        ui.label("Needs compute shaders, which this adapter lacks");
+        return;
+    }
+    ui.add(Checkbox::new(&mut state.frame_stats_enabled, "Enable"))
+        .on_hover_text(AppState::setting_help("frame_stats_enabled"));
+    state.nan_guard_changed |= ui
+        .add(Checkbox::new(&mut state.nan_guard, "NaN guard"))
+        .on_hover_text(AppState::setting_help("nan_guard"))
+        .changed();
+    let Some(stats) = &state.frame_stats else {
+        return;
+    };
+    egui::Grid::new("Frame Statistics")
+        .striped(true)
+        .show(ui, |ui| {
+            for (label, value) in [
+                ("Finite pixels", stats.pixels.to_string()),
+                ("Mean luminance", format!("{:.4}", stats.mean_luminance)),
+                ("Median luminance", format!("{:.4}", stats.median_luminance)),
+                ("95th percentile", format!("{:.4}", stats.p95_luminance)),
+                ("99th percentile", format!("{:.4}", stats.p99_luminance)),
+                ("NaN pixels", stats.nan_count.to_string()),
+                ("Inf pixels", stats.inf_count.to_string()),
+            ] {
+                ui.label(label);
+                ui.label(value);
+                ui.end_row();
+            }
+            if let Some((nan_count, inf_count)) = stats.opaque_invalid {
+                ui.label("NaN / Inf after opaque");
+                ui.label(format!("{} / {}", nan_count, inf_count));
+                ui.end_row();
+            }
+        });
+}
+
+fn turntable_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Turntable")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            if let Some(turntable) = &state.turntable {
+                ui.add(
+                    ProgressBar::new(turntable.frame as f32 / turntable.frames as f32)
+                        .text(format!("{} / {}", turntable.frame, turntable.frames)),
+                );
+                if ui.button("Stop").clicked() {
+                    state.stop_turntable = true;
+                }
+                return;
+            }
+            let settings = &mut state.turntable_settings;
+            ui.horizontal(|ui| {
+                ui.label("Frames");
+                ui.add(DragValue::new(&mut settings.frames).range(1..=3600))
+                    .on_hover_text(AppState::setting_help("turntable_settings.frames"));
+            });
+            egui::ComboBox::from_label("Pivot")
+                .selected_text(settings.pivot_object.as_deref().unwrap_or("Whole scene"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut settings.pivot_object, None, "Whole scene");
+                    for name in &state.objects {
+                        ui.selectable_value(&mut settings.pivot_object, Some(name.clone()), name);
+                    }
+                })
+                .response
+                .on_hover_text(AppState::setting_help("turntable_settings.pivot_object"));
+            if ui
+                .add_enabled(!state.objects.is_empty(), egui::Button::new("Capture..."))
+                .clicked()
+            {
+                state.start_turntable = rfd::FileDialog::new()
+                    .set_title("Turntable Output Directory")
+                    .pick_folder();
+            }
+        });
+}
+
+fn panorama_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Panorama")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let settings = &mut state.panorama_settings;
+            egui::ComboBox::from_label("Face resolution")
+                .selected_text(settings.face_resolution.to_string())
+                .show_ui(ui, |ui| {
+                    for resolution in PanoramaSettings::RESOLUTIONS {
+                        ui.selectable_value(
+                            &mut settings.face_resolution,
+                            resolution,
+                            resolution.to_string(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text(AppState::setting_help("panorama_settings.face_resolution"));
+            if ui
+                .button("Capture...")
+                .on_hover_text("Write the scene around the camera as an equirectangular image")
+                .clicked()
+            {
+                state.capture_panorama = rfd::FileDialog::new()
+                    .set_title("Save Panorama")
+                    .add_filter("PNG", &["png"])
+                    .add_filter("OpenEXR", &["exr"])
+                    .set_file_name("panorama.png")
+                    .save_file();
+            }
+        });
+}
+
+fn sweep_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Settings Sweep")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            if let Some(sweep) = &state.sweep {
+                ui.add(
+                    ProgressBar::new(sweep.cell as f32 / sweep.cell_count() as f32).text(format!(
+                        "{} / {}",
+                        sweep.cell,
+                        sweep.cell_count()
+                    )),
+                );
+                if ui.button("Stop").clicked() {
+                    state.stop_sweep = true;
+                }
+                return;
+            }
+            let settings = &mut state.sweep_settings;
+            let capabilities = &state.capabilities;
+            for (label, axis, field) in [
+                ("Rows", &mut settings.rows, "sweep_settings.rows"),
+                ("Columns", &mut settings.columns, "sweep_settings.columns"),
+            ] {
+                egui::ComboBox::from_label(label)
+                    .selected_text(axis.name())
+                    .show_ui(ui, |ui| {
+                        for option in SweepAxis::ALL {
+                            ui.add_enabled_ui(option.is_supported(capabilities), |ui| {
+                                ui.selectable_value(axis, option, option.name());
+                            });
+                        }
+                    })
+                    .response
+                    .on_hover_text(AppState::setting_help(field));
+            }
+            let distinct = settings.rows != settings.columns;
+            if !distinct {
+                ui.colored_label(ui.visuals().warn_fg_color, "Pick two different settings");
+            }
+            if ui
+                .add_enabled(
+                    distinct && !state.objects.is_empty(),
+                    egui::Button::new(format!("Capture {} cells...", settings.cell_count())),
+                )
+                .clicked()
+            {
+                state.start_sweep = rfd::FileDialog::new()
+                    .set_title("Settings Sweep Contact Sheet")
+                    .add_filter("PNG", &["png"])
+                    .set_file_name("sweep.png")
+                    .save_file();
+            }
+        });
+}
+
+fn camera_path_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Camera Path")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            if let Some(capture) = &state.path_capture {
+                ui.add(
+                    ProgressBar::new(capture.frame as f32 / capture.frames as f32)
+                        .text(format!("{} / {}", capture.frame, capture.frames)),
+                );
+                if ui.button("Stop").clicked() {
+                    state.stop_path_capture = true;
+                }
+                return;
+            }
+            let path = &mut state.scene_file.camera_path;
+            let playback = &mut state.path_playback;
+            ui.label("Keyframes")
+                .on_hover_text(AppState::setting_help("scene_file.camera_path"));
+            let mut removed = None;
+            let mut retimed = false;
+            for (i, key) in path.keyframes.iter_mut().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.horizontal(|ui| {
+                        retimed |= ui
+                            .add(
+                                DragValue::new(&mut key.time)
+                                    .range(0.0..=f32::MAX)
+                                    .speed(0.05)
+                                    .suffix(" s"),
+                            )
+                            .changed();
+                        if ui.button("Go").clicked() {
+                            playback.time = key.time;
+                            playback.playing = false;
+                            playback.following = true;
+                        }
+                        if ui.button("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                });
+            }
+            if let Some(i) = removed {
+                path.keyframes.remove(i);
+            }
+            if retimed {
+                path.sort();
+            }
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Record")
+                    .on_hover_text("Append the current view as a keyframe")
+                    .clicked()
+                {
+                    path.record(&state.camera, playback.record_interval);
+                }
+                ui.add(
+                    DragValue::new(&mut playback.record_interval)
+                        .range(0.01..=60.0)
+                        .speed(0.05)
+                        .suffix(" s later"),
+                )
+                .on_hover_text(AppState::setting_help("path_playback.record_interval"));
+            });
+            ui.separator();
+            let duration = path.duration();
+            if ui
+                .add(Slider::new(&mut playback.time, 0.0..=duration).suffix(" s"))
+                .changed()
+            {
+                playback.following = true;
+            }
+            ui.horizontal(|ui| {
+                let play_label = if playback.playing { "Pause" } else { "Play" };
+                if ui
+                    .add_enabled(!path.keyframes.is_empty(), egui::Button::new(play_label))
+                    .clicked()
+                {
+                    playback.playing = !playback.playing;
+                    if playback.playing && playback.time >= duration {
+                        playback.time = 0.0;
+                    }
+                    playback.following = true;
+                }
+                if ui
+                    .add_enabled(playback.following, egui::Button::new("Release"))
+                    .on_hover_text("Stop following the path and fly freely")
+                    .clicked()
+                {
+                    playback.playing = false;
+                    playback.following = false;
+                }
+            });
+            ui.separator();
+            let can_capture = !path.keyframes.is_empty();
+            let mut save = false;
+            ui.horizontal(|ui| {
+                ui.add(
+                    DragValue::new(&mut playback.capture_fps)
+                        .range(1..=240)
+                        .suffix(" fps"),
+                )
+                .on_hover_text(AppState::setting_help("path_playback.capture_fps"));
+                if ui
+                    .add_enabled(can_capture, egui::Button::new("Capture..."))
+                    .clicked()
+                {
+                    state.start_path_capture = rfd::FileDialog::new()
+                        .set_title("Camera Path Output Directory")
+                        .pick_folder();
+                }
+                save = ui
+                    .add_enabled(state.scene_path.is_some(), egui::Button::new("Save"))
+                    .on_hover_text("Store the keyframes in the scene file")
+                    .clicked();
+            });
+            if save {
+                state.save_scene_file();
+            }
+        });
+}
+
+fn roi_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Region Of Interest")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let roi = &mut state.roi;
+            ui.add(Checkbox::new(&mut roi.enabled, "Enabled"))
+                .on_hover_text(AppState::setting_help("roi.enabled"));
+            ui.horizontal(|ui| {
+                ui.label("Position");
+                ui.add(
+                    DragValue::new(&mut roi.min.x)
+                        .range(0.0..=1.0 - roi.size.x)
+                        .speed(0.002),
+                );
+                ui.add(
+                    DragValue::new(&mut roi.min.y)
+                        .range(0.0..=1.0 - roi.size.y)
+                        .speed(0.002),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Size");
+                ui.add(
+                    DragValue::new(&mut roi.size.x)
+                        .range(0.01..=1.0 - roi.min.x)
+                        .speed(0.002),
+                );
+                ui.add(
+                    DragValue::new(&mut roi.size.y)
+                        .range(0.01..=1.0 - roi.min.y)
+                        .speed(0.002),
+                );
+            });
+            ui.add(Slider::new(&mut roi.zoom, 1..=8).text("Zoom"))
+                .on_hover_text(AppState::setting_help("roi.zoom"));
+            let Some((id, [width, height])) = state.roi_texture.filter(|_| state.roi.enabled)
+            else {
+                return;
+            };
+            ui.label(format!("{} x {} pixels", width, height));
+            // One texel per physical pixel
+            let size = egui::vec2(width as f32, height as f32) / ui.ctx().pixels_per_point();
+            egui::ScrollArea::both().show(ui, |ui| {
+                ui.image(egui::load::SizedTexture::new(id, size));
+            });
+        });
+    if state.roi.enabled {
+        let context = renderer.context();
+        let screen = context.screen_rect();
+        let to_screen = |v: glam::Vec2| egui::vec2(v.x, v.y) * screen.size();
+        let rect = egui::Rect::from_min_size(
+            screen.min + to_screen(state.roi.min),
+            to_screen(state.roi.size),
+        );
+        context
+            .layer_painter(egui::LayerId::background())
+            .rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::YELLOW));
+    }
+}
+
+/// Picks the renderer textures to preview, each preview opens its own window.
+fn texture_previews_show(state: &mut AppState, renderer: &EguiRenderer) {
+    let context = renderer.context();
+    egui::Window::new("Texture Previews")
+        .default_open(false)
+        .show(context, |ui| {
+            let previews = &mut state.texture_previews;
+            let help = &AppState::setting_help("texture_previews");
+            ui.horizontal(|ui| {
+                for source in [PreviewSource::SceneColor, PreviewSource::SceneDepth] {
+                    if ui.button(source.name()).on_hover_text(help).clicked() {
+                        previews.push(TexturePreview::new(source));
+                    }
+                }
+            });
+            let probes = state
+                .scene_file
+                .reflection_probes
+                .len()
+                .min(MAX_REFLECTION_PROBES);
+            if probes == 0 {
+                ui.label("No reflection probes");
+            }
+            for probe in 0..probes {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Probe {}", probe));
+                    for (face, name) in CUBE_FACES.iter().enumerate() {
+                        if ui.button(*name).on_hover_text(help).clicked() {
+                            previews.push(TexturePreview::new(PreviewSource::ProbeFace {
+                                probe,
+                                face,
+                            }));
+                        }
+                    }
                 });
+            }
+        });
+    for (i, preview) in state.texture_previews.iter_mut().enumerate() {
+        egui::Window::new(preview.source.name())
+            .id(egui::Id::new(("texture_preview", i)))
+            .open(&mut preview.open)
+            .show(context, |ui| {
+                if !preview.source.is_depth() {
+                    ui.add(
+                        Slider::new(&mut preview.exposure, 0.01..=16.0)
+                            .logarithmic(true)
+                            .text("Exposure"),
+                    )
+                    .on_hover_text(AppState::setting_help("texture_previews.exposure"));
+                }
+                let Some(&(id, [width, height])) = state.preview_textures.get(i) else {
+                    return;
+                };
+                // One texel per physical pixel
+                let size = egui::vec2(width as f32, height as f32) / ui.ctx().pixels_per_point();
+                ui.image(egui::load::SizedTexture::new(id, size));
             });
+    }
+    state.texture_previews.retain(|preview| preview.open);
+}
+
+fn passes_show(state: &mut AppState, ui: &mut egui::Ui) {
+    let stages = &mut state.render_stages;
+    let before = stages.clone();
+    let enabled_help = &AppState::setting_help("render_stages.enabled");
+    let mut moved = None;
+    let mut removed = None;
+    let count = stages.len();
+    for (i, slot) in stages.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.add(Checkbox::new(&mut slot.enabled, slot.kind.name()))
+                .on_hover_text(enabled_help);
+            if let StageKind::Clear { color } = &mut slot.kind {
+                ui.color_edit_button_rgb(color);
+            }
+            if ui.add_enabled(i > 0, egui::Button::new("Up")).clicked() {
+                moved = Some((i, i - 1));
+            }
+            if ui
+                .add_enabled(i + 1 < count, egui::Button::new("Down"))
+                .clicked()
+            {
+                moved = Some((i, i + 1));
+            }
+            // The renderer also loads and picks, its stages can only be disabled
+            if slot.kind.removable() && ui.button("Remove").clicked() {
+                removed = Some(i);
+            }
+        });
+    }
+    if let Some((from, to)) = moved {
+        stages.swap(from, to);
+    }
+    if let Some(i) = removed {
+        stages.remove(i);
+    }
+    ui.separator();
+    ui.horizontal(|ui| {
+        for kind in StageKind::INSERTABLE {
+            if ui
+                .button(format!("Add {}", kind.name()))
+                .on_hover_text(AppState::setting_help("render_stages"))
+                .clicked()
+            {
+                stages.push(StageSlot::new(kind));
+            }
+        }
+    });
+    if *stages != before {
+        state.render_stages_changed = true;
+    }
+}
+
+fn post_process_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Post Processing")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let effects = &mut state.post_effects;
+            let enabled_help = &AppState::setting_help("post_effects.enabled");
+            let mut moved = None;
+            let mut removed = None;
+            let count = effects.len();
+            if count == 0 {
+                ui.label("The scene color is presented as rendered");
+            }
+            for (i, slot) in effects.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(Checkbox::new(&mut slot.enabled, slot.kind.name()))
+                        .on_hover_text(enabled_help);
+                    match &mut slot.kind {
+                        PostEffectKind::Tonemap { exposure } => {
+                            ui.add(
+                                Slider::new(exposure, 0.01..=16.0)
+                                    .logarithmic(true)
+                                    .text("Exposure"),
+                            )
+                            .on_hover_text(AppState::setting_help("post_effects.tonemap"));
+                        }
+                        PostEffectKind::Vignette { strength, start } => {
+                            ui.add(Slider::new(strength, 0.0..=1.0).text("Strength"))
+                                .on_hover_text(AppState::setting_help("post_effects.vignette"));
+                            ui.add(Slider::new(start, 0.0..=1.0).text("Start"))
+                                .on_hover_text(AppState::setting_help("post_effects.vignette"));
+                        }
+                        PostEffectKind::MotionBlur { shutter_angle } => {
+                            ui.add(
+                                Slider::new(shutter_angle, 0.0..=360.0)
+                                    .suffix("°")
+                                    .text("Shutter angle"),
+                            )
+                            .on_hover_text(AppState::setting_help("post_effects.motion_blur"));
+                        }
+                        PostEffectKind::ColorGrade {
+                            lift,
+                            gamma,
+                            gain,
+                            saturation,
+                        } => {
+                            let help = AppState::setting_help("post_effects.color_grade");
+                            ui.add(Slider::new(lift, -0.5..=0.5).text("Lift"))
+                                .on_hover_text(&help);
+                            ui.add(
+                                Slider::new(gamma, 0.2..=5.0)
+                                    .logarithmic(true)
+                                    .text("Gamma"),
+                            )
+                            .on_hover_text(&help);
+                            ui.add(Slider::new(gain, 0.0..=2.0).text("Gain"))
+                                .on_hover_text(&help);
+                            ui.add(Slider::new(saturation, 0.0..=2.0).text("Saturation"))
+                                .on_hover_text(&help);
+                        }
+                    }
+                    if ui.add_enabled(i > 0, egui::Button::new("Up")).clicked() {
+                        moved = Some((i, i - 1));
+                    }
+                    if ui
+                        .add_enabled(i + 1 < count, egui::Button::new("Down"))
+                        .clicked()
+                    {
+                        moved = Some((i, i + 1));
+                    }
+                    if ui.button("Remove").clicked() {
+                        removed = Some(i);
+                    }
+                });
+            }
+            if let Some((from, to)) = moved {
+                effects.swap(from, to);
+            }
+            if let Some(i) = removed {
+                effects.remove(i);
+            }
             ui.separator();
-            state.normal_map_changed = ui
+            ui.horizontal(|ui| {
+                let name = state.color_lut.as_ref().map_or("None".into(), |path| {
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                });
+                ui.label(format!("Color table: {}", name))
+                    .on_hover_text(AppState::setting_help("color_lut"));
+                if ui.button("Open...").clicked() {
+                    let picked = rfd::FileDialog::new()
+                        .set_title("Open Color Table")
+                        .add_filter("Cube LUT", &["cube"])
+                        .pick_file();
+                    if picked.is_some() {
+                        state.color_lut = picked;
+                        state.color_lut_changed = true;
+                    }
+                }
+                if ui
+                    .add_enabled(state.color_lut.is_some(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    state.color_lut = None;
+                    state.color_lut_changed = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                for kind in PostEffectKind::ALL {
+                    if ui
+                        .button(format!("Add {}", kind.name()))
+                        .on_hover_text(AppState::setting_help("post_effects"))
+                        .clicked()
+                    {
+                        effects.push(PostEffectSlot::new(kind));
+                    }
+                }
+            });
+        });
+}
+
+fn depth_contours_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Depth Contours")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let contours = &mut state.depth_contours;
+            state.depth_contours_changed |= ui
+                .add(Checkbox::new(&mut contours.enabled, "Enabled"))
+                .on_hover_text(AppState::setting_help("depth_contours.enabled"))
+                .changed();
+            ui.add(
+                Slider::new(&mut contours.spacing, 0.01..=100.0)
+                    .logarithmic(true)
+                    .text("Spacing"),
+            )
+            .on_hover_text(AppState::setting_help("depth_contours.spacing"));
+            ui.add(
+                Slider::new(&mut contours.focus_distance, 0.01..=1000.0)
+                    .logarithmic(true)
+                    .text("Focus distance"),
+            )
+            .on_hover_text(AppState::setting_help("depth_contours.focus_distance"));
+            ui.add(
+                Slider::new(&mut contours.focus_range, 0.0..=100.0)
+                    .logarithmic(true)
+                    .text("Focus range"),
+            )
+            .on_hover_text(AppState::setting_help("depth_contours.focus_range"));
+        });
+}
+
+fn ground_grid_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Grid And Axes")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let grid = &mut state.ground_grid;
+            ui.add(Checkbox::new(&mut grid.enabled, "Ground grid"))
+                .on_hover_text(AppState::setting_help("ground_grid.enabled"));
+            ui.add_enabled_ui(grid.enabled, |ui| {
+                ui.add(
+                    Slider::new(&mut grid.spacing, 0.01..=100.0)
+                        .logarithmic(true)
+                        .text("Spacing"),
+                )
+                .on_hover_text(AppState::setting_help("ground_grid.spacing"));
+                ui.add(
+                    Slider::new(&mut grid.fade_distance, 1.0..=10000.0)
+                        .logarithmic(true)
+                        .text("Fade distance"),
+                )
+                .on_hover_text(AppState::setting_help("ground_grid.fade_distance"));
+            });
+            ui.add(Checkbox::new(&mut state.axis_gizmo, "Axis gizmo"))
+                .on_hover_text(AppState::setting_help("axis_gizmo"));
+        });
+}
+
+fn volumetric_fog_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Volumetric Fog")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            if !state.capabilities.compute_shaders {
+                ui.label("Needs compute shaders, which this adapter lacks");
+                return;
+            }
+            let fog = &mut state.volumetric_fog;
+            ui.add(Checkbox::new(&mut fog.enabled, "Enabled"))
+                .on_hover_text(AppState::setting_help("volumetric_fog.enabled"));
+            ui.add_enabled_ui(fog.enabled, |ui| {
+                ui.add(
+                    Slider::new(&mut fog.density, 0.0..=1.0)
+                        .logarithmic(true)
+                        .text("Density"),
+                )
+                .on_hover_text(AppState::setting_help("volumetric_fog.density"));
+                ui.add(Slider::new(&mut fog.height, -100.0..=100.0).text("Height"))
+                    .on_hover_text(AppState::setting_help("volumetric_fog.height"));
+                ui.add(
+                    Slider::new(&mut fog.height_falloff, 0.0..=10.0)
+                        .logarithmic(true)
+                        .text("Height falloff"),
+                )
+                .on_hover_text(AppState::setting_help("volumetric_fog.height_falloff"));
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_rgb(&mut fog.albedo);
+                    ui.label("Albedo");
+                })
+                .response
+                .on_hover_text(AppState::setting_help("volumetric_fog.albedo"));
+                ui.add(Slider::new(&mut fog.anisotropy, -0.9..=0.9).text("Anisotropy"))
+                    .on_hover_text(AppState::setting_help("volumetric_fog.anisotropy"));
+                ui.add(Slider::new(&mut fog.light_intensity, 0.0..=10.0).text("Light intensity"))
+                    .on_hover_text(AppState::setting_help("volumetric_fog.light_intensity"));
+                ui.add(Slider::new(&mut fog.ambient_intensity, 0.0..=4.0).text("Probe ambient"))
+                    .on_hover_text(AppState::setting_help("volumetric_fog.ambient_intensity"));
+                ui.add(
+                    Slider::new(&mut fog.max_distance, 1.0..=1000.0)
+                        .logarithmic(true)
+                        .text("Max distance"),
+                )
+                .on_hover_text(AppState::setting_help("volumetric_fog.max_distance"));
+            });
+        });
+}
+
+fn sky_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Sky")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let sky = &mut state.sky;
+            let mut changed = ui
+                .add(Checkbox::new(&mut sky.enabled, "Enabled"))
+                .on_hover_text(AppState::setting_help("sky.enabled"))
+                .changed();
+            ui.add_enabled_ui(sky.enabled, |ui| {
+                let hours = sky.time_of_day as u32;
+                let minutes = (sky.time_of_day.fract() * 60.0) as u32;
+                changed |= ui
+                    .add(
+                        Slider::new(&mut sky.time_of_day, 0.0..=24.0)
+                            .text("Time of day")
+                            .custom_formatter(|_, _| format!("{:02}:{:02}", hours, minutes)),
+                    )
+                    .on_hover_text(AppState::setting_help("sky.time_of_day"))
+                    .changed();
+                changed |= ui
+                    .add(Slider::new(&mut sky.noon_elevation, 0.0..=90.0).text("Noon elevation"))
+                    .on_hover_text(AppState::setting_help("sky.noon_elevation"))
+                    .changed();
+                changed |= ui
+                    .add(Slider::new(&mut sky.azimuth, -180.0..=180.0).text("Azimuth"))
+                    .on_hover_text(AppState::setting_help("sky.azimuth"))
+                    .changed();
+                changed |= ui
+                    .add(Slider::new(&mut sky.turbidity, 2.0..=10.0).text("Turbidity"))
+                    .on_hover_text(AppState::setting_help("sky.turbidity"))
+                    .changed();
+                changed |= ui
+                    .add(Slider::new(&mut sky.intensity, 0.0..=4.0).text("Intensity"))
+                    .on_hover_text(AppState::setting_help("sky.intensity"))
+                    .changed();
+                changed |= ui
+                    .add(Slider::new(&mut sky.environment_intensity, 0.0..=2.0).text("Environment"))
+                    .on_hover_text(AppState::setting_help("sky.environment_intensity"))
+                    .changed();
+                changed |= ui
+                    .add(Checkbox::new(&mut sky.sun_light, "Sun drives the light"))
+                    .on_hover_text(AppState::setting_help("sky.sun_light"))
+                    .changed();
+            });
+            state.sky_changed |= changed;
+        });
+}
+
+/// The world axes rotated into view in the bottom left corner, the axes
+/// pointing towards the camera are drawn last.
+fn axis_gizmo_paint(state: &AppState, renderer: &EguiRenderer) {
+    if !state.axis_gizmo {
+        return;
+    }
+    const RADIUS: f32 = 40.0;
+    let screen = renderer.context().screen_rect();
+    let center = egui::pos2(screen.min.x + RADIUS + 16.0, screen.max.y - RADIUS - 16.0);
+    let painter = renderer
+        .context()
+        .layer_painter(egui::LayerId::background());
+    let view = state.camera.calc_matrix();
+    let mut axes = [
+        (glam::Vec3::X, "X", egui::Color32::from_rgb(230, 60, 60)),
+        (glam::Vec3::Y, "Y", egui::Color32::from_rgb(80, 200, 80)),
+        (glam::Vec3::Z, "Z", egui::Color32::from_rgb(60, 110, 230)),
+    ]
+    .map(|(axis, label, color)| (view.transform_vector3(axis), label, color));
+    // View space looks down -Z
+    axes.sort_by(|(a, ..), (b, ..)| a.z.total_cmp(&b.z));
+    for (direction, label, color) in axes {
+        let end = center + egui::vec2(direction.x, -direction.y) * RADIUS;
+        painter.line_segment([center, end], egui::Stroke::new(2.0, color));
+        painter.text(
+            end,
+            egui::Align2::CENTER_CENTER,
+            label,
+            egui::FontId::proportional(13.0),
+            color,
+        );
+    }
+}
+
+fn streaming_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Streaming")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let streaming = &mut state.streaming;
+            ui.add(Checkbox::new(&mut streaming.enabled, "Enabled"))
+                .on_hover_text(AppState::setting_help("streaming.enabled"));
+            ui.add(
+                Slider::new(&mut streaming.load_distance, 1.0..=10000.0)
+                    .logarithmic(true)
+                    .text("Load distance"),
+            )
+            .on_hover_text(AppState::setting_help("streaming.load_distance"));
+            ui.label(format!(
+                "{} of {} chunks resident, {} loading",
+                state.resident_chunks,
+                state.scene_file.chunks.len(),
+                state.loading_chunks
+            ));
+        });
+}
+
+fn animation_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Animation")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            ui.horizontal(|ui| {
+                ui.add(Checkbox::new(&mut state.play_animation, "Play"))
+                    .on_hover_text(AppState::setting_help("play_animation"));
+                if ui.button("Restart").clicked() {
+                    state.animation_time = 0.0;
+                    state.animation_time_changed = true;
+                }
+                ui.label(format!("{:.2} s", state.animation_time));
+            });
+            let scrubbed = ui
+                .add(
+                    Slider::new(&mut state.animation_time, 0.0..=state.animation_duration)
+                        .text("Time")
+                        .suffix(" s"),
+                )
+                .on_hover_text(AppState::setting_help("animation_time"))
+                .changed();
+            state.animation_time_changed |= scrubbed;
+            ui.add(Checkbox::new(
+                &mut state.animate_reflection_probes,
+                "Re-bake reflection probes while playing",
+            ))
+            .on_hover_text(AppState::setting_help("animate_reflection_probes"));
+        });
+}
+
+fn objects_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Objects")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let previous_wireframe = state.wireframe_object.clone();
+            egui::ComboBox::from_label("Wireframe overlay")
+                .selected_text(state.wireframe_object.as_deref().unwrap_or("None"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut state.wireframe_object, None, "None");
+                    for name in &state.objects {
+                        ui.selectable_value(&mut state.wireframe_object, Some(name.clone()), name);
+                    }
+                })
+                .response
+                .on_hover_text(AppState::setting_help("wireframe_object"));
+            state.wireframe_object_changed |= state.wireframe_object != previous_wireframe;
+            ui.horizontal(|ui| {
+                ui.add(Checkbox::new(
+                    &mut state.hover_highlight,
+                    "Highlight under cursor",
+                ))
+                .on_hover_text(AppState::setting_help("hover_highlight"));
+                if let Some(name) = state
+                    .hovered_object
+                    .as_ref()
+                    .filter(|_| state.hover_highlight)
+                {
+                    ui.label(name);
+                }
+            });
+            ui.label(
+                "Visible on the left, dynamic objects are not baked into the reflection probes",
+            )
+            .on_hover_text(AppState::setting_help("scene_file.dynamic_objects"));
+            let mut changed = false;
+            let visible_help = &AppState::setting_help("hidden_objects");
+            let mirror_help = &AppState::setting_help("scene_file.mirrors");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let dynamic_objects = &mut state.scene_file.dynamic_objects;
+                let mirrors = &mut state.scene_file.mirrors;
+                let hidden_objects = &mut state.hidden_objects;
+                for name in &state.objects {
+                    ui.horizontal(|ui| {
+                        let mut visible = !hidden_objects.contains(name);
+                        if ui
+                            .add(Checkbox::without_text(&mut visible))
+                            .on_hover_text(visible_help)
+                            .changed()
+                        {
+                            if visible {
+                                hidden_objects.retain(|n| n != name);
+                            } else {
+                                hidden_objects.push(name.clone());
+                            }
+                            changed = true;
+                        }
+                        let mut dynamic = dynamic_objects.contains(name);
+                        if ui.add(Checkbox::new(&mut dynamic, name)).changed() {
+                            if dynamic {
+                                dynamic_objects.push(name.clone());
+                            } else {
+                                dynamic_objects.retain(|n| n != name);
+                            }
+                            changed = true;
+                        }
+                        let mut mirror = mirrors.contains(name);
+                        if ui
+                            .add(Checkbox::new(&mut mirror, "Mirror"))
+                            .on_hover_text(mirror_help)
+                            .changed()
+                        {
+                            if mirror {
+                                mirrors.push(name.clone());
+                            } else {
+                                mirrors.retain(|n| n != name);
+                            }
+                            changed = true;
+                        }
+                    });
+                }
+            });
+            state.object_classes_changed |= changed;
+            if changed {
+                state.bake_reflection_probes = true;
+            }
+            orientation_show(state, ui);
+        });
+}
+
+/// Normal map strength and handedness of the materials with a normal map, kept
+/// in `SceneFile::normal_maps` once changed.
+fn normal_maps_show(state: &mut AppState, ui: &mut egui::Ui) {
+    if state.normal_mapped_materials.is_empty() {
+        return;
+    }
+    ui.separator();
+    ui.label("Normal maps");
+    let normal_maps = &mut state.scene_file.normal_maps;
+    let mut changed = false;
+    for (name, loaded) in &state.normal_mapped_materials {
+        ui.push_id(name, |ui| {
+            let mut mapping = normal_maps.get(name).copied().unwrap_or(*loaded);
+            ui.horizontal(|ui| {
+                ui.label(if name.is_empty() { "Unnamed" } else { name });
+                let mut edited = ui
+                    .add(Slider::new(&mut mapping.strength, 0.0..=2.0).text("Strength"))
+                    .on_hover_text(AppState::setting_help("scene_file.normal_maps.strength"))
+                    .changed();
+                edited |= ui
+                    .add(Checkbox::new(&mut mapping.flip_y, "Flip Y (DirectX)"))
+                    .on_hover_text(AppState::setting_help("scene_file.normal_maps.flip_y"))
+                    .changed();
+                if edited {
+                    normal_maps.insert(name.clone(), mapping);
+                    changed = true;
+                }
+                if ui
+                    .add_enabled(normal_maps.contains_key(name), egui::Button::new("Reset"))
+                    .on_hover_text("Back to the normal mapping the material was loaded with")
+                    .clicked()
+                {
+                    normal_maps.remove(name);
+                    changed = true;
+                }
+            });
+        });
+    }
+    if changed {
+        state.normal_map_changed = true;
+        state.bake_reflection_probes = true;
+    }
+}
+
+/// Meshes that look inside out or were flipped before, with their flips. Flipping
+/// saves the scene file and loads the scene again.
+fn orientation_show(state: &mut AppState, ui: &mut egui::Ui) {
+    let scene_file = &mut state.scene_file;
+    let mut seen = HashSet::new();
+    let listed = state
+        .object_orientations
+        .iter()
+        .filter(|(name, orientation)| {
+            orientation.suggested_fix().is_some()
+                || scene_file.flipped_winding.contains(name)
+                || scene_file.flipped_normals.contains(name)
+        })
+        // Flips apply by name, meshes sharing one are listed once
+        .filter(|(name, _)| seen.insert(name.as_str()))
+        .collect::<Vec<_>>();
+    if listed.is_empty() {
+        return;
+    }
+    ui.separator();
+    ui.label("Orientation");
+    let mut changed = false;
+    for (name, orientation) in listed {
+        ui.push_id(name, |ui| {
+            ui.label(name);
+            let problems = orientation.problems();
+            if !problems.is_empty() {
+                ui.small(problems.join(", "));
+            }
+            ui.horizontal(|ui| {
+                for (list, label, field) in [
+                    (
+                        &mut scene_file.flipped_winding,
+                        "Flip winding",
+                        "scene_file.flipped_winding",
+                    ),
+                    (
+                        &mut scene_file.flipped_normals,
+                        "Flip normals",
+                        "scene_file.flipped_normals",
+                    ),
+                ] {
+                    let mut flipped = list.contains(name);
+                    if ui
+                        .add(Checkbox::new(&mut flipped, label))
+                        .on_hover_text(AppState::setting_help(field))
+                        .changed()
+                    {
+                        toggle_name(list, name);
+                        changed = true;
+                    }
+                }
+                if let Some(fix) = orientation.suggested_fix() {
+                    if ui
+                        .button("Apply suggestion")
+                        .on_hover_text(format!("Looks inside out, {}", fix.name()))
+                        .clicked()
+                    {
+                        if fix.flips_winding() {
+                            toggle_name(&mut scene_file.flipped_winding, name);
+                        }
+                        if fix.flips_normals() {
+                            toggle_name(&mut scene_file.flipped_normals, name);
+                        }
+                        changed = true;
+                    }
+                }
+            });
+        });
+    }
+    if changed {
+        state.save_scene_file();
+        state.requested_scene = state.scene_path.clone();
+    }
+}
+
+/// Add `name` to `names` or remove it when it is listed already.
+fn toggle_name(names: &mut Vec<String>, name: &str) {
+    if names.iter().any(|n| n == name) {
+        names.retain(|n| n != name);
+    } else {
+        names.push(name.to_owned());
+    }
+}
+
+fn triangle_inspector_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Triangle Inspector")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let Some(picked) = &state.picked_triangle else {
+                ui.label("Middle click a triangle to inspect it");
+                return;
+            };
+            picked_triangle_show(picked, ui);
+            if ui.button("Clear").clicked() {
+                state.picked_triangle = None;
+            }
+        });
+}
+
+fn picked_triangle_show(picked: &PickedTriangle, ui: &mut egui::Ui) {
+    let vector = |v: glam::Vec3| format!("{:.3} {:.3} {:.3}", v.x, v.y, v.z);
+    ui.label(format!("{}, triangle {}", picked.object, picked.triangle));
+    let face_normal = picked.face_normal();
+    ui.label(format!("Face normal {}", vector(face_normal)));
+    let uv_area = picked.uv_area();
+    ui.label(format!(
+        "UV area {:.5}{}",
+        uv_area,
+        if uv_area < 0.0 { ", mirrored" } else { "" }
+    ));
+    if picked.mirrored_transform {
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            "The object transform mirrors, flipping every tangent frame",
+        );
+    }
+    egui::Grid::new("Picked Vertices")
+        .striped(true)
+        .show(ui, |ui| {
+            for header in ["", "Position", "Normal", "Tangent", "Sign", "UV", "TBN det"] {
+                ui.strong(header);
+            }
+            ui.end_row();
+            for (index, vertex) in picked.indices.iter().zip(&picked.vertices) {
+                ui.label(index.to_string());
+                ui.label(vector(vertex.position));
+                // Normals facing away from the face hint at flipped winding or normals
+                let normal = ui.label(vector(vertex.normal));
+                if vertex.normal.dot(face_normal) < 0.0 {
+                    normal.on_hover_text("Points away from the face normal");
+                }
+                ui.label(vector(vertex.tangent));
+                ui.label(format!("{:+.0}", vertex.tangent_sign));
+                ui.label(format!("{:.4} {:.4}", vertex.texcoord.x, vertex.texcoord.y));
+                let determinant = picking::tbn_determinant(vertex);
+                let text = format!("{:+.4}", determinant);
+                if (determinant.abs() - 1.0).abs() > 1e-3 {
+                    ui.colored_label(ui.visuals().warn_fg_color, text)
+                        .on_hover_text("The tangent frame is not orthonormal");
+                } else {
+                    ui.label(text);
+                }
+                ui.end_row();
+            }
+        });
+}
+
+fn reflection_probe_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::Window::new("Reflection Probes")
+        .default_open(false)
+        .show(renderer.context(), |ui| {
+            let mut changed = ui
                 .add(Checkbox::new(
-                    &mut state.enable_normal_map,
-                    "Enable normal map",
+                    &mut state.enable_reflection_probes,
+                    "Enable reflection probes",
                 ))
+                .on_hover_text(AppState::setting_help("enable_reflection_probes"))
+                .changed();
+            changed |= ui
+                .add(
+                    Slider::new(&mut state.reflection_probe_intensity, 0.0..=2.0).text("Intensity"),
+                )
+                .on_hover_text(AppState::setting_help("reflection_probe_intensity"))
                 .changed();
+            ui.add(Checkbox::new(
+                &mut state.planar_reflections,
+                "Planar reflections",
+            ))
+            .on_hover_text(AppState::setting_help("planar_reflections"));
+            ui.add(
+                Slider::new(&mut state.probe_bake.budget_ms, 0.0..=16.0).text("Update budget (ms)"),
+            )
+            .on_hover_text(AppState::setting_help("probe_bake.budget_ms"));
+            let total_faces = state
+                .scene_file
+                .reflection_probes
+                .len()
+                .min(MAX_REFLECTION_PROBES)
+                * 6;
+            ui.label(format!(
+                "Full refresh: {} frames ({:.2} ms per face, last took {} frames)",
+                state.probe_bake.frames_per_refresh(total_faces),
+                state.probe_bake.face_cost_ms(),
+                state.probe_bake.last_refresh_frames,
+            ));
+            ui.add_enabled_ui(state.capabilities.compute_shaders, |ui| {
+                ui.horizontal(|ui| {
+                    let running = state
+                        .probe_convergence
+                        .as_ref()
+                        .is_some_and(ConvergenceRun::is_running);
+                    if ui
+                        .add_enabled(!running, egui::Button::new("Bake until converged"))
+                        .clicked()
+                    {
+                        state.converge_reflection_probes = true;
+                    }
+                    ui.add(
+                        DragValue::new(&mut state.probe_convergence_threshold)
+                            .range(1e-5..=0.1)
+                            .speed(1e-4),
+                    )
+                    .on_hover_text(AppState::setting_help("probe_convergence_threshold"));
+                });
+                ui.add(Checkbox::new(&mut state.async_compute, "Async GI compute"))
+                    .on_hover_text(AppState::setting_help("async_compute"));
+            });
+            if let Some(timings) = state.pass_timings {
+                ui.label(format!(
+                    "GI compute {:.2} ms, raster {:.2} ms, overlap {:.2} ms",
+                    timings.compute_ms, timings.raster_ms, timings.overlap_ms
+                ));
+            }
+            if let Some(run) = &state.probe_convergence {
+                let change = run
+                    .last_change
+                    .map_or("-".to_owned(), |change| format!("{:.3}%", change * 100.0));
+                ui.label(match run.elapsed_ms {
+                    None => format!("Bounce {}, last change {}", run.refreshes + 1, change),
+                    Some(ms) if run.converged => format!(
+                        "Converged after {} bounces, {} frames / {:.0} ms",
+                        run.refreshes, run.frames, ms
+                    ),
+                    Some(_) => format!(
+                        "Not converged after {} bounces, last change {}",
+                        run.refreshes, change
+                    ),
+                });
+            }
+            ui.add_enabled_ui(state.capabilities.compute_shaders, |ui| {
+                let previous_storage = state.probe_storage;
+                egui::ComboBox::from_label("Storage")
+                    .selected_text(
+                        state
+                            .probe_storage
+                            .map_or("As rendered", ProbeStorage::name),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut state.probe_storage, None, "As rendered");
+                        for storage in ProbeStorage::ALL {
+                            ui.selectable_value(
+                                &mut state.probe_storage,
+                                Some(storage),
+                                storage.name(),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text(AppState::setting_help("probe_storage"));
+                state.probe_storage_changed = state.probe_storage != previous_storage;
+                if ui
+                    .add_enabled(total_faces > 0, egui::Button::new("Compare formats..."))
+                    .clicked()
+                {
+                    state.compare_probe_storage = rfd::FileDialog::new()
+                        .set_title("Probe Storage Comparison")
+                        .add_filter("PNG", &["png"])
+                        .set_file_name("probe_storage.png")
+                        .save_file();
+                }
+            });
+            if !state.probe_storage_comparison.is_empty() {
+                egui::Grid::new("Probe Storage Comparison").show(ui, |ui| {
+                    for comparison in &state.probe_storage_comparison {
+                        ui.label(format!("{:?}", comparison.format));
+                        ui.label(format!("{:.1} MiB", comparison.mib));
+                        ui.label(match comparison.psnr_db {
+                            None => "reference".to_owned(),
+                            Some(psnr) if psnr.is_infinite() => "lossless".to_owned(),
+                            Some(psnr) => format!("PSNR {:.1} dB", psnr),
+                        });
+                        ui.end_row();
+                    }
+                });
+            }
+            ui.separator();
+            let probes = &mut state.scene_file.reflection_probes;
+            let mut removed = None;
+            for (i, probe) in probes.iter_mut().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Probe {}", i));
+                        if ui.button("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                    for (label, value) in [
+                        ("Position", &mut probe.position),
+                        ("Box min", &mut probe.box_min),
+                        ("Box max", &mut probe.box_max),
+                    ] {
+                        ui.horizontal(|ui| {
+                            ui.label(label);
+                            for v in value.iter_mut() {
+                                changed |= ui.add(DragValue::new(v).speed(0.05)).changed();
+                            }
+                        });
+                    }
+                });
+            }
+            if let Some(i) = removed {
+                probes.remove(i);
+                changed = true;
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                let probes = &mut state.scene_file.reflection_probes;
+                let can_add = probes.len() < MAX_REFLECTION_PROBES;
+                if ui
+                    .add_enabled(can_add, egui::Button::new("Add at camera"))
+                    .clicked()
+                {
+                    probes.push(ReflectionProbe::around(state.camera.position, 5.0));
+                    changed = true;
+                }
+                if ui.button("Rebake probes").clicked() {
+                    state.bake_reflection_probes = true;
+                    state.announce_probe_bake = true;
+                }
+                if ui
+                    .add_enabled(state.scene_path.is_some(), egui::Button::new("Save"))
+                    .on_hover_text("Store the probes in the scene file")
+                    .clicked()
+                {
+                    state.save_scene_file();
+                }
+                let can_cache = state.scene_path.is_some() && !state.probe_bake.is_pending();
+                if ui
+                    .add_enabled(can_cache, egui::Button::new("Save cache"))
+                    .on_hover_text("Store the baked probes so the scene opens without baking")
+                    .clicked()
+                {
+                    state.save_probe_cache = true;
+                }
+                if ui
+                    .add_enabled(can_cache, egui::Button::new("Export..."))
+                    .on_hover_text("Write the baked probes as JSON and binary for other engines")
+                    .clicked()
+                {
+                    state.export_probes = rfd::FileDialog::new()
+                        .set_title("Export Reflection Probes")
+                        .add_filter("JSON", &["json"])
+                        .set_file_name("probes.json")
+                        .save_file();
+                }
+            });
+            state.reflection_probes_changed |= changed;
+        });
+}
+
+fn menu_bar_show(state: &mut AppState, renderer: &EguiRenderer) {
+    egui::TopBottomPanel::top("Menu Bar").show(renderer.context(), |ui| {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                if ui.button("Open...").clicked() {
+                    ui.close_menu();
+                    let picked = rfd::FileDialog::new()
+                        .set_title("Open Scene")
+                        .add_filter("Scenes", &["obj", "gltf", "glb"])
+                        .set_directory(resource_path(""))
+                        .pick_file();
+                    if picked.is_some() {
+                        state.requested_scene = picked;
+                    }
+                }
+            });
+            ui.menu_button("Panels", |ui| {
+                for panel in Panel::ALL {
+                    if ui.button(panel.name()).clicked() {
+                        ui.close_menu();
+                        state.dock_layout.open(panel);
+                    }
+                }
+                ui.separator();
+                if ui
+                    .button("Hide UI (F1)")
+                    .on_hover_text(AppState::setting_help("ui_hidden"))
+                    .clicked()
+                {
+                    ui.close_menu();
+                    state.ui_hidden = true;
+                }
+                ui.add(Checkbox::new(&mut state.clean_capture, "Clean capture"))
+                    .on_hover_text(AppState::setting_help("clean_capture"));
+                if ui
+                    .button("Reset layout")
+                    .on_hover_text(AppState::setting_help("dock_layout"))
+                    .clicked()
+                {
+                    ui.close_menu();
+                    state.dock_layout = DockLayout::default();
+                }
+            });
+            ui.menu_button("Debug", |ui| {
+                let previous_channel = state.channel_view;
+                ui.label("Channel")
+                    .on_hover_text(AppState::setting_help("channel_view"));
+                for channel in ChannelView::ALL {
+                    ui.radio_value(&mut state.channel_view, channel, channel.name());
+                }
+                state.channel_view_changed |= state.channel_view != previous_channel;
+                ui.add_enabled_ui(state.channel_view == ChannelView::Matcap, |ui| {
+                    let previous_matcap = state.matcap.clone();
+                    ui.menu_button(format!("Matcap: {}", state.matcap.name()), |ui| {
+                        for matcap in Matcap::PRESETS {
+                            let name = matcap.name().to_owned();
+                            ui.radio_value(&mut state.matcap, matcap, name);
+                        }
+                        if ui.button("Open image...").clicked() {
+                            ui.close_menu();
+                            let picked = rfd::FileDialog::new()
+                                .set_title("Open Matcap")
+                                .add_filter("Images", &["png", "jpg", "jpeg"])
+                                .pick_file();
+                            if let Some(path) = picked {
+                                state.matcap = Matcap::File(path);
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text(AppState::setting_help("matcap"));
+                    state.matcap_changed |= state.matcap != previous_matcap;
+                });
+                ui.separator();
+                ui.label("Heatmap")
+                    .on_hover_text(AppState::setting_help("heatmap"));
+                for heatmap in Heatmap::ALL {
+                    let supported =
+                        heatmap != Heatmap::QuadUsage || state.capabilities.fragment_storage;
+                    ui.add_enabled_ui(supported, |ui| {
+                        ui.radio_value(&mut state.heatmap, heatmap, heatmap.name())
+                            .on_disabled_hover_text(
+                                "The GPU cannot write storage buffers from fragment shaders",
+                            );
+                    });
+                }
+                ui.separator();
+                let views = &mut state.debug_views;
+                ui.add(Checkbox::new(&mut views.bounding_boxes, "Bounding boxes"))
+                    .on_hover_text(AppState::setting_help("debug_views.bounding_boxes"));
+                ui.separator();
+                ui.add(Checkbox::new(&mut views.normals, "Normals"))
+                    .on_hover_text(AppState::setting_help("debug_views.normals"));
+                ui.add(Checkbox::new(&mut views.tangents, "Tangents"))
+                    .on_hover_text(AppState::setting_help("debug_views.tangents"));
+                ui.add(Checkbox::new(&mut views.bitangents, "Bitangents"))
+                    .on_hover_text(AppState::setting_help("debug_views.bitangents"));
+                ui.add_enabled(
+                    views.vectors(),
+                    Slider::new(&mut views.vector_length, 0.001..=10.0)
+                        .logarithmic(true)
+                        .text("Length"),
+                )
+                .on_hover_text(AppState::setting_help("debug_views.vector_length"));
+                ui.separator();
+                if ui
+                    .button("Log")
+                    .on_hover_text(AppState::setting_help("log_view"))
+                    .clicked()
+                {
+                    ui.close_menu();
+                    state.dock_layout.open(Panel::Log);
+                }
+                if ui
+                    .button("Capture GPU frame (F11)")
+                    .on_hover_text(AppState::setting_help("gpu_capture_requested"))
+                    .clicked()
+                {
+                    ui.close_menu();
+                    state.gpu_capture_requested = true;
+                }
+                if ui
+                    .button("Reload shaders")
+                    .on_hover_text(AppState::setting_help("shader_reload_requested"))
+                    .clicked()
+                {
+                    ui.close_menu();
+                    state.shader_reload_requested = true;
+                }
+                ui.add(Checkbox::new(&mut state.watch_shaders, "Reload on change"))
+                    .on_hover_text(AppState::setting_help("watch_shaders"));
+            });
+            ui.menu_button("Help", |ui| {
+                if ui.button("Key bindings and settings (F2)").clicked() {
+                    ui.close_menu();
+                    state.show_help = true;
+                }
+            });
         });
+    });
 }
@@ -1,10 +1,29 @@
+use super::bindings;
 use super::egui_tools::EguiRenderer;
-use crate::camera::UniformCamera;
-use crate::primitives::UniformLight;
+use crate::atlas::AtlasOptions;
+use crate::benchmark::{Benchmark, BenchmarkPath, BenchmarkSettings};
+use crate::camera;
+use crate::capabilities::Capabilities;
+use crate::error::{Error, Result};
+use crate::flythrough::PathCapture;
+use crate::input::Action;
+use crate::loader::{LoadOptions, SceneLoader};
+use crate::logging;
+use crate::render_settings::RenderSettings;
 use crate::renderer::DefaultRenderer;
-use crate::{widget, AppState, RenderStage};
+use crate::simulation::{self, Simulation};
+use crate::stages::SharedRenderer;
+use crate::streaming::{ChunkEvent, ChunkStreamer};
+use crate::sweep::{Sweep, SweepSettings};
+use crate::turntable::Turntable;
+use crate::watchdog::Watchdog;
+use crate::{widget, AppState, ComputeStage, RenderStage};
 use egui_wgpu::{wgpu, ScreenDescriptor};
-use glam::Vec3;
+use glam::Vec2;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use winit::application::ApplicationHandler;
 use winit::dpi::PhysicalSize;
@@ -13,16 +32,38 @@ use winit::event::{
     WindowEvent,
 };
 use winit::event_loop::ActiveEventLoop;
-use winit::window::{Window, WindowId};
+use winit::window::{CursorGrabMode, Window, WindowId};
 
 pub struct AppInternal {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface_config: wgpu::SurfaceConfiguration,
     pub surface: wgpu::Surface<'static>,
-    pub renderer: DefaultRenderer,
+    /// Loads and picks, its passes are recorded by stages, see `stages`
+    pub renderer: SharedRenderer,
+    /// In the order of `AppState::render_stages`
+    stages: Vec<Box<dyn RenderStage<AppState>>>,
+    /// Recorded before the stages every frame
+    compute_stages: Vec<Box<dyn ComputeStage<AppState>>>,
     pub egui_renderer: EguiRenderer,
     pub app_state: AppState,
+    scene_loader: Option<SceneLoader>,
+    load_options: LoadOptions,
+    /// Chunks of the current scene file
+    chunk_streamer: ChunkStreamer,
+    /// The window has no area, the surface keeps its last configuration
+    minimized: bool,
+    /// Set by the driver, every GPU resource has to be recreated
+    device_lost: Arc<AtomicBool>,
+    /// Quit once the sweep given with `--sweep` ended
+    exit_after_sweep: bool,
+    /// Quit once the benchmark given with `--benchmark` ended
+    exit_after_benchmark: bool,
+    /// Steps what moves on its own at a fixed rate
+    simulation: Simulation,
+    /// Written back with the panel layout on close, `None` when the file could
+    /// not be read and is left as it is
+    render_settings: Option<RenderSettings>,
 }
 
 impl AppInternal {
@@ -32,36 +73,59 @@ impl AppInternal {
         window: &Window,
         width: u32,
         height: u32,
-    ) -> Self {
+    ) -> Result<Self> {
+        let args: Vec<_> = std::env::args().skip(1).collect();
+        // Software adapters (lavapipe, WARP) let machines without a GPU run the app
+        let force_fallback_adapter = args.iter().any(|arg| arg == "--force-fallback-adapter");
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
                 compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+                force_fallback_adapter,
             })
             .await
-            .unwrap();
+            .ok_or(if force_fallback_adapter {
+                Error::NoFallbackAdapter
+            } else {
+                Error::NoAdapter
+            })?;
+        if force_fallback_adapter {
+            tracing::info!(
+                target: logging::GPU,
+                "using fallback adapter {}",
+                adapter.get_info().name
+            );
+        }
+        let capabilities = Capabilities::query(&adapter);
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web, we'll have to disable some.
-                    required_limits: wgpu::Limits::default(),
+                    // Only what the adapter has, subsystems check `capabilities` for fallbacks
+                    required_features: capabilities.features(),
+                    required_limits: capabilities.limits.clone(),
                     label: None,
                     memory_hints: Default::default(),
                 },
                 None, // Trace path
             )
-            .await
-            .unwrap();
+            .await?;
+        logging::log_uncaptured_errors(&device);
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let lost = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Dropping the device reports a loss as well, e.g. when recovering
+            if reason == wgpu::DeviceLostReason::Unknown {
+                tracing::error!(target: logging::GPU, "device lost: {}", message);
+                lost.store(true, Ordering::Release);
+            }
+        });
         let swapchain_capabilities = surface.get_capabilities(&adapter);
         let selected_format = wgpu::TextureFormat::Bgra8UnormSrgb;
         let swapchain_format = swapchain_capabilities
             .formats
             .iter()
             .find(|d| **d == selected_format)
-            .expect("failed to select proper surface texture format!");
+            .ok_or(Error::SurfaceFormat(selected_format))?;
 
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -77,89 +141,895 @@ impl AppInternal {
         surface.configure(&device, &surface_config);
 
         let mut app_state = AppState::new();
+        app_state.capabilities = capabilities;
         app_state
             .projection
             .resize(surface_config.width, surface_config.height);
         let egui_renderer = EguiRenderer::new(&device, surface_config.format, None, 1, window);
-        let args: Vec<_> = std::env::args().collect();
+        let render_settings = RenderSettings::load()
+            .inspect_err(|err| {
+                tracing::error!(
+                    target: logging::APP,
+                    "failed to read {}: {}",
+                    RenderSettings::path().display(),
+                    err
+                )
+            })
+            .ok();
         let renderer = DefaultRenderer::new(
             &device,
             &surface_config,
             &queue,
             &mut app_state,
-            args.get(1).unwrap_or(&"cube/cube.obj".to_owned()),
-        );
+            render_settings
+                .as_ref()
+                .unwrap_or(&RenderSettings::default())
+                .resolve(&adapter),
+        )?;
+        if let Some(layout) = render_settings
+            .as_ref()
+            .and_then(|settings| settings.layout.clone())
+        {
+            app_state.dock_layout = layout;
+        }
+        let load_options = LoadOptions {
+            texture_atlas: args
+                .iter()
+                .any(|arg| arg == "--atlas")
+                .then(AtlasOptions::default),
+            ..Default::default()
+        };
+        app_state.deterministic = args.iter().any(|arg| arg == "--deterministic");
+        let exit_after_sweep = match args.iter().find_map(|arg| arg.strip_prefix("--sweep=")) {
+            Some(axes) => {
+                app_state.sweep_settings = SweepSettings::parse(axes).map_err(Error::Argument)?;
+                let output = args
+                    .iter()
+                    .find_map(|arg| arg.strip_prefix("--sweep-output="))
+                    .unwrap_or("sweep.png");
+                app_state.start_sweep = Some(PathBuf::from(output));
+                true
+            }
+            None => false,
+        };
+        let exit_after_benchmark =
+            match args.iter().find_map(|arg| arg.strip_prefix("--benchmark=")) {
+                Some(frames) => {
+                    let output = args
+                        .iter()
+                        .find_map(|arg| arg.strip_prefix("--benchmark-output="))
+                        .unwrap_or("benchmark.csv");
+                    app_state.start_benchmark = Some(
+                        BenchmarkSettings::parse(frames, output.as_ref())
+                            .map_err(Error::Argument)?,
+                    );
+                    true
+                }
+                None => false,
+            };
+        let scene_path = args
+            .iter()
+            .find(|arg| !arg.starts_with("--"))
+            .map_or("cube/cube.obj", String::as_str);
 
-        Self {
+        let mut app_internal = Self {
             device,
             queue,
             surface,
             surface_config,
             egui_renderer,
-            renderer,
+            renderer: Rc::new(RefCell::new(renderer)),
+            stages: Vec::new(),
+            compute_stages: Vec::new(),
             app_state,
+            scene_loader: None,
+            load_options,
+            chunk_streamer: ChunkStreamer::default(),
+            minimized: false,
+            device_lost,
+            exit_after_sweep,
+            exit_after_benchmark,
+            simulation: Simulation::default(),
+            render_settings,
+        };
+        app_internal.create_stages();
+        app_internal.load_scene(scene_path);
+        Ok(app_internal)
+    }
+
+    /// Keep the arrangement of the panels for the next start.
+    fn save_layout(&mut self) {
+        let Some(settings) = &mut self.render_settings else {
+            return;
+        };
+        settings.layout = Some(self.app_state.dock_layout.clone());
+        if let Err(err) = settings.save() {
+            tracing::error!(
+                target: logging::APP,
+                "failed to write {}: {}",
+                RenderSettings::path().display(),
+                err
+            );
+        }
+    }
+
+    /// Whether the cursor is over a panel or window rather than the scene, or
+    /// drags a widget.
+    fn pointer_over_ui(&self) -> bool {
+        let context = self.egui_renderer.context();
+        let state = &self.app_state;
+        state.ui_visible()
+            && !state.mouse_captured
+            && (context.is_using_pointer()
+                || context
+                    .input(|input| input.pointer.latest_pos())
+                    .is_some_and(|position| !widget::over_viewport(state, context, position)))
+    }
+
+    /// Whether a text field of the UI has the keyboard focus.
+    fn keyboard_over_ui(&self) -> bool {
+        self.app_state.ui_visible() && self.egui_renderer.context().wants_keyboard_input()
+    }
+
+    /// Orbit the camera around the configured pivot, writing a frame into `output` each time.
+    fn start_turntable(&mut self, output: PathBuf) {
+        let settings = &self.app_state.turntable_settings;
+        let Some(pivot) = self
+            .renderer
+            .borrow()
+            .pivot(settings.pivot_object.as_deref())
+        else {
+            self.app_state
+                .notifications
+                .error("Nothing to orbit around, load a scene first");
+            return;
+        };
+        if let Err(err) = std::fs::create_dir_all(&output) {
+            self.app_state.notifications.error(format!(
+                "Failed to create {}: {}",
+                output.display(),
+                err
+            ));
+            return;
+        }
+        tracing::info!(
+            target: logging::APP,
+            "capturing a {} frame turntable into {}",
+            settings.frames,
+            output.display()
+        );
+        self.app_state.turntable = Some(Turntable::new(
+            settings,
+            pivot,
+            &self.app_state.camera,
+            &output,
+        ));
+    }
+
+    /// Write the frame just submitted and move the turntable on, restoring the
+    /// camera once the revolution is complete or the capture failed.
+    fn capture_turntable_frame(&mut self) {
+        let Some(turntable) = &mut self.app_state.turntable else {
+            return;
+        };
+        let path = turntable.frame_path();
+        let result = self
+            .renderer
+            .borrow_mut()
+            .capture(&self.device, &self.queue, &self.surface_config)
+            .and_then(|image| Ok(image.save(&path)?));
+        let done = match result {
+            Ok(()) => !turntable.advance(),
+            Err(err) => {
+                self.app_state.notifications.error(format!(
+                    "Failed to write {}: {}",
+                    path.display(),
+                    err
+                ));
+                true
+            }
+        };
+        if done || std::mem::take(&mut self.app_state.stop_turntable) {
+            let turntable = self.app_state.turntable.take().unwrap();
+            self.app_state.notifications.info(format!(
+                "Wrote {} turntable frames to {}",
+                turntable.frame,
+                turntable.output().display()
+            ));
+            self.app_state.camera = turntable.into_camera();
+        }
+    }
+
+    /// Follow the camera path with a fixed frame rate, writing a frame into `output` each time.
+    fn start_path_capture(&mut self, output: PathBuf) {
+        let path = &self.app_state.scene_file.camera_path;
+        if path.keyframes.is_empty() {
+            self.app_state
+                .notifications
+                .error("Nothing to capture, record camera path keyframes first");
+            return;
+        }
+        if let Err(err) = std::fs::create_dir_all(&output) {
+            self.app_state.notifications.error(format!(
+                "Failed to create {}: {}",
+                output.display(),
+                err
+            ));
+            return;
+        }
+        let capture = PathCapture::new(
+            path,
+            self.app_state.path_playback.capture_fps,
+            &self.app_state.camera,
+            &output,
+        );
+        tracing::info!(
+            target: logging::APP,
+            "capturing {} camera path frames into {}",
+            capture.frames,
+            output.display()
+        );
+        self.app_state.path_capture = Some(capture);
+    }
+
+    /// Write the frame just submitted and move the path capture on, restoring the
+    /// camera once the path is complete or the capture failed.
+    fn capture_path_frame(&mut self) {
+        let Some(capture) = &mut self.app_state.path_capture else {
+            return;
+        };
+        let path = capture.frame_path();
+        let result = self
+            .renderer
+            .borrow_mut()
+            .capture(&self.device, &self.queue, &self.surface_config)
+            .and_then(|image| Ok(image.save(&path)?));
+        let done = match result {
+            Ok(()) => !capture.advance(),
+            Err(err) => {
+                self.app_state.notifications.error(format!(
+                    "Failed to write {}: {}",
+                    path.display(),
+                    err
+                ));
+                true
+            }
+        };
+        if done || std::mem::take(&mut self.app_state.stop_path_capture) {
+            let capture = self.app_state.path_capture.take().unwrap();
+            self.app_state.notifications.info(format!(
+                "Wrote {} camera path frames to {}",
+                capture.frame,
+                capture.output().display()
+            ));
+            self.app_state.path_playback.following = false;
+            self.app_state.camera = capture.into_camera();
+        }
+    }
+
+    /// Capture the view once per combination of the swept settings, composed into
+    /// a contact sheet at `output`.
+    fn start_sweep(&mut self, output: PathBuf) {
+        let settings = self.app_state.sweep_settings;
+        if settings.rows == settings.columns {
+            self.app_state
+                .notifications
+                .error("Pick two different settings to sweep");
+            return;
+        }
+        let unsupported = [settings.rows, settings.columns]
+            .into_iter()
+            .find(|axis| !axis.is_supported(&self.app_state.capabilities));
+        if let Some(axis) = unsupported {
+            self.app_state.notifications.error(format!(
+                "Cannot sweep {}, the adapter has no compute shaders",
+                axis.name()
+            ));
+            return;
+        }
+        let sweep = Sweep::new(settings, &mut self.app_state, &output);
+        tracing::info!(
+            target: logging::APP,
+            "capturing a {} cell settings sweep into {}",
+            sweep.cell_count(),
+            output.display()
+        );
+        self.app_state.sweep = Some(sweep);
+    }
+
+    /// Capture the current cell of the sweep once it settled and move on, writing
+    /// the contact sheet and restoring the settings once every cell is done.
+    fn capture_sweep_frame(&mut self) {
+        let Some(mut sweep) = self.app_state.sweep.take() else {
+            return;
+        };
+        let stop = std::mem::take(&mut self.app_state.stop_sweep);
+        if !stop && !sweep.frame_rendered(&self.app_state) {
+            self.app_state.sweep = Some(sweep);
+            return;
+        }
+        let more = !stop
+            && match self.renderer.borrow_mut().capture(
+                &self.device,
+                &self.queue,
+                &self.surface_config,
+            ) {
+                Ok(image) => sweep.capture(image, &mut self.app_state),
+                Err(err) => {
+                    self.app_state
+                        .notifications
+                        .error(format!("Failed to capture a sweep cell: {}", err));
+                    false
+                }
+            };
+        if more {
+            self.app_state.sweep = Some(sweep);
+            return;
+        }
+        let output = sweep.output().to_owned();
+        match sweep.finish(&mut self.app_state) {
+            Ok(0) => {}
+            Ok(cells) => self.app_state.notifications.info(format!(
+                "Wrote a {} cell settings sweep to {}",
+                cells,
+                output.display()
+            )),
+            Err(err) => self.app_state.notifications.error(format!(
+                "Failed to write {}: {}",
+                output.display(),
+                err
+            )),
+        }
+    }
+
+    /// Play the camera path of the scene file, or orbit the scene without one,
+    /// recording the frame times into a report.
+    fn start_benchmark(&mut self, settings: BenchmarkSettings) {
+        let Some(pivot) = self.renderer.borrow().pivot(None) else {
+            self.app_state
+                .notifications
+                .error("Nothing to benchmark, load a scene first");
+            return;
+        };
+        if !self.app_state.capabilities.encoder_timestamps {
+            tracing::warn!(
+                target: logging::APP,
+                "{} cannot write timestamps between passes, the benchmark only measures \
+                 CPU times",
+                self.app_state.capabilities.adapter_name
+            );
+        }
+        let path = BenchmarkPath::new(
+            &self.app_state.scene_file.camera_path,
+            pivot,
+            &self.app_state.camera,
+            settings.frames,
+        );
+        tracing::info!(
+            target: logging::APP,
+            "benchmarking {} frames along the {} into {}",
+            settings.frames,
+            match path {
+                BenchmarkPath::Camera(_) => "camera path",
+                BenchmarkPath::Orbit(_) => "orbit",
+            },
+            settings.output.display()
+        );
+        self.app_state.benchmark = Some(Benchmark::new(
+            settings,
+            path,
+            self.renderer.borrow().pass_names(),
+            (self.surface_config.width, self.surface_config.height),
+        ));
+    }
+
+    /// Record the frame just submitted, writing the report once every frame is
+    /// recorded. GPU times still being read back are waited for.
+    fn record_benchmark_frame(&mut self, cpu_ms: f32) {
+        let Some(mut benchmark) = self.app_state.benchmark.take() else {
+            return;
+        };
+        if !benchmark.frame_rendered(&self.app_state, cpu_ms) {
+            self.app_state.benchmark = Some(benchmark);
+            return;
+        }
+        self.device.poll(wgpu::Maintain::Wait);
+        self.app_state.benchmark = Some(benchmark);
+        self.renderer
+            .borrow()
+            .poll_pass_profiles(&mut self.app_state);
+        let benchmark = self.app_state.benchmark.take().unwrap();
+        let frames = benchmark.frames();
+        let output = benchmark.output().to_owned();
+        match benchmark.finish(&self.app_state) {
+            Ok(frame_ms) => self.app_state.notifications.info(format!(
+                "Wrote a {} frame benchmark to {}{}",
+                frames,
+                output.display(),
+                frame_ms.map_or(String::new(), |frame_ms| format!(
+                    ", {:.2} ms per frame, {:.2} ms at the 95th percentile",
+                    frame_ms.mean, frame_ms.p95
+                ))
+            )),
+            Err(err) => self.app_state.notifications.error(format!(
+                "Failed to write {}: {}",
+                output.display(),
+                err
+            )),
+        }
+    }
+
+    /// Start loading the scene at `path` in the background, replacing the current one.
+    pub fn load_scene<P: Into<PathBuf>>(&mut self, path: P) {
+        self.spawn_scene_loader(path.into(), false);
+    }
+
+    fn spawn_scene_loader(&mut self, path: PathBuf, recompute_normals: bool) {
+        self.renderer.borrow_mut().clear_scene();
+        self.chunk_streamer = ChunkStreamer::default();
+        let options = LoadOptions {
+            recompute_normals,
+            hard_edge_angle: self
+                .app_state
+                .hard_edges
+                .then_some(self.app_state.hard_edge_angle),
+            ..self.load_options
+        };
+        let scene_loader = SceneLoader::spawn(path, options);
+        self.app_state.loading = Some(scene_loader.progress().clone());
+        self.scene_loader = Some(scene_loader);
+    }
+
+    fn poll_scene_loader(&mut self) {
+        let Some(loader) = self.scene_loader.as_mut() else {
+            return;
+        };
+        // Deterministic runs must not depend on how fast the loader thread is
+        let result = if self.app_state.deterministic {
+            Some(loader.wait())
+        } else {
+            loader.poll()
+        };
+        match result {
+            Some(Ok(scene)) => {
+                self.renderer.borrow_mut().set_scene(
+                    &self.device,
+                    &self.queue,
+                    &mut self.app_state,
+                    scene,
+                );
+                self.chunk_streamer = ChunkStreamer::new(self.app_state.scene_file.chunks.len());
+                self.scene_loader = None;
+                self.app_state.loading = None;
+            }
+            Some(Err(err)) => {
+                tracing::error!(target: logging::SCENE, "failed to load scene: {:?}", err);
+                self.app_state.error = Some(format!("Failed to load the scene: {:#}", err));
+                if self.exit_after_sweep {
+                    self.app_state.start_sweep = None;
+                }
+                if self.exit_after_benchmark {
+                    self.app_state.start_benchmark = None;
+                }
+                self.scene_loader = None;
+                self.app_state.loading = None;
+            }
+            None => self.app_state.loading = Some(loader.progress().clone()),
+        }
+    }
+
+    /// Load the chunks near the camera and drop the ones it moved away from.
+    fn stream_chunks(&mut self) {
+        let events = self.chunk_streamer.update(
+            &self.app_state.scene_file.chunks,
+            self.app_state.camera.position,
+            &self.app_state.streaming,
+            self.load_options,
+            self.app_state.deterministic,
+        );
+        for event in events {
+            match event {
+                ChunkEvent::Loaded(chunk, scene) => self.renderer.borrow_mut().add_chunk(
+                    &self.device,
+                    &self.queue,
+                    &mut self.app_state,
+                    chunk,
+                    *scene,
+                ),
+                ChunkEvent::Unloaded(chunk) => self.renderer.borrow_mut().remove_chunk(
+                    &self.device,
+                    &self.queue,
+                    &mut self.app_state,
+                    chunk,
+                ),
+                ChunkEvent::Failed(chunk, err) => self.app_state.notifications.error(format!(
+                    "Failed to load chunk {}: {:#}",
+                    self.app_state.scene_file.chunks[chunk].path, err
+                )),
+            }
+        }
+        self.app_state.resident_chunks = self.chunk_streamer.resident();
+        self.app_state.loading_chunks = self.chunk_streamer.loading();
+    }
+
+    /// Create the stages of `AppState::render_stages` again.
+    fn create_stages(&mut self) {
+        self.stages = self
+            .app_state
+            .render_stages
+            .iter()
+            .map(|slot| {
+                slot.kind
+                    .create(&self.renderer, &self.device, &self.surface_config)
+            })
+            .collect();
+    }
+
+    fn render_stages(&mut self, view: &wgpu::TextureView, encoder: &mut wgpu::CommandEncoder) {
+        encoder.push_debug_group("compute stages");
+        for stage in &self.compute_stages {
+            stage.record(&mut self.app_state, encoder);
+        }
+        encoder.pop_debug_group();
+        self.renderer.borrow().begin_frame(&mut self.app_state);
+        for (i, stage) in self.stages.iter().enumerate() {
+            let slot = &self.app_state.render_stages[i];
+            if !slot.enabled {
+                continue;
+            }
+            let _span =
+                tracing::error_span!(target: logging::RENDER, "stage", name = slot.kind.name())
+                    .entered();
+            encoder.push_debug_group(&format!("{} stage", slot.kind.name()));
+            stage.render(&mut self.app_state, view, encoder);
+            encoder.pop_debug_group();
         }
+        self.renderer.borrow().end_frame(&self.app_state, encoder);
     }
 
     fn resize_surface(&mut self, width: u32, height: u32) {
+        // Zero sized surfaces cannot be configured, frames are skipped until restored
+        self.minimized = width == 0 || height == 0;
+        if self.minimized {
+            return;
+        }
         self.surface_config.width = width;
         self.surface_config.height = height;
         self.surface.configure(&self.device, &self.surface_config);
         self.app_state.projection.resize(width, height);
-        self.renderer.resize(&self.device, &self.surface_config);
+        self.renderer
+            .borrow_mut()
+            .resize(&self.device, &self.surface_config);
+        for stage in &mut self.stages {
+            stage.resize(&self.device, &self.surface_config);
+        }
+        for stage in &mut self.compute_stages {
+            stage.resize(&self.device, &self.surface_config);
+        }
     }
 
     fn update(&mut self, dt: std::time::Duration) {
+        let dt = if self.app_state.deterministic {
+            // Measured times would make the probe slicing differ between runs
+            self.app_state.frame_time_ms = 0.0;
+            simulation::TIMESTEP
+        } else if self.app_state.benchmark.is_some() {
+            // Every run animates the same frames, however long they took
+            self.app_state.frame_time_ms = dt.as_secs_f32() * 1000.0;
+            simulation::TIMESTEP
+        } else {
+            self.app_state.frame_time_ms = dt.as_secs_f32() * 1000.0;
+            dt
+        };
+        self.app_state.frame_index += 1;
+        self.update_pointer();
+        self.apply_actions();
+        Watchdog::update(&mut self.app_state);
+        if let Some(path) = self.app_state.requested_scene.take() {
+            self.load_scene(path);
+        } else if self.app_state.recompute_normals {
+            if let Some(path) = self.app_state.scene_path.clone() {
+                self.spawn_scene_loader(path, true);
+            }
+        }
+        self.app_state.recompute_normals = false;
+        if let Some(output) = self.app_state.start_turntable.take() {
+            self.start_turntable(output);
+        }
+        if let Some(output) = self.app_state.start_path_capture.take() {
+            self.start_path_capture(output);
+        }
+        // Sweeps and benchmarks wait for the scene, batch runs start right away
+        if self.scene_loader.is_none() {
+            if let Some(output) = self.app_state.start_sweep.take() {
+                self.start_sweep(output);
+            }
+            if let Some(settings) = self.app_state.start_benchmark.take() {
+                self.start_benchmark(settings);
+            }
+        }
+        self.poll_scene_loader();
+        self.renderer
+            .borrow()
+            .poll_frame_stats(&self.device, &mut self.app_state);
+        self.renderer
+            .borrow()
+            .poll_probe_convergence(&mut self.app_state);
+        self.renderer
+            .borrow()
+            .poll_pass_timings(&mut self.app_state);
+        self.renderer
+            .borrow()
+            .poll_pass_profiles(&mut self.app_state);
+        if self.app_state.save_probe_cache {
+            self.app_state.save_probe_cache = false;
+            self.renderer
+                .borrow()
+                .save_probe_cache(&self.device, &self.queue, &self.app_state);
+        }
+        if let Some(path) = self.app_state.export_probes.take() {
+            self.renderer
+                .borrow()
+                .export_probes(&self.device, &self.queue, &self.app_state, &path);
+        }
+        if let Some(path) = self.app_state.capture_panorama.take() {
+            self.renderer.borrow().capture_panorama(
+                &self.device,
+                &self.queue,
+                &self.app_state,
+                &path,
+            );
+        }
+        if std::mem::take(&mut self.app_state.probe_storage_changed) {
+            self.renderer
+                .borrow_mut()
+                .set_probe_storage(&self.device, &mut self.app_state);
+        }
+        self.renderer
+            .borrow_mut()
+            .poll_hovered_object(&self.device, &mut self.app_state);
+        let state = &mut self.app_state;
+        // Nothing flickers while the camera is dragged or flown
+        state.hover_at = state.input.pointer().filter(|_| {
+            state.hover_highlight && !state.mouse_captured && !state.input.held(Action::Look)
+        });
+        if let Some(ndc) = self.app_state.pick_triangle_at.take() {
+            self.renderer.borrow_mut().pick_triangle(
+                &self.device,
+                &self.queue,
+                &mut self.app_state,
+                ndc,
+            );
+        } else if self.app_state.picked_triangle.is_none() {
+            self.renderer.borrow_mut().clear_picked_triangle();
+        }
+        if std::mem::take(&mut self.app_state.wireframe_object_changed) {
+            self.renderer
+                .borrow_mut()
+                .set_wireframe_object(&self.device, self.app_state.wireframe_object.as_deref());
+        }
+        if let Some(path) = self.app_state.compare_probe_storage.take() {
+            self.renderer.borrow_mut().compare_probe_storage(
+                &self.device,
+                &self.queue,
+                &mut self.app_state,
+                &path,
+            );
+        }
+        self.simulation.advance(&mut self.app_state, dt);
+        // Captures step by frames, not by the simulated time
+        if let Some(turntable) = &self.app_state.turntable {
+            turntable.place_camera(&mut self.app_state.camera);
+        }
+        if let Some(capture) = &self.app_state.path_capture {
+            let playback = &mut self.app_state.path_playback;
+            playback.time = capture.time();
+            playback.following = true;
+            self.app_state
+                .scene_file
+                .camera_path
+                .place_camera(playback.time, &mut self.app_state.camera);
+        }
+        if let Some(benchmark) = &mut self.app_state.benchmark {
+            benchmark.place_camera(&mut self.app_state.camera);
+        }
+        self.stream_chunks();
+        self.app_state.scene_bounds = self.renderer.borrow().bounds(None);
+        let clip_planes = &mut self.app_state.clip_planes;
+        if let Some((min, max)) = self.app_state.scene_bounds.filter(|_| clip_planes.auto) {
+            (clip_planes.znear, clip_planes.zfar) =
+                camera::ClipPlanes::fit(self.app_state.camera.position, min, max);
+        }
         self.app_state
-            .camera_controller
-            .update_camera(&mut self.app_state.camera, dt);
-        self.queue.write_buffer(
-            &self.renderer.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[UniformCamera::from_camera_project(
-                &self.app_state.camera,
-                &self.app_state.projection,
-            )]),
+            .projection
+            .set_planes(clip_planes.znear, clip_planes.zfar);
+        if let Some(view) = self.renderer.borrow_mut().prepare_roi(
+            &self.device,
+            &self.app_state,
+            &self.surface_config,
+        ) {
+            let id = self.app_state.roi_texture.map(|(id, _)| id);
+            let id = self.egui_renderer.register_texture(&self.device, view, id);
+            let (width, height) = self
+                .app_state
+                .roi
+                .extent(self.surface_config.width, self.surface_config.height);
+            self.app_state.roi_texture = Some((id, [width, height]));
+        }
+        self.renderer.borrow_mut().prepare_render_scale(
+            &self.device,
+            &self.app_state,
+            &self.surface_config,
         );
-        self.queue.write_buffer(
-            &self.renderer.light_buffer,
-            0,
-            bytemuck::cast_slice(&[Into::<UniformLight>::into(Vec3::from(
-                self.app_state.light_position,
-            ))]),
+        self.renderer
+            .borrow_mut()
+            .prepare_post(&self.device, &self.queue, &self.app_state);
+        self.renderer
+            .borrow_mut()
+            .prepare_shaders(&self.device, &mut self.app_state);
+        self.renderer.borrow_mut().prepare_debug_draw(
+            &self.device,
+            &self.queue,
+            &mut self.app_state,
         );
-        self.renderer.update(&self.app_state, &self.queue);
+        self.renderer.borrow_mut().prepare_split_view(
+            &self.device,
+            &self.app_state,
+            &self.surface_config,
+        );
+        // The views borrow the renderer until the loop ends
+        for (i, view, extent) in self.renderer.borrow_mut().prepare_previews(
+            &self.device,
+            &self.app_state,
+            &self.surface_config,
+        ) {
+            let textures = &mut self.app_state.preview_textures;
+            let id = textures.get(i).map(|(id, _)| *id);
+            let id = self.egui_renderer.register_texture(&self.device, view, id);
+            if i < textures.len() {
+                textures[i] = (id, extent);
+            } else {
+                textures.push((id, extent));
+            }
+        }
+        let textures = &mut self.app_state.preview_textures;
+        while textures.len() > self.app_state.texture_previews.len() {
+            if let Some((id, _)) = textures.pop() {
+                self.egui_renderer.free_texture(id);
+            }
+        }
+        if !self.app_state.roi.enabled {
+            if let Some((id, _)) = self.app_state.roi_texture.take() {
+                self.egui_renderer.free_texture(id);
+            }
+        }
+        if std::mem::take(&mut self.app_state.render_stages_changed) {
+            self.create_stages();
+        }
+        self.renderer
+            .borrow_mut()
+            .update(&self.app_state, &self.queue);
+        for stage in &mut self.stages {
+            stage.update(&self.app_state, &self.queue);
+        }
+        for stage in &mut self.compute_stages {
+            stage.update(&self.app_state, &self.queue);
+        }
+        // Widgets flag their edits every frame, changes made elsewhere such as by
+        // a sweep are picked up once even while the widget's window is collapsed
+        self.app_state.material_override_changed = false;
+        self.app_state.channel_view_changed = false;
+        self.app_state.matcap_changed = false;
+        self.app_state.color_lut_changed = false;
+        self.app_state.render_scale_changed = false;
+        self.app_state.animation_time_changed = false;
+        self.app_state.normal_map_changed = false;
+        self.app_state.reflection_probes_changed = false;
+        self.app_state.object_classes_changed = false;
+        self.app_state.sky_changed = false;
+        self.app_state.split_view_changed = false;
+        self.app_state.nan_guard_changed = false;
+        self.app_state.depth_contours_changed = false;
+        self.app_state.input.end_frame();
+    }
+
+    /// Pointer of the view this frame, `None` while it is over the UI.
+    fn update_pointer(&mut self) {
+        let context = self.egui_renderer.context();
+        let pointer = context
+            .input(|input| input.pointer.hover_pos())
+            .filter(|&position| widget::over_viewport(&self.app_state, context, position))
+            .map(|position| screen_to_ndc(context.screen_rect(), position));
+        self.app_state.input.set_pointer(pointer);
+    }
+
+    /// React to the actions pressed since the last frame.
+    fn apply_actions(&mut self) {
+        let state = &mut self.app_state;
+        let input = &state.input;
+        if input.pressed(Action::ToggleUi) {
+            state.ui_hidden = !state.ui_hidden;
+        }
+        if input.pressed(Action::ToggleHelp) {
+            state.show_help = !state.show_help;
+        }
+        if input.pressed(Action::CaptureGpuFrame) {
+            state.gpu_capture_requested = true;
+        }
+        if input.pressed(Action::ToggleMouseLook) {
+            state.mouse_captured = !state.mouse_captured;
+        }
+        if input.pressed(Action::ReleaseMouseLook) {
+            state.mouse_captured = false;
+        }
+        if input.pressed(Action::TogglePause) {
+            state.paused = !state.paused;
+        }
+        if input.pressed(Action::StepFrame) {
+            state.paused = true;
+            state.step_frame = true;
+        }
+        if input.pressed(Action::Pick) {
+            // A captured cursor is hidden, pick what is in the center of the view
+            state.pick_triangle_at = if state.mouse_captured {
+                Some(Vec2::ZERO)
+            } else {
+                input.pointer()
+            };
+        }
+        if let Some(index) = input.pressed_bookmark() {
+            state.restore_bookmark(index);
+        }
     }
 
     fn keyboard_input(&mut self, event: &KeyEvent) -> bool {
-        self.app_state.camera_controller.process_keyboard(
-            &event.physical_key,
-            &event.logical_key,
-            event.state,
-        );
+        let Some(action) = bindings::key_action(&event.physical_key, &event.logical_key) else {
+            return false;
+        };
+        if !event.state.is_pressed() {
+            self.app_state.input.release(action);
+            return true;
+        }
+        // Typing into a text field moves nothing, Tab moves between the fields
+        if !action.global() && self.keyboard_over_ui() {
+            return false;
+        }
+        self.app_state.input.press(action);
         true
     }
 
     fn mouse_click(&mut self, state: ElementState, button: MouseButton) -> bool {
-        if button == MouseButton::Left {
-            self.app_state.mouse_pressed = state == ElementState::Pressed;
-            true
-        } else {
-            false
+        let Some(action) = bindings::button_action(button) else {
+            return false;
+        };
+        if !state.is_pressed() {
+            self.app_state.input.release(action);
+            return true;
+        }
+        // Clicks on the UI are left to egui unless the cursor is captured
+        if self.pointer_over_ui() {
+            return false;
         }
+        self.app_state.input.press(action);
+        true
     }
 
     fn mouse_wheel(&mut self, delta: MouseScrollDelta, _phase: TouchPhase) -> bool {
-        self.app_state.camera_controller.process_scroll(&delta);
+        // The panels scroll their own content
+        if self.pointer_over_ui() {
+            return false;
+        }
+        self.app_state
+            .input
+            .add_scroll(bindings::scroll_pixels(&delta));
         true
     }
 
     fn device_input(&mut self, event: &DeviceEvent) -> bool {
         if let DeviceEvent::MouseMotion { delta } = event {
-            if self.app_state.mouse_pressed {
-                self.app_state
-                    .camera_controller
-                    .process_mouse(delta.0, delta.1);
+            let input = &mut self.app_state.input;
+            if input.held(Action::Look) || self.app_state.mouse_captured {
+                input.add_look(Vec2::new(delta.0 as f32, delta.1 as f32));
                 return true;
             }
         }
@@ -167,11 +1037,31 @@ impl AppInternal {
     }
 }
 
+/// A point of the window in normalized device coordinates of the view.
+fn screen_to_ndc(screen: egui::Rect, position: egui::Pos2) -> Vec2 {
+    let fraction = (position - screen.min) / screen.size();
+    Vec2::new(fraction.x * 2.0 - 1.0, 1.0 - fraction.y * 2.0)
+}
+
+/// Tell the user about an error the app cannot continue after and quit, there
+/// is no device to show it with egui.
+fn exit_with_error(event_loop: &ActiveEventLoop, err: Error) {
+    tracing::error!(target: logging::APP, "{}", err);
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Error)
+        .set_title("Radiance Cascade")
+        .set_description(err.to_string())
+        .show();
+    event_loop.exit();
+}
+
 pub struct App {
     instance: wgpu::Instance,
     last_render_time: std::time::Instant,
     state: Option<AppInternal>,
     window: Option<Arc<Window>>,
+    /// Whether the cursor is currently grabbed, see `sync_cursor_capture`
+    cursor_captured: bool,
 }
 
 impl App {
@@ -185,21 +1075,19 @@ impl App {
             instance,
             state: None,
             window: None,
+            cursor_captured: false,
             last_render_time: std::time::Instant::now(),
         }
     }
 
-    async fn set_window(&mut self, window: Window) {
+    async fn set_window(&mut self, window: Window) -> Result<()> {
         let window = Arc::new(window);
         let initial_width = 1360;
         let initial_height = 768;
 
         let _ = window.request_inner_size(PhysicalSize::new(initial_width, initial_height));
 
-        let surface = self
-            .instance
-            .create_surface(window.clone())
-            .expect("Failed to create surface!");
+        let surface = self.instance.create_surface(window.clone())?;
 
         let state = AppInternal::new(
             &self.instance,
@@ -208,18 +1096,97 @@ impl App {
             initial_width,
             initial_width,
         )
-        .await;
+        .await?;
 
         self.window.get_or_insert(window);
         self.state.get_or_insert(state);
+        Ok(())
     }
 
     fn handle_resized(&mut self, width: u32, height: u32) {
         self.state.as_mut().unwrap().resize_surface(width, height);
     }
 
-    fn handle_redraw(&mut self, dt: std::time::Duration) {
+    /// Recreate the device and every GPU resource after the device was lost. The
+    /// scene is reloaded and the camera kept, other settings start over.
+    fn recover_device(&mut self) -> Result<()> {
+        let window = self.window.clone().unwrap();
+        let lost = self.state.take().unwrap();
+        let scene_path = lost.app_state.scene_path.clone();
+        let camera = lost.app_state.camera.clone();
+        let render_stages = lost.app_state.render_stages.clone();
+        // The old surface has to be gone before the window gets a new one
+        drop(lost);
+        let surface = self.instance.create_surface(window.clone())?;
+        let size = window.inner_size();
+        let mut state = pollster::block_on(AppInternal::new(
+            &self.instance,
+            surface,
+            &window,
+            size.width.max(1),
+            size.height.max(1),
+        ))?;
+        state.app_state.camera = camera;
+        state.app_state.render_stages = render_stages;
+        state.create_stages();
+        if let Some(path) = scene_path {
+            state.load_scene(path);
+        }
+        self.state = Some(state);
+        tracing::info!(target: logging::GPU, "recovered from device loss");
+        Ok(())
+    }
+
+    /// Grab and hide the cursor when mouse look was captured, release it otherwise.
+    fn sync_cursor_capture(&mut self) {
+        let (Some(state), Some(window)) = (self.state.as_mut(), self.window.as_ref()) else {
+            return;
+        };
+        let captured = state.app_state.mouse_captured;
+        if captured == self.cursor_captured {
+            return;
+        }
+        let result = if captured {
+            // Not every platform can lock the cursor, a confined one still reports motion
+            window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            window.set_cursor_grab(CursorGrabMode::None)
+        };
+        match result {
+            Ok(()) => {
+                window.set_cursor_visible(!captured);
+                self.cursor_captured = captured;
+            }
+            Err(err) => {
+                state
+                    .app_state
+                    .notifications
+                    .error(format!("Failed to capture the cursor: {}", err));
+                state.app_state.mouse_captured = self.cursor_captured;
+            }
+        }
+    }
+
+    fn handle_redraw(&mut self, event_loop: &ActiveEventLoop, dt: std::time::Duration) {
+        if self
+            .state
+            .as_ref()
+            .unwrap()
+            .device_lost
+            .load(Ordering::Acquire)
+        {
+            if let Err(err) = self.recover_device() {
+                exit_with_error(event_loop, err);
+            }
+            return;
+        }
         let state = self.state.as_mut().unwrap();
+        if state.minimized {
+            return;
+        }
+        let frame_start = std::time::Instant::now();
         state.update(dt);
 
         let screen_descriptor = ScreenDescriptor {
@@ -228,24 +1195,54 @@ impl App {
                 * state.app_state.scale_factor,
         };
 
-        let surface_texture = state
-            .surface
-            .get_current_texture()
-            .expect("Failed to acquire next swap chain texture");
+        let surface_texture = match state.surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(wgpu::SurfaceError::Timeout) => {
+                tracing::warn!(
+                    target: logging::GPU,
+                    "timed out acquiring the surface texture, skipping the frame"
+                );
+                return;
+            }
+            Err(err @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+                tracing::warn!(target: logging::GPU, "reconfiguring the surface: {}", err);
+                let size = self.window.as_ref().unwrap().inner_size();
+                state.resize_surface(size.width, size.height);
+                return;
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                tracing::error!(
+                    target: logging::GPU,
+                    "out of memory acquiring the surface texture"
+                );
+                event_loop.exit();
+                return;
+            }
+        };
 
         let surface_view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        // An attached RenderDoc or Xcode records everything up to the present
+        let gpu_capture = std::mem::take(&mut state.app_state.gpu_capture_requested);
+        if gpu_capture {
+            state.device.start_capture();
+        }
+
         let mut encoder = state
             .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Frame Encoder"),
+            });
 
         let window = self.window.as_ref().unwrap();
 
         state
             .renderer
-            .render(&mut state.app_state, &surface_view, &mut encoder);
+            .borrow_mut()
+            .upload(&state.device, &mut encoder, &state.app_state);
+        state.render_stages(&surface_view, &mut encoder);
 
         {
             state.egui_renderer.begin_frame(window);
@@ -263,19 +1260,48 @@ impl App {
         }
 
         state.queue.submit(Some(encoder.finish()));
+        state.renderer.borrow_mut().frame_submitted();
+        state
+            .renderer
+            .borrow()
+            .submit_gi_compute(&state.device, &state.queue);
+        state.record_benchmark_frame(frame_start.elapsed().as_secs_f32() * 1000.0);
+        state.capture_turntable_frame();
+        state.capture_path_frame();
+        state.capture_sweep_frame();
         surface_texture.present();
+        if gpu_capture {
+            state.device.stop_capture();
+            state
+                .app_state
+                .notifications
+                .info("Requested a capture of the frame from RenderDoc or Xcode");
+        }
+        let sweep_done = state.app_state.sweep.is_none() && state.app_state.start_sweep.is_none();
+        let benchmark_done =
+            state.app_state.benchmark.is_none() && state.app_state.start_benchmark.is_none();
+        if state.exit_after_sweep && sweep_done || state.exit_after_benchmark && benchmark_done {
+            event_loop.exit();
+        }
     }
 }
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window = event_loop
+        let result = event_loop
             .create_window(Window::default_attributes())
-            .unwrap();
-        pollster::block_on(self.set_window(window));
+            .map_err(Error::from)
+            .and_then(|window| pollster::block_on(self.set_window(window)));
+        if let Err(err) = result {
+            exit_with_error(event_loop, err);
+        }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        // Nothing to handle when the app failed to start
+        if self.state.is_none() {
+            return;
+        }
         // let egui render to process the event first
         self.state
             .as_mut()
@@ -285,7 +1311,8 @@ impl ApplicationHandler for App {
 
         match event {
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
+                tracing::info!(target: logging::APP, "the close button was pressed, stopping");
+                self.state.as_mut().unwrap().save_layout();
                 event_loop.exit();
             }
             WindowEvent::KeyboardInput { event, .. } => {
@@ -301,15 +1328,22 @@ impl ApplicationHandler for App {
                 let now = std::time::Instant::now();
                 let dt = now - self.last_render_time;
                 self.last_render_time = now;
-                self.handle_redraw(dt);
+                self.handle_redraw(event_loop, dt);
 
                 self.window.as_ref().unwrap().request_redraw();
             }
             WindowEvent::Resized(new_size) => {
                 self.handle_resized(new_size.width, new_size.height);
             }
+            WindowEvent::Focused(false) => {
+                let state = &mut self.state.as_mut().unwrap().app_state;
+                // The releases of what is held go to the focused window
+                state.input.release_all();
+                state.mouse_captured = false;
+            }
             _ => (),
         }
+        self.sync_cursor_capture();
     }
 
     fn device_event(
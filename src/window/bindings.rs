@@ -0,0 +1,68 @@
+//! Keys and buttons of winit mapped to the actions of `InputState`.
+
+use winit::{
+    dpi::PhysicalPosition,
+    event::{MouseButton, MouseScrollDelta},
+    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
+};
+
+use crate::input::Action;
+
+/// Restore the camera bookmark at their index.
+const BOOKMARK_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// Movement keys are physical so they stay in place on every layout.
+pub fn key_action(physical_key: &PhysicalKey, logical_key: &Key) -> Option<Action> {
+    if *logical_key == Key::Named(NamedKey::Space) {
+        return Some(Action::MoveUp);
+    }
+    let PhysicalKey::Code(code) = *physical_key else {
+        return None;
+    };
+    if let Some(index) = BOOKMARK_KEYS.iter().position(|key| *key == code) {
+        return Some(Action::Bookmark(index));
+    }
+    Some(match code {
+        KeyCode::KeyW => Action::MoveForward,
+        KeyCode::KeyS => Action::MoveBackward,
+        KeyCode::KeyA => Action::MoveLeft,
+        KeyCode::KeyD => Action::MoveRight,
+        KeyCode::ShiftLeft => Action::MoveDown,
+        KeyCode::Tab => Action::ToggleMouseLook,
+        KeyCode::Escape => Action::ReleaseMouseLook,
+        KeyCode::KeyP => Action::TogglePause,
+        KeyCode::Period => Action::StepFrame,
+        KeyCode::F1 => Action::ToggleUi,
+        KeyCode::F2 => Action::ToggleHelp,
+        KeyCode::F11 => Action::CaptureGpuFrame,
+        _ => return None,
+    })
+}
+
+pub fn button_action(button: MouseButton) -> Option<Action> {
+    match button {
+        MouseButton::Left => Some(Action::Look),
+        MouseButton::Right => Some(Action::ToggleMouseLook),
+        MouseButton::Middle => Some(Action::Pick),
+        _ => None,
+    }
+}
+
+/// Movement along the view direction of a wheel step, in pixels.
+pub fn scroll_pixels(delta: &MouseScrollDelta) -> f32 {
+    -match delta {
+        // A line counts as 100 pixels
+        MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
+        MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => *scroll as f32,
+    }
+}
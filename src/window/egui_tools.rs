@@ -53,6 +53,34 @@ impl EguiRenderer {
         let _ = self.state.on_window_event(window, event);
     }
 
+    /// Show `view` in egui, replacing the texture of `id` if given. Pixels stay
+    /// sharp when the image is magnified.
+    pub fn register_texture(
+        &mut self,
+        device: &Device,
+        view: &TextureView,
+        id: Option<egui::TextureId>,
+    ) -> egui::TextureId {
+        match id {
+            Some(id) => {
+                self.renderer.update_egui_texture_from_wgpu_texture(
+                    device,
+                    view,
+                    wgpu::FilterMode::Nearest,
+                    id,
+                );
+                id
+            }
+            None => self
+                .renderer
+                .register_native_texture(device, view, wgpu::FilterMode::Nearest),
+        }
+    }
+
+    pub fn free_texture(&mut self, id: egui::TextureId) {
+        self.renderer.free_texture(&id);
+    }
+
     pub fn ppp(&mut self, v: f32) {
         self.context().set_pixels_per_point(v);
     }
@@ -1,2 +1,3 @@
 pub(super) mod app;
+pub(super) mod bindings;
 pub(super) mod egui_tools;
@@ -0,0 +1,163 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use wgpu::{util::DeviceExt, Device};
+
+use crate::{
+    capabilities::Capabilities,
+    primitives::Vertex,
+    shader_preprocessor::{self, ShaderDefs},
+    texture,
+};
+
+/// Lines of one geom in `WireframeOverlay::index_buffer`.
+struct WireframeDraw {
+    lines: Range<u32>,
+    base_vertex: i32,
+    object_index: u32,
+}
+
+/// Triangle edges drawn over the shaded image in a solid color, independent of
+/// how the geometry itself is shaded. The lines read the vertices of the scene
+/// buffers, only their own index buffer is kept here.
+pub struct WireframeOverlay {
+    pipeline: wgpu::RenderPipeline,
+    index_buffer: Option<wgpu::Buffer>,
+    draws: Vec<WireframeDraw>,
+}
+
+impl WireframeOverlay {
+    pub fn new(
+        device: &Device,
+        capabilities: &Capabilities,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        object_bind_group_layout: &wgpu::BindGroupLayout,
+        color: [f64; 3],
+    ) -> Self {
+        let shader = shader_preprocessor::create_shader_module(
+            device,
+            capabilities,
+            "wireframe.wgsl",
+            &ShaderDefs::default(),
+        );
+        let constants = HashMap::from(
+            [
+                ("line_red", color[0]),
+                ("line_green", color[1]),
+                ("line_blue", color[2]),
+            ]
+            .map(|(name, value)| (name.to_owned(), value)),
+        );
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Wireframe Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, object_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Wireframe Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::layout()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+            }),
+            // Tested against the shaded scene but never occluding it
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            pipeline,
+            index_buffer: None,
+            draws: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.index_buffer = None;
+        self.draws.clear();
+    }
+
+    /// Replace the lines with the edges of `geoms`, each given as its triangle
+    /// indices, base vertex and object index. Edges shared by two triangles are
+    /// drawn once.
+    pub fn set_lines<'a>(
+        &mut self,
+        device: &Device,
+        geoms: impl IntoIterator<Item = (&'a [u32], i32, u32)>,
+    ) {
+        let mut indices = Vec::new();
+        self.draws.clear();
+        for (triangles, base_vertex, object_index) in geoms {
+            let start = indices.len() as u32;
+            let mut edges = HashSet::new();
+            for triangle in triangles.chunks_exact(3) {
+                for (a, b) in [(0, 1), (1, 2), (2, 0)].map(|(a, b)| (triangle[a], triangle[b])) {
+                    if edges.insert((a.min(b), a.max(b))) {
+                        indices.extend([a, b]);
+                    }
+                }
+            }
+            if indices.len() as u32 > start {
+                self.draws.push(WireframeDraw {
+                    lines: start..indices.len() as u32,
+                    base_vertex,
+                    object_index,
+                });
+            }
+        }
+        self.index_buffer = (!indices.is_empty()).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Index Buffer: Wireframe"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            })
+        });
+    }
+
+    /// Draw the lines, the scene vertex buffer and the camera and object bind
+    /// groups are bound by the caller.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        let Some(index_buffer) = &self.index_buffer else {
+            return;
+        };
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for draw in &self.draws {
+            let instance = draw.object_index..draw.object_index + 1;
+            render_pass.draw_indexed(draw.lines.clone(), draw.base_vertex, instance);
+        }
+    }
+}
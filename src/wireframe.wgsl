@@ -0,0 +1,36 @@
+// Triangle edges drawn as lines over the shaded image
+
+#define OBJECT_GROUP 1
+#include "skinning.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// Normalized depth the lines are pulled towards the camera by, so the faces
+// they lie on do not hide them. Depth bias states only apply to triangles.
+const DEPTH_OFFSET: f32 = 5e-5;
+
+// Line color, picked by each overlay when its pipeline is created
+override line_red: f32 = 1.0;
+override line_green: f32 = 0.55;
+override line_blue: f32 = 0.1;
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(6) joint_indices: vec4<u32>,
+    @location(7) weights: vec4<f32>,
+    @builtin(instance_index) object_index: u32,
+) -> @builtin(position) vec4<f32> {
+    // Skinned like in `shader.wgsl`, so the lines stay on the posed mesh
+    let transform = object_transform(objects[object_index], joint_indices, weights);
+    let world_position = transform * vec4<f32>(position, 1.0);
+    var clip_position = camera.view_matrix * world_position;
+    clip_position.z -= DEPTH_OFFSET * clip_position.w;
+    return clip_position;
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(line_red, line_green, line_blue, 1.0);
+}